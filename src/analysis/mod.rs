@@ -0,0 +1,267 @@
+//! The control flow of a program, found by following it from its start: the
+//! instructions that it reaches, where each of them leads and its
+//! subroutines.
+
+use super::language::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How an instruction passes control to another
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Edge {
+    /// To the next instruction
+    Next,
+    /// A jump, or the base address of a `JP V0`
+    Jump,
+    /// Past the next instruction, when the condition of a skip holds
+    Skip,
+    /// To a subroutine, which returns to the next instruction
+    Call,
+}
+
+/// Whether an instruction skips the next one on a condition
+pub fn is_skip(instr: &Instr) -> bool {
+    matches!(
+        instr,
+        Instr::SkipEq { .. }
+            | Instr::SkipNEq { .. }
+            | Instr::SkipEqV { .. }
+            | Instr::SkipNEqV { .. }
+            | Instr::Pressed { .. }
+            | Instr::NotPressed { .. }
+    )
+}
+
+/// Where control can go after the instruction at `addr`. Where a `JP V0`
+/// goes depends on V0, so only its base address is known. `RET` returns to
+/// the caller, and `SYS` and data lead nowhere
+pub fn successors(addr: u16, instr: &Instr) -> Vec<(Edge, u16)> {
+    let next = addr + 2;
+    match instr {
+        Instr::Data(_) | Instr::System { .. } | Instr::Ret => vec![],
+        Instr::Goto { addr } => vec![(Edge::Jump, addr.into())],
+        Instr::Jump { n } => vec![(Edge::Jump, n.into())],
+        Instr::Call { addr } => vec![(Edge::Call, addr.into()), (Edge::Next, next)],
+        i if is_skip(i) => vec![(Edge::Next, next), (Edge::Skip, next + 2)],
+        _ => vec![(Edge::Next, next)],
+    }
+}
+
+/// The instruction at `addr` of a ROM loaded at `start`, if the ROM has
+/// its two bytes
+pub fn decode(rom: &[u8], start: u16, addr: u16) -> Option<Instr> {
+    let ix = (addr as usize).checked_sub(start as usize)?;
+    let bytes = rom.get(ix..ix + 2)?;
+    Some(RawInstr::from_bytes([bytes[0], bytes[1]]).into_instr())
+}
+
+/// The instructions reached by following the program from its start, along
+/// all the edges. `SYS` ends a path, as programs rarely mean it and zeros
+/// decode to it
+pub fn reachable(rom: &[u8], start: u16) -> BTreeMap<u16, Instr> {
+    let mut code = BTreeMap::new();
+    let mut todo = vec![start];
+    while let Some(addr) = todo.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+        let Some(instr) = decode(rom, start, addr) else {
+            continue;
+        };
+        if matches!(instr, Instr::Data(_) | Instr::System { .. }) {
+            continue;
+        }
+        todo.extend(successors(addr, &instr).into_iter().map(|(_, a)| a));
+        code.insert(addr, instr);
+    }
+    code
+}
+
+/// The entry of the program, `start`, and the subroutines that it calls
+pub fn routines(code: &BTreeMap<u16, Instr>, start: u16) -> BTreeSet<u16> {
+    let calls = code.values().filter_map(|instr| match instr {
+        Instr::Call { addr } => Some(u16::from(addr)),
+        _ => None,
+    });
+    calls
+        .chain([start])
+        .filter(|a| code.contains_key(a))
+        .collect()
+}
+
+/// The instructions of the routine at `entry`: those reached from it
+/// without entering the subroutines that it calls
+pub fn body(code: &BTreeMap<u16, Instr>, entry: u16) -> BTreeSet<u16> {
+    let mut body = BTreeSet::new();
+    let mut todo = vec![entry];
+    while let Some(addr) = todo.pop() {
+        let Some(instr) = code.get(&addr) else {
+            continue;
+        };
+        if !body.insert(addr) {
+            continue;
+        }
+        for (edge, next) in successors(addr, instr) {
+            if edge != Edge::Call {
+                todo.push(next);
+            }
+        }
+    }
+    body
+}
+
+/// The value of I at an instruction, as far as the code tells
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum IValue {
+    Known(u16),
+    /// Different on different paths, or changed by an unknown amount
+    Unknown,
+}
+
+/// The sprites that the reachable code draws, by their addresses, with their
+/// heights, where 0 is a 16x16 sprite of `DXY0`. I is followed from the
+/// `LD I` that sets it through the jumps and the skips to the `DRW` that
+/// draws the bytes at I, and is lost where paths with different values
+/// meet, after the instructions that change it by an unknown amount and
+/// after calls. A sprite drawn with different heights has the largest
+pub fn sprites(code: &BTreeMap<u16, Instr>, start: u16) -> BTreeMap<u16, u8> {
+    let mut at: BTreeMap<u16, IValue> = BTreeMap::from([(start, IValue::Unknown)]);
+    let mut todo = vec![start];
+    while let Some(addr) = todo.pop() {
+        let Some(instr) = code.get(&addr) else {
+            continue;
+        };
+        let i = match instr {
+            Instr::SetI { n } => IValue::Known(n.into()),
+            Instr::IncrI { .. }
+            | Instr::SpriteAddr { .. }
+            | Instr::RegDump { .. }
+            | Instr::RegLoad { .. } => IValue::Unknown,
+            _ => at[&addr],
+        };
+        for (edge, next) in successors(addr, instr) {
+            // The subroutine can change I before it returns
+            let i = match (instr, edge) {
+                (Instr::Call { .. }, Edge::Next) => IValue::Unknown,
+                _ => i,
+            };
+            let merged = match at.get(&next) {
+                None => i,
+                Some(&old) if old == i => continue,
+                Some(_) => IValue::Unknown,
+            };
+            if at.insert(next, merged) != Some(merged) {
+                todo.push(next);
+            }
+        }
+    }
+    let mut sprites: BTreeMap<u16, u8> = BTreeMap::new();
+    for (addr, instr) in code {
+        if let (Instr::Draw { height, .. }, Some(IValue::Known(i))) = (instr, at.get(addr)) {
+            let height = *height;
+            sprites
+                .entry(*i)
+                .and_modify(|h| {
+                    if *h != 0 && (height == 0 || height > *h) {
+                        *h = height
+                    }
+                })
+                .or_insert(height);
+        }
+    }
+    sprites
+}
+
+/// A basic block: instructions that run one after the other, from the
+/// first to the last, and where the last one leads
+pub struct Block {
+    /// The addresses of the instructions
+    pub instrs: Vec<u16>,
+    pub edges: Vec<(Edge, u16)>,
+}
+
+/// The basic blocks of the reachable code, by their first addresses. A
+/// block starts at the start, at the targets of the jumps, the skips and
+/// the calls, and after what doesn't only lead to the next instruction
+pub fn blocks(code: &BTreeMap<u16, Instr>, start: u16) -> BTreeMap<u16, Block> {
+    let only_next =
+        |addr: u16, instr: &Instr| matches!(successors(addr, instr).as_slice(), [(Edge::Next, _)]);
+    let mut leaders = BTreeSet::from([start]);
+    for (&addr, instr) in code {
+        for (edge, target) in successors(addr, instr) {
+            if edge != Edge::Next {
+                leaders.insert(target);
+            }
+        }
+        if !only_next(addr, instr) {
+            leaders.insert(addr + 2);
+        }
+    }
+    let mut blocks = BTreeMap::new();
+    for &leader in leaders.iter().filter(|a| code.contains_key(a)) {
+        let mut instrs = vec![leader];
+        let mut addr = leader;
+        while only_next(addr, &code[&addr])
+            && code.contains_key(&(addr + 2))
+            && !leaders.contains(&(addr + 2))
+        {
+            addr += 2;
+            instrs.push(addr);
+        }
+        let edges = successors(addr, &code[&addr]);
+        blocks.insert(leader, Block { instrs, edges });
+    }
+    blocks
+}
+
+/// A routine in the call graph
+#[derive(Default)]
+pub struct Routine {
+    /// The routines that it calls
+    pub calls: BTreeSet<u16>,
+    /// The routines that call it
+    pub callers: BTreeSet<u16>,
+    /// Whether it can return, or it loops forever or jumps away
+    pub returns: bool,
+}
+
+/// The routines of the reachable code, by their entries, and the calls
+/// between them
+pub fn call_graph(code: &BTreeMap<u16, Instr>, start: u16) -> BTreeMap<u16, Routine> {
+    let mut graph: BTreeMap<u16, Routine> = BTreeMap::new();
+    for entry in routines(code, start) {
+        let body = body(code, entry);
+        let routine = graph.entry(entry).or_default();
+        for addr in &body {
+            match &code[addr] {
+                Instr::Ret => routine.returns = true,
+                Instr::Call { addr } => {
+                    routine.calls.insert(addr.into());
+                }
+                _ => (),
+            }
+        }
+        for callee in routine.calls.clone() {
+            graph.entry(callee).or_default().callers.insert(entry);
+        }
+    }
+    graph
+}
+
+/// Whether a routine calls itself, directly or through others
+pub fn recursive(graph: &BTreeMap<u16, Routine>, entry: u16) -> bool {
+    let mut seen = BTreeSet::new();
+    let mut todo: Vec<u16> = graph
+        .get(&entry)
+        .map_or(vec![], |r| r.calls.iter().copied().collect());
+    while let Some(addr) = todo.pop() {
+        if addr == entry {
+            return true;
+        }
+        if seen.insert(addr)
+            && let Some(r) = graph.get(&addr)
+        {
+            todo.extend(&r.calls);
+        }
+    }
+    false
+}
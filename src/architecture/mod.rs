@@ -1,5 +1,9 @@
 use super::base::*;
+use super::font::{ALL_CHARS_BYTES, BigFontBytes, Font, FontBytes};
 use bitvec::prelude::*;
+use clap::ValueEnum;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::num::*;
@@ -25,6 +29,162 @@ pub struct Chip8 {
     pub registers: [Wrapping<u8>; 16],
     /// the display state
     pub screen: Screen,
+    /// the keypad state. keys[k] is true while key k is pressed
+    pub keys: [bool; 16],
+    /// the behaviours that differ between interpreters
+    pub quirks: Quirks,
+    /// the sprites of the hexadecimal digits, copied to memory at FONT_START
+    /// when a program is loaded
+    pub font: FontBytes,
+    /// the sprites of the big digits of SUPER-CHIP and XO-CHIP, copied to memory at
+    /// BIG_FONT_START when a program is loaded, if there are any
+    pub big_font: Option<BigFontBytes>,
+    /// the random number generator used by RND. It is part of the state so
+    /// that two machines with the same seed run identically
+    pub rng: StdRng,
+    /// the seed of `rng`, kept so that runs can be replayed
+    pub seed: u64,
+}
+
+/// Behaviours of ambiguous instructions, which differ between interpreters.
+/// The default follows Cowgod's technical reference.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY into VX instead of shifting VX in place
+    pub shift_vy: bool,
+    /// FX55 and FX65 leave I pointing past the last register
+    pub load_store_incr_i: bool,
+    /// BXNN jumps to XNN + VX instead of BNNN jumping to NNN + V0
+    pub jump_vx: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around
+    pub clip: bool,
+}
+
+/// Interpreters with well known quirks
+#[derive(PartialEq, Eq, Clone, Copy, Debug, ValueEnum)]
+pub enum Platform {
+    /// Cowgod's technical reference
+    Cowgod,
+    /// The original COSMAC VIP interpreter
+    Chip8,
+    /// SUPER-CHIP 1.1
+    Schip,
+    /// XO-CHIP
+    Xochip,
+}
+
+impl Platform {
+    /// Guesses the platform a ROM was written for from the instructions that
+    /// only exist in the extensions. Data that happens to look like them can
+    /// fool the guess.
+    pub fn detect(rom: &[u8]) -> Platform {
+        let mut platform = Platform::Chip8;
+        for w in rom.chunks_exact(2) {
+            match Platform::extension(w[0], w[1]) {
+                Some((Platform::Xochip, _)) => return Platform::Xochip,
+                Some((p, _)) => platform = p,
+                None => (),
+            }
+        }
+        platform
+    }
+
+    /// The extension that added the opcode `hi lo`, if it isn't CHIP-8's,
+    /// with the opcode's pattern and what it does
+    pub fn extension(hi: u8, lo: u8) -> Option<(Platform, &'static str)> {
+        let (op, x) = (hi >> 4, hi & 0xF);
+        let xochip = match op {
+            0x0 if hi == 0 && lo & 0xF0 == 0xD0 => Some("00DN scroll up"),
+            0x5 if lo & 0xF == 2 => Some("5XY2 save VX to VY"),
+            0x5 if lo & 0xF == 3 => Some("5XY3 load VX to VY"),
+            0xF if lo == 0x01 => Some("FN01 select planes"),
+            0xF if lo == 0x02 => Some("F002 load audio"),
+            0xF if lo == 0x3A => Some("FX3A pitch"),
+            0xF if x == 0 && lo == 0 => Some("F000 long I"),
+            _ => None,
+        };
+        if let Some(opcode) = xochip {
+            return Some((Platform::Xochip, opcode));
+        }
+        let schip = match op {
+            0x0 if hi == 0 && lo & 0xF0 == 0xC0 => Some("00CN scroll down"),
+            0x0 if hi == 0 && lo == 0xFB => Some("00FB scroll right"),
+            0x0 if hi == 0 && lo == 0xFC => Some("00FC scroll left"),
+            0x0 if hi == 0 && lo == 0xFD => Some("00FD exit"),
+            0x0 if hi == 0 && lo == 0xFE => Some("00FE low resolution"),
+            0x0 if hi == 0 && lo == 0xFF => Some("00FF high resolution"),
+            0xD if lo & 0xF == 0 => Some("DXY0 16x16 sprite"),
+            0xF if lo == 0x30 => Some("FX30 big font"),
+            0xF if lo == 0x75 => Some("FX75 save flags"),
+            0xF if lo == 0x85 => Some("FX85 load flags"),
+            _ => None,
+        };
+        schip.map(|opcode| (Platform::Schip, opcode))
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Cowgod => Quirks::default(),
+            Platform::Chip8 => Quirks {
+                shift_vy: true,
+                load_store_incr_i: true,
+                jump_vx: false,
+                vf_reset: true,
+                clip: true,
+            },
+            Platform::Schip => Quirks {
+                shift_vy: false,
+                load_store_incr_i: false,
+                jump_vx: true,
+                vf_reset: false,
+                clip: true,
+            },
+            Platform::Xochip => Quirks {
+                shift_vy: true,
+                load_store_incr_i: true,
+                jump_vx: false,
+                vf_reset: false,
+                clip: false,
+            },
+        }
+    }
+}
+
+impl Quirks {
+    /// The quirks by their names, as the opcode reference names them
+    pub fn named(&self) -> [(&'static str, bool); 5] {
+        [
+            ("shift_vy", self.shift_vy),
+            ("load_store_incr_i", self.load_store_incr_i),
+            ("jump_vx", self.jump_vx),
+            ("vf_reset", self.vf_reset),
+            ("clip", self.clip),
+        ]
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            Platform::Cowgod => "cowgod",
+            Platform::Chip8 => "chip8",
+            Platform::Schip => "schip",
+            Platform::Xochip => "xochip",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// The instructions run between two ticks of the timers. At 60 ticks per
+/// second, about the speed of the original interpreter
+pub const STEPS_PER_FRAME: u32 = 10;
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chip8 {
@@ -33,10 +193,18 @@ impl Chip8 {
     /// The character sprites are put in sequence starting at this position
     pub const FONT_START: usize = 0x0;
 
+    /// The big character sprites are put right after the small ones
+    pub const BIG_FONT_START: usize = Self::FONT_START + ALL_CHARS_BYTES;
+
     /// Code starts at memory[CODE_START]
     pub const CODE_START: usize = 0x200;
 
     pub fn new() -> Chip8 {
+        Self::with_seed(rand::random())
+    }
+
+    /// A machine whose random numbers are determined by `seed`
+    pub fn with_seed(seed: u64) -> Chip8 {
         Chip8 {
             memory: [0; Self::MEM_SIZE],
             i: 0,
@@ -47,6 +215,12 @@ impl Chip8 {
             stack: [0; 16],
             registers: [Wrapping(0); 16],
             screen: Screen::new(),
+            keys: [false; 16],
+            quirks: Quirks::default(),
+            font: *Font::default().bytes(),
+            big_font: None,
+            rng: StdRng::seed_from_u64(seed),
+            seed,
         }
     }
 }
@@ -57,6 +231,12 @@ pub struct Screen {
     pub rows: [BitArr!(for 64, in u64); 32],
 }
 
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Screen {
     pub const NROWS: usize = 32;
     pub const NCOLS: usize = 64;
@@ -0,0 +1,109 @@
+//! The errors of the assemblers, which point at the text that they are about:
+//!
+//! ```text
+//! game.s8:12:8: unknown register V16, did you mean VF?
+//!    12 |     LD V16, 3
+//!       |        ^^^
+//! ```
+
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+/// An error in a line. `at` is the text that it is about, if it is known
+pub struct Error {
+    pub message: String,
+    pub at: Option<String>,
+}
+
+impl Error {
+    pub fn at(at: &str, message: String) -> Error {
+        Error {
+            message,
+            at: Some(at.to_string()),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error { message, at: None }
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Error {
+        message.to_string().into()
+    }
+}
+
+/// Where a line is, and its text, for the errors
+#[derive(Clone)]
+pub struct Loc {
+    pub file: Rc<Path>,
+    pub line: usize,
+    pub text: Rc<str>,
+}
+
+impl fmt::Display for Loc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+impl Loc {
+    /// The error, with the line and a mark under what it is about. Without
+    /// `at`, or when `at` is not in the line, e.g. because it comes from a
+    /// macro, the whole line is marked
+    pub fn report(&self, e: impl Into<Error>) -> String {
+        let e = e.into();
+        let text = self.text.replace('\t', " ");
+        let (col, len) = match e.at.as_deref().and_then(|at| Some((text.find(at)?, at))) {
+            Some((ix, at)) if !at.is_empty() => (text[..ix].chars().count(), at.chars().count()),
+            _ => {
+                let code = text.trim();
+                let ix = text.len() - text.trim_start().len();
+                (text[..ix].chars().count(), code.chars().count().max(1))
+            }
+        };
+        let number = self.line.to_string();
+        let pad = " ".repeat(number.len());
+        format!(
+            "{self}:{}: {}\n {number} | {text}\n {pad} | {}{}",
+            col + 1,
+            e.message,
+            " ".repeat(col),
+            "^".repeat(len)
+        )
+    }
+}
+
+/// The candidate closest to `word`, ignoring the case, if it is close enough
+/// to be a typo of it
+pub fn similar<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let word = word.to_ascii_lowercase();
+    candidates
+        .into_iter()
+        .map(|c| (distance(&word, &c.to_ascii_lowercase()), c))
+        .filter(|&(d, _)| d > 0 && d <= (word.len() / 3).max(1))
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, c)| c)
+}
+
+/// The edit distance between two words
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let next = (prev + usize::from(ca != cb))
+                .min(row[j] + 1)
+                .min(row[j + 1] + 1);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,159 @@
+//! Constant expressions in the operands, e.g. `sprites + 5 * CHAR_SIZE`,
+//! evaluated when the program is assembled. The operators are those of C,
+//! with the same precedence: `|`, `^`, `&`, `<<` `>>`, `+` `-`, `*` `/` `%`,
+//! and the unary `-` and `~`.
+
+use crate::base::parse_number;
+
+/// The binary operators, from the loosest to the tightest
+const LEVELS: [&[&str]; 6] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+struct Parser<'s, 'f> {
+    s: &'s str,
+    pos: usize,
+    /// The value of a name, a label or a constant
+    name: &'f dyn Fn(&str) -> Result<u32, String>,
+}
+
+impl<'s> Parser<'s, '_> {
+    fn rest(&self) -> &'s str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        let spaces = rest.len() - rest.trim_start().len();
+        self.pos += spaces;
+    }
+
+    /// Reads one of `ops`, if it comes next
+    fn op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        self.skip_spaces();
+        let op = *ops.iter().find(|op| self.rest().starts_with(**op))?;
+        self.pos += op.len();
+        Some(op)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut a = self.binary(level + 1)?;
+        while let Some(op) = self.op(LEVELS[level]) {
+            let b = self.binary(level + 1)?;
+            a = apply(op, a, b)?;
+        }
+        Ok(a)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        match self.op(&["-", "~", "("]) {
+            Some("-") => Ok(-self.unary()?),
+            Some("~") => Ok(!self.unary()?),
+            Some(_) => {
+                let n = self.binary(0)?;
+                self.op(&[")"]).ok_or("expected )")?;
+                Ok(n)
+            }
+            None => self.atom(),
+        }
+    }
+
+    /// A number or a name
+    fn atom(&mut self) -> Result<i64, String> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let word = &rest[..len];
+        if word.is_empty() {
+            return Err(match rest.chars().next() {
+                Some(c) => format!("unexpected {c}"),
+                None => "unexpected end of the expression".into(),
+            });
+        }
+        self.pos += len;
+        match parse_number(word) {
+            Some(n) => Ok(n.into()),
+            None => (self.name)(word).map(i64::from),
+        }
+    }
+}
+
+fn apply(op: &str, a: i64, b: i64) -> Result<i64, String> {
+    let shift = |b: i64| (0..32).contains(&b).then_some(b as u32);
+    match op {
+        "|" => Some(a | b),
+        "^" => Some(a ^ b),
+        "&" => Some(a & b),
+        "<<" => shift(b).and_then(|b| a.checked_shl(b)),
+        ">>" => shift(b).and_then(|b| a.checked_shr(b)),
+        "+" => a.checked_add(b),
+        "-" => a.checked_sub(b),
+        "*" => a.checked_mul(b),
+        "/" => a.checked_div(b),
+        "%" => a.checked_rem(b),
+        _ => unreachable!("not an operator: {op}"),
+    }
+    .ok_or_else(|| format!("invalid operation: {a} {op} {b}"))
+}
+
+/// The value of an expression, which must not be negative. The operand
+/// that it is used in checks that it fits
+pub fn eval(s: &str, name: &dyn Fn(&str) -> Result<u32, String>) -> Result<u32, String> {
+    let mut p = Parser { s, pos: 0, name };
+    let n = p.binary(0)?;
+    p.skip_spaces();
+    if !p.rest().is_empty() {
+        return Err(format!("unexpected {} in {s}", p.rest()));
+    }
+    u32::try_from(n).map_err(|_| format!("{s} is {n}, out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(s: &str) -> Result<u32, String> {
+        let name = |n: &str| match n {
+            "sprites" => Ok(0x300),
+            "SIZE" => Ok(5),
+            _ => Err(format!("unknown name {n}")),
+        };
+        eval(s, &name)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(value("1 + 2 * 3"), Ok(7));
+        assert_eq!(value("(1 + 2) * 3"), Ok(9));
+        assert_eq!(value("10 - 4 - 3"), Ok(3));
+        assert_eq!(value("1 | 2 << 3"), Ok(17));
+        assert_eq!(value("6 & 3 ^ 1"), Ok(3));
+        assert_eq!(value("1 << 2 + 1"), Ok(8));
+        assert_eq!(value("7 % 4 * 2"), Ok(6));
+        assert_eq!(value("~0 & 0xF"), Ok(0xF));
+        assert_eq!(value("-1 + 2"), Ok(1));
+        assert_eq!(value("sprites + 2 * SIZE"), Ok(0x30A));
+        assert_eq!(value("0b101 | 0x10"), Ok(0x15));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(value("1 +"), Err("unexpected end of the expression".into()));
+        assert_eq!(value("(1 + 2"), Err("expected )".into()));
+        assert_eq!(value("1 2"), Err("unexpected 2 in 1 2".into()));
+        assert_eq!(value("1 + $"), Err("unexpected $".into()));
+        assert_eq!(value("1 / 0"), Err("invalid operation: 1 / 0".into()));
+        assert_eq!(value("1 << 32"), Err("invalid operation: 1 << 32".into()));
+        assert_eq!(value("1 - 2"), Err("1 - 2 is -1, out of range".into()));
+        assert_eq!(value("missing + 1"), Err("unknown name missing".into()));
+    }
+}
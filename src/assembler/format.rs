@@ -0,0 +1,282 @@
+//! The formatter of `chip-8 fmt`, which writes programs in one style:
+//!
+//! ```text
+//! SPEED EQU 2
+//!
+//! main:
+//!     LD V0, 0x0A             ; the column
+//!     ADD V0, SPEED * 2
+//!     JP main
+//! ```
+//!
+//! The labels and the definitions go on lines of their own at the start of
+//! the line, and the instructions are indented. The mnemonics and the
+//! registers are in capitals, the operands are separated by `, `, the
+//! operators of the expressions by spaces, and the hexadecimal numbers are
+//! written `0x2A`. The comments after the code start at the same column,
+//! and runs of blank lines become one. Octo's programs are written the same
+//! way, with the blocks of `loop` and `begin` indented.
+
+use super::*;
+
+/// The column where the comments after the code start
+const COMMENT_COLUMN: usize = 24;
+
+/// The number of spaces of each level of indentation
+const INDENT: usize = 4;
+
+/// Formats a program written with the mnemonics
+pub fn format(source: &str) -> String {
+    let mut lines = vec![];
+    // The parameters of the macro being defined
+    let mut params = vec![];
+    for line in source.lines() {
+        let (code, comment) = split_comment(line);
+        let comment = comment.map(|c| normalize_comment(c, ';'));
+        let (label, text) = split_label(code.trim());
+        let mut out: Vec<String> = label.map(|l| format!("{l}:")).into_iter().collect();
+        if !text.is_empty() {
+            out.push(statement(text, &mut params));
+        }
+        match (out.pop(), comment) {
+            (Some(last), Some(c)) => {
+                lines.extend(out);
+                lines.push(with_comment(&last, &c));
+            }
+            (Some(last), None) => {
+                lines.extend(out);
+                lines.push(last);
+            }
+            (None, Some(c)) if line.starts_with(char::is_whitespace) => {
+                lines.push(format!("{}{c}", " ".repeat(INDENT)))
+            }
+            (None, Some(c)) => lines.push(c),
+            (None, None) => lines.push(String::new()),
+        }
+    }
+    join(lines)
+}
+
+/// The code of a line and its comment, which can't start in a string
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let code = strip_comment(line);
+    match line.find(code).map(|ix| line[ix + code.len()..].trim()) {
+        Some(rest) if rest.starts_with(';') => (code, Some(rest)),
+        _ => (code, None),
+    }
+}
+
+/// A comment with a space after its marks, e.g. `;; like this`
+fn normalize_comment(comment: &str, mark: char) -> String {
+    let text = comment.trim_start_matches(mark).trim();
+    let marks = &comment[..comment.len() - comment.trim_start_matches(mark).len()];
+    if text.is_empty() {
+        marks.to_string()
+    } else {
+        format!("{marks} {text}")
+    }
+}
+
+fn with_comment(code: &str, comment: &str) -> String {
+    format!("{code:<COMMENT_COLUMN$} {comment}")
+}
+
+/// The lines, with one blank line at most between them and none at the
+/// start and at the end
+fn join(lines: Vec<String>) -> String {
+    let mut out = String::new();
+    let mut blank = true;
+    for line in lines {
+        let line = line.trim_end();
+        if line.is_empty() && blank {
+            continue;
+        }
+        blank = line.is_empty();
+        out += line;
+        out += "\n";
+    }
+    if blank {
+        out.pop();
+    }
+    out
+}
+
+/// Formats the code of a line, without its label. `params` are those of the
+/// macro being defined, which `MACRO` sets and `ENDM` clears
+fn statement(text: &str, params: &mut Vec<String>) -> String {
+    let (word, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if let Some((name, value)) = equ(text) {
+        return format!("{name} EQU {}", expression(value));
+    }
+    if word.eq_ignore_ascii_case("MACRO") {
+        let args = args.trim();
+        let (name, names) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        *params = split_args(names).into_iter().map(str::to_string).collect();
+        return format!("MACRO {name} {}", params.join(", "))
+            .trim_end()
+            .to_string();
+    }
+    if word.eq_ignore_ascii_case("ENDM") {
+        params.clear();
+        return "ENDM".into();
+    }
+    if let Some(Ok(file)) = include(text) {
+        return format!("include \"{file}\"");
+    }
+    let upper = word.to_ascii_uppercase();
+    let mnemonic = if MNEMONICS.contains(&upper.as_str()) {
+        &upper
+    } else {
+        word
+    };
+    let indent = " ".repeat(INDENT);
+    let args = split_args(args);
+    if args.is_empty() {
+        return format!("{indent}{mnemonic}");
+    }
+    let args: Vec<String> = args.into_iter().map(|a| operand(a, params)).collect();
+    format!("{indent}{mnemonic} {}", args.join(", "))
+}
+
+/// An operand, with the registers and the other keywords in capitals. The
+/// parameters of a macro keep their case, which the arguments replace them by
+fn operand(arg: &str, params: &[String]) -> String {
+    if string(arg).is_some() || params.iter().any(|p| p == arg) {
+        arg.to_string()
+    } else if keyword(arg).is_some() {
+        arg.to_ascii_uppercase()
+    } else {
+        expression(arg)
+    }
+}
+
+/// An expression with spaces around its binary operators and none after
+/// the unary ones and inside the parentheses
+fn expression(s: &str) -> String {
+    let mut out = String::new();
+    // Whether the last token is an operand, after which `-` is binary
+    let mut operand = false;
+    let mut rest = s.trim();
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = rest.trim_start();
+            continue;
+        }
+        let len = if c.is_ascii_alphanumeric() || c == '_' || c == '@' {
+            rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '@'))
+                .unwrap_or(rest.len())
+        } else if rest.starts_with("<<") || rest.starts_with(">>") {
+            2
+        } else {
+            c.len_utf8()
+        };
+        let token = &rest[..len];
+        rest = &rest[len..];
+        match token {
+            "(" => {
+                out += "(";
+                operand = false;
+            }
+            ")" => {
+                out += ")";
+                operand = true;
+            }
+            "~" => out += "~",
+            "-" if !operand => out += "-",
+            "|" | "^" | "&" | "<<" | ">>" | "+" | "-" | "*" | "/" | "%" => {
+                out += &format!(" {token} ");
+                operand = false;
+            }
+            _ => {
+                out += &number(token);
+                operand = true;
+            }
+        }
+    }
+    out
+}
+
+/// A hexadecimal number as `0x2A` and a binary one as `0b101`. Other words
+/// are kept
+fn number(word: &str) -> String {
+    let (at, n) = match word.strip_prefix('@') {
+        Some(n) => ("@", n),
+        None => ("", word),
+    };
+    let digits = |prefix: &str| {
+        n.get(2..)
+            .filter(|d| !d.is_empty() && n[..2].eq_ignore_ascii_case(prefix))
+            .map(|d| d.to_string())
+    };
+    if let Some(hex) = digits("0x").filter(|d| d.chars().all(|c| c.is_ascii_hexdigit())) {
+        format!("{at}0x{}", hex.to_ascii_uppercase())
+    } else if let Some(bin) = digits("0b").filter(|d| d.chars().all(|c| c == '0' || c == '1')) {
+        format!("{at}0b{bin}")
+    } else {
+        word.to_string()
+    }
+}
+
+/// Formats a program written in Octo. The statements keep their lines,
+/// except the labels, which go on lines of their own
+pub fn format_octo(source: &str) -> String {
+    let mut lines = vec![];
+    let mut depth: usize = 0;
+    for line in source.lines() {
+        let (code, comment) = match line.split_once('#') {
+            Some((code, comment)) => (code, Some(normalize_comment(&format!("#{comment}"), '#'))),
+            None => (line, None),
+        };
+        // The statements of the line, with the labels apart
+        let mut groups: Vec<Vec<String>> = vec![vec![]];
+        let mut tokens = code.split_whitespace();
+        while let Some(t) = tokens.next() {
+            if t == ":" {
+                let label = [":"].into_iter().chain(tokens.next());
+                groups.push(label.map(String::from).collect());
+                groups.push(vec![]);
+            } else if let Some(group) = groups.last_mut() {
+                group.push(number(t));
+            }
+        }
+        groups.retain(|g| !g.is_empty());
+        let mut out = vec![];
+        for group in groups {
+            let first = group[0].as_str();
+            let level = match first {
+                "end" | "again" | "else" => depth.saturating_sub(1),
+                _ => depth,
+            };
+            for t in &group {
+                match t.as_str() {
+                    "loop" | "begin" => depth += 1,
+                    "end" | "again" => depth = depth.saturating_sub(1),
+                    _ => (),
+                }
+            }
+            // The labels and the definitions start the line
+            let level = if first.starts_with(':') || first == "}" {
+                level
+            } else {
+                level + 1
+            };
+            out.push(format!("{}{}", " ".repeat(level * INDENT), group.join(" ")));
+        }
+        match (out.pop(), comment) {
+            (Some(last), Some(c)) => {
+                lines.extend(out);
+                lines.push(with_comment(&last, &c));
+            }
+            (Some(last), None) => {
+                lines.extend(out);
+                lines.push(last);
+            }
+            (None, Some(c)) if line.starts_with(char::is_whitespace) => {
+                lines.push(format!("{}{c}", " ".repeat((depth + 1) * INDENT)))
+            }
+            (None, Some(c)) => lines.push(c),
+            (None, None) => lines.push(String::new()),
+        }
+    }
+    join(lines)
+}
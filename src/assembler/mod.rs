@@ -0,0 +1,670 @@
+use super::architecture::*;
+use super::base::*;
+use super::language::*;
+use super::sourcemap::SourceMap;
+use error::{Error, Loc, similar};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+mod error;
+mod expr;
+pub mod format;
+pub mod octo;
+
+/// An operand of an instruction in the source
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Arg {
+    V(u8),
+    I,
+    /// `[I]`, the memory at I
+    AtI,
+    Dt,
+    St,
+    /// The key pressed, in `LD Vx, K`
+    K,
+    /// The font sprite, in `LD F, Vx`
+    F,
+    /// The decimal digits, in `LD B, Vx`
+    B,
+    Number(u32),
+}
+
+/// The mnemonics, to tell unknown instructions from wrong operands
+pub const MNEMONICS: [&str; 24] = [
+    "DB", "DW", "DS", "SYS", "CLS", "RET", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND",
+    "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "SKPN",
+];
+
+/// How deep macros can use other macros, which stops a macro that uses itself
+const MAX_DEPTH: usize = 64;
+
+/// Assembles a program written with the mnemonics of the disassembler, one
+/// instruction per line, e.g. `LD V0, 0x0A`. Comments start with `;`. A line
+/// can start with a label, `loop:`, which names the address of the next
+/// instruction, to be used by the jumps, the calls and `LD I`, before or after
+/// its definition. The program is loaded at `0x200`.
+///
+/// `NAME EQU 5` names a number, and a macro is defined between
+/// `MACRO name param, ...` and `ENDM`, then used as `name arg, ...`. The
+/// numbers can be expressions, e.g. `LD I, sprites + 5 * CHAR_SIZE`.
+///
+/// Data is written with `DB 0xF0, 0x90` or `DB "TEXT"` for bytes, `DW 0x1234`
+/// for words and `DS 32` for that many zeros.
+///
+/// `include "sprites.s8"` reads another file in place of the line, relative to
+/// the one that includes it. `source` is the text of `file`. Returns the bytes
+/// of the ROM and where they come from, or the first error with its file, its
+/// line and its column, the line and the text that it is about.
+pub fn assemble(source: &str, file: &Path) -> Result<(Vec<u8>, SourceMap), String> {
+    let mut lines = vec![];
+    read(source, file, &mut vec![], &mut lines)?;
+    let lines = expand_macros(lines)?;
+    // The first pass finds the addresses of the labels and the values of
+    // the constants, and the second one encodes the instructions
+    let mut names: HashMap<String, u32> = HashMap::new();
+    let mut map = SourceMap::default();
+    let mut code: Vec<(&Loc, &str)> = vec![];
+    let mut addr = Chip8::CODE_START as u32;
+    for (loc, line) in &lines {
+        let (label, text) = split_label(line);
+        if let Some(label) = label {
+            define(&mut names, label, addr).map_err(|e| loc.report(e))?;
+            map.labels.insert(addr as u16, label.to_string());
+        }
+        if let Some((name, value)) = equ(text) {
+            let value = match parse_arg(value, &names) {
+                Ok(Arg::Number(v)) => v,
+                Ok(_) => {
+                    return Err(loc.report(Error::at(value, format!("{value} is not a number"))));
+                }
+                Err(e) => return Err(loc.report(e)),
+            };
+            define(&mut names, name, value).map_err(|e| loc.report(e))?;
+        } else if !text.is_empty() {
+            code.push((loc, text));
+            addr += size(text, &names).map_err(|e| loc.report(e))?;
+        }
+    }
+    let mut rom = vec![];
+    for (loc, text) in code {
+        let bytes = match data(text) {
+            Some((directive, args)) => parse_data(directive, args, &names),
+            None => parse_instr(text, &names).map(|i| i.encode().to_vec()),
+        };
+        let bytes = bytes.map_err(|e| loc.report(e))?;
+        if !bytes.is_empty() {
+            let addr = (Chip8::CODE_START + rom.len()) as u16;
+            map.lines.insert(addr, (loc.file.to_path_buf(), loc.line));
+        }
+        rom.extend(bytes);
+    }
+    Ok((rom, map))
+}
+
+/// The instruction of a line, if it is one. The names in `names` stand for
+/// zero, so that the operands that use them parse. For the hovers of the
+/// language server
+pub fn instruction(line: &str, names: &[&str]) -> Option<Instr> {
+    let (_, text) = split_label(strip_comment(line));
+    let names = names.iter().map(|n| (n.to_string(), 0)).collect();
+    if text.is_empty() || data(text).is_some() {
+        return None;
+    }
+    parse_instr(text, &names).ok()
+}
+
+/// The instruction of the `n`th line typed at `chip-8 repl`, or `None` if
+/// it is blank or a comment. The error points at what is wrong in the line
+pub fn repl_instruction(line: &str, n: usize) -> Result<Option<Instr>, String> {
+    let loc = Loc {
+        file: Path::new("repl").into(),
+        line: n,
+        text: line.into(),
+    };
+    let text = strip_comment(line).trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    if data(text).is_some() {
+        return Err(loc.report("only instructions run at the prompt"));
+    }
+    parse_instr(text, &HashMap::new())
+        .map(Some)
+        .map_err(|e| loc.report(e))
+}
+
+/// Adds the lines of `file`, without their comments, to `out`, with those
+/// of the files that it includes in their place. `stack` holds the files
+/// being read, which can't be included again
+fn read(
+    source: &str,
+    file: &Path,
+    stack: &mut Vec<PathBuf>,
+    out: &mut Vec<(Loc, String)>,
+) -> Result<(), String> {
+    stack.push(fs::canonicalize(file).unwrap_or(file.to_path_buf()));
+    let rc: Rc<Path> = file.into();
+    for (n, line) in source.lines().enumerate() {
+        let loc = Loc {
+            file: rc.clone(),
+            line: n + 1,
+            text: line.into(),
+        };
+        let line = strip_comment(line);
+        let name = match include(line) {
+            None => {
+                out.push((loc, line.to_string()));
+                continue;
+            }
+            Some(name) => name.map_err(|e| loc.report(e))?,
+        };
+        let path = file.parent().unwrap_or(Path::new("")).join(name);
+        if stack.contains(&fs::canonicalize(&path).unwrap_or(path.clone())) {
+            let e = format!("{} is already being included", path.display());
+            return Err(loc.report(Error::at(name, e)));
+        }
+        let source = fs::read_to_string(&path).map_err(|e| {
+            loc.report(Error::at(
+                name,
+                format!("could not read {}: {e}", path.display()),
+            ))
+        })?;
+        read(&source, &path, stack, out)?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// The file of an `include "file"`
+fn include(line: &str) -> Option<Result<&str, String>> {
+    let (word, file) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if !word.eq_ignore_ascii_case("include") {
+        return None;
+    }
+    Some(
+        file.trim()
+            .strip_prefix('"')
+            .and_then(|f| f.strip_suffix('"'))
+            .filter(|f| !f.is_empty())
+            .ok_or(format!("expected include \"file\", found {line}")),
+    )
+}
+
+/// A data directive, `DB`, `DW` or `DS`, and its operands
+fn data(text: &str) -> Option<(&str, &str)> {
+    let (directive, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    ["DB", "DW", "DS"]
+        .into_iter()
+        .find(|d| d.eq_ignore_ascii_case(directive))
+        .map(|d| (d, args.trim()))
+}
+
+/// The operands separated by commas, which strings can contain
+fn split_args(args: &str) -> Vec<&str> {
+    let mut out = vec![];
+    let (mut start, mut quoted) = (0, false);
+    for (ix, c) in args.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                out.push(args[start..ix].trim());
+                start = ix + 1;
+            }
+            _ => (),
+        }
+    }
+    out.push(args[start..].trim());
+    out.retain(|a| !a.is_empty());
+    out
+}
+
+/// The bytes of a string, `"TEXT"`
+fn string(arg: &str) -> Option<Result<&[u8], Error>> {
+    let s = arg.strip_prefix('"')?;
+    Some(match s.strip_suffix('"') {
+        Some(s) if s.is_ascii() => Ok(s.as_bytes()),
+        Some(_) => Err(Error::at(arg, "only ASCII in strings".into())),
+        None => Err(Error::at(arg, "unterminated string".into())),
+    })
+}
+
+/// The number of bytes that a line takes. `DS` must only use the names
+/// defined before it
+fn size(text: &str, names: &HashMap<String, u32>) -> Result<u32, Error> {
+    Ok(match data(text) {
+        None => 2,
+        Some(("DS", args)) => parse_data("DS", args, names)?.len() as u32,
+        Some((directive, args)) => split_args(args)
+            .into_iter()
+            .map(|a| match (directive, string(a)) {
+                ("DB", Some(s)) => s.map(|s| s.len() as u32),
+                ("DB", None) => Ok(1),
+                (_, Some(_)) => Err(Error::at(a, "strings only go in DB".into())),
+                (_, None) => Ok(2),
+            })
+            .sum::<Result<u32, Error>>()?,
+    })
+}
+
+/// The bytes of a data directive
+fn parse_data(directive: &str, args: &str, names: &HashMap<String, u32>) -> Result<Vec<u8>, Error> {
+    let number = |a: &str| -> Result<u32, Error> {
+        match parse_arg(a, names)? {
+            Arg::Number(n) => Ok(n),
+            _ => Err(Error::at(a, format!("{a} is not a number"))),
+        }
+    };
+    let args = split_args(args);
+    if args.is_empty() {
+        return Err(format!("{directive} without operands").into());
+    }
+    let mut bytes = vec![];
+    match directive {
+        "DS" => match args.as_slice() {
+            [n] => {
+                let size = fits(number(n)?, Chip8::MEM_SIZE as u32, "the memory");
+                bytes.resize(size.map_err(|e| Error::at(n, e))? as usize, 0)
+            }
+            _ => return Err("DS takes one operand".into()),
+        },
+        _ => {
+            for a in args {
+                match string(a) {
+                    Some(s) if directive == "DB" => bytes.extend(s?),
+                    Some(_) => return Err(Error::at(a, "strings only go in DB".into())),
+                    None if directive == "DB" => {
+                        bytes.push(byte(number(a)?).map_err(|e| Error::at(a, e))?)
+                    }
+                    None => {
+                        let word =
+                            fits(number(a)?, 0xFFFF, "a word").map_err(|e| Error::at(a, e))?;
+                        bytes.extend((word as u16).to_be_bytes())
+                    }
+                }
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Names the address of a label or the value of a constant
+fn define(names: &mut HashMap<String, u32>, name: &str, value: u32) -> Result<(), Error> {
+    if !is_name(name) {
+        return Err(Error::at(name, format!("invalid name: {name}")));
+    }
+    if names.insert(name.to_string(), value).is_some() {
+        return Err(Error::at(name, format!("{name} is already defined")));
+    }
+    Ok(())
+}
+
+/// Splits the label, `loop:`, from the start of a line
+fn split_label(line: &str) -> (Option<&str>, &str) {
+    match line.split_once(':') {
+        Some((label, rest)) if !label.contains(char::is_whitespace) => (Some(label), rest.trim()),
+        _ => (None, line),
+    }
+}
+
+/// The name and the value of a constant, `NAME EQU value`
+fn equ(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.split_once(char::is_whitespace)?;
+    let (word, value) = rest.trim().split_once(char::is_whitespace)?;
+    word.eq_ignore_ascii_case("EQU")
+        .then_some((name, value.trim()))
+}
+
+/// The line without its comment, which can't start in a string
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let end = line
+        .char_indices()
+        .find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == ';' && !quoted
+        })
+        .map_or(line.len(), |(ix, _)| ix);
+    line[..end].trim()
+}
+
+/// A macro: its parameters and its lines
+type Macro = (Vec<String>, Vec<String>);
+
+/// Expands the macros. The lines of a macro are where it is used
+fn expand_macros(lines: Vec<(Loc, String)>) -> Result<Vec<(Loc, String)>, String> {
+    let mut macros: HashMap<String, Macro> = HashMap::new();
+    let mut out = vec![];
+    let mut lines = lines.into_iter();
+    while let Some((loc, line)) = lines.next() {
+        let (first, rest) = line
+            .split_once(char::is_whitespace)
+            .unwrap_or((line.as_str(), ""));
+        if !first.eq_ignore_ascii_case("MACRO") {
+            expand(&loc, &line, &macros, &mut out, 0)?;
+            continue;
+        }
+        let rest = rest.trim();
+        let (name, params) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if !is_name(name) || MNEMONICS.contains(&name.to_ascii_uppercase().as_str()) {
+            return Err(loc.report(Error::at(name, format!("invalid macro name: {name}"))));
+        }
+        let params = params
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(String::from)
+            .collect();
+        let mut body = vec![];
+        loop {
+            match lines.next() {
+                Some((_, l)) if l.eq_ignore_ascii_case("ENDM") => break,
+                Some((_, l)) => body.push(l),
+                None => return Err(loc.report("MACRO without ENDM")),
+            }
+        }
+        macros.insert(name.to_string(), (params, body));
+    }
+    Ok(out)
+}
+
+/// Adds the line at `loc` to `out`, expanded if it uses a macro
+fn expand(
+    loc: &Loc,
+    line: &str,
+    macros: &HashMap<String, Macro>,
+    out: &mut Vec<(Loc, String)>,
+    depth: usize,
+) -> Result<(), String> {
+    let (label, code) = split_label(line);
+    let (name, args) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let Some((params, body)) = macros.get(name) else {
+        out.push((loc.clone(), line.to_string()));
+        return Ok(());
+    };
+    if depth == MAX_DEPTH {
+        let e = format!("macros nested too deep in {name}");
+        return Err(loc.report(Error::at(name, e)));
+    }
+    let args: Vec<&str> = args
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .collect();
+    if args.len() != params.len() {
+        let e = format!(
+            "{name} takes {} arguments, not {}",
+            params.len(),
+            args.len()
+        );
+        return Err(loc.report(Error::at(name, e)));
+    }
+    if let Some(label) = label {
+        out.push((loc.clone(), format!("{label}:")));
+    }
+    for b in body {
+        expand(loc, &substitute(b, params, &args), macros, out, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Replaces the parameters of a macro with its arguments, as whole words
+fn substitute(line: &str, params: &[String], args: &[&str]) -> String {
+    let mut out = String::new();
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        match params.iter().position(|p| p == word) {
+            Some(i) => out.push_str(args[i]),
+            None => out.push_str(word),
+        }
+        word.clear();
+    };
+    for c in line.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
+/// Whether `s` can be a label: letters, digits and `_`, not starting with a
+/// digit, and not a register
+fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && keyword(s).is_none()
+}
+
+/// The registers and the other operands written as names
+fn keyword(s: &str) -> Option<Arg> {
+    let upper = s.to_ascii_uppercase();
+    Some(match upper.as_str() {
+        "I" => Arg::I,
+        "[I]" => Arg::AtI,
+        "DT" => Arg::Dt,
+        "ST" => Arg::St,
+        "K" => Arg::K,
+        "F" => Arg::F,
+        "B" => Arg::B,
+        _ => match upper.strip_prefix('V') {
+            Some(x) if x.len() == 1 => Arg::V(u8::from_str_radix(x, 16).ok()?),
+            _ => return None,
+        },
+    })
+}
+
+fn parse_arg(s: &str, labels: &HashMap<String, u32>) -> Result<Arg, Error> {
+    if let Some(arg) = keyword(s) {
+        return Ok(arg);
+    }
+    if let Some(e) = register_typo(s).filter(|_| !labels.contains_key(s)) {
+        return Err(Error::at(s, e));
+    }
+    let name = |w: &str| match labels.get(w) {
+        Some(&n) => Ok(n),
+        None if is_name(w) => Err(match similar(w, labels.keys().map(String::as_str)) {
+            Some(like) => format!("unknown label {w}, did you mean {like}?"),
+            None => format!("unknown label {w}"),
+        }),
+        None => Err(format!("not an operand: {w}")),
+    };
+    // Addresses are written `@0x200` by the disassembler
+    expr::eval(s.strip_prefix('@').unwrap_or(s), &name)
+        .map(Arg::Number)
+        .map_err(|e| Error::at(s, e))
+}
+
+/// What is wrong with `s`, if it looks like a register but is not one, e.g.
+/// `V16`
+fn register_typo(s: &str) -> Option<String> {
+    let n = s.strip_prefix(['V', 'v'])?;
+    if n.len() < 2 || !n.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(match n.parse::<u32>() {
+        Ok(n) => format!("unknown register {s}, did you mean V{:X}?", n.min(0xF)),
+        Err(_) => format!("unknown register {s}, the registers are V0 to VF"),
+    })
+}
+
+fn fits(n: u32, max: u32, what: &str) -> Result<u32, String> {
+    if n <= max {
+        Ok(n)
+    } else {
+        Err(format!("{n:#X} does not fit in {what}"))
+    }
+}
+
+fn address(n: u32) -> Result<u16, String> {
+    fits(n, 0xFFF, "an address").map(|n| n as u16)
+}
+
+fn byte(n: u32) -> Result<u8, String> {
+    fits(n, 0xFF, "a byte").map(|n| n as u8)
+}
+
+fn nibble(n: u32) -> Result<u8, String> {
+    fits(n, 0xF, "a nibble").map(|n| n as u8)
+}
+
+/// Parses one instruction, e.g. `DRW V0, V1, 5`
+fn parse_instr(code: &str, labels: &HashMap<String, u32>) -> Result<Instr, Error> {
+    use Arg::*;
+    let (word, rest) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let args: Vec<Arg> = if rest.trim().is_empty() {
+        vec![]
+    } else {
+        rest.split(',')
+            .map(|a| parse_arg(a.trim(), labels))
+            .collect::<Result<_, _>>()?
+    };
+    let r = |x: &u8| Register::from(*x);
+    let mnemonic = word.to_ascii_uppercase();
+    Ok(match (mnemonic.as_str(), args.as_slice()) {
+        ("CLS", []) => Instr::Clear,
+        ("RET", []) => Instr::Ret,
+        ("SYS", [Number(a)]) => Instr::System {
+            addr: Address::new(address(*a)?),
+        },
+        ("JP", [Number(a)]) => Instr::Goto {
+            addr: Address::new(address(*a)?),
+        },
+        ("JP", [V(0), Number(a)]) => Instr::Jump {
+            n: U12::new(address(*a)?),
+        },
+        ("CALL", [Number(a)]) => Instr::Call {
+            addr: Address::new(address(*a)?),
+        },
+        ("SE", [V(x), Number(c)]) => Instr::SkipEq {
+            r: r(x),
+            c: byte(*c)?,
+        },
+        ("SE", [V(x), V(y)]) => Instr::SkipEqV { r: r(x), s: r(y) },
+        ("SNE", [V(x), Number(c)]) => Instr::SkipNEq {
+            r: r(x),
+            c: byte(*c)?,
+        },
+        ("SNE", [V(x), V(y)]) => Instr::SkipNEqV { r: r(x), s: r(y) },
+        ("LD", [V(x), Number(c)]) => Instr::Set {
+            r: r(x),
+            a: byte(*c)?,
+        },
+        ("LD", [V(x), V(y)]) => Instr::Copy { r: r(x), s: r(y) },
+        ("LD", [I, Number(a)]) => Instr::SetI {
+            n: U12::new(address(*a)?),
+        },
+        ("LD", [V(x), Dt]) => Instr::GetDelay { r: r(x) },
+        ("LD", [V(x), K]) => Instr::LoadKey { r: r(x) },
+        ("LD", [Dt, V(x)]) => Instr::SetDelayTimer { r: r(x) },
+        ("LD", [St, V(x)]) => Instr::SetSoundTimer { r: r(x) },
+        ("LD", [F, V(x)]) => Instr::SpriteAddr { r: r(x) },
+        ("LD", [B, V(x)]) => Instr::StoreBCD { r: r(x) },
+        ("LD", [AtI, V(x)]) => Instr::RegDump { x: Nibble::new(*x) },
+        ("LD", [V(x), AtI]) => Instr::RegLoad { x: Nibble::new(*x) },
+        // The disassembler writes the last register as a number
+        ("LD", [AtI, Number(x)]) => Instr::RegDump {
+            x: Nibble::new(nibble(*x)?),
+        },
+        ("LD", [Number(x), AtI]) => Instr::RegLoad {
+            x: Nibble::new(nibble(*x)?),
+        },
+        ("ADD", [V(x), Number(c)]) => Instr::Incr {
+            r: r(x),
+            a: byte(*c)?,
+        },
+        ("ADD", [V(x), V(y)]) => Instr::Add { r: r(x), s: r(y) },
+        ("ADD", [I, V(x)]) => Instr::IncrI { r: r(x) },
+        ("OR", [V(x), V(y)]) => Instr::BitOr { r: r(x), s: r(y) },
+        ("AND", [V(x), V(y)]) => Instr::BitAnd { r: r(x), s: r(y) },
+        ("XOR", [V(x), V(y)]) => Instr::BitXOr { r: r(x), s: r(y) },
+        ("SUB", [V(x), V(y)]) => Instr::Sub { r: r(x), s: r(y) },
+        ("SUBN", [V(x), V(y)]) => Instr::Lt { r: r(x), s: r(y) },
+        ("SHR", [V(x), V(y)]) => Instr::ShiftR { r: r(x), s: r(y) },
+        ("SHL", [V(x), V(y)]) => Instr::ShiftL { r: r(x), s: r(y) },
+        // Without a source, the register shifts itself under both quirks
+        ("SHR", [V(x)]) => Instr::ShiftR { r: r(x), s: r(x) },
+        ("SHL", [V(x)]) => Instr::ShiftL { r: r(x), s: r(x) },
+        ("RND", [V(x), Number(c)]) => Instr::Rand {
+            r: r(x),
+            n: byte(*c)?,
+        },
+        ("DRW", [V(x), V(y), Number(n)]) => Instr::Draw {
+            x: r(x),
+            y: r(y),
+            height: nibble(*n)?,
+        },
+        ("SKP", [V(x)]) => Instr::Pressed { r: r(x) },
+        ("SKNP" | "SKPN", [V(x)]) => Instr::NotPressed { r: r(x) },
+        (m, _) if MNEMONICS.contains(&m) => {
+            let e = format!("invalid operands for {m}: {}", rest.trim());
+            return Err(Error::at(rest.trim(), e));
+        }
+        _ => {
+            return Err(Error::at(
+                word,
+                match similar(word, MNEMONICS) {
+                    Some(like) => format!("unknown instruction {word}, did you mean {like}?"),
+                    None => format!("unknown instruction {word}"),
+                },
+            ));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::reachable;
+    use crate::disassembler::{Format, disassemble};
+    use crate::symbols::Symbols;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// The ROMs of the test suite
+    fn roms() -> Vec<(PathBuf, Vec<u8>)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let mut roms: Vec<(PathBuf, Vec<u8>)> = fs::read_dir(dir)
+            .expect("the tests directory")
+            .map(|e| e.expect("a test ROM").path())
+            .filter(|p| p.extension().is_some_and(|e| e == "ch8"))
+            .map(|p| (p.clone(), fs::read(&p).expect("a test ROM")))
+            .collect();
+        roms.sort();
+        assert!(!roms.is_empty());
+        roms
+    }
+
+    type Assembler = fn(&str, &Path) -> Result<(Vec<u8>, SourceMap), String>;
+
+    /// Disassembles every test ROM, with and without the code analysis, and
+    /// assembles the listing back into the same bytes
+    fn round_trip(format: Format, assemble: Assembler) {
+        let start = Chip8::CODE_START as u16;
+        for (file, rom) in roms() {
+            let code = reachable(&rom, start);
+            for code in [None, Some(&code)] {
+                let listing = disassemble(&rom, start, format, &Symbols::default(), code);
+                let (bytes, _) = assemble(&listing, &file)
+                    .unwrap_or_else(|e| panic!("{}: {e}\n{listing}", file.display()));
+                assert_eq!(bytes, rom, "{}", file.display());
+            }
+        }
+    }
+
+    #[test]
+    fn asm_round_trip() {
+        round_trip(Format::Asm, assemble);
+    }
+
+    #[test]
+    fn octo_round_trip() {
+        round_trip(Format::Octo, octo::assemble);
+    }
+}
@@ -0,0 +1,658 @@
+//! Octo's assembly language, e.g.
+//!
+//! ```text
+//! : main
+//!     i := hex v2
+//!     loop
+//!         sprite v0 v1 5
+//!         v0 += 6
+//!         if v0 == 60 then v0 := 0
+//!     again
+//! ```
+//!
+//! Statements are separated by spaces, not lines, and comments start with
+//! `#`. `: name` is a label and a name alone calls it. Numbers alone are
+//! bytes, e.g. sprites. `:const name 5` names a number, `:calc name { 5 * 3 }`
+//! names the value of an expression, and `:macro name params { ... }` defines
+//! a macro, used as `name args`.
+
+use super::error::{Error, Loc, similar};
+use super::{MAX_DEPTH, address, byte, expr, nibble};
+use crate::architecture::*;
+use crate::base::*;
+use crate::language::*;
+use crate::sourcemap::SourceMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+/// A word of the source, with its line
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    line: usize,
+    text: &'a str,
+}
+
+/// An error and its line
+type Located = (usize, Error);
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(n, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace()
+                .map(move |text| Token { line: n + 1, text })
+        })
+        .collect()
+}
+
+/// A macro: its parameters and its tokens
+type Macro<'a> = (Vec<&'a str>, Vec<Token<'a>>);
+
+/// Expands the macros. The tokens of a macro have the line that uses it
+fn expand_macros(tokens: Vec<Token<'_>>) -> Result<Vec<Token<'_>>, Located> {
+    let mut macros = HashMap::new();
+    let mut out = vec![];
+    let mut tokens = tokens.into_iter();
+    while let Some(t) = tokens.next() {
+        if t.text != ":macro" {
+            expand(t, &mut tokens, &macros, &mut out, 0)?;
+            continue;
+        }
+        let err = |e: Error| (t.line, e);
+        let name = tokens
+            .next()
+            .ok_or_else(|| err(Error::at(t.text, ":macro without a name".into())))?;
+        if !is_name(name.text) {
+            let e = format!("invalid macro name: {}", name.text);
+            return Err((name.line, Error::at(name.text, e)));
+        }
+        let mut params = vec![];
+        loop {
+            match tokens.next() {
+                Some(p) if p.text == "{" => break,
+                Some(p) if is_name(p.text) => params.push(p.text),
+                Some(p) => {
+                    let e = format!("invalid parameter: {}", p.text);
+                    return Err((p.line, Error::at(p.text, e)));
+                }
+                None => return Err(err(Error::at(t.text, ":macro without {".into()))),
+            }
+        }
+        let (mut body, mut depth) = (vec![], 0);
+        loop {
+            match tokens.next() {
+                Some(b) if b.text == "}" && depth == 0 => break,
+                Some(b) => {
+                    match b.text {
+                        "{" => depth += 1,
+                        "}" => depth -= 1,
+                        _ => (),
+                    }
+                    body.push(b);
+                }
+                None => return Err(err(Error::at(t.text, ":macro without }".into()))),
+            }
+        }
+        macros.insert(name.text, (params, body));
+    }
+    Ok(out)
+}
+
+/// Adds `t` to `out`, expanded if it is a macro, which takes its arguments
+/// from `rest`
+fn expand<'a>(
+    t: Token<'a>,
+    rest: &mut dyn Iterator<Item = Token<'a>>,
+    macros: &HashMap<&'a str, Macro<'a>>,
+    out: &mut Vec<Token<'a>>,
+    depth: usize,
+) -> Result<(), Located> {
+    let Some((params, body)) = macros.get(t.text) else {
+        out.push(t);
+        return Ok(());
+    };
+    let err = |e: String| (t.line, Error::at(t.text, e));
+    if depth == MAX_DEPTH {
+        return Err(err(format!("macros nested too deep in {}", t.text)));
+    }
+    let mut args = HashMap::new();
+    for p in params {
+        let a = rest
+            .next()
+            .ok_or_else(|| err(format!("{} takes {} arguments", t.text, params.len())))?;
+        args.insert(*p, a.text);
+    }
+    let mut body = body.iter().map(|b| Token {
+        line: t.line,
+        text: args.get(b.text).copied().unwrap_or(b.text),
+    });
+    while let Some(b) = body.next() {
+        expand(b, &mut body, macros, out, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// The right side of a comparison
+#[derive(Clone, Copy)]
+enum Rhs {
+    V(Register),
+    N(u8),
+}
+
+/// The condition of an `if` or a `while`
+#[derive(Clone, Copy)]
+enum Cond {
+    Eq(Register, Rhs),
+    NotEq(Register, Rhs),
+    Key(Register),
+    NotKey(Register),
+}
+
+/// The instruction that skips the next one when `cond` is `holds`
+fn skip(cond: Cond, holds: bool) -> Instr {
+    let (cond, holds) = match cond {
+        Cond::NotEq(r, rhs) => (Cond::Eq(r, rhs), !holds),
+        Cond::NotKey(r) => (Cond::Key(r), !holds),
+        c => (c, holds),
+    };
+    match (cond, holds) {
+        (Cond::Eq(r, Rhs::N(c)), true) => Instr::SkipEq { r, c },
+        (Cond::Eq(r, Rhs::N(c)), false) => Instr::SkipNEq { r, c },
+        (Cond::Eq(r, Rhs::V(s)), true) => Instr::SkipEqV { r, s },
+        (Cond::Eq(r, Rhs::V(s)), false) => Instr::SkipNEqV { r, s },
+        (Cond::Key(r), true) => Instr::Pressed { r },
+        (Cond::Key(r), false) => Instr::NotPressed { r },
+        (Cond::NotEq(..) | Cond::NotKey(_), _) => unreachable!("negated above"),
+    }
+}
+
+/// A `loop` or an `if ... begin` that was not closed yet
+enum Block {
+    /// Jumped back to by `again`, and out of by `while`, to the mark `end`
+    Loop { line: usize, start: u32, end: usize },
+    /// Jumps to the mark `otherwise` when the condition does not hold
+    If {
+        line: usize,
+        otherwise: usize,
+        end: usize,
+        has_else: bool,
+    },
+}
+
+/// A pass over the tokens. The first pass finds the addresses of the labels
+/// and of the ends of the blocks, and the second one uses them.
+struct Pass<'a, 't> {
+    tokens: &'t [Token<'a>],
+    pos: usize,
+    /// The line of the last token read, for the errors
+    line: usize,
+    rom: Vec<u8>,
+    /// Whether this is the second pass, where all the labels are known
+    last: bool,
+    /// The labels found by the first pass
+    known: &'t HashMap<String, u32>,
+    labels: HashMap<String, u32>,
+    /// The addresses that the blocks jump to, numbered in the order the
+    /// blocks open. The first pass finds them
+    known_marks: &'t [u32],
+    marks: Vec<u32>,
+    blocks: Vec<Block>,
+    /// Where the bytes come from, and the labels
+    map: SourceMap,
+}
+
+impl<'a> Pass<'a, '_> {
+    fn here(&self) -> u32 {
+        (Chip8::CODE_START + self.rom.len()) as u32
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.rom.extend(instr.encode());
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let t = self
+            .tokens
+            .get(self.pos)
+            .ok_or("unexpected end of the program")?;
+        self.pos += 1;
+        self.line = t.line;
+        Ok(t.text)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|t| t.text)
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), String> {
+        match self.next()? {
+            w if w == word => Ok(()),
+            w => Err(format!("expected {word}, found {w}")),
+        }
+    }
+
+    /// A new mark, to be set by `set_mark`
+    fn mark(&mut self) -> usize {
+        self.marks.push(0);
+        self.marks.len() - 1
+    }
+
+    fn set_mark(&mut self, id: usize) {
+        self.marks[id] = self.here();
+    }
+
+    /// The address of a mark, known in the second pass
+    fn mark_addr(&self, id: usize) -> u32 {
+        self.known_marks.get(id).copied().unwrap_or(0)
+    }
+
+    fn register(&mut self) -> Result<Register, String> {
+        let w = self.next()?;
+        register(w).ok_or(format!("expected a register, found {w}"))
+    }
+
+    /// A number or a label
+    fn value(&mut self) -> Result<u32, String> {
+        let w = self.next()?;
+        self.value_of(w)
+    }
+
+    fn value_of(&self, w: &str) -> Result<u32, String> {
+        if let Some(n) = number(w) {
+            return Ok(n);
+        }
+        if !is_name(w) {
+            return Err(format!("expected a number or a label, found {w}"));
+        }
+        // The constants defined so far, then the labels of the first pass
+        match self.labels.get(w).or(self.known.get(w)) {
+            Some(&addr) => Ok(addr),
+            None if self.last => {
+                let names = self.known.keys().chain(self.labels.keys());
+                Err(match similar(w, names.map(String::as_str)) {
+                    Some(like) => format!("unknown label {w}, did you mean {like}?"),
+                    None => format!("unknown label {w}"),
+                })
+            }
+            // The first pass only needs the sizes
+            None => Ok(0),
+        }
+    }
+
+    /// Names the address of a label or the value of a constant
+    fn define(&mut self, name: &str, value: u32) -> Result<(), String> {
+        if !is_name(name) {
+            return Err(format!("invalid name: {name}"));
+        }
+        if self.labels.insert(name.to_string(), value).is_some() {
+            return Err(format!("{name} is already defined"));
+        }
+        Ok(())
+    }
+
+    fn address(&mut self) -> Result<Address, String> {
+        Ok(Address::new(address(self.value()?)?))
+    }
+
+    fn condition(&mut self) -> Result<Cond, String> {
+        let r = self.register()?;
+        match self.next()? {
+            "key" => Ok(Cond::Key(r)),
+            "-key" => Ok(Cond::NotKey(r)),
+            op @ ("==" | "!=") => {
+                let w = self.next()?;
+                let rhs = match register(w) {
+                    Some(s) => Rhs::V(s),
+                    None => Rhs::N(byte(self.value_of(w)?)?),
+                };
+                Ok(if op == "==" {
+                    Cond::Eq(r, rhs)
+                } else {
+                    Cond::NotEq(r, rhs)
+                })
+            }
+            op => Err(format!("unsupported comparison: {op}")),
+        }
+    }
+
+    /// `vx := vy`, `vx += 1`, ...
+    fn register_statement(&mut self, r: Register) -> Result<(), String> {
+        let op = self.next()?;
+        let w = self.next()?;
+        let instr = match (op, register(w)) {
+            (":=", Some(s)) => Instr::Copy { r, s },
+            (":=", None) => match w {
+                "random" => Instr::Rand {
+                    r,
+                    n: byte(self.value()?)?,
+                },
+                "key" => Instr::LoadKey { r },
+                "delay" => Instr::GetDelay { r },
+                _ => Instr::Set {
+                    r,
+                    a: byte(self.value_of(w)?)?,
+                },
+            },
+            ("+=", Some(s)) => Instr::Add { r, s },
+            ("+=", None) => Instr::Incr {
+                r,
+                a: byte(self.value_of(w)?)?,
+            },
+            ("-=", Some(s)) => Instr::Sub { r, s },
+            ("-=", None) => Instr::Incr {
+                r,
+                a: byte(self.value_of(w)?)?.wrapping_neg(),
+            },
+            ("=-", Some(s)) => Instr::Lt { r, s },
+            ("|=", Some(s)) => Instr::BitOr { r, s },
+            ("&=", Some(s)) => Instr::BitAnd { r, s },
+            ("^=", Some(s)) => Instr::BitXOr { r, s },
+            (">>=", Some(s)) => Instr::ShiftR { r, s },
+            ("<<=", Some(s)) => Instr::ShiftL { r, s },
+            _ => return Err(format!("invalid operation: {r} {op} {w}")),
+        };
+        self.emit(instr);
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let w = self.next()?;
+        match w {
+            ":" => {
+                let name = self.next()?;
+                self.define(name, self.here())?;
+                self.map.labels.insert(self.here() as u16, name.to_string());
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.define(name, value)?;
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let mut words = vec![];
+                loop {
+                    match self.next()? {
+                        "}" => break,
+                        w => words.push(w),
+                    }
+                }
+                let value = match expr::eval(&words.join(" "), &|w| self.value_of(w)) {
+                    Ok(v) => v,
+                    // The labels after it are still 0 in the first pass
+                    Err(_) if !self.last => 0,
+                    Err(e) => return Err(e),
+                };
+                self.define(name, value)?;
+            }
+            ":call" => {
+                let addr = self.address()?;
+                self.emit(Instr::Call { addr });
+            }
+            ":byte" => {
+                let b = byte(self.value()?)?;
+                self.rom.push(b);
+            }
+            "clear" => self.emit(Instr::Clear),
+            "return" | ";" => self.emit(Instr::Ret),
+            "jump" => {
+                let addr = self.address()?;
+                self.emit(Instr::Goto { addr });
+            }
+            "jump0" => {
+                let n = U12::new(address(self.value()?)?);
+                self.emit(Instr::Jump { n });
+            }
+            "native" => {
+                let addr = self.address()?;
+                self.emit(Instr::System { addr });
+            }
+            "bcd" => {
+                let r = self.register()?;
+                self.emit(Instr::StoreBCD { r });
+            }
+            "save" | "load" => {
+                let x = Nibble::new(u8::from(&self.register()?));
+                self.emit(if w == "save" {
+                    Instr::RegDump { x }
+                } else {
+                    Instr::RegLoad { x }
+                });
+            }
+            "sprite" => {
+                let (x, y) = (self.register()?, self.register()?);
+                let height = nibble(self.value()?)?;
+                self.emit(Instr::Draw { x, y, height });
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let r = self.register()?;
+                self.emit(if w == "delay" {
+                    Instr::SetDelayTimer { r }
+                } else {
+                    Instr::SetSoundTimer { r }
+                });
+            }
+            "i" => match self.next()? {
+                ":=" if self.peek() == Some("hex") => {
+                    self.next()?;
+                    let r = self.register()?;
+                    self.emit(Instr::SpriteAddr { r });
+                }
+                ":=" => {
+                    let n = U12::new(address(self.value()?)?);
+                    self.emit(Instr::SetI { n });
+                }
+                "+=" => {
+                    let r = self.register()?;
+                    self.emit(Instr::IncrI { r });
+                }
+                op => return Err(format!("invalid operation: i {op}")),
+            },
+            "loop" => {
+                let end = self.mark();
+                self.blocks.push(Block::Loop {
+                    line: self.line,
+                    start: self.here(),
+                    end,
+                });
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, end, .. }) => {
+                    self.emit(Instr::Goto {
+                        addr: Address::new(address(start)?),
+                    });
+                    self.set_mark(end);
+                }
+                _ => return Err("again without loop".into()),
+            },
+            "while" => {
+                let cond = self.condition()?;
+                let Some(end) = self.blocks.iter().rev().find_map(|b| match b {
+                    Block::Loop { end, .. } => Some(*end),
+                    Block::If { .. } => None,
+                }) else {
+                    return Err("while outside of a loop".into());
+                };
+                self.emit(skip(cond, true));
+                let addr = Address::new(address(self.mark_addr(end))?);
+                self.emit(Instr::Goto { addr });
+            }
+            "if" => {
+                let cond = self.condition()?;
+                match self.next()? {
+                    // The next statement runs when the condition holds
+                    "then" => self.emit(skip(cond, false)),
+                    "begin" => {
+                        let (otherwise, end) = (self.mark(), self.mark());
+                        self.emit(skip(cond, true));
+                        let addr = Address::new(address(self.mark_addr(otherwise))?);
+                        self.emit(Instr::Goto { addr });
+                        self.blocks.push(Block::If {
+                            line: self.line,
+                            otherwise,
+                            end,
+                            has_else: false,
+                        });
+                    }
+                    w => return Err(format!("expected then or begin, found {w}")),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If {
+                    line,
+                    otherwise,
+                    end,
+                    has_else: false,
+                }) => {
+                    let addr = Address::new(address(self.mark_addr(end))?);
+                    self.emit(Instr::Goto { addr });
+                    self.set_mark(otherwise);
+                    self.blocks.push(Block::If {
+                        line,
+                        otherwise,
+                        end,
+                        has_else: true,
+                    });
+                }
+                _ => return Err("else without if ... begin".into()),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If {
+                    otherwise,
+                    end,
+                    has_else,
+                    ..
+                }) => {
+                    if !has_else {
+                        self.set_mark(otherwise);
+                    }
+                    self.set_mark(end);
+                }
+                _ => return Err("end without if ... begin".into()),
+            },
+            _ => {
+                if let Some(r) = register(w) {
+                    return self.register_statement(r);
+                }
+                if let Some(n) = number(w) {
+                    self.rom.push(byte(n)?);
+                } else if is_name(w) {
+                    let addr = Address::new(address(self.value_of(w)?)?);
+                    self.emit(Instr::Call { addr });
+                } else {
+                    return Err(format!("unknown statement: {w}"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `v0` to `vf`
+fn register(w: &str) -> Option<Register> {
+    let x = w.strip_prefix(['v', 'V'])?;
+    (x.len() == 1)
+        .then(|| u8::from_str_radix(x, 16).ok())
+        .flatten()
+        .map(Register::from)
+}
+
+/// A number, where a negative one is a byte in two's complement
+fn number(w: &str) -> Option<u32> {
+    match w.strip_prefix('-') {
+        Some(n) => parse_number(n)
+            .filter(|&n| (1..=0x80).contains(&n))
+            .map(|n| 0x100 - n),
+        None => parse_number(w),
+    }
+}
+
+/// Whether `w` can be a label: letters, digits, `_` and `-`, not starting
+/// with a digit, and not a register or a keyword
+fn is_name(w: &str) -> bool {
+    const KEYWORDS: [&str; 23] = [
+        "clear", "return", "jump", "jump0", "native", "bcd", "save", "load", "sprite", "delay",
+        "buzzer", "i", "loop", "again", "while", "if", "then", "begin", "else", "end", "key",
+        "random", "hex",
+    ];
+    w.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && w.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && register(w).is_none()
+        && !KEYWORDS.contains(&w)
+}
+
+/// The ROM, the labels, the marks and the source map of a pass
+type Passed = (Vec<u8>, HashMap<String, u32>, Vec<u32>, SourceMap);
+
+/// The second pass is `last`. The program is `file`
+fn pass(
+    tokens: &[Token],
+    file: &Path,
+    last: bool,
+    known: &HashMap<String, u32>,
+    known_marks: &[u32],
+) -> Result<Passed, Located> {
+    let mut p = Pass {
+        tokens,
+        pos: 0,
+        line: 0,
+        rom: vec![],
+        last,
+        known,
+        labels: HashMap::new(),
+        known_marks,
+        marks: vec![],
+        blocks: vec![],
+        map: SourceMap::default(),
+    };
+    while p.pos < tokens.len() {
+        let (at, start) = (p.pos, p.here());
+        p.statement().map_err(|e| {
+            // The error is about the last word read
+            let t = tokens[p.pos.max(at + 1) - 1];
+            (t.line, Error::at(t.text, e))
+        })?;
+        if p.here() > start {
+            let line = tokens[at].line;
+            p.map.lines.insert(start as u16, (file.to_path_buf(), line));
+        }
+    }
+    if let Some(b) = p.blocks.last() {
+        return Err(match b {
+            Block::Loop { line, .. } => (*line, Error::at("loop", "loop without again".into())),
+            Block::If { line, .. } => (*line, Error::at("if", "if ... begin without end".into())),
+        });
+    }
+    Ok((p.rom, p.labels, p.marks, p.map))
+}
+
+/// Assembles a program written in Octo, `source` being the text of `file`.
+/// Returns the bytes of the ROM, loaded at `0x200`, and where they come
+/// from, or the first error with its file, its line and its column, the line
+/// and the text that it is about.
+pub fn assemble(source: &str, file: &Path) -> Result<(Vec<u8>, SourceMap), String> {
+    let path = file;
+    let file: Rc<Path> = file.into();
+    let report = |(line, e): Located| {
+        let loc = Loc {
+            file: file.clone(),
+            line,
+            text: source
+                .lines()
+                .nth(line.saturating_sub(1))
+                .unwrap_or_default()
+                .into(),
+        };
+        loc.report(e)
+    };
+    let tokens = expand_macros(tokenize(source)).map_err(report)?;
+    let (_, labels, marks, _) = pass(&tokens, path, false, &HashMap::new(), &[]).map_err(report)?;
+    let (rom, _, _, map) = pass(&tokens, path, true, &labels, &marks).map_err(report)?;
+    Ok((rom, map))
+}
@@ -40,14 +40,26 @@ impl From<Nibble> for u8 {
 }
 
 impl Display for Nibble {
+    // The width is that of every nibble, 0x and a digit
+    #[allow(clippy::unused_format_specs)]
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "{:#03X}", self.0)
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub struct U12(u16);
 
+impl U12 {
+    pub fn new(n: u16) -> Self {
+        assert!(
+            n <= 0xFFF,
+            "U12 must satisfy n <= 0xFFF. Actual value = {n}"
+        );
+        U12(n)
+    }
+}
+
 impl Display for U12 {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "{:#05X}", self.0)
@@ -61,6 +73,12 @@ impl From<U12> for u16 {
     }
 }
 
+impl From<&U12> for u16 {
+    fn from(value: &U12) -> u16 {
+        value.0
+    }
+}
+
 impl From<[UNibble; 3]> for U12 {
     fn from(value: [u8; 3]) -> Self {
         U12(mk_un(value.as_slice()) as u16)
@@ -74,3 +92,14 @@ pub fn mk_un(bs: &[UNibble]) -> u32 {
     }
     ret
 }
+
+/// Parses a decimal, hexadecimal (0x) or binary (0b) number
+pub fn parse_number(s: &str) -> Option<u32> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else {
+        s.parse().ok()
+    }
+}
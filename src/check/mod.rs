@@ -0,0 +1,206 @@
+//! Static checks of a ROM, for `chip-8 check`: what is likely a bug, or
+//! runs differently on other interpreters, found without running it.
+
+use super::analysis::*;
+use super::architecture::*;
+use super::language::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Something suspicious at an address
+pub struct Warning {
+    pub addr: u16,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#05X}: {}", self.addr, self.message)
+    }
+}
+
+/// Checks the code reachable from the start of a ROM loaded at `start`:
+/// - the jumps and the calls out of the ROM or to odd addresses,
+/// - the paths that run into data or past the end of the ROM,
+/// - the bytes that are neither reached nor pointed at by an `LD I`,
+/// - the returns without a call, the recursive subroutines, those that
+///   never return and the calls nested deeper than the stack,
+/// - the opcodes that the quirks of the interpreters change.
+///
+/// The warnings are sorted by their addresses
+pub fn check(rom: &[u8], start: u16) -> Vec<Warning> {
+    let code = reachable(rom, start);
+    let mut warnings = flow(rom, start, &code);
+    warnings.extend(unreachable(rom, start, &code));
+    warnings.extend(stack(&code, start));
+    warnings.extend(quirks(&code));
+    warnings.sort_by_key(|w| w.addr);
+    warnings
+}
+
+/// Where the control goes
+fn flow(rom: &[u8], start: u16, code: &BTreeMap<u16, Instr>) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for (&addr, instr) in code {
+        for (edge, target) in successors(addr, instr) {
+            let verb = match edge {
+                Edge::Next => "continues to",
+                Edge::Skip => "skips to",
+                Edge::Jump => "jumps to",
+                Edge::Call => "calls",
+            };
+            let mut warn = |what: String| {
+                warnings.push(Warning {
+                    addr,
+                    message: format!("{verb} {target:#05X}, {what}"),
+                })
+            };
+            let Some(to) = decode(rom, start, target) else {
+                warn(match edge {
+                    Edge::Next | Edge::Skip => "past the end of the ROM".into(),
+                    Edge::Jump | Edge::Call => "out of the ROM".into(),
+                });
+                continue;
+            };
+            if matches!(edge, Edge::Jump | Edge::Call) && target % 2 == 1 {
+                warn("an odd address".into());
+            }
+            match to {
+                Instr::Data(_) => warn(format!("which is not an instruction: {to}")),
+                Instr::System { .. } => {
+                    warn(format!("which is {to}, that most interpreters ignore"))
+                }
+                _ => (),
+            }
+        }
+    }
+    warnings
+}
+
+/// The runs of bytes that are neither reached nor pointed at by an `LD I`,
+/// except those of zeros, which pad ROMs
+fn unreachable(rom: &[u8], start: u16, code: &BTreeMap<u16, Instr>) -> Vec<Warning> {
+    let mut reached = vec![false; rom.len()];
+    for &addr in code.keys() {
+        let ix = (addr - start) as usize;
+        reached[ix] = true;
+        reached[ix + 1] = true;
+    }
+    let pointed: BTreeSet<u16> = code
+        .values()
+        .filter_map(|instr| match instr {
+            Instr::SetI { n } => Some(u16::from(n)),
+            _ => None,
+        })
+        .collect();
+    let mut warnings = vec![];
+    let mut ix = 0;
+    while ix < rom.len() {
+        if reached[ix] {
+            ix += 1;
+            continue;
+        }
+        let len = reached[ix..]
+            .iter()
+            .position(|&r| r)
+            .unwrap_or(rom.len() - ix);
+        let (first, last) = (start + ix as u16, start + (ix + len - 1) as u16);
+        let zeros = rom[ix..ix + len].iter().all(|&b| b == 0);
+        if !zeros && pointed.range(first..=last).next().is_none() {
+            warnings.push(Warning {
+                addr: first,
+                message: format!(
+                    "{len} bytes up to {last:#05X} are never reached nor pointed at by LD I"
+                ),
+            });
+        }
+        ix += len;
+    }
+    warnings
+}
+
+/// The uses of the stack: returns without a call, recursion, subroutines
+/// that never return, and calls nested deeper than the stack
+fn stack(code: &BTreeMap<u16, Instr>, start: u16) -> Vec<Warning> {
+    let levels = Chip8::new().stack.len();
+    let graph = call_graph(code, start);
+    let mut warnings = vec![];
+    let mut warn = |addr, message: String| warnings.push(Warning { addr, message });
+    for addr in body(code, start) {
+        if code[&addr] == Instr::Ret {
+            warn(
+                addr,
+                "returns outside a subroutine, with an empty stack".into(),
+            );
+        }
+    }
+    for (&entry, routine) in graph.iter().filter(|&(&a, _)| a != start) {
+        if recursive(&graph, entry) {
+            warn(
+                entry,
+                format!("subroutine calls itself, with a stack of {levels} levels"),
+            );
+        }
+        if !routine.returns && code.contains_key(&entry) {
+            warn(
+                entry,
+                "subroutine never returns, and leaves its return address on the stack".into(),
+            );
+        }
+    }
+    let depth = depth(&graph, start, &mut vec![]);
+    if depth > levels {
+        warn(
+            start,
+            format!("calls nest {depth} deep, deeper than the stack of {levels} levels"),
+        );
+    }
+    warnings
+}
+
+/// How deep the calls from a routine nest, without the recursive ones
+fn depth(graph: &BTreeMap<u16, Routine>, entry: u16, path: &mut Vec<u16>) -> usize {
+    let Some(routine) = graph.get(&entry) else {
+        return 0;
+    };
+    path.push(entry);
+    let mut deepest = 0;
+    for &callee in &routine.calls {
+        if !path.contains(&callee) {
+            deepest = deepest.max(1 + depth(graph, callee, path));
+        }
+    }
+    path.pop();
+    deepest
+}
+
+/// The opcodes whose behaviour depends on the quirks of the interpreter,
+/// once per opcode, at its first use
+fn quirks(code: &BTreeMap<u16, Instr>) -> Vec<Warning> {
+    let mut uses: BTreeMap<&str, (u16, usize, &str)> = BTreeMap::new();
+    for (&addr, instr) in code {
+        let info = instr.info();
+        let Some(quirk) = info.quirk else {
+            continue;
+        };
+        // Shifting a register into itself is the same either way
+        if let Instr::ShiftR { r, s } | Instr::ShiftL { r, s } = instr
+            && u8::from(r) == u8::from(s)
+        {
+            continue;
+        }
+        uses.entry(info.encoding).or_insert((addr, 0, quirk)).1 += 1;
+    }
+    uses.into_iter()
+        .map(|(encoding, (addr, count, quirk))| {
+            let others = match count {
+                1 => String::new(),
+                n => format!(", and {} more", n - 1),
+            };
+            Warning {
+                addr,
+                message: format!("{encoding} depends on a quirk ({quirk}){others}"),
+            }
+        })
+        .collect()
+}
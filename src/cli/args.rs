@@ -1,6 +1,17 @@
+use crate::architecture::Platform;
+use crate::base::parse_number;
+use crate::convert::RomFormat;
+use crate::disassembler::Format;
+use crate::font::Font;
+use crate::frames::FrameFormat;
+use crate::gui::Scaling;
+use crate::render::Renderer;
+use crate::sprite::SpriteFormat;
+use crate::trace::TraceFormat;
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
 use std::path::*;
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(version, about)]
@@ -20,7 +31,625 @@ pub enum Commands {
     ///Run the CHIP-8 emulator
     #[command(about = format!("Run the CHIP-8 emulator"))]
     Run {
+        /// The ROM, or the http or https URL of one. Without it, a picker lists
+        /// the ROMs in the current directory
+        #[arg()]
+        file: Option<PathBuf>,
+
+        /// Also load the file FILE at the address ADDR, e.g. a table of data.
+        /// Can be repeated. A file at 0x200 is the ROM, which makes FILE
+        /// optional
+        #[arg(long, num_args = 2, value_names = ["ADDR", "FILE"])]
+        at: Vec<String>,
+
+        /// Check that the ROM has this SHA-256, in hexadecimal, before running
+        /// it. A downloaded ROM that doesn't is not kept
+        #[arg(long)]
+        sha256: Option<String>,
+
+        /// The directory where the ROMs given by URL are downloaded. Defaults
+        /// to `$XDG_CACHE_HOME/chip-8/roms`, or `~/.cache/chip-8/roms`
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+
+        /// Apply this IPS patch to the ROM before loading it, and again when
+        /// it is reloaded
+        #[arg(long)]
+        patch: Option<PathBuf>,
+
+        /// Reload the ROM whenever its file changes, e.g. when it is
+        /// assembled again, as `R` does
+        #[arg(long)]
+        watch: bool,
+
+        /// The interpreter whose quirks are emulated. Defaults to the one of
+        /// the ROM's profile in the ROM database, or to cowgod
+        #[arg(long, value_enum)]
+        quirks: Option<Platform>,
+
+        /// The font of the hexadecimal digits that `LD F` points at. Each
+        /// interpreter drew them its own way
+        #[arg(long, value_enum, default_value_t = Font::Cowgod)]
+        font: Font,
+
+        /// Load the font from a file instead: the 80 bytes of the digits 0
+        /// to F, 5 bytes each, optionally followed by the 160 of the big
+        /// digits of SUPER-CHIP and XO-CHIP, 10 bytes each
+        #[arg(long, conflicts_with = "font")]
+        font_file: Option<PathBuf>,
+
+        /// The instructions that the window runs in each frame. Defaults to
+        /// the ROM's profile in the ROM database, or to 10
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        ipf: Option<u32>,
+
+        /// Don't apply the ROM's profile from the ROM database
+        #[arg(long)]
+        no_profile: bool,
+
+        /// How the display pane draws the pixels. Defaults to a bitmap if the
+        /// terminal seems to support sixel or kitty graphics, and to half blocks
+        /// otherwise
+        #[arg(long, value_enum)]
+        renderer: Option<Renderer>,
+
+        /// Fade pixels out over a few frames while playing, instead of turning
+        /// them off at once
+        #[arg(long)]
+        phosphor: bool,
+
+        /// Draw with ASCII characters only, and mark with characters what is
+        /// otherwise only told by colors. Also set by `accessible = true` in
+        /// the `[debugger]` section of the config
+        #[arg(long)]
+        accessible: bool,
+
+        /// Scale the sixel and kitty bitmaps by at most this factor, instead
+        /// of by the largest one that fits
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..=16))]
+        scale: Option<u16>,
+
+        /// Scale the character renderers by the largest integer factor that
+        /// fills the display pane, in the layouts where it can grow. Also set
+        /// by `fit = true` in the `[debugger]` section of the config
+        #[arg(long)]
+        fit: bool,
+
+        /// Play the ROM in a native window instead of the debugger, with the
+        /// keypad on 1234 QWER ASDF ZXCV. The window is scaled by `--scale`.
+        /// Needs the `gui` feature
+        #[arg(long)]
+        gui: bool,
+
+        /// Open the native window next to the debugger. It shows the screen
+        /// of the current step, and the keys held in it are pressed on the
+        /// keypad. Needs the `gui` feature
+        #[arg(long, conflicts_with = "gui")]
+        window: bool,
+
+        /// Start the window with scanlines, curvature and glow, which F1, F2
+        /// and F3 toggle
+        #[arg(long)]
+        crt: bool,
+
+        /// How the screen fills the window when it is resized. F4 switches it
+        #[arg(long, value_enum, default_value_t = Scaling::Integer)]
+        window_scaling: Scaling,
+
+        /// Record the screen from the start to an animated GIF, saved on
+        /// exit. Recordings can also be started and stopped with `w`
+        #[arg(long)]
+        record: Option<PathBuf>,
+
+        /// The size in pixels of the square each CHIP-8 pixel becomes in
+        /// the recordings
+        #[arg(long, default_value_t = 4)]
+        record_scale: usize,
+
+        /// Write every frame drawn to this directory, one file per frame, or
+        /// else to this file one after the other, e.g. a named pipe read by
+        /// ffmpeg
+        #[arg(long)]
+        dump_frames: Option<PathBuf>,
+
+        /// The format of the dumped frames
+        #[arg(long, value_enum, default_value_t = FrameFormat::Pgm)]
+        dump_format: FrameFormat,
+    },
+
+    /// Assemble a program into a ROM. Programs with the `.8o` extension are
+    /// written in Octo, and the others with the mnemonics of the disassembler
+    Assemble {
+        #[arg()]
+        file: PathBuf,
+
+        /// The ROM. Defaults to the program with the `.ch8` extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Also write a source map next to the ROM, `game.map`, with the line
+        /// of each instruction and the labels, which the debugger reads
+        #[arg(long)]
+        map: bool,
+    },
+
+    /// Format programs in place: labels on their own lines, indented
+    /// instructions, capital mnemonics and registers and aligned comments.
+    /// Programs with the `.8o` extension are formatted as Octo
+    Fmt {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Don't write the files, list those that are not formatted and exit
+        /// with 1 if there are any
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Serve the language server of the programs of `assemble` on the
+    /// standard input and output, for editors
+    Lsp,
+
+    /// Print the instructions of a ROM with their addresses and raw bytes
+    Disassemble {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+
+        /// Write the listing to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// The syntax of the listing
+        #[arg(long, value_enum, default_value_t = Format::Plain)]
+        format: Format,
+
+        /// Write the labels inferred from the jumps, the calls and `LD I` to
+        /// the symbol file of the ROM, `game.sym`, keeping the names already in
+        /// it, and use them in the listing
+        #[arg(long)]
+        write_symbols: bool,
+
+        /// Don't label the targets of the jumps, the calls and `LD I`: use
+        /// only the names of the symbol file
+        #[arg(long, conflicts_with = "write_symbols")]
+        no_labels: bool,
+
+        /// Follow the jumps, the calls and the skips from the start to find
+        /// the instructions, and list the rest as data, instead of decoding
+        /// every two bytes
+        #[arg(long)]
+        traverse: bool,
+    },
+
+    /// Experimental: write the code of a ROM as pseudocode, with its `if`s,
+    /// loops and subroutines and the registers that each of them uses
+    Decompile {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+
+        /// Write the pseudocode to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write the control-flow graph of a ROM in Graphviz's DOT language: its
+    /// basic blocks and the jumps, the skips and the calls between them
+    Cfg {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+
+        /// Write the graph to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the subroutines that a ROM calls as a tree, with their callers
+    /// and those that never return or are recursive
+    Calls {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+
+        /// Write the call graph in Graphviz's DOT language instead
+        #[arg(long)]
+        dot: bool,
+
+        /// Write to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print what a ROM is before running it: its size, SHA-256, memory,
+    /// the opcodes of the extensions that it uses, its first instruction and
+    /// the quirks that it likely needs
+    Info {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+    },
+
+    /// Run the test ROMs built into the emulator without a terminal, and
+    /// check the screens that they leave, run one ROM and check its screen
+    /// against a golden file, or smoke test the ROMs of a directory. Exits
+    /// with 1 if a test fails
+    Test {
+        /// Only run the tests whose names contain one of these
+        #[arg(conflicts_with = "rom")]
+        names: Vec<String>,
+
+        /// Run this ROM instead, and print the screen that it leaves
+        #[arg(long)]
+        rom: Option<PathBuf>,
+
+        /// Run every ROM of this directory and its subdirectories instead, in
+        /// parallel, and report those that crash the emulator, fault, stop
+        /// or reach opcodes that it doesn't run
+        #[arg(long, conflicts_with_all = ["names", "rom"])]
+        dir: Option<PathBuf>,
+
+        /// The instructions to run each ROM for, with `--rom` or `--dir`
+        #[arg(long, default_value_t = 10_000)]
+        steps: u32,
+
+        /// The interpreter whose quirks are emulated, with `--rom` or
+        /// `--dir`. Defaults to the one of the ROM's profile in the ROM
+        /// database, or to cowgod
+        #[arg(long, value_enum)]
+        quirks: Option<Platform>,
+
+        /// Check that the ROM leaves the screen of this file, text art as the
+        /// screenshots or a PGM as `--dump-frames`, and show the difference
+        /// if it doesn't
+        #[arg(long, requires = "rom")]
+        assert_screen: Option<PathBuf>,
+    },
+
+    /// Check a ROM for likely bugs without running it: jumps out of the ROM
+    /// or to odd addresses, paths into data, unreachable bytes, unbalanced
+    /// calls and opcodes that depend on quirks. Exits with 1 on a warning
+    Check {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+    },
+
+    /// Step a ROM in this emulator and in a reference one, and report where they diverge
+    Difftest {
+        #[arg()]
+        file: PathBuf,
+
+        /// The command of the reference emulator. The ROM path is appended to it
+        #[arg(long)]
+        reference: String,
+
+        /// The maximum number of steps
+        #[arg(long, default_value_t = 10_000)]
+        steps: u64,
+
+        /// The interpreter whose quirks are emulated
+        #[arg(long, value_enum, default_value_t = Platform::Cowgod)]
+        quirks: Platform,
+
+        /// The seed of the random numbers of CXNN. The reference emulator
+        /// must use the same one for the two to agree
+        #[arg(long, default_value = "0", value_parser = parse_seed)]
+        seed: u64,
+    },
+
+    /// Run a ROM without a terminal and write a record of each instruction
+    /// that it runs, with the registers that the instruction changed
+    Trace {
+        #[arg()]
+        file: PathBuf,
+
+        /// The instructions to run
+        #[arg(long, default_value = "1000", value_parser = parse_count::<u64>)]
+        cycles: u64,
+
+        /// The syntax of the records
+        #[arg(long, value_enum, default_value_t = TraceFormat::Text)]
+        format: TraceFormat,
+
+        /// The interpreter whose quirks are emulated
+        #[arg(long, value_enum, default_value_t = Platform::Cowgod)]
+        quirks: Platform,
+
+        /// Write the trace to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Run a ROM without a terminal and save the screen that it leaves
+    Screenshot {
+        #[arg()]
+        file: PathBuf,
+
+        /// The instructions to run, with the timers counting down every 10
+        #[arg(long, default_value = "10_000", value_parser = parse_count::<u32>)]
+        cycles: u32,
+
+        /// The file of the screenshot: a PNG, in the colors of the theme, if
+        /// it has the `.png` extension, and text art otherwise
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// The interpreter whose quirks are emulated. Defaults to the one of
+        /// the ROM's profile in the ROM database, or to cowgod
+        #[arg(long, value_enum)]
+        quirks: Option<Platform>,
+    },
+
+    /// Type instructions at a prompt and run them right away on a machine,
+    /// which shows what each one changed
+    Repl {
+        /// The interpreter whose quirks are emulated
+        #[arg(long, value_enum, default_value_t = Platform::Cowgod)]
+        quirks: Platform,
+    },
+
+    /// Dump a ROM in hexadecimal and ASCII, with the instructions of its
+    /// reachable code next to their bytes
+    Hexdump {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+
+        /// Draw each byte of the data as a row of a sprite
+        #[arg(long)]
+        sprites: bool,
+    },
+
+    /// Show the sprites that the code of a ROM draws: the bytes at the
+    /// addresses that `LD I` sets before a `DRW`, with the height it draws
+    Sprites {
+        #[arg()]
+        file: PathBuf,
+
+        /// The address where the ROM is loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+
+        /// Write the gallery to a file instead: a PNG, in the colors of the
+        /// theme, if it has the `.png` extension, and text art otherwise
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Count the opcodes, the quirks and the extensions that the ROMs of a
+    /// directory and its subdirectories use, and their sizes
+    Stats {
+        #[arg()]
+        dir: PathBuf,
+
+        /// The address where the ROMs are loaded
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+    },
+
+    /// Run a ROM without a terminal with two sets of quirks and show the
+    /// pixels and the registers that differ at the end, or show the pixels
+    /// that differ between two saved screens. Exits with 1 if they differ
+    DiffScreens {
+        /// The ROM, or the first screen, text art as the screenshots or a PGM
+        /// as `--dump-frames`
+        #[arg()]
+        file: PathBuf,
+
+        /// The second screen, to compare two saved screens instead
+        #[arg()]
+        other: Option<PathBuf>,
+
+        /// The instructions to run, with the timers counting down every 10
+        #[arg(long, default_value = "10_000", value_parser = parse_count::<u32>)]
+        cycles: u32,
+
+        /// The quirks of the first run
+        #[arg(long, value_enum, default_value_t = Platform::Chip8)]
+        quirks_a: Platform,
+
+        /// The quirks of the second run
+        #[arg(long, value_enum, default_value_t = Platform::Schip)]
+        quirks_b: Platform,
+    },
+
+    /// Convert a ROM between the raw bytes, hexadecimal text and C or Rust
+    /// arrays of the bytes
+    Convert {
+        /// The ROM, raw, or hexadecimal text if it ends in `.hex` or `.txt`
+        #[arg()]
+        file: PathBuf,
+
+        /// Write the ROM to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// The format of the input. Defaults to the one of its extension
+        #[arg(long, value_enum)]
+        from: Option<RomFormat>,
+
+        /// The format of the output. Defaults to the one of the extension of
+        /// the output, `.ch8` raw, `.hex` or `.txt` hexadecimal, `.c` or `.h`
+        /// C and `.rs` Rust, and to hexadecimal on the standard output
+        #[arg(long, value_enum)]
+        to: Option<RomFormat>,
+
+        /// The address where the ROM is loaded, for the comments of the arrays
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+    },
+
+    /// Apply an IPS patch to a ROM and write the patched ROM
+    Patch {
         #[arg()]
         file: PathBuf,
+
+        /// The IPS patch
+        #[arg()]
+        patch: PathBuf,
+
+        /// The file of the patched ROM
+        #[arg(short, long)]
+        output: PathBuf,
     },
+
+    /// Draw a sprite on a grid and print its bytes
+    SpriteEdit {
+        /// The bytes of a sprite to start from, in hexadecimal, e.g.
+        /// "F0 90 90 F0" or "0xF0, 0x90"
+        #[arg(long)]
+        bytes: Option<String>,
+
+        /// Edit a 16x16 sprite, as SUPER-CHIP draws with DXY0, instead of one
+        /// 8 pixels wide
+        #[arg(long)]
+        wide: bool,
+
+        /// How the bytes are printed
+        #[arg(long, value_enum, default_value_t = SpriteFormat::Db)]
+        format: SpriteFormat,
+    },
+
+    /// Create a starter assembly project in a new directory: a program that
+    /// moves a sprite, its sprites, and a Makefile that assembles it and
+    /// runs it with `--watch`
+    New {
+        #[arg()]
+        dir: PathBuf,
+    },
+
+    /// Run a ROM as fast as possible without a terminal, in the interpreter,
+    /// in the interpreter with cached decoding and in other cores, and report
+    /// their speeds
+    Bench {
+        #[arg()]
+        file: PathBuf,
+
+        /// The instructions to run in each core
+        #[arg(long, default_value = "1_000_000", value_parser = parse_count::<u64>)]
+        cycles: u64,
+
+        /// The instructions of a frame, for the time per frame
+        #[arg(long, default_value_t = 10)]
+        ipf: u64,
+
+        /// The interpreter whose quirks are emulated
+        #[arg(long, value_enum, default_value_t = Platform::Cowgod)]
+        quirks: Platform,
+
+        /// The command of another core, which speaks the protocol of
+        /// `difftest`. The ROM path is appended to it. Can be repeated
+        #[arg(long)]
+        core: Vec<String>,
+    },
+
+    /// Replay a run recorded with :record and check that it reproduces the same states
+    Replay {
+        #[arg()]
+        file: PathBuf,
+
+        /// The replay file
+        #[arg()]
+        replay: PathBuf,
+
+        /// The interpreter whose quirks are emulated
+        #[arg(long, value_enum, default_value_t = Platform::Cowgod)]
+        quirks: Platform,
+    },
+
+    /// Check a state saved with :state save: its version, that it is of the
+    /// ROM, and that its stack pointer and addresses are possible. Prints a
+    /// summary of the state, and exits with 1 if it is not valid
+    ValidateState {
+        #[arg()]
+        file: PathBuf,
+
+        /// The ROM that the state must be of
+        #[arg(long)]
+        rom: Option<PathBuf>,
+    },
+
+    /// Run a ROM as a reference emulator for difftest, speaking its protocol on stdin/stdout
+    Serve {
+        #[arg()]
+        file: PathBuf,
+
+        /// The interpreter whose quirks are emulated
+        #[arg(long, value_enum, default_value_t = Platform::Cowgod)]
+        quirks: Platform,
+
+        /// The seed of the random numbers of CXNN. The reference emulator
+        /// must use the same one for the two to agree
+        #[arg(long, default_value = "0", value_parser = parse_seed)]
+        seed: u64,
+    },
+
+    /// Run a ROM under two quirk profiles in lockstep and show where they diverge
+    Compare {
+        #[arg()]
+        file: PathBuf,
+
+        /// The quirks of the left machine
+        #[arg(long, value_enum, default_value_t = Platform::Chip8)]
+        quirks_a: Platform,
+
+        /// The quirks of the right machine
+        #[arg(long, value_enum, default_value_t = Platform::Schip)]
+        quirks_b: Platform,
+    },
+}
+
+/// A count, with `_` between its digits if wanted, e.g. `10_000_000`
+fn parse_count<T: FromStr>(s: &str) -> Result<T, String> {
+    s.replace('_', "")
+        .parse()
+        .map_err(|_| format!("not a count: {s}"))
+}
+
+/// A seed of the random numbers, in hexadecimal as in the replays, e.g.
+/// `0x1234`, or in decimal
+fn parse_seed(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("not a seed: {s}"))
+}
+
+/// The files of `run --at`, with their addresses
+pub fn segments(at: &[String]) -> Result<Vec<(u16, PathBuf)>, String> {
+    at.chunks(2)
+        .map(|pair| Ok((parse_address(&pair[0])?, PathBuf::from(&pair[1]))))
+        .collect()
+}
+
+/// An address in memory, e.g. `0x200` or `512`
+fn parse_address(s: &str) -> Result<u16, String> {
+    parse_number(s)
+        .filter(|&a| a <= 0xFFF)
+        .map(|a| a as u16)
+        .ok_or(format!("not an address: {s}"))
 }
@@ -0,0 +1,179 @@
+use super::architecture::*;
+use super::debugger::*;
+use super::render;
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::*;
+use ratatui::style::{Color, Stylize};
+use ratatui::text::*;
+use ratatui::widgets::*;
+use std::io::Result;
+
+/// Two machines running the same program in lockstep, usually with different
+/// quirks
+pub struct Compare {
+    a: Debugger,
+    b: Debugger,
+    platforms: [Platform; 2],
+    /// The first step where the two machines differ
+    divergence: Option<usize>,
+    message: Option<String>,
+}
+
+impl Widget for &Compare {
+    fn render(self, area: Rect, buf: &mut ratatui::buffer::Buffer) {
+        /// Pixels that differ from the other machine are highlighted
+        fn display<'a>(title: String, c: &Chip8, other: &Chip8) -> Paragraph<'a> {
+            let title: Line = Line::from(title).bold().blue().centered();
+            let lines = render::half_blocks(1, |r, col| {
+                let now = c.screen.rows[r][col];
+                if other.screen.rows[r][col] != now {
+                    Some(Color::Yellow)
+                } else {
+                    now.then_some(Color::White)
+                }
+            });
+            Paragraph::new(Text::from(lines))
+                .block(Block::bordered().title(title))
+                .centered()
+        }
+
+        /// Values that differ from the other machine are highlighted
+        fn registers<'a>(c: &Chip8, other: &Chip8) -> Paragraph<'a> {
+            let field = |name: String, now: u16, then: u16| -> Vec<Span<'a>> {
+                let v = Span::from(format!("{now:#04X}"));
+                vec![
+                    Span::from(format!("{name} ")),
+                    if now != then {
+                        v.black().on_yellow()
+                    } else {
+                        v
+                    },
+                    Span::from("  "),
+                ]
+            };
+            let mut lines: Vec<Line> = vec![Line::from(
+                [
+                    field("PC".into(), c.pc, other.pc),
+                    field("I".into(), c.i, other.i),
+                    field("SP".into(), c.sp as u16, other.sp as u16),
+                    field("DT".into(), c.delay as u16, other.delay as u16),
+                    field("ST".into(), c.sound as u16, other.sound as u16),
+                ]
+                .concat(),
+            )];
+            for half in 0..2 {
+                let spans: Vec<Span> = (0..8)
+                    .flat_map(|i| {
+                        let r = Register::from(half * 8 + i);
+                        field(r.to_string(), c.rv(r) as u16, other.rv(r) as u16)
+                    })
+                    .collect();
+                lines.push(Line::from(spans));
+            }
+            let title: Line = Line::from("Registers").bold().blue().centered();
+            Paragraph::new(Text::from(lines)).block(Block::bordered().title(title))
+        }
+
+        let (a, b) = (self.a.peek(), self.b.peek());
+        let header = Line::from(vec![
+            Span::from(format!(
+                "step {}/{}  ",
+                self.a.step_number(),
+                self.a.step_max()
+            )),
+            match self.divergence {
+                Some(d) => Span::from(format!("first divergence at step {d}")).red(),
+                None => Span::from("no divergence yet").green(),
+            },
+            Span::from(format!(
+                "  now differing: {}",
+                match a.differences(b) {
+                    d if d.is_empty() => "nothing".to_string(),
+                    d => d.join(", "),
+                }
+            )),
+        ]);
+        let [header_area, main_area, message_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+        let columns: [Rect; 2] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(main_area);
+        header.render(header_area, buf);
+        for (ix, (c, other)) in [(a, b), (b, a)].into_iter().enumerate() {
+            let [display_area, registers_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(5)]).areas(columns[ix]);
+            let title = format!("{} ({})", ["A", "B"][ix], self.platforms[ix]);
+            display(title, c, other).render(display_area, buf);
+            registers(c, other).render(registers_area, buf);
+        }
+        let help = "n/p step  N/P 10 steps  r run to divergence  q quit";
+        Line::from(self.message.clone().unwrap_or(help.into()))
+            .italic()
+            .render(message_area, buf);
+    }
+}
+
+impl Compare {
+    pub fn new(a: Chip8, b: Chip8, platforms: [Platform; 2]) -> Self {
+        Compare {
+            a: Debugger::new(a),
+            b: Debugger::new(b),
+            platforms,
+            divergence: None,
+            message: None,
+        }
+    }
+
+    pub fn step_forward(&mut self) {
+        self.a.step_forward();
+        self.b.step_forward();
+        // Steps are visited in order, so the first divergence seen is the first
+        let p = self.a.step_number();
+        if self.divergence.is_none() && !self.a.peek().differences(self.b.peek()).is_empty() {
+            self.divergence = Some(p);
+        }
+    }
+
+    pub fn step_back(&mut self) {
+        self.a.step_back();
+        self.b.step_back();
+    }
+
+    /// Steps forward until the machines differ, for at most `limit` steps
+    pub fn run_to_divergence(&mut self, limit: u32) {
+        for _ in 0..limit {
+            self.step_forward();
+            if !self.a.peek().differences(self.b.peek()).is_empty() {
+                self.message = None;
+                return;
+            }
+        }
+        self.message = Some(format!("no divergence after {limit} steps"));
+    }
+
+    pub fn run(mut self, mut terminal: ratatui::DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
+            let Event::Key(k) = crossterm::event::read()? else {
+                continue;
+            };
+            if k.kind != KeyEventKind::Press {
+                continue;
+            }
+            match (k.modifiers, k.code) {
+                (_, KeyCode::Esc | KeyCode::Char('q'))
+                | (KeyModifiers::CONTROL, KeyCode::Char('c')) => break,
+                (_, KeyCode::Char('n') | KeyCode::Right) => self.step_forward(),
+                (_, KeyCode::Char('p') | KeyCode::Left) => self.step_back(),
+                (_, KeyCode::Char('N')) => (0..10).for_each(|_| self.step_forward()),
+                (_, KeyCode::Char('P')) => (0..10).for_each(|_| self.step_back()),
+                (_, KeyCode::Char('r')) => self.run_to_divergence(10_000),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::*;
+use std::path::PathBuf;
+
+/// The user's settings. They are read from `chip-8/config.toml` in the XDG
+/// config directory, which is written in a subset of TOML: `[section]`
+/// headers followed by `key = value` lines, where a value is a quoted string,
+/// a number or a boolean. `#` starts a comment.
+pub struct Config {
+    /// The values of each section, by key. Quotes are removed from strings
+    pub sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip-8/config.toml`, or `~/.config/chip-8/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(d) if !d.is_empty() => PathBuf::from(d),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("chip-8").join("config.toml"))
+    }
+
+    /// Loads the config file. A missing file means the default settings.
+    pub fn load() -> Result<Config> {
+        let mut sections = BTreeMap::new();
+        let Some(path) = Self::path() else {
+            return Ok(Config { sections });
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config { sections }),
+            Err(e) => return Err(e),
+        };
+        let mut section = String::new();
+        for (n, line) in contents.lines().enumerate() {
+            let invalid = |what: &str| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}:{}: {what}", path.display(), n + 1),
+                )
+            };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(invalid("expected [section] or key = value"))?;
+            let value = value.trim();
+            let value = match value.strip_prefix('"') {
+                Some(v) => v
+                    .strip_suffix('"')
+                    .ok_or(invalid("unterminated string"))?
+                    .to_string(),
+                None => value.to_string(),
+            };
+            let key = key.trim().trim_matches('"').to_string();
+            sections
+                .entry(section.clone())
+                .or_insert_with(BTreeMap::new)
+                .insert(key, value);
+        }
+        Ok(Config { sections })
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section)?.get(key).map(|v| v.as_str())
+    }
+}
+
+/// Removes a `#` comment, unless the `#` is inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (ix, ch) in line.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..ix],
+            _ => (),
+        }
+    }
+    line
+}
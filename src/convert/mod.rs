@@ -0,0 +1,114 @@
+//! The formats that `chip-8 convert` reads and writes ROMs in: the raw
+//! bytes, hexadecimal text and arrays of the bytes in C and Rust source.
+
+use super::analysis::reachable;
+use super::disassembler::{self, Format};
+use super::symbols::Symbols;
+use clap::ValueEnum;
+use std::path::Path;
+
+/// How a ROM is written
+#[derive(PartialEq, Eq, Clone, Copy, Debug, ValueEnum)]
+pub enum RomFormat {
+    /// The bytes themselves, as `.ch8` files
+    Raw,
+    /// The bytes in hexadecimal, 16 per line
+    Hex,
+    /// A C array of the bytes, with the mnemonics in comments
+    C,
+    /// A Rust array of the bytes, with the mnemonics in comments
+    Rust,
+}
+
+/// The bytes of a row of `RomFormat::Hex`
+const HEX_WIDTH: usize = 16;
+
+impl RomFormat {
+    /// The format of a file, from its extension: `.hex` and `.txt` are
+    /// hexadecimal, `.c` and `.h` C, `.rs` Rust, and others raw
+    pub fn of(path: &Path) -> RomFormat {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "hex" | "txt" => RomFormat::Hex,
+            "c" | "h" => RomFormat::C,
+            "rs" => RomFormat::Rust,
+            _ => RomFormat::Raw,
+        }
+    }
+}
+
+/// Reads a ROM from the contents of a file. The text formats are read
+/// leniently, so that hexadecimal pasted from anywhere works: the comments,
+/// what comes before an `=`, the brackets, the separators and the `0x` are
+/// left out, and the rest must be pairs of hexadecimal digits, as in
+/// `00E0 A22A` or `0x00, 0xE0`. That reads back every text format
+pub fn read(contents: &[u8], format: RomFormat) -> Result<Vec<u8>, String> {
+    if format == RomFormat::Raw {
+        return Ok(contents.to_vec());
+    }
+    let text = std::str::from_utf8(contents).map_err(|_| "the text is not UTF-8".to_string())?;
+    let mut code = String::new();
+    let mut rest = text;
+    // Without the comments
+    while !rest.is_empty() {
+        let line_comment = ["//", "#"].iter().filter_map(|c| rest.find(c)).min();
+        match (rest.find("/*"), line_comment) {
+            (Some(block), line) if line.is_none_or(|l| block < l) => {
+                code += &rest[..block];
+                rest = rest[block..].split_once("*/").map_or("", |(_, r)| r);
+            }
+            (_, Some(line)) => {
+                code += &rest[..line];
+                rest = rest[line..].split_once('\n').map_or("", |(_, r)| r);
+                code.push('\n');
+            }
+            (_, None) => {
+                code += rest;
+                rest = "";
+            }
+        }
+    }
+    let code = code.split_once('=').map_or(code.as_str(), |(_, r)| r);
+    let mut rom = vec![];
+    for word in code.split(|c: char| c.is_whitespace() || ",;{}[]".contains(c)) {
+        let digits = word
+            .strip_prefix("0x")
+            .or(word.strip_prefix("0X"))
+            .unwrap_or(word);
+        if digits.is_empty() {
+            continue;
+        }
+        if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("not hexadecimal bytes: {word}"));
+        }
+        for pair in digits.as_bytes().chunks(2) {
+            let pair = std::str::from_utf8(pair).expect("ASCII digits");
+            rom.push(u8::from_str_radix(pair, 16).expect("hexadecimal digits"));
+        }
+    }
+    Ok(rom)
+}
+
+/// Writes a ROM loaded at `start`. The mnemonics of the arrays are those of
+/// the code reachable from the start, and the rest are bytes of data
+pub fn write(rom: &[u8], start: u16, format: RomFormat) -> Vec<u8> {
+    let array = |format| {
+        let code = reachable(rom, start);
+        disassembler::disassemble(rom, start, format, &Symbols::default(), Some(&code))
+    };
+    match format {
+        RomFormat::Raw => rom.to_vec(),
+        RomFormat::Hex => {
+            let lines: Vec<String> = rom
+                .chunks(HEX_WIDTH)
+                .map(|row| {
+                    let bytes: Vec<String> = row.iter().map(|b| format!("{b:02X}")).collect();
+                    bytes.join(" ") + "\n"
+                })
+                .collect();
+            lines.concat().into_bytes()
+        }
+        RomFormat::C => array(Format::CArray).into_bytes(),
+        RomFormat::Rust => array(Format::RustArray).into_bytes(),
+    }
+}
@@ -0,0 +1,350 @@
+//! Emulator cores that can be stepped side by side for differential testing.
+//!
+//! External cores are processes that speak a line based protocol. The process
+//! is started with the ROM path as its last argument and prints its initial
+//! state. Then, for each `step` line it reads, it runs one instruction and
+//! prints its new state. A state is a line of hexadecimal numbers:
+//!
+//! ```text
+//! pc i sp dt st v0 v1 v2 v3 v4 v5 v6 v7 v8 v9 va vb vc vd ve vf
+//! ```
+//!
+//! A line starting with `error` reports that the core cannot continue.
+
+use super::architecture::*;
+use super::language::*;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::*;
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// The part of the machine state that cores are compared on
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct CoreState {
+    pub pc: u16,
+    pub i: u16,
+    pub sp: u8,
+    pub delay: u8,
+    pub sound: u8,
+    pub registers: [u8; 16],
+}
+
+impl Display for CoreState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:X} {:X} {:X} {:X} {:X}",
+            self.pc, self.i, self.sp, self.delay, self.sound
+        )?;
+        for r in self.registers {
+            write!(f, " {r:X}")?;
+        }
+        Ok(())
+    }
+}
+
+impl CoreState {
+    pub fn parse(line: &str) -> std::result::Result<CoreState, String> {
+        let nums: Vec<u16> = line
+            .split_whitespace()
+            .map(|w| u16::from_str_radix(w, 16).map_err(|_| format!("not a number: {w}")))
+            .collect::<std::result::Result<_, _>>()?;
+        let [pc, i, sp, dt, st, v @ ..] = nums.as_slice() else {
+            return Err(format!("expected 21 numbers: {line}"));
+        };
+        let registers: [u16; 16] = v
+            .try_into()
+            .map_err(|_| format!("expected 21 numbers: {line}"))?;
+        Ok(CoreState {
+            pc: *pc,
+            i: *i,
+            sp: *sp as u8,
+            delay: *dt as u8,
+            sound: *st as u8,
+            registers: registers.map(|r| r as u8),
+        })
+    }
+
+    /// The registers with their names, `PC`, `I`, `SP`, `DT`, `ST` and `V0`
+    /// to `VF`
+    pub fn named(&self) -> Vec<(String, u16)> {
+        let mut named: Vec<(String, u16)> = [
+            ("PC", self.pc),
+            ("I", self.i),
+            ("SP", self.sp as u16),
+            ("DT", self.delay as u16),
+            ("ST", self.sound as u16),
+        ]
+        .map(|(name, v)| (name.to_string(), v))
+        .into();
+        for r in 0..16 {
+            named.push((
+                Register::from(r as u8).to_string(),
+                self.registers[r] as u16,
+            ));
+        }
+        named
+    }
+
+    /// Names of the registers that differ from `other`
+    pub fn differences(&self, other: &CoreState) -> Vec<String> {
+        self.named()
+            .into_iter()
+            .zip(other.named())
+            .filter(|((_, a), (_, b))| a != b)
+            .map(|((name, _), _)| name)
+            .collect()
+    }
+}
+
+pub trait EmulatorCore {
+    fn name(&self) -> String;
+
+    /// Runs one instruction
+    fn step(&mut self) -> std::result::Result<(), String>;
+
+    fn state(&self) -> CoreState;
+}
+
+impl EmulatorCore for Chip8 {
+    fn name(&self) -> String {
+        "chip-8".into()
+    }
+
+    fn step(&mut self) -> std::result::Result<(), String> {
+        match self.read_instr() {
+            Instr::Data(_) => Err(format!("data at pc {:#05X} is not an instruction", self.pc)),
+            _ => {
+                self.run_instr();
+                Ok(())
+            }
+        }
+    }
+
+    fn state(&self) -> CoreState {
+        CoreState {
+            pc: self.pc,
+            i: self.i,
+            sp: self.sp,
+            delay: self.delay,
+            sound: self.sound,
+            registers: self.registers.map(|r| r.0),
+        }
+    }
+}
+
+/// The interpreter with the opcodes decoded once, the first time that they
+/// run, instead of at every step
+pub struct CachedCore {
+    pub chip: Chip8,
+    /// The instructions by their opcodes
+    decoded: Vec<Option<Instr>>,
+}
+
+impl CachedCore {
+    pub fn new(chip: Chip8) -> CachedCore {
+        CachedCore {
+            chip,
+            decoded: vec![None; 1 << 16],
+        }
+    }
+}
+
+impl EmulatorCore for CachedCore {
+    fn name(&self) -> String {
+        "chip-8 (cached decode)".into()
+    }
+
+    fn step(&mut self) -> std::result::Result<(), String> {
+        let pc = self.chip.pc as usize;
+        let bytes = [self.chip.memory[pc], self.chip.memory[pc + 1]];
+        let instr = self.decoded[u16::from_be_bytes(bytes) as usize]
+            .get_or_insert_with(|| RawInstr::from_bytes(bytes).into_instr())
+            .clone();
+        match instr {
+            Instr::Data(_) => Err(format!("data at pc {pc:#05X} is not an instruction")),
+            _ => {
+                self.chip.execute(instr);
+                Ok(())
+            }
+        }
+    }
+
+    fn state(&self) -> CoreState {
+        self.chip.state()
+    }
+}
+
+/// A core running in another process
+pub struct ExternalCore {
+    command: String,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    state: CoreState,
+}
+
+impl ExternalCore {
+    /// Starts `command` with the ROM path appended to its arguments
+    pub fn spawn(command: &str, rom: &Path) -> Result<ExternalCore> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or(Error::new(ErrorKind::InvalidInput, "empty command"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .arg(rom)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut core = ExternalCore {
+            command: command.into(),
+            child,
+            stdin,
+            stdout,
+            state: CoreState::parse("0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0")
+                .expect("valid state"),
+        };
+        core.state = core
+            .read_state()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        Ok(core)
+    }
+
+    fn read_state(&mut self) -> std::result::Result<CoreState, String> {
+        let mut line = String::new();
+        match self.stdout.read_line(&mut line) {
+            Ok(0) => Err("the reference exited".into()),
+            Ok(_) if line.starts_with("error") => Err(line.trim().into()),
+            Ok(_) => CoreState::parse(line.trim()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+impl Drop for ExternalCore {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl EmulatorCore for ExternalCore {
+    fn name(&self) -> String {
+        self.command.clone()
+    }
+
+    fn step(&mut self) -> std::result::Result<(), String> {
+        writeln!(self.stdin, "step").map_err(|e| e.to_string())?;
+        self.stdin.flush().map_err(|e| e.to_string())?;
+        self.state = self.read_state()?;
+        Ok(())
+    }
+
+    fn state(&self) -> CoreState {
+        self.state.clone()
+    }
+}
+
+/// Where two cores stopped agreeing
+pub struct Divergence {
+    pub step: u64,
+    /// The differing registers, or the error that stopped one of the cores
+    pub reason: Vec<String>,
+    pub states: [CoreState; 2],
+}
+
+/// Steps both cores until their states differ, for at most `steps` steps
+pub fn difftest(
+    a: &mut dyn EmulatorCore,
+    b: &mut dyn EmulatorCore,
+    steps: u64,
+) -> Option<Divergence> {
+    for step in 0..=steps {
+        let reason = if step == 0 {
+            vec![]
+        } else {
+            match (a.step(), b.step()) {
+                (Ok(()), Ok(())) => vec![],
+                (ra, rb) => [
+                    ra.err().map(|e| format!("{}: {e}", a.name())),
+                    rb.err().map(|e| format!("{}: {e}", b.name())),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            }
+        };
+        let states = [a.state(), b.state()];
+        let reason = if reason.is_empty() {
+            states[0].differences(&states[1])
+        } else {
+            reason
+        };
+        if !reason.is_empty() {
+            return Some(Divergence {
+                step,
+                reason,
+                states,
+            });
+        }
+    }
+    None
+}
+
+/// How fast a core ran
+pub struct Bench {
+    pub name: String,
+    /// The instructions run
+    pub steps: u64,
+    pub elapsed: Duration,
+    /// The error that stopped the core before all the steps
+    pub error: Option<String>,
+}
+
+impl Bench {
+    pub fn per_second(&self) -> f64 {
+        self.steps as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Runs `steps` instructions of a core as fast as it goes
+pub fn bench(core: &mut dyn EmulatorCore, steps: u64) -> Bench {
+    let start = Instant::now();
+    let mut error = None;
+    let mut ran = 0;
+    while ran < steps {
+        if let Err(e) = core.step() {
+            error = Some(e);
+            break;
+        }
+        ran += 1;
+    }
+    Bench {
+        name: core.name(),
+        steps: ran,
+        elapsed: start.elapsed(),
+        error,
+    }
+}
+
+/// Speaks the external core protocol on stdin and stdout
+pub fn serve(chip: &mut Chip8) -> Result<()> {
+    let mut out = stdout();
+    writeln!(out, "{}", chip.state())?;
+    for line in stdin().lines() {
+        match line?.trim() {
+            "step" => match chip.step() {
+                Ok(()) => writeln!(out, "{}", chip.state())?,
+                Err(e) => writeln!(out, "error {e}")?,
+            },
+            other => writeln!(out, "error unknown command: {other}")?,
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
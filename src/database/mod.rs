@@ -0,0 +1,113 @@
+use super::architecture::*;
+use super::config::Config;
+use super::info::sha256;
+use super::json::Json;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::*;
+use std::path::PathBuf;
+
+/// The profiles that come with the emulator, in the format of `roms.json`
+const BUILT_IN: &str = include_str!("roms.json");
+
+/// How a known ROM runs best
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub title: String,
+    pub author: Option<String>,
+    /// The interpreter whose quirks it needs
+    pub quirks: Platform,
+    /// The instructions to run in each frame, if it needs a speed of its own
+    pub ipf: Option<u32>,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.title)?;
+        if let Some(author) = &self.author {
+            write!(f, " by {author}")?;
+        }
+        write!(f, " ({}", self.quirks)?;
+        if let Some(ipf) = self.ipf {
+            write!(f, ", {ipf} instructions per frame")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The profiles of the known ROMs, by the SHA-256 of the ROM. Those that
+/// come with the emulator are completed by `chip-8/roms.json` in the XDG
+/// config directory, an array of objects like:
+///
+/// ```text
+/// {"sha256": "1c7e14ea...", "title": "Corax+ opcode test",
+///  "author": "corax89", "quirks": "chip8", "ipf": 15}
+/// ```
+///
+/// where only `sha256`, `title` and `quirks` are required. Its profiles
+/// replace the built-in ones of the same ROMs
+pub struct Database {
+    profiles: HashMap<String, Profile>,
+}
+
+impl Database {
+    /// `$XDG_CONFIG_HOME/chip-8/roms.json`, or `~/.config/chip-8/roms.json`
+    pub fn path() -> Option<PathBuf> {
+        Some(Config::path()?.with_file_name("roms.json"))
+    }
+
+    /// Loads the built-in profiles and those of the user's file, if there is one
+    pub fn load() -> Result<Database> {
+        let mut profiles = parse(BUILT_IN, "the built-in ROM database")?;
+        let Some(path) = Self::path() else {
+            return Ok(Database { profiles });
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => profiles.extend(parse(&contents, &path.display().to_string())?),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        Ok(Database { profiles })
+    }
+
+    /// The profile of a ROM, if it is known
+    pub fn get(&self, rom: &[u8]) -> Option<&Profile> {
+        self.profiles.get(&sha256(rom))
+    }
+}
+
+/// The profiles of a database file, by their hashes. `source` names the file
+/// in the errors
+fn parse(contents: &str, source: &str) -> Result<HashMap<String, Profile>> {
+    let invalid = |what: String| Error::new(ErrorKind::InvalidData, format!("{source}: {what}"));
+    let json = Json::parse(contents).map_err(invalid)?;
+    let Json::Array(entries) = json else {
+        return Err(invalid("expected an array of ROMs".into()));
+    };
+    let mut profiles = HashMap::new();
+    for (n, entry) in entries.iter().enumerate() {
+        let field = |key: &str| entry.get(key).and_then(Json::as_str);
+        let missing = |key: &str| invalid(format!("ROM {} has no {key}", n + 1));
+        let hash = field("sha256").ok_or_else(|| missing("sha256"))?;
+        let quirks = field("quirks").ok_or_else(|| missing("quirks"))?;
+        let profile = Profile {
+            title: field("title").ok_or_else(|| missing("title"))?.to_string(),
+            author: field("author").map(String::from),
+            quirks: Platform::from_str(quirks, true)
+                .map_err(|_| invalid(format!("ROM {} has unknown quirks {quirks}", n + 1)))?,
+            ipf: match entry.get("ipf") {
+                Some(ipf) => Some(
+                    ipf.as_usize()
+                        .and_then(|ipf| u32::try_from(ipf).ok())
+                        .filter(|&ipf| ipf > 0)
+                        .ok_or_else(|| invalid(format!("ROM {} has an invalid ipf", n + 1)))?,
+                ),
+                None => None,
+            },
+        };
+        profiles.insert(hash.to_ascii_lowercase(), profile);
+    }
+    Ok(profiles)
+}
@@ -1,8 +1,180 @@
 use super::architecture::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::num::Wrapping;
 
 pub struct Debugger {
     pub history: Vec<Chip8>,
     pub p: usize,
     pub p_max: usize,
     pub diff: bool,
+    /// Bookmarked steps, by name
+    pub marks: BTreeMap<char, usize>,
+    pub breakpoints: Vec<Breakpoint>,
+    /// Manual edits that can be undone, the last one on top
+    pub undo: Vec<Edit>,
+    /// Undone edits that can be applied again, the last one on top
+    pub redo: Vec<Edit>,
+}
+
+/// A manual change to the machine state, e.g. a memory poke
+pub struct Edit {
+    /// The step the edit was applied to
+    pub step: usize,
+    /// The edited state, which follows `step` in the history
+    pub state: Chip8,
+    /// The recorded steps that the edit discarded
+    pub discarded: Vec<Chip8>,
+    /// The debugger's `p_max` before the edit
+    pub p_max: usize,
+    /// The debugger's marks before the edit
+    pub marks: BTreeMap<char, usize>,
+}
+
+/// A register that can be inspected or edited by the user
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Operand {
+    V(Register),
+    I,
+    Pc,
+    Delay,
+    Sound,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CmpOp {
+    Eq,
+    NEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A condition such as `V3 == 5`
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Condition {
+    pub lhs: Operand,
+    pub op: CmpOp,
+    pub rhs: u16,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum BreakKind {
+    /// Breaks when the pc reaches the address
+    Pc(u16),
+    /// Breaks when the byte at the address changes
+    Watch(u16),
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Breakpoint {
+    pub kind: BreakKind,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+    /// Number of times the breakpoint stopped a run
+    pub hits: u32,
+}
+
+impl Operand {
+    pub fn get(&self, c: &Chip8) -> u16 {
+        match self {
+            Operand::V(r) => c.rv(*r) as u16,
+            Operand::I => c.i,
+            Operand::Pc => c.pc,
+            Operand::Delay => c.delay as u16,
+            Operand::Sound => c.sound as u16,
+        }
+    }
+
+    /// Values are truncated to the width of the register
+    pub fn set(&self, c: &mut Chip8, value: u16) {
+        match self {
+            Operand::V(r) => *c.v(*r) = Wrapping(value as u8),
+            Operand::I => c.i = value,
+            Operand::Pc => c.pc = value % Chip8::MEM_SIZE as u16,
+            Operand::Delay => c.delay = value as u8,
+            Operand::Sound => c.sound = value as u8,
+        }
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, c: &Chip8) -> bool {
+        let lhs: u16 = self.lhs.get(c);
+        match self.op {
+            CmpOp::Eq => lhs == self.rhs,
+            CmpOp::NEq => lhs != self.rhs,
+            CmpOp::Lt => lhs < self.rhs,
+            CmpOp::Le => lhs <= self.rhs,
+            CmpOp::Gt => lhs > self.rhs,
+            CmpOp::Ge => lhs >= self.rhs,
+        }
+    }
+}
+
+impl Breakpoint {
+    pub fn new(kind: BreakKind, condition: Option<Condition>) -> Self {
+        Breakpoint {
+            kind,
+            condition,
+            enabled: true,
+            hits: 0,
+        }
+    }
+
+    /// Whether the breakpoint stops a run that moves from `prev` to `now`
+    pub fn triggers(&self, prev: &Chip8, now: &Chip8) -> bool {
+        let hit = match self.kind {
+            BreakKind::Pc(a) => now.pc == a,
+            BreakKind::Watch(a) => prev.memory[a as usize] != now.memory[a as usize],
+        };
+        self.enabled && hit && self.condition.as_ref().is_none_or(|c| c.holds(now))
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Operand::V(r) => write!(f, "{r}"),
+            Operand::I => write!(f, "I"),
+            Operand::Pc => write!(f, "PC"),
+            Operand::Delay => write!(f, "DT"),
+            Operand::Sound => write!(f, "ST"),
+        }
+    }
+}
+
+impl Display for CmpOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            CmpOp::Eq => "==",
+            CmpOp::NEq => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.lhs, self.op, self.rhs)
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.kind {
+            BreakKind::Pc(a) => write!(f, "break {a:#05X}")?,
+            BreakKind::Watch(a) => write!(f, "watch {a:#05X}")?,
+        }
+        if let Some(c) = &self.condition {
+            write!(f, " if {c}")?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,342 @@
+//! An experimental decompiler, which writes a program as pseudocode to read
+//! an unknown ROM faster than its listing. The skips and the jumps become
+//! `if`s, `else`s and loops where they follow the patterns that compile to
+//! them, and `goto`s where they don't:
+//!
+//! ```text
+//! fn main() {  // reads v0 v1, writes v0 v1 vf
+//!     clear()
+//!     loop {
+//!         v0 = wait_key()
+//!         if v0 == 0x05 {
+//!             v1 += 0x01
+//!         }
+//!     }
+//! }
+//! ```
+
+use super::analysis::*;
+use super::architecture::Register;
+use super::language::*;
+use super::symbols::Symbols;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Decompiles the code reachable from the start of a ROM loaded at `start`,
+/// one function per routine. The names of `symbols` name the routines and
+/// the addresses
+pub fn decompile(rom: &[u8], start: u16, symbols: &Symbols) -> String {
+    let code = reachable(rom, start);
+    let mut out = String::new();
+    for entry in routines(&code, start) {
+        let instrs: Vec<(u16, &Instr)> = body(&code, entry)
+            .into_iter()
+            .map(|a| (a, &code[&a]))
+            .collect();
+        let mut routine = Routine {
+            body: &instrs,
+            symbols,
+            start,
+            lines: vec![],
+            gotos: BTreeSet::new(),
+        };
+        if instrs.first().is_some_and(|&(a, _)| a != entry) {
+            routine.goto(None, 1, entry);
+        }
+        routine.block(0, instrs.len(), 1);
+        let name = symbols.routine(entry, start);
+        out += &format!("fn {name}() {{  // {}\n", usage(&instrs));
+        out += &routine.text();
+        out += "}\n\n";
+    }
+    out.pop();
+    out
+}
+
+/// The instructions of a routine, in the order of their addresses, and the
+/// pseudocode written so far
+struct Routine<'a> {
+    body: &'a [(u16, &'a Instr)],
+    symbols: &'a Symbols,
+    /// Where the program starts
+    start: u16,
+    /// The address where each line starts, if it has one, its depth and its
+    /// text
+    lines: Vec<(Option<u16>, usize, String)>,
+    /// The targets of the `goto`s, which are labelled
+    gotos: BTreeSet<u16>,
+}
+
+impl Routine<'_> {
+    fn line(&mut self, addr: Option<u16>, depth: usize, text: String) {
+        self.lines.push((addr, depth, text));
+    }
+
+    fn label(&self, addr: u16) -> String {
+        match self.symbols.get(addr) {
+            Some(name) => name.to_string(),
+            None => format!("L_{addr:04X}"),
+        }
+    }
+
+    fn goto(&mut self, at: Option<u16>, depth: usize, target: u16) {
+        self.gotos.insert(target);
+        let text = format!("goto {}", self.label(target));
+        self.line(at, depth, text);
+    }
+
+    /// The index of the instruction at `addr`, between `lo` and `hi`. `hi`
+    /// itself is the end of the range, where the code after it starts
+    fn find(&self, addr: u16, lo: usize, hi: usize) -> Option<usize> {
+        let ix = self.body.iter().position(|&(a, _)| a == addr)?;
+        (lo..=hi).contains(&ix).then_some(ix)
+    }
+
+    fn target(&self, ix: usize) -> Option<u16> {
+        match self.body.get(ix) {
+            Some((_, Instr::Goto { addr })) => Some(addr.into()),
+            _ => None,
+        }
+    }
+
+    /// Writes the instructions from `lo` to `hi`, not included
+    fn block(&mut self, lo: usize, hi: usize, depth: usize) {
+        let mut i = lo;
+        while i < hi {
+            let (addr, instr) = self.body[i];
+            // A loop, up to the last jump back here in the range
+            if let Some(j) = (i..hi).rev().find(|&j| self.target(j) == Some(addr)) {
+                self.line(Some(addr), depth, "loop {".into());
+                // Which ends with a skip over the jump back
+                let until = (j > i && self.body[j - 1].0 + 2 == self.body[j].0)
+                    .then(|| condition(self.body[j - 1].1))
+                    .flatten();
+                match until {
+                    Some((_, unless)) => {
+                        self.block(i, j - 1, depth + 1);
+                        self.line(None, depth, format!("}} while {unless}"));
+                    }
+                    _ => {
+                        self.block(i, j, depth + 1);
+                        self.line(None, depth, "}".into());
+                    }
+                }
+                i = j + 1;
+                continue;
+            }
+            let Some((when, unless)) = condition(instr) else {
+                self.statement(i, depth);
+                i += 1;
+                continue;
+            };
+            if i + 1 == hi || self.body[i + 1].0 != addr + 2 {
+                self.line(Some(addr), depth, format!("skip if {when}"));
+                i += 1;
+                continue;
+            }
+            // A skip over a jump forward: the code up to the jump's target
+            // runs when the condition holds
+            let then = self
+                .target(i + 1)
+                .filter(|&t| t > addr + 2)
+                .and_then(|t| self.find(t, i + 2, hi));
+            let Some(k) = then else {
+                // A skip over any other instruction
+                self.line(Some(addr), depth, format!("if {unless} {{"));
+                self.block(i + 1, i + 2, depth + 1);
+                self.line(None, depth, "}".into());
+                i += 2;
+                continue;
+            };
+            self.line(Some(addr), depth, format!("if {when} {{"));
+            // Which ends with a jump over the `else`
+            let end = (k > i + 2)
+                .then(|| self.target(k - 1))
+                .flatten()
+                .filter(|&e| Some(e) > self.body.get(k).map(|&(a, _)| a))
+                .and_then(|e| self.find(e, k, hi));
+            match end {
+                Some(m) => {
+                    self.block(i + 2, k - 1, depth + 1);
+                    self.line(None, depth, "} else {".into());
+                    self.block(k, m, depth + 1);
+                    i = m;
+                }
+                None => {
+                    self.block(i + 2, k, depth + 1);
+                    i = k;
+                }
+            }
+            self.line(None, depth, "}".into());
+        }
+    }
+
+    fn statement(&mut self, ix: usize, depth: usize) {
+        let (addr, instr) = self.body[ix];
+        let text = match instr {
+            Instr::Goto { addr: target } => return self.goto(Some(addr), depth, target.into()),
+            Instr::Call { addr: target } => {
+                format!("{}()", self.symbols.routine(target.into(), self.start))
+            }
+            Instr::SetI { n } => format!("i = {}", self.label(n.into())),
+            Instr::Jump { n } => format!("goto {} + v0", self.label(n.into())),
+            _ => pseudocode(instr),
+        };
+        self.line(Some(addr), depth, text);
+    }
+
+    /// The lines, indented, with the labels of the `goto`s before the
+    /// first line at or after their targets
+    fn text(&self) -> String {
+        let mut labels: BTreeMap<usize, Vec<u16>> = BTreeMap::new();
+        for &target in &self.gotos {
+            let ix = self
+                .lines
+                .iter()
+                .position(|&(a, _, _)| a.is_some_and(|a| a >= target));
+            labels
+                .entry(ix.unwrap_or(self.lines.len()))
+                .or_default()
+                .push(target);
+        }
+        let mut out = String::new();
+        for (ix, (_, depth, text)) in self.lines.iter().enumerate() {
+            for &target in labels.get(&ix).into_iter().flatten() {
+                out += &format!("{}:\n", self.label(target));
+            }
+            out += &format!("{}{text}\n", "    ".repeat(*depth));
+        }
+        for &target in labels.get(&self.lines.len()).into_iter().flatten() {
+            out += &format!("{}:\n", self.label(target));
+        }
+        out
+    }
+}
+
+fn v(r: &Register) -> String {
+    format!("v{:x}", u8::from(r))
+}
+
+/// The conditions of a skip: when it skips and when it doesn't
+fn condition(instr: &Instr) -> Option<(String, String)> {
+    let (a, b) = match instr {
+        Instr::SkipEq { r, c } => (
+            format!("{} == {c:#04X}", v(r)),
+            format!("{} != {c:#04X}", v(r)),
+        ),
+        Instr::SkipNEq { r, c } => (
+            format!("{} != {c:#04X}", v(r)),
+            format!("{} == {c:#04X}", v(r)),
+        ),
+        Instr::SkipEqV { r, s } => (
+            format!("{} == {}", v(r), v(s)),
+            format!("{} != {}", v(r), v(s)),
+        ),
+        Instr::SkipNEqV { r, s } => (
+            format!("{} != {}", v(r), v(s)),
+            format!("{} == {}", v(r), v(s)),
+        ),
+        Instr::Pressed { r } => (format!("key({})", v(r)), format!("!key({})", v(r))),
+        Instr::NotPressed { r } => (format!("!key({})", v(r)), format!("key({})", v(r))),
+        _ => return None,
+    };
+    Some((a, b))
+}
+
+/// An instruction as a statement
+fn pseudocode(instr: &Instr) -> String {
+    match instr {
+        Instr::Clear => "clear()".into(),
+        Instr::Ret => "return".into(),
+        Instr::Set { r, a } => format!("{} = {a:#04X}", v(r)),
+        Instr::Incr { r, a } => format!("{} += {a:#04X}", v(r)),
+        Instr::Copy { r, s } => format!("{} = {}", v(r), v(s)),
+        Instr::BitOr { r, s } => format!("{} |= {}", v(r), v(s)),
+        Instr::BitAnd { r, s } => format!("{} &= {}", v(r), v(s)),
+        Instr::BitXOr { r, s } => format!("{} ^= {}", v(r), v(s)),
+        Instr::Add { r, s } => format!("{} += {}  // vf = carry", v(r), v(s)),
+        Instr::Sub { r, s } => format!("{} -= {}  // vf = no borrow", v(r), v(s)),
+        Instr::Lt { r, s } => format!("{} = {} - {}  // vf = no borrow", v(r), v(s), v(r)),
+        Instr::ShiftR { r, s } => format!("{} >>= 1  // vf = bit 0, {} with shift_vy", v(r), v(s)),
+        Instr::ShiftL { r, s } => format!("{} <<= 1  // vf = bit 7, {} with shift_vy", v(r), v(s)),
+        Instr::Rand { r, n } => format!("{} = random() & {n:#04X}", v(r)),
+        Instr::Draw { x, y, height } => {
+            format!("vf = draw({}, {}, {height})  // vf = collision", v(x), v(y))
+        }
+        Instr::GetDelay { r } => format!("{} = delay", v(r)),
+        Instr::LoadKey { r } => format!("{} = wait_key()", v(r)),
+        Instr::SetDelayTimer { r } => format!("delay = {}", v(r)),
+        Instr::SetSoundTimer { r } => format!("sound = {}", v(r)),
+        Instr::IncrI { r } => format!("i += {}", v(r)),
+        Instr::SpriteAddr { r } => format!("i = digit({})", v(r)),
+        Instr::StoreBCD { r } => format!("memory[i..i + 3] = bcd({})", v(r)),
+        Instr::RegDump { x } => format!("memory[i..] = v0..=v{:x}", x.0),
+        Instr::RegLoad { x } => format!("v0..=v{:x} = memory[i..]", x.0),
+        Instr::SkipEq { .. }
+        | Instr::SkipNEq { .. }
+        | Instr::SkipEqV { .. }
+        | Instr::SkipNEqV { .. }
+        | Instr::Pressed { .. }
+        | Instr::NotPressed { .. }
+        | Instr::Goto { .. }
+        | Instr::Call { .. }
+        | Instr::SetI { .. }
+        | Instr::Jump { .. }
+        | Instr::System { .. }
+        | Instr::Data(_) => instr.to_string(),
+    }
+}
+
+/// The registers that an instruction reads and those that it writes
+fn registers(instr: &Instr) -> (Vec<u8>, Vec<u8>) {
+    let n = |r: &Register| u8::from(r);
+    match instr {
+        Instr::SkipEq { r, .. } | Instr::SkipNEq { r, .. } => (vec![n(r)], vec![]),
+        Instr::SkipEqV { r, s } | Instr::SkipNEqV { r, s } => (vec![n(r), n(s)], vec![]),
+        Instr::Pressed { r }
+        | Instr::NotPressed { r }
+        | Instr::SetDelayTimer { r }
+        | Instr::SetSoundTimer { r }
+        | Instr::IncrI { r }
+        | Instr::SpriteAddr { r }
+        | Instr::StoreBCD { r } => (vec![n(r)], vec![]),
+        Instr::Set { r, .. }
+        | Instr::Rand { r, .. }
+        | Instr::GetDelay { r }
+        | Instr::LoadKey { r } => (vec![], vec![n(r)]),
+        Instr::Incr { r, .. } => (vec![n(r)], vec![n(r)]),
+        Instr::Copy { r, s } => (vec![n(s)], vec![n(r)]),
+        Instr::BitOr { r, s } | Instr::BitAnd { r, s } | Instr::BitXOr { r, s } => {
+            (vec![n(r), n(s)], vec![n(r)])
+        }
+        Instr::Add { r, s }
+        | Instr::Sub { r, s }
+        | Instr::Lt { r, s }
+        | Instr::ShiftR { r, s }
+        | Instr::ShiftL { r, s } => (vec![n(r), n(s)], vec![n(r), 0xF]),
+        Instr::Draw { x, y, .. } => (vec![n(x), n(y)], vec![0xF]),
+        Instr::Jump { .. } => (vec![0], vec![]),
+        Instr::RegDump { x } => ((0..=x.0).collect(), vec![]),
+        Instr::RegLoad { x } => (vec![], (0..=x.0).collect()),
+        _ => (vec![], vec![]),
+    }
+}
+
+/// The registers that a routine reads and writes, e.g. `reads v0 v1,
+/// writes vf`
+fn usage(body: &[(u16, &Instr)]) -> String {
+    let (mut reads, mut writes) = (BTreeSet::new(), BTreeSet::new());
+    for (_, instr) in body {
+        let (r, w) = registers(instr);
+        reads.extend(r);
+        writes.extend(w);
+    }
+    let list = |rs: BTreeSet<u8>| {
+        let rs: Vec<String> = rs.iter().map(|r| format!("v{r:x}")).collect();
+        if rs.is_empty() {
+            "nothing".to_string()
+        } else {
+            rs.join(" ")
+        }
+    };
+    format!("reads {}, writes {}", list(reads), list(writes))
+}
@@ -0,0 +1,192 @@
+use super::language::*;
+use super::symbols::Symbols;
+use clap::ValueEnum;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The syntax of a listing
+#[derive(PartialEq, Eq, Clone, Copy, Debug, ValueEnum)]
+pub enum Format {
+    /// The mnemonics of the memory pane, with the addresses and the raw bytes
+    Plain,
+    /// The mnemonics alone, with the addresses in comments, which `assemble`
+    /// turns back into the ROM
+    Asm,
+    /// Octo's assembly language, with the addresses in comments
+    Octo,
+    /// An array of the lines, with their addresses, bytes, kinds (`code` or
+    /// `data`), mnemonics and labels, for other tools
+    Json,
+    /// A C array of the bytes, with the mnemonics in comments, to embed the
+    /// ROM in other programs
+    CArray,
+    /// The same as a Rust array
+    RustArray,
+}
+
+/// The most bytes in a line of data
+const DATA_WIDTH: usize = 8;
+
+/// Disassembles a ROM loaded at `start`, one instruction per line with its
+/// address and its raw bytes, as in the memory pane:
+///
+/// ```text
+/// 0x200 0x00E0 CLS
+/// 0x202 0xA22A LD I, 0x22A
+/// ```
+///
+/// Without `code`, every two bytes are decoded, and what is not an
+/// instruction is listed as data, `DW 0xF090`, and a last odd byte as
+/// `DB 0xF0`. With the instructions found by `analysis::reachable`, the
+/// bytes between them are listed as data, `DB 0xF0, 0x90, 0x90, 0xF0`, so
+/// that sprites are not read as instructions. Either way the listing
+/// assembles back into the ROM.
+///
+/// The addresses named by `symbols` are labelled, and the jumps, the calls
+/// and `LD I` use the names. Only the names of the addresses where a line
+/// starts are used, so that every name used is defined in the listing.
+pub fn disassemble(
+    rom: &[u8],
+    start: u16,
+    format: Format,
+    symbols: &Symbols,
+    code: Option<&BTreeMap<u16, Instr>>,
+) -> String {
+    // The address and the bytes of each line, and whether they are an
+    // instruction
+    let mut lines: Vec<(usize, &[u8], bool)> = vec![];
+    match code {
+        None => {
+            for (ix, chunk) in rom.chunks(2).enumerate() {
+                lines.push((start as usize + 2 * ix, chunk, chunk.len() == 2));
+            }
+        }
+        Some(code) => {
+            let mut ix = 0;
+            while ix < rom.len() {
+                let addr = start as usize + ix;
+                if code.contains_key(&(addr as u16)) {
+                    lines.push((addr, &rom[ix..ix + 2], true));
+                    ix += 2;
+                    continue;
+                }
+                // Data, up to the next instruction or label
+                let len = (1..DATA_WIDTH)
+                    .find(|&n| {
+                        let next = (addr + n) as u16;
+                        ix + n == rom.len()
+                            || code.contains_key(&next)
+                            || symbols.get(next).is_some()
+                    })
+                    .unwrap_or(DATA_WIDTH);
+                lines.push((addr, &rom[ix..ix + len], false));
+                ix += len;
+            }
+        }
+    }
+    let starts: BTreeSet<usize> = lines.iter().map(|&(addr, _, _)| addr).collect();
+    let symbols = Symbols {
+        labels: symbols
+            .labels
+            .iter()
+            .filter(|&(&a, _)| starts.contains(&(a as usize)))
+            .map(|(&a, name)| (a, name.clone()))
+            .collect(),
+    };
+    let mut out = String::new();
+    // The lines of the JSON array
+    let mut objects: Vec<String> = vec![];
+    for (addr, bytes, is_instr) in lines {
+        let label = symbols.get(addr as u16);
+        let hex = |sep| {
+            let bytes: Vec<String> = bytes.iter().map(|b| format!("{b:#04X}")).collect();
+            bytes.join(sep)
+        };
+        // The mnemonics, and Octo's syntax
+        let (text, octo) = if is_instr {
+            let instr = RawInstr::from_bytes([bytes[0], bytes[1]]).into_instr();
+            let octo = match instr {
+                // Written as bytes
+                Instr::System { .. } => instr.octo(),
+                _ => symbols.label_operand(&instr, &instr.octo()),
+            };
+            (symbols.label_operand(&instr, &instr.to_string()), octo)
+        } else {
+            (format!("DB {}", hex(", ")), hex(" "))
+        };
+        match format {
+            Format::Plain => {
+                if let Some(name) = label {
+                    out += &format!("{name}:\n");
+                }
+                let raw = if is_instr {
+                    RawInstr::from_bytes([bytes[0], bytes[1]]).to_string()
+                } else {
+                    String::new()
+                };
+                out += &format!("{addr:#05X} {raw:<6} {text}\n");
+            }
+            Format::Asm => {
+                if let Some(name) = label {
+                    out += &format!("{name}:\n");
+                }
+                out += &format!("    {text:<20} ; {addr:#05X}\n");
+            }
+            Format::Octo => {
+                if let Some(name) = label {
+                    out += &format!(": {name}\n");
+                }
+                out += &format!("{octo:<24} # {addr:#05X}\n");
+            }
+            Format::Json => {
+                let bytes: Vec<String> = bytes.iter().map(u8::to_string).collect();
+                let label = label.map_or("null".into(), json_string);
+                objects.push(format!(
+                    "  {{\"address\": {addr}, \"bytes\": [{}], \"kind\": \"{}\", \"text\": {}, \"label\": {label}}}",
+                    bytes.join(", "),
+                    if is_instr { "code" } else { "data" },
+                    json_string(&text),
+                ));
+            }
+            Format::CArray => {
+                if let Some(name) = label {
+                    out += &format!("    /* {name}: */\n");
+                }
+                let bytes = format!("{},", hex(", "));
+                out += &format!("    {bytes:<12} /* {addr:#05X} {text} */\n");
+            }
+            Format::RustArray => {
+                if let Some(name) = label {
+                    out += &format!("    // {name}:\n");
+                }
+                let bytes = format!("{},", hex(", "));
+                out += &format!("    {bytes:<12} // {addr:#05X} {text}\n");
+            }
+        }
+    }
+    match format {
+        Format::Json => format!("[\n{}\n]\n", objects.join(",\n")),
+        Format::CArray => format!(
+            "/* Loaded at {start:#05X} */\nconst unsigned char rom[{}] = {{\n{out}}};\n",
+            rom.len()
+        ),
+        Format::RustArray => format!(
+            "// Loaded at {start:#05X}\npub const ROM: [u8; {}] = [\n{out}];\n",
+            rom.len()
+        ),
+        _ => out,
+    }
+}
+
+/// A string in JSON, quoted and escaped
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            c if c.is_control() => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
@@ -0,0 +1,205 @@
+use super::assembler;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::*;
+use ratatui::style::Stylize;
+use ratatui::text::*;
+use ratatui::widgets::*;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// A text editor for assembly source, which the debugger assembles and runs
+/// in place of the ROM
+pub struct Editor {
+    /// Where the source is saved with Ctrl+S
+    pub file: PathBuf,
+    lines: Vec<String>,
+    /// The line and the character of the cursor
+    row: usize,
+    col: usize,
+    /// The result of the last save or assembly
+    pub status: Option<String>,
+}
+
+/// What a key did to the editor
+pub enum EditorAction {
+    Editing,
+    /// The editor was hidden
+    Close,
+    /// The source was assembled into this ROM, to be run
+    Run(Vec<u8>),
+}
+
+impl Editor {
+    /// Opens `file`, which may not exist yet
+    pub fn open(file: PathBuf) -> std::io::Result<Editor> {
+        let source = match fs::read_to_string(&file) {
+            Ok(s) => s,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut lines: Vec<String> = source.lines().map(String::from).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        Ok(Editor {
+            file,
+            lines,
+            row: 0,
+            col: 0,
+            status: None,
+        })
+    }
+
+    pub fn source(&self) -> String {
+        self.lines.join("\n") + "\n"
+    }
+
+    /// The byte index of the cursor in its line
+    fn byte(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices()
+            .nth(self.col)
+            .map_or(line.len(), |(ix, _)| ix)
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    pub fn key(&mut self, k: KeyEvent) -> EditorAction {
+        let ctrl = k.modifiers.contains(KeyModifiers::CONTROL);
+        match k.code {
+            KeyCode::Esc => return EditorAction::Close,
+            KeyCode::F(5) => return self.assemble(),
+            KeyCode::Char('r') if ctrl => return self.assemble(),
+            KeyCode::Char('s') if ctrl => {
+                self.status = Some(match fs::write(&self.file, self.source()) {
+                    Ok(()) => format!("saved {}", self.file.display()),
+                    Err(e) => format!("error: could not save {}: {e}", self.file.display()),
+                })
+            }
+            KeyCode::Char(c) if !ctrl => {
+                let ix = self.byte();
+                self.lines[self.row].insert(ix, c);
+                self.col += 1;
+            }
+            KeyCode::Tab => {
+                let ix = self.byte();
+                self.lines[self.row].insert_str(ix, "    ");
+                self.col += 4;
+            }
+            KeyCode::Enter => {
+                let ix = self.byte();
+                let rest = self.lines[self.row].split_off(ix);
+                self.row += 1;
+                self.lines.insert(self.row, rest);
+                self.col = 0;
+            }
+            KeyCode::Backspace if self.col > 0 => {
+                self.col -= 1;
+                let ix = self.byte();
+                self.lines[self.row].remove(ix);
+            }
+            KeyCode::Backspace if self.row > 0 => {
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.col = self.line_len();
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Delete if self.col < self.line_len() => {
+                let ix = self.byte();
+                self.lines[self.row].remove(ix);
+            }
+            KeyCode::Delete if self.row + 1 < self.lines.len() => {
+                let line = self.lines.remove(self.row + 1);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Left => self.col = self.col.saturating_sub(1),
+            KeyCode::Right => self.col = (self.col + 1).min(self.line_len()),
+            KeyCode::Up => self.row = self.row.saturating_sub(1),
+            KeyCode::Down => self.row = (self.row + 1).min(self.lines.len() - 1),
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(),
+            _ => (),
+        }
+        self.col = self.col.min(self.line_len());
+        EditorAction::Editing
+    }
+
+    /// Assembles the source. An error in it, not in a file that it includes,
+    /// moves the cursor to its line
+    fn assemble(&mut self) -> EditorAction {
+        match assembler::assemble(&self.source(), &self.file) {
+            Ok((rom, _)) => {
+                self.status = None;
+                EditorAction::Run(rom)
+            }
+            Err(e) => {
+                // `file:line:column: message`, then the line
+                let mut at = e
+                    .strip_prefix(&format!("{}:", self.file.display()))
+                    .unwrap_or_default()
+                    .split(':')
+                    .map(|n| n.parse::<usize>().ok());
+                if let (Some(Some(line)), Some(Some(col))) = (at.next(), at.next()) {
+                    self.row = line.saturating_sub(1).min(self.lines.len() - 1);
+                    self.col = col.saturating_sub(1).min(self.line_len());
+                }
+                let message = e.lines().next().unwrap_or_default();
+                self.status = Some(format!("error: {message}"));
+                EditorAction::Editing
+            }
+        }
+    }
+}
+
+impl Widget for &Editor {
+    fn render(self, area: Rect, buf: &mut ratatui::buffer::Buffer) {
+        let block = Block::bordered()
+            .title(
+                Line::from(format!("Assembly: {}", self.file.display()))
+                    .bold()
+                    .blue()
+                    .centered(),
+            )
+            .title_bottom(match &self.status {
+                Some(s) if s.starts_with("error") => Line::from(s.clone()).red().centered(),
+                Some(s) => Line::from(s.clone()).centered(),
+                None => Line::from("F5 assemble and run  Ctrl+S save  Esc close")
+                    .dim()
+                    .centered(),
+            });
+        let height = block.inner(area).height.max(1) as usize;
+        // The view follows the cursor down
+        let scroll = self.row.saturating_sub(height - 1);
+        // Line numbers, so that errors can be found
+        let width = self.lines.len().to_string().len();
+        let lines: Vec<Line> = self
+            .lines
+            .iter()
+            .enumerate()
+            .skip(scroll)
+            .take(height)
+            .map(|(n, l)| {
+                let number = Span::from(format!("{:>width$} ", n + 1)).dim();
+                if n != self.row {
+                    return Line::from(vec![number, Span::from(l.clone())]);
+                }
+                let chars: Vec<char> = l.chars().collect();
+                let (before, after) = chars.split_at(self.col);
+                let (cursor, after) = match after.split_first() {
+                    Some((c, after)) => (c.to_string(), after),
+                    None => (" ".to_string(), after),
+                };
+                Line::from(vec![
+                    number,
+                    Span::from(before.iter().collect::<String>()),
+                    Span::from(cursor).reversed(),
+                    Span::from(after.iter().collect::<String>()),
+                ])
+            })
+            .collect();
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
@@ -4,6 +4,10 @@ use super::debugger::*;
 use super::font;
 use super::language::*;
 use bitvec::prelude::*;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::fs::*;
 use std::io::*;
 use std::num::*;
@@ -22,19 +26,63 @@ impl Screen {
         old && !new
     }
 
-    pub fn to_string(&self) -> String {
-        let mut s: String = String::new();
-        for ln in self.rows {
-            for c in ln {
-                s.push(if c { '█' } else { '.' })
+    pub fn print(&self) {
+        print!("{self}");
+    }
+
+    /// The screen of text art as `Display` writes it, with `█` or `#` for
+    /// the pixels that are on and `.` or a space for those that are off.
+    /// Missing columns at the end of a line are off
+    pub fn parse(text: &str) -> std::result::Result<Screen, String> {
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.len() != Self::NROWS {
+            return Err(format!(
+                "expected {} lines, found {}",
+                Self::NROWS,
+                lines.len()
+            ));
+        }
+        let mut screen = Screen::new();
+        for (r, line) in lines.iter().enumerate() {
+            for (c, ch) in line.chars().enumerate() {
+                let on = match ch {
+                    '█' | '#' => true,
+                    '.' | ' ' => false,
+                    _ => return Err(format!("line {}: unexpected {ch}", r + 1)),
+                };
+                if c >= Self::NCOLS {
+                    return Err(format!("line {}: more than {} columns", r + 1, Self::NCOLS));
+                }
+                screen.rows[r].set(c, on);
             }
-            s.push('\n');
         }
-        s
+        Ok(screen)
     }
 
-    pub fn print(&self) {
-        print!("{}", self.to_string());
+    /// A hash of the pixels, with FNV-1a like `Chip8::checksum`, to tell
+    /// screens apart
+    pub fn checksum(&self) -> u64 {
+        let mut h: u64 = 0xCBF29CE484222325;
+        for row in &self.rows {
+            for w in row.as_raw_slice() {
+                for b in w.to_be_bytes() {
+                    h = (h ^ b as u64).wrapping_mul(0x100000001B3);
+                }
+            }
+        }
+        h
+    }
+}
+
+impl Display for Screen {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for ln in self.rows {
+            for c in ln {
+                write!(f, "{}", if c { '█' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
@@ -63,6 +111,104 @@ impl Chip8 {
         r.into_instr()
     }
 
+    /// Addresses where `pattern` starts in memory
+    pub fn find(&self, pattern: &[u8]) -> Vec<usize> {
+        self.memory
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, w)| *w == pattern)
+            .map(|(a, _)| a)
+            .collect()
+    }
+
+    /// Describes the parts of the state that differ from `other`, e.g.
+    /// `["V3", "memory 0x300", "screen"]`. The keypad, the quirks and the
+    /// random number generator are not compared.
+    pub fn differences(&self, other: &Chip8) -> Vec<String> {
+        let mut d: Vec<String> = vec![];
+        if self.pc != other.pc {
+            d.push("PC".into());
+        }
+        if self.i != other.i {
+            d.push("I".into());
+        }
+        for r in 0..16 {
+            let r = Register::from(r);
+            if self.rv(r) != other.rv(r) {
+                d.push(r.to_string());
+            }
+        }
+        if self.sp != other.sp || self.stack != other.stack {
+            d.push("stack".into());
+        }
+        if self.delay != other.delay {
+            d.push("DT".into());
+        }
+        if self.sound != other.sound {
+            d.push("ST".into());
+        }
+        let mem: Vec<usize> = (0..Chip8::MEM_SIZE)
+            .filter(|&a| self.memory[a] != other.memory[a])
+            .collect();
+        match mem.as_slice() {
+            [] => (),
+            [a] => d.push(format!("memory {a:#05X}")),
+            [a, ..] => d.push(format!("memory {a:#05X} (+{} bytes)", mem.len() - 1)),
+        }
+        if self.screen != other.screen {
+            d.push("screen".into());
+        }
+        d
+    }
+
+    /// A hash of the registers, the memory and the screen. It uses FNV-1a,
+    /// which unlike the std hasher gives the same result in every build.
+    pub fn checksum(&self) -> u64 {
+        let mut h: u64 = 0xCBF29CE484222325;
+        let mut feed = |bytes: &[u8]| {
+            for b in bytes {
+                h = (h ^ *b as u64).wrapping_mul(0x100000001B3);
+            }
+        };
+        feed(&self.pc.to_be_bytes());
+        feed(&self.i.to_be_bytes());
+        feed(&[self.sp, self.delay, self.sound]);
+        for a in self.stack {
+            feed(&a.to_be_bytes());
+        }
+        feed(&self.registers.map(|r| r.0));
+        feed(&self.memory);
+        for row in &self.screen.rows {
+            for w in row.as_raw_slice() {
+                feed(&w.to_be_bytes());
+            }
+        }
+        h
+    }
+
+    /// Only the lowest nibble of the key is considered
+    pub fn key_pressed(&self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+
+    fn vf_reset(&mut self) {
+        if self.quirks.vf_reset {
+            *self.v(Register::VF) = Wrapping(0);
+        }
+    }
+
+    fn load_store_incr_i(&mut self, x: UNibble) {
+        if self.quirks.load_store_incr_i {
+            self.i += x as u16 + 1;
+        }
+    }
+
+    /// Counts the delay and the sound timers down, 60 times per second
+    pub fn tick_timers(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
     pub fn pc_incr(&mut self) {
         self.pc += 2;
     }
@@ -79,7 +225,21 @@ impl Chip8 {
     }
 
     pub fn run_instr(&mut self) {
-        let i = self.read_instr();
+        self.execute(self.read_instr());
+    }
+
+    /// Runs the instruction of `step`, counting from 1, and then counts the
+    /// timers down if the step ends a frame of `STEPS_PER_FRAME`. Every run
+    /// without a window steps like this, so that they all agree
+    pub fn run_step(&mut self, step: u64) {
+        self.run_instr();
+        if step.is_multiple_of(STEPS_PER_FRAME as u64) {
+            self.tick_timers();
+        }
+    }
+
+    /// Runs an instruction as if it were at the pc
+    pub fn execute(&mut self, i: Instr) {
         match i {
             Instr::System { addr: _ } => {
                 self.pc_incr();
@@ -129,14 +289,17 @@ impl Chip8 {
             }
             Instr::BitOr { r, s } => {
                 *self.v(r) = *self.v(r) | *self.v(s);
+                self.vf_reset();
                 self.pc_incr();
             }
             Instr::BitAnd { r, s } => {
                 *self.v(r) = *self.v(r) & *self.v(s);
+                self.vf_reset();
                 self.pc_incr();
             }
             Instr::BitXOr { r, s } => {
                 *self.v(r) = *self.v(r) ^ *self.v(s);
+                self.vf_reset();
                 self.pc_incr();
             }
             Instr::Add { r, s } => {
@@ -145,9 +308,10 @@ impl Chip8 {
                 *self.v(Register::VF) = Wrapping(overflow as u8);
                 self.pc_incr();
             }
-            Instr::ShiftR { r } => {
-                let vf = self.rv(r) % 2 as u8;
-                let (n, _overflow) = self.rv(r).overflowing_shr(1);
+            Instr::ShiftR { r, s } => {
+                let src = if self.quirks.shift_vy { s } else { r };
+                let vf = self.rv(src) % 2;
+                let (n, _overflow) = self.rv(src).overflowing_shr(1);
                 *self.v(r) = Wrapping(n);
                 *self.v(Register::VF) = Wrapping(vf);
                 self.pc_incr();
@@ -164,9 +328,10 @@ impl Chip8 {
                 *self.v(Register::VF) = Wrapping(!borrow as u8);
                 self.pc_incr();
             }
-            Instr::ShiftL { r } => {
-                let vf = self.rv(r) / (2u8.pow(7));
-                let (n, _overflow) = self.rv(r).overflowing_shl(1);
+            Instr::ShiftL { r, s } => {
+                let src = if self.quirks.shift_vy { s } else { r };
+                let vf = self.rv(src) / (2u8.pow(7));
+                let (n, _overflow) = self.rv(src).overflowing_shl(1);
                 *self.v(r) = Wrapping(n);
                 *self.v(Register::VF) = Wrapping(vf);
                 self.pc_incr();
@@ -182,41 +347,63 @@ impl Chip8 {
                 self.pc_incr();
             }
             Instr::Jump { n } => {
-                self.pc = self.rv(Register::V0) as u16 + u16::from(n);
+                let n = u16::from(n);
+                let base = if self.quirks.jump_vx {
+                    Register::from((n >> 8) as u8)
+                } else {
+                    Register::V0
+                };
+                self.pc = self.rv(base) as u16 + n;
             }
             Instr::Rand { r, n } => {
-                *self.v(r) = Wrapping((n & rand::random::<u8>()) as u8);
+                *self.v(r) = Wrapping(n & self.rng.random::<u8>());
                 self.pc_incr();
             }
             Instr::Draw { x, y, height } => {
                 let reg_i: usize = self.i as usize;
-                let i0 = self.rv(y) as u16;
-                let j0 = self.rv(x) as u16;
+                // The sprite's position wraps around the screen. Its pixels
+                // wrap too, unless the clip quirk is set
+                let i0 = self.rv(y) as u16 % Screen::NROWS as u16;
+                let j0 = self.rv(x) as u16 % Screen::NCOLS as u16;
+                let clip = self.quirks.clip;
                 let sprite: &[u8] = &self.memory[reg_i..reg_i + height as usize];
                 let mut collision: bool = false;
                 for (i, line) in sprite.iter().enumerate() {
                     let line_bits: &BitSlice<u8, Msb0> = line.view_bits();
                     for j in 0..8 {
-                        collision |=
-                            self.screen
-                                .draw_bit(i0 + i as u16, j0 + j as u16, line_bits[j]);
+                        let (row, col) = (i0 + i as u16, j0 + j as u16);
+                        if clip && (row as usize >= Screen::NROWS || col as usize >= Screen::NCOLS)
+                        {
+                            continue;
+                        }
+                        collision |= self.screen.draw_bit(row, col, line_bits[j]);
                     }
                 }
                 *self.v(Register::VF) = Wrapping(collision as u8);
                 self.pc_incr();
             }
             Instr::Pressed { r } => {
-                todo!()
+                if self.key_pressed(self.rv(r)) {
+                    self.pc_incr();
+                }
+                self.pc_incr();
             }
             Instr::NotPressed { r } => {
-                todo!()
+                if !self.key_pressed(self.rv(r)) {
+                    self.pc_incr();
+                }
+                self.pc_incr();
             }
             Instr::GetDelay { r } => {
                 *self.v(r) = Wrapping(self.delay);
                 self.pc_incr();
             }
             Instr::LoadKey { r } => {
-                todo!()
+                // Waits for a key press by not moving the pc until a key is down
+                if let Some(k) = self.keys.iter().position(|&k| k) {
+                    *self.v(r) = Wrapping(k as u8);
+                    self.pc_incr();
+                }
             }
             Instr::SetDelayTimer { r } => {
                 self.delay = self.rv(r);
@@ -231,9 +418,10 @@ impl Chip8 {
                 self.pc_incr();
             }
             Instr::SpriteAddr { r } => {
-                let char : u8 = self.rv(r);
-                assert!(char <= 15);
+                // Only the low nibble selects the digit
+                let char: u8 = self.rv(r) & 0xF;
                 self.i = Chip8::FONT_START as u16 + char as u16 * font::SPRITE_BYTES as u16;
+                self.pc_incr();
             }
             Instr::StoreBCD { r } => {
                 let mut v: u16 = self.rv(r) as u16;
@@ -253,6 +441,7 @@ impl Chip8 {
                 for r in 0..=n as usize {
                     self.memory[self.i as usize + r] = self.rv(Register::from(r as u8));
                 }
+                self.load_store_incr_i(n);
                 self.pc_incr();
             }
             Instr::RegLoad { x } => {
@@ -260,6 +449,7 @@ impl Chip8 {
                 for r in 0..=n as usize {
                     *self.v(Register::from(r as u8)) = Wrapping(self.memory[self.i as usize + r]);
                 }
+                self.load_store_incr_i(n);
                 self.pc_incr();
             }
             Instr::Data(_) => {
@@ -271,15 +461,52 @@ impl Chip8 {
     pub fn load_memory(&mut self, filepath: &PathBuf) -> Result<()> {
         let mut v: Vec<u8> = Vec::new();
         let mut f: File = File::open(filepath)?;
-        let len: usize = Read::read_to_end(&mut f, &mut v)?;
+        Read::read_to_end(&mut f, &mut v)?;
+        self.load_bytes(&v)
+    }
+
+    /// Copies a program to memory at the start of the code, and the fonts
+    pub fn load_bytes(&mut self, v: &[u8]) -> Result<()> {
+        let len = v.len();
         if len >= Chip8::MEM_SIZE - Chip8::CODE_START {
-            panic!(
-                "The given file size exceeds Chip8 memory.\nFile bytes = {len}; Max bytes = {:?}",
-                Chip8::MEM_SIZE
-            )
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "the file has {len} bytes, more than the {} that fit in memory",
+                    Chip8::MEM_SIZE - Chip8::CODE_START - 1
+                ),
+            ));
         }
         self.memory[Chip8::CODE_START..Chip8::CODE_START + len].copy_from_slice(&v[..len]);
-        font::copy_chars::<{Chip8::MEM_SIZE}, {Chip8::FONT_START}>(&mut self.memory);
+        font::copy_chars::<{Chip8::MEM_SIZE}, {Chip8::FONT_START}>(
+            &mut self.memory,
+            &self.font,
+        );
+        if let Some(big) = &self.big_font {
+            self.memory[Chip8::BIG_FONT_START..Chip8::BIG_FONT_START + big.len()]
+                .copy_from_slice(big);
+        }
+        Ok(())
+    }
+
+    /// Copies a segment, e.g. a table of data, to memory at `addr`, which
+    /// can't be below the start of the code, where the font is
+    pub fn load_at(&mut self, addr: u16, v: &[u8]) -> Result<()> {
+        let start = addr as usize;
+        let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+        if start < Chip8::CODE_START {
+            return invalid(format!(
+                "{addr:#05X} is below the start of the code, {:#05X}",
+                Chip8::CODE_START
+            ));
+        }
+        if start + v.len() > Chip8::MEM_SIZE {
+            return invalid(format!(
+                "{} bytes at {addr:#05X} go past the end of the memory",
+                v.len()
+            ));
+        }
+        self.memory[start..start + v.len()].copy_from_slice(v);
         Ok(())
     }
 }
@@ -291,6 +518,10 @@ impl Debugger {
             p: 0,
             p_max: 0,
             diff: true,
+            marks: BTreeMap::new(),
+            breakpoints: vec![],
+            undo: vec![],
+            redo: vec![],
         }
     }
 
@@ -306,6 +537,19 @@ impl Debugger {
         }
     }
 
+    /// Memory addresses whose value differs from the previous step
+    pub fn memory_diff(&self) -> Vec<usize> {
+        match self.peek_prev() {
+            Some(prev) => {
+                let now = self.peek();
+                (0..Chip8::MEM_SIZE)
+                    .filter(|&a| prev.memory[a] != now.memory[a])
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+
     pub fn step_back(&mut self) -> bool {
         let possible = self.p > 0;
         if possible {
@@ -320,6 +564,28 @@ impl Debugger {
         }
     }
 
+    /// Moves to a recorded step. Returns false if the step is not recorded
+    pub fn goto_step(&mut self, step: usize) -> bool {
+        let possible = step < self.history.len();
+        if possible {
+            self.p = step;
+        }
+        possible
+    }
+
+    pub fn set_mark(&mut self, name: char) {
+        self.marks.insert(name, self.p);
+    }
+
+    /// Names of the marks at the current step
+    pub fn marks_here(&self) -> Vec<char> {
+        self.marks
+            .iter()
+            .filter(|&(_, &step)| step == self.p)
+            .map(|(&name, _)| name)
+            .collect()
+    }
+
     pub fn step_max(&self) -> usize {
         self.p_max
     }
@@ -328,10 +594,13 @@ impl Debugger {
         self.p
     }
 
+    /// Runs the next instruction, unless the next step is already recorded.
+    /// The timers count down as in `Chip8::run_step`, counting the steps from
+    /// the first of the history, and the step records them
     pub fn step_forward(&mut self) {
         if self.p == self.history.len() - 1 {
             let mut next = self.history.last().unwrap().clone();
-            next.run_instr();
+            next.run_step(self.history.len() as u64);
             self.history.push(next);
         }
         self.p += 1;
@@ -343,4 +612,94 @@ impl Debugger {
             self.step_forward();
         }
     }
+
+    /// Steps forward until an enabled breakpoint triggers, for at most `limit`
+    /// steps. Returns the index of the breakpoint that stopped the run.
+    pub fn continue_run(&mut self, limit: u32) -> Option<usize> {
+        for _ in 0..limit {
+            self.step_forward();
+            let (prev, now) = (&self.history[self.p - 1], &self.history[self.p]);
+            if let Some(ix) = self.breakpoints.iter().position(|b| b.triggers(prev, now)) {
+                self.breakpoints[ix].hits += 1;
+                return Some(ix);
+            }
+        }
+        None
+    }
+
+    /// Removes the breakpoint on the pc at `addr` if there is one, otherwise
+    /// adds it. Returns true if the breakpoint was added.
+    pub fn toggle_breakpoint(&mut self, addr: u16) -> bool {
+        let kind = BreakKind::Pc(addr);
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|b| b.kind != kind);
+        let add = self.breakpoints.len() == before;
+        if add {
+            self.breakpoints.push(Breakpoint::new(kind, None));
+        }
+        add
+    }
+
+    /// Applies a manual edit to a copy of the current state, which becomes the
+    /// next step. The recorded steps after the current one are discarded, but
+    /// they come back if the edit is undone.
+    pub fn edit(&mut self, f: impl FnOnce(&mut Chip8)) {
+        let mut state = self.peek().clone();
+        f(&mut state);
+        self.apply_edit(Edit {
+            step: self.p,
+            state,
+            discarded: vec![],
+            p_max: self.p_max,
+            marks: self.marks.clone(),
+        });
+        self.redo.clear();
+    }
+
+    fn apply_edit(&mut self, mut e: Edit) {
+        self.p = e.step;
+        e.discarded = self.history.split_off(e.step + 1);
+        e.p_max = self.p_max;
+        e.marks = self.marks.clone();
+        self.history.push(e.state.clone());
+        self.p += 1;
+        self.p_max = self.p;
+        self.marks.retain(|_, &mut step| step < self.p);
+        self.undo.push(e);
+    }
+
+    /// Reverts the last manual edit, restoring the recorded steps it discarded.
+    /// Returns false if there is nothing to undo.
+    pub fn undo_edit(&mut self) -> bool {
+        let Some(mut e) = self.undo.pop() else {
+            return false;
+        };
+        self.history.truncate(e.step + 1);
+        self.history.append(&mut e.discarded);
+        self.p = e.step;
+        self.p_max = e.p_max;
+        self.marks = e.marks.clone();
+        self.redo.push(e);
+        true
+    }
+
+    /// Applies again the last undone edit. Returns false if there is nothing
+    /// to redo.
+    pub fn redo_edit(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(e) => {
+                self.apply_edit(e);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Toggles a keypad key as a manual edit
+    pub fn toggle_key(&mut self, key: u8) {
+        self.edit(|c| {
+            let k = &mut c.keys[(key & 0xF) as usize];
+            *k = !*k;
+        });
+    }
 }
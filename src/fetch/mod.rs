@@ -0,0 +1,78 @@
+//! ROMs given by URL to `chip-8 run`, downloaded with `curl` into a cache
+//! so that they are only downloaded once.
+
+use super::info::sha256;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Whether a ROM argument is a URL rather than a path
+pub fn is_url(file: &Path) -> bool {
+    file.to_str()
+        .is_some_and(|f| f.starts_with("http://") || f.starts_with("https://"))
+}
+
+/// `$XDG_CACHE_HOME/chip-8/roms`, or `~/.cache/chip-8/roms`
+pub fn cache_dir() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(d) if !d.is_empty() => PathBuf::from(d),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(dir.join("chip-8").join("roms"))
+}
+
+/// Checks that a ROM has the SHA-256 `expected`, in hexadecimal
+pub fn verify(rom: &[u8], expected: &str) -> Result<(), String> {
+    let found = sha256(rom);
+    if found.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(format!("the SHA-256 is {found}, expected {expected}"))
+    }
+}
+
+/// The file of the ROM at `url` in the cache directory `cache`, downloaded
+/// if it isn't there yet. The file is named after the last segment of the
+/// URL, after a hash of the whole URL so that ROMs of the same name from
+/// different places don't clash. With `expected`, a download that doesn't
+/// have that SHA-256 is not kept
+pub fn fetch(url: &str, cache: &Path, expected: Option<&str>) -> Result<PathBuf, String> {
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|n| !n.is_empty())
+        .unwrap_or("rom.ch8");
+    let file = cache.join(format!("{}-{name}", &sha256(url.as_bytes())[..16]));
+    if !file.exists() {
+        fs::create_dir_all(cache)
+            .map_err(|e| format!("could not create {}: {e}", cache.display()))?;
+        let partial = file.with_extension("part");
+        let status = Command::new("curl")
+            .args([
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--location",
+                "--output",
+            ])
+            .arg(&partial)
+            .arg(url)
+            .status()
+            .map_err(|e| format!("could not run curl: {e}"))?;
+        if !status.success() {
+            let _ = fs::remove_file(&partial);
+            return Err(format!("could not download {url}"));
+        }
+        if let Some(expected) = expected {
+            let rom = fs::read(&partial).map_err(|e| e.to_string())?;
+            if let Err(e) = verify(&rom, expected) {
+                let _ = fs::remove_file(&partial);
+                return Err(format!("{url}: {e}"));
+            }
+        }
+        fs::rename(&partial, &file).map_err(|e| e.to_string())?;
+    }
+    Ok(file)
+}
@@ -1,39 +1,166 @@
-use std::*;
+use clap::ValueEnum;
 
 pub const SPRITE_BYTES: usize = 5;
 
-type CharSprite = [u8; SPRITE_BYTES];
-
-pub const ZERO: CharSprite = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-pub const ONE: CharSprite = [0x20, 0x60, 0x20, 0x20, 0x70];
-pub const TWO: CharSprite = [0xf0, 0x10, 0xf0, 0x80, 0xf0];
-pub const THREE: CharSprite = [0xf0, 0x10, 0xf0, 0x10, 0xf0];
-pub const FOUR: CharSprite = [0x90, 0x90, 0xf0, 0x10, 0x10];
-pub const FIVE: CharSprite = [0xf0, 0x80, 0xf0, 0x10, 0xf0];
-pub const SIX: CharSprite = [0xf0, 0x80, 0xf0, 0x90, 0xf0];
-pub const SEVEN: CharSprite = [0xf0, 0x10, 0x20, 0x40, 0x40];
-pub const EIGHT: CharSprite = [0xf0, 0x90, 0xf0, 0x90, 0xf0];
-pub const NINE: CharSprite = [0xf0, 0x90, 0xf0, 0x10, 0xf0];
-pub const A: CharSprite = [0xf0, 0x90, 0xf0, 0x90, 0x90];
-pub const B: CharSprite = [0xe0, 0x90, 0xe0, 0x90, 0xe0];
-pub const C: CharSprite = [0xf0, 0x80, 0x80, 0x80, 0x90];
-pub const D: CharSprite = [0xe0, 0x90, 0x90, 0x90, 0xe0];
-pub const E: CharSprite = [0xf0, 0x80, 0xf0, 0x80, 0xf0];
-pub const F: CharSprite = [0xf0, 0x80, 0xf0, 0x80, 0x80];
-
-pub const ALL_CHARS: [&CharSprite; 16] = [
-    &ZERO, &ONE, &TWO, &THREE, &FOUR, &FIVE, &SIX, &SEVEN, &EIGHT, &NINE, &A, &B, &C, &D, &E, &F,
+// Total bytes = 16 * 5 = 80
+pub const ALL_CHARS_BYTES: usize = 16 * SPRITE_BYTES;
+
+/// The sprites of the hexadecimal digits, 0 to F, one after the other
+pub type FontBytes = [u8; ALL_CHARS_BYTES];
+
+/// The bytes of a big font, as SUPER-CHIP and XO-CHIP have, the digits 0
+/// to F in sprites of 8x10 pixels
+pub const BIG_FONT_BYTES: usize = 16 * 2 * SPRITE_BYTES;
+
+pub type BigFontBytes = [u8; BIG_FONT_BYTES];
+
+/// The fonts of the interpreters. Each drew the digits its own way, and some
+/// ROMs were made to look right with one of them
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Font {
+    /// The font of Cowgod's reference, which most emulators use
+    #[default]
+    Cowgod,
+    /// The font of the COSMAC VIP interpreter, with square B and D
+    Vip,
+    /// The font of the DREAM 6800, 3 pixels wide
+    Dream6800,
+    /// The font of the ETI-660, 3 pixels wide with a lowercase b and d
+    Eti660,
+    /// A font with rounded corners
+    Modern,
+}
+
+#[rustfmt::skip]
+const COWGOD: FontBytes = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0x90, 0x90, 0xf0, 0x10, 0x10, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xe0, 0x90, 0xe0, 0x90, 0xe0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xe0, 0x90, 0x90, 0x90, 0xe0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
 ];
 
-// Total bytes = 16 * 5 = 80
-pub const ALL_CHARS_BYTES: usize = ALL_CHARS.len() * SPRITE_BYTES;
+#[rustfmt::skip]
+const VIP: FontBytes = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0xa0, 0xa0, 0xf0, 0x20, 0x20, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xf0, 0x50, 0x70, 0x50, 0xf0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xf0, 0x50, 0x50, 0x50, 0xf0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
+];
 
-/// Copies all character bytes sequentially starting from the beginning of the array
-/// The array needs to have at least length 80
-pub fn copy_chars<const N: usize, const START: usize>(m: &mut [u8; N]) {
-    assert!(N - START >= ALL_CHARS_BYTES);
-    let chunks_x_sprites = m[START..].chunks_mut(SPRITE_BYTES).zip(ALL_CHARS);
-    for (chunk, sprite) in chunks_x_sprites {
-        chunk.copy_from_slice(sprite);
+#[rustfmt::skip]
+const DREAM6800: FontBytes = [
+    0xe0, 0xa0, 0xa0, 0xa0, 0xe0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xe0, 0x20, 0xe0, 0x80, 0xe0, // 2
+    0xe0, 0x20, 0xe0, 0x20, 0xe0, // 3
+    0x80, 0xa0, 0xa0, 0xe0, 0x20, // 4
+    0xe0, 0x80, 0xe0, 0x20, 0xe0, // 5
+    0xe0, 0x80, 0xe0, 0xa0, 0xe0, // 6
+    0xe0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xe0, 0xa0, 0xe0, 0xa0, 0xe0, // 8
+    0xe0, 0xa0, 0xe0, 0x20, 0xe0, // 9
+    0xe0, 0xa0, 0xe0, 0xa0, 0xa0, // A
+    0xc0, 0xa0, 0xe0, 0xa0, 0xc0, // B
+    0xe0, 0x80, 0x80, 0x80, 0xe0, // C
+    0xc0, 0xa0, 0xa0, 0xa0, 0xc0, // D
+    0xe0, 0x80, 0xe0, 0x80, 0xe0, // E
+    0xe0, 0x80, 0xc0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const ETI660: FontBytes = [
+    0xe0, 0xa0, 0xa0, 0xa0, 0xe0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xe0, 0x20, 0xe0, 0x80, 0xe0, // 2
+    0xe0, 0x20, 0xe0, 0x20, 0xe0, // 3
+    0xa0, 0xa0, 0xe0, 0x20, 0x20, // 4
+    0xe0, 0x80, 0xe0, 0x20, 0xe0, // 5
+    0xe0, 0x80, 0xe0, 0xa0, 0xe0, // 6
+    0xe0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xe0, 0xa0, 0xe0, 0xa0, 0xe0, // 8
+    0xe0, 0xa0, 0xe0, 0x20, 0xe0, // 9
+    0xe0, 0xa0, 0xe0, 0xa0, 0xa0, // A
+    0x80, 0x80, 0xe0, 0xa0, 0xe0, // B
+    0xe0, 0x80, 0x80, 0x80, 0xe0, // C
+    0x20, 0x20, 0xe0, 0xa0, 0xe0, // D
+    0xe0, 0x80, 0xe0, 0x80, 0xe0, // E
+    0xe0, 0x80, 0xc0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const MODERN: FontBytes = [
+    0x60, 0x90, 0x90, 0x90, 0x60, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xe0, 0x10, 0x60, 0x80, 0xf0, // 2
+    0xe0, 0x10, 0x60, 0x10, 0xe0, // 3
+    0x90, 0x90, 0xf0, 0x10, 0x10, // 4
+    0xf0, 0x80, 0xe0, 0x10, 0xe0, // 5
+    0x60, 0x80, 0xe0, 0x90, 0x60, // 6
+    0xf0, 0x10, 0x20, 0x40, 0x40, // 7
+    0x60, 0x90, 0x60, 0x90, 0x60, // 8
+    0x60, 0x90, 0x70, 0x10, 0x60, // 9
+    0x60, 0x90, 0xf0, 0x90, 0x90, // A
+    0xe0, 0x90, 0xe0, 0x90, 0xe0, // B
+    0x70, 0x80, 0x80, 0x80, 0x70, // C
+    0xe0, 0x90, 0x90, 0x90, 0xe0, // D
+    0xf0, 0x80, 0xe0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xe0, 0x80, 0x80, // F
+];
+
+impl Font {
+    pub fn bytes(self) -> &'static FontBytes {
+        match self {
+            Font::Cowgod => &COWGOD,
+            Font::Vip => &VIP,
+            Font::Dream6800 => &DREAM6800,
+            Font::Eti660 => &ETI660,
+            Font::Modern => &MODERN,
+        }
     }
 }
+
+/// Reads a font file: the 80 bytes of the font, optionally followed by the
+/// 160 of the big font
+pub fn read(bytes: &[u8]) -> Result<(FontBytes, Option<BigFontBytes>), String> {
+    let (small, big) = bytes.split_at(bytes.len().min(ALL_CHARS_BYTES));
+    match (small.try_into(), big.len()) {
+        (Ok(small), 0) => Ok((small, None)),
+        (Ok(small), BIG_FONT_BYTES) => Ok((small, Some(big.try_into().expect("160 bytes")))),
+        _ => Err(format!(
+            "the font has {} bytes, expected {ALL_CHARS_BYTES}, or {} with the big font",
+            bytes.len(),
+            ALL_CHARS_BYTES + BIG_FONT_BYTES
+        )),
+    }
+}
+
+/// Copies the character bytes of `font` starting at position START of the array
+/// The array needs to have at least length START + 80
+pub fn copy_chars<const N: usize, const START: usize>(m: &mut [u8; N], font: &FontBytes) {
+    assert!(N - START >= ALL_CHARS_BYTES);
+    m[START..START + ALL_CHARS_BYTES].copy_from_slice(font);
+}
@@ -0,0 +1,136 @@
+use super::architecture::*;
+use super::render::rgb;
+use clap::ValueEnum;
+use ratatui::style::Color;
+use std::fs;
+use std::fs::File;
+use std::io::*;
+use std::path::PathBuf;
+
+/// How each frame is written
+#[derive(PartialEq, Eq, Clone, Copy, Debug, ValueEnum)]
+pub enum FrameFormat {
+    /// A binary PGM (`P5`) image, black and white
+    Pgm,
+    /// Raw 64x32 RGBA pixels with the colors of the theme, e.g. for
+    /// `ffmpeg -f rawvideo -pixel_format rgba -video_size 64x32`
+    Rgba,
+}
+
+/// Where the frames go
+enum Sink {
+    /// One numbered file per frame
+    Dir(PathBuf),
+    /// All the frames one after the other, e.g. to a named pipe
+    Stream(File),
+}
+
+/// Writes every frame drawn by the debugger, for external tools such as
+/// ffmpeg
+pub struct FrameDump {
+    sink: Sink,
+    format: FrameFormat,
+    /// The number of frames written
+    pub count: u64,
+    on: (u8, u8, u8),
+    off: (u8, u8, u8),
+}
+
+impl FrameDump {
+    /// Dumps to `path`: if it is a directory, each frame is a file in it,
+    /// and otherwise the frames are appended to it
+    pub fn new(path: PathBuf, format: FrameFormat, on: Color, off: Option<Color>) -> Result<Self> {
+        let sink = if path.is_dir() {
+            Sink::Dir(path)
+        } else {
+            Sink::Stream(File::create(path)?)
+        };
+        Ok(FrameDump {
+            sink,
+            format,
+            count: 0,
+            on: rgb(on),
+            off: rgb(off.unwrap_or(Color::Black)),
+        })
+    }
+
+    pub fn frame(&mut self, screen: &Screen) -> Result<()> {
+        let mut bytes = vec![];
+        if self.format == FrameFormat::Pgm {
+            bytes.extend(format!("P5\n{} {}\n255\n", Screen::NCOLS, Screen::NROWS).bytes());
+        }
+        for row in screen.rows {
+            for px in row {
+                match self.format {
+                    FrameFormat::Pgm => bytes.push(if px { 255 } else { 0 }),
+                    FrameFormat::Rgba => {
+                        let (r, g, b) = if px { self.on } else { self.off };
+                        bytes.extend([r, g, b, 255]);
+                    }
+                }
+            }
+        }
+        match &mut self.sink {
+            Sink::Dir(dir) => {
+                let ext = match self.format {
+                    FrameFormat::Pgm => "pgm",
+                    FrameFormat::Rgba => "rgba",
+                };
+                fs::write(dir.join(format!("frame-{:06}.{ext}", self.count)), bytes)?
+            }
+            Sink::Stream(f) => f.write_all(&bytes)?,
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// The screen of a binary PGM of 64x32 pixels, as `FrameFormat::Pgm` writes
+/// them. The pixels brighter than half the maximum are on
+pub fn read_pgm(bytes: &[u8]) -> std::result::Result<Screen, String> {
+    // The magic number, the width, the height and the maximum value, then
+    // one whitespace before the pixels
+    let mut fields = vec![];
+    let mut pos = 0;
+    while fields.len() < 4 {
+        while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+            pos += 1;
+        }
+        let start = pos;
+        while bytes.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        if start == pos {
+            return Err("truncated PGM header".into());
+        }
+        fields.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+    }
+    let pixels = &bytes[(pos + 1).min(bytes.len())..];
+    let number = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|_| format!("invalid PGM header {s}"))
+    };
+    if fields[0] != "P5" {
+        return Err(format!("expected a binary PGM (P5), found {}", fields[0]));
+    }
+    let (width, height, max) = (
+        number(&fields[1])?,
+        number(&fields[2])?,
+        number(&fields[3])?,
+    );
+    if (width, height) != (Screen::NCOLS, Screen::NROWS) {
+        return Err(format!(
+            "expected {}x{} pixels, found {width}x{height}",
+            Screen::NCOLS,
+            Screen::NROWS
+        ));
+    }
+    if max > 255 || pixels.len() < width * height {
+        return Err("expected one byte per pixel".into());
+    }
+    let mut screen = Screen::new();
+    for (ix, &px) in pixels[..width * height].iter().enumerate() {
+        screen.rows[ix / width].set(ix % width, px as usize * 2 > max);
+    }
+    Ok(screen)
+}
@@ -0,0 +1,158 @@
+//! A gallery of the sprites of a ROM for `chip-8 sprites`: the bytes that
+//! its code draws, found by `analysis::sprites`, as text art side by side
+//! or as a PNG.
+
+use super::analysis::{reachable, sprites};
+use super::render::Bitmap;
+use ratatui::style::Color;
+
+/// The widest row of the text gallery, in characters
+const TEXT_WIDTH: usize = 80;
+
+/// The sprites in a row of the PNG gallery
+const PNG_COLUMNS: usize = 8;
+
+/// The pixels between the sprites of the PNG gallery, and around them
+const PNG_GAP: usize = 2;
+
+/// A sprite drawn by a ROM
+pub struct Sprite {
+    pub addr: u16,
+    /// Whether it is 16x16, drawn by `DXY0`, instead of 8 pixels wide
+    pub wide: bool,
+    /// The rows, the leftmost pixel in the highest bit of the width
+    pub rows: Vec<u16>,
+}
+
+impl Sprite {
+    pub fn width(&self) -> usize {
+        if self.wide { 16 } else { 8 }
+    }
+
+    pub fn pixel(&self, row: usize, col: usize) -> bool {
+        self.rows[row] & (1 << (self.width() - 1 - col)) != 0
+    }
+
+    /// `8x5` or `16x16`
+    pub fn size(&self) -> String {
+        format!("{}x{}", self.width(), self.rows.len())
+    }
+}
+
+/// The sprites drawn by the code of a ROM loaded at `start`, by address.
+/// The bytes past the end of the ROM are zeros, as in memory, and the
+/// sprites before the start, as those of the font, are left out
+pub fn gallery(rom: &[u8], start: u16) -> Vec<Sprite> {
+    let byte = |addr: usize| {
+        let ix = addr - start as usize;
+        rom.get(ix).copied().unwrap_or(0)
+    };
+    sprites(&reachable(rom, start), start)
+        .into_iter()
+        .filter(|&(addr, _)| addr >= start)
+        .map(|(addr, height)| {
+            let addr_ix = addr as usize;
+            let rows = if height == 0 {
+                (0..16)
+                    .map(|r| u16::from_be_bytes([byte(addr_ix + 2 * r), byte(addr_ix + 2 * r + 1)]))
+                    .collect()
+            } else {
+                (0..height as usize)
+                    .map(|r| byte(addr_ix + r) as u16)
+                    .collect()
+            };
+            Sprite {
+                addr,
+                wide: height == 0,
+                rows,
+            }
+        })
+        .collect()
+}
+
+/// The sprites as text art, side by side in rows up to 80 characters
+/// wide, each under its address and its size:
+///
+/// ```text
+/// 0x4A5 8x4  0x4A9 8x4  0x4AD 8x4
+/// ........   ███.....   ██......
+/// █.█.....   █.█.....   .█......
+/// ██......   █.█.....   .█......
+/// █.......   ███.....   ███.....
+/// ```
+pub fn text(sprites: &[Sprite]) -> String {
+    let cells: Vec<(usize, Vec<String>)> = sprites
+        .iter()
+        .map(|s| {
+            let title = format!("{:#05X} {}", s.addr, s.size());
+            let width = title.chars().count().max(s.width());
+            let mut lines = vec![title];
+            lines.extend((0..s.rows.len()).map(|r| {
+                (0..s.width())
+                    .map(|c| if s.pixel(r, c) { '█' } else { '.' })
+                    .collect()
+            }));
+            (width, lines)
+        })
+        .collect();
+    let mut out = String::new();
+    let mut row: Vec<&(usize, Vec<String>)> = vec![];
+    let flush = |row: &mut Vec<&(usize, Vec<String>)>, out: &mut String| {
+        let height = row.iter().map(|(_, l)| l.len()).max().unwrap_or(0);
+        for line in 0..height {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|(width, lines)| {
+                    format!("{:<width$}", lines.get(line).map_or("", |l| l.as_str()))
+                })
+                .collect();
+            *out += cells.join("  ").trim_end();
+            out.push('\n');
+        }
+        out.push('\n');
+        row.clear();
+    };
+    let mut used = 0;
+    for cell in &cells {
+        if !row.is_empty() && used + 2 + cell.0 > TEXT_WIDTH {
+            flush(&mut row, &mut out);
+            used = 0;
+        }
+        used += if row.is_empty() { cell.0 } else { 2 + cell.0 };
+        row.push(cell);
+    }
+    if !row.is_empty() {
+        flush(&mut row, &mut out);
+    }
+    out
+}
+
+/// The sprites as a PNG, in rows of 8 cells as large as the largest
+/// sprite, each CHIP-8 pixel a `scale` x `scale` square. The pixels of the
+/// sprites are `on` and `off`, and those around them black
+pub fn png(sprites: &[Sprite], scale: usize, on: Color, off: Option<Color>) -> Vec<u8> {
+    let width = sprites.iter().map(Sprite::width).max().unwrap_or(8) + PNG_GAP;
+    let height = sprites.iter().map(|s| s.rows.len()).max().unwrap_or(1) + PNG_GAP;
+    let columns = sprites.len().clamp(1, PNG_COLUMNS);
+    let rows = sprites.len().div_ceil(PNG_COLUMNS).max(1);
+    let bitmap = Bitmap::new(
+        PNG_GAP + columns * width,
+        PNG_GAP + rows * height,
+        scale,
+        |r, c| {
+            let (r, c) = (r.checked_sub(PNG_GAP)?, c.checked_sub(PNG_GAP)?);
+            let sprite = sprites.get(r / height * PNG_COLUMNS + c / width)?;
+            let (r, c) = (r % height, c % width);
+            if r >= sprite.rows.len() || c >= sprite.width() {
+                None
+            } else if sprite.pixel(r, c) {
+                Some(on)
+            } else {
+                // A dark gray where the theme leaves the pixels that are off
+                // black, so that the sprite can be told from its surroundings
+                Some(off.unwrap_or(Color::Rgb(0x20, 0x20, 0x20)))
+            }
+        },
+    );
+    bitmap.png()
+}
@@ -0,0 +1,185 @@
+//! Graphs of a program: its control flow and its calls, in Graphviz's DOT
+//! language, e.g. for `dot -Tsvg rom.dot`
+
+use super::analysis::*;
+use super::symbols::Symbols;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A string in DOT, quoted and escaped
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn node(addr: u16) -> String {
+    quote(&format!("{addr:#05X}"))
+}
+
+/// The control-flow graph of the code reachable from the start of a ROM
+/// loaded at `start`: a box per basic block, with its instructions, and an
+/// edge per way out of it. The jumps are bold, the skips dashed and the
+/// calls blue, and the names of `symbols` label the blocks and the operands
+pub fn cfg(rom: &[u8], start: u16, symbols: &Symbols) -> String {
+    let code = reachable(rom, start);
+    let mut out = String::from("digraph cfg {\n");
+    out += "    node [shape=box, fontname=monospace];\n";
+    for (&leader, block) in &blocks(&code, start) {
+        let mut label = format!("{}\\l", symbols.describe(leader));
+        for addr in &block.instrs {
+            let instr = &code[addr];
+            let text = symbols.label_operand(instr, &instr.to_string());
+            label += &format!("{addr:#05X}  {}\\l", text.replace('"', "\\\""));
+        }
+        out += &format!("    {} [label=\"{label}\"];\n", node(leader));
+        for &(edge, target) in &block.edges {
+            let style = match edge {
+                Edge::Next => "",
+                Edge::Jump => " [style=bold]",
+                Edge::Skip => " [style=dashed, label=skip]",
+                Edge::Call => " [color=blue, label=call]",
+            };
+            out += &format!("    {} -> {}{style};\n", node(leader), node(target));
+        }
+    }
+    out + "}\n"
+}
+
+/// What is remarkable about a routine: whether it never returns and
+/// whether it is recursive
+fn notes(graph: &BTreeMap<u16, Routine>, entry: u16, start: u16) -> Vec<&'static str> {
+    let mut notes = vec![];
+    if entry != start && !graph[&entry].returns {
+        notes.push("never returns");
+    }
+    if recursive(graph, entry) {
+        notes.push("recursive");
+    }
+    notes
+}
+
+/// The call graph of the code reachable from the start of a ROM loaded at
+/// `start`, as a tree from the start, followed by the callers of each
+/// subroutine:
+///
+/// ```text
+/// main
+/// ├── draw_score
+/// │   └── draw_digit
+/// └── game_over  (never returns)
+///
+/// draw_digit  called by draw_score
+/// ```
+///
+/// A subroutine is expanded the first time that it shows, and a recursive
+/// call is not expanded
+pub fn calls_tree(rom: &[u8], start: u16, symbols: &Symbols) -> String {
+    fn walk(
+        graph: &BTreeMap<u16, Routine>,
+        entry: u16,
+        ctx: (u16, &Symbols),
+        prefix: &str,
+        path: &mut Vec<u16>,
+        shown: &mut BTreeSet<u16>,
+        out: &mut String,
+    ) {
+        let calls: Vec<u16> = graph
+            .get(&entry)
+            .map_or(vec![], |r| r.calls.iter().copied().collect());
+        for (ix, &callee) in calls.iter().enumerate() {
+            let last = ix + 1 == calls.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let mut notes = notes(graph, callee, ctx.0);
+            let cycle = path.contains(&callee);
+            if cycle {
+                notes = vec!["recursive call"];
+            } else if shown.contains(&callee) && !graph[&callee].calls.is_empty() {
+                notes.push("see above");
+            }
+            let notes = if notes.is_empty() {
+                String::new()
+            } else {
+                format!("  ({})", notes.join(", "))
+            };
+            *out += &format!("{prefix}{branch}{}{notes}\n", ctx.1.routine(callee, ctx.0));
+            if !cycle && shown.insert(callee) {
+                path.push(callee);
+                walk(
+                    graph,
+                    callee,
+                    ctx,
+                    &format!("{prefix}{indent}"),
+                    path,
+                    shown,
+                    out,
+                );
+                path.pop();
+            }
+        }
+    }
+
+    let code = reachable(rom, start);
+    let graph = call_graph(&code, start);
+    let mut out = String::new();
+    if graph.contains_key(&start) {
+        out += &format!("{}\n", symbols.routine(start, start));
+        let mut shown = BTreeSet::from([start]);
+        walk(
+            &graph,
+            start,
+            (start, symbols),
+            "",
+            &mut vec![start],
+            &mut shown,
+            &mut out,
+        );
+    }
+    let mut callers = vec![];
+    for (&entry, routine) in graph.iter().filter(|&(&a, _)| a != start) {
+        let names: Vec<String> = routine
+            .callers
+            .iter()
+            .map(|&a| symbols.routine(a, start))
+            .collect();
+        let mut line = format!(
+            "{}  called by {}",
+            symbols.routine(entry, start),
+            names.join(", ")
+        );
+        for note in notes(&graph, entry, start) {
+            line += &format!("; {note}");
+        }
+        callers.push(line);
+    }
+    if !callers.is_empty() {
+        out += &format!("\n{}\n", callers.join("\n"));
+    }
+    out
+}
+
+/// The call graph, with a node per routine and an edge per routine that it
+/// calls. The routines that never return are red and the recursive ones
+/// are drawn with a double border
+pub fn calls_dot(rom: &[u8], start: u16, symbols: &Symbols) -> String {
+    let code = reachable(rom, start);
+    let graph = call_graph(&code, start);
+    let mut out = String::from("digraph calls {\n");
+    out += "    node [shape=box, fontname=monospace];\n";
+    for (&entry, routine) in &graph {
+        let mut attrs = vec![format!("label={}", quote(&symbols.routine(entry, start)))];
+        let notes = notes(&graph, entry, start);
+        if notes.contains(&"never returns") {
+            attrs.push("color=red".into());
+        }
+        if notes.contains(&"recursive") {
+            attrs.push("peripheries=2".into());
+        }
+        out += &format!("    {} [{}];\n", node(entry), attrs.join(", "));
+        for &callee in &routine.calls {
+            out += &format!("    {} -> {};\n", node(entry), node(callee));
+        }
+    }
+    out + "}\n"
+}
@@ -0,0 +1,89 @@
+//! A native window that plays a ROM, for when a terminal is not enough. The
+//! window itself needs the `gui` feature; its options are always there, for
+//! the command line.
+
+#[cfg(feature = "gui")]
+mod window;
+
+#[cfg(feature = "gui")]
+pub use window::{Gui, SCALE, play};
+
+use super::architecture::Screen;
+use clap::ValueEnum;
+use std::sync::{Arc, Mutex};
+
+/// Filters that give the window the look of a CRT. F1, F2 and F3 toggle them
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub struct Effects {
+    /// The bottom line of each row of pixels is darker
+    pub scanlines: bool,
+    /// The screen bulges like the glass of a tube
+    pub curvature: bool,
+    /// Light bleeds from the pixels that are on into their neighbours
+    pub glow: bool,
+}
+
+impl Effects {
+    /// All of them, for `--crt`
+    pub fn crt() -> Effects {
+        Effects {
+            scanlines: true,
+            curvature: true,
+            glow: true,
+        }
+    }
+}
+
+/// What the debugger and a window on its own thread share
+#[derive(Default)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+struct Shared {
+    /// The screen of the step the debugger is at
+    screen: Screen,
+    /// The CHIP-8 keys held down in the window
+    keys: [bool; 16],
+    /// Why the window closed, if it did
+    closed: Option<String>,
+}
+
+/// A window that follows the debugger, see `--window`. It shows the screen of
+/// the current step, and the keys held in it are pressed on the keypad.
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub struct Attached {
+    shared: Arc<Mutex<Shared>>,
+    /// The keys held in the window when they were last read
+    keys: [bool; 16],
+}
+
+impl Attached {
+    /// Shows `screen` in the window. Returns the keys held in it if they
+    /// changed since the last time, or why the window closed.
+    pub fn sync(&mut self, screen: &Screen) -> Result<Option<[bool; 16]>, String> {
+        let mut shared = self.shared.lock().expect("window lock poisoned");
+        if let Some(reason) = &shared.closed {
+            return Err(reason.clone());
+        }
+        if shared.screen != *screen {
+            shared.screen = screen.clone();
+        }
+        if shared.keys == self.keys {
+            return Ok(None);
+        }
+        self.keys = shared.keys;
+        Ok(Some(self.keys))
+    }
+}
+
+/// How the screen fills a window of another shape. The space left is black
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Scaling {
+    /// Pixels are squares of the largest whole size that fits, so that they
+    /// all have the same size
+    #[default]
+    Integer,
+    /// The largest 2:1 screen that fits
+    Aspect,
+    /// The whole window, whatever its shape
+    Stretch,
+}
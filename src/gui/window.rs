@@ -0,0 +1,374 @@
+use super::{Attached, Effects, Scaling, Shared};
+use crate::architecture::*;
+use crate::render::rgb;
+use crate::theme::Theme;
+use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The default size in pixels of the square each CHIP-8 pixel becomes
+pub const SCALE: usize = 10;
+
+/// How much a pixel that is off lights up when all its neighbours are on
+const GLOW: f32 = 0.35;
+
+/// How much the scanlines darken the pixels
+const SCANLINE: f32 = 0.5;
+
+/// How far the corners of the screen bend in
+const CURVE: f32 = 0.12;
+
+/// The keys of the CHIP-8 keypad on a QWERTY keyboard, by CHIP-8 key:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  ->  Q W E R
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+const KEYS: [Key; 16] = [
+    Key::X,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Q,
+    Key::W,
+    Key::E,
+    Key::A,
+    Key::S,
+    Key::D,
+    Key::Z,
+    Key::C,
+    Key::Key4,
+    Key::R,
+    Key::F,
+    Key::V,
+];
+
+/// A window that shows the screen and reads the keypad
+pub struct Gui {
+    window: Window,
+    title: String,
+    /// The pixels of the window, `0RGB`
+    buffer: Vec<u32>,
+    /// The pixels of the screen before the curvature bends them
+    flat: Vec<u32>,
+    on: u32,
+    off: u32,
+    pub effects: Effects,
+    pub scaling: Scaling,
+    /// Whether the window is borderless and on top, see `toggle_fullscreen`
+    fullscreen: bool,
+    /// Whether the sound timer is running, shown as a note in the title
+    sound: bool,
+}
+
+/// A resizable window of `width` x `height` pixels, without borders and on
+/// top of the others if `fullscreen`
+fn new_window(
+    title: &str,
+    width: usize,
+    height: usize,
+    fullscreen: bool,
+) -> Result<Window, String> {
+    let mut window = Window::new(
+        title,
+        width,
+        height,
+        WindowOptions {
+            resize: true,
+            // The buffer always has the size of the window
+            scale_mode: ScaleMode::UpperLeft,
+            borderless: fullscreen,
+            topmost: fullscreen,
+            ..WindowOptions::default()
+        },
+    )
+    .map_err(|e| format!("could not open the window: {e}"))?;
+    window.set_target_fps(60);
+    Ok(window)
+}
+
+fn pixel(c: (u8, u8, u8)) -> u32 {
+    let (r, g, b) = c;
+    ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
+/// The color between `a`, at 0, and `b`, at 1
+fn mix(a: u32, b: u32, t: f32) -> u32 {
+    let channel = |shift: u32| {
+        let (x, y) = ((a >> shift) & 0xFF, (b >> shift) & 0xFF);
+        ((x as f32 + (y as f32 - x as f32) * t) as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+impl Effects {
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.scanlines, "scanlines"),
+            (self.curvature, "curvature"),
+            (self.glow, "glow"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect()
+    }
+}
+
+impl Scaling {
+    /// The position and the size of the screen in a window of `width` x
+    /// `height` pixels
+    pub fn layout(&self, width: usize, height: usize) -> (usize, usize, usize, usize) {
+        let (w, h) = match self {
+            Scaling::Integer => {
+                let s = (width / Screen::NCOLS).min(height / Screen::NROWS).max(1);
+                (Screen::NCOLS * s, Screen::NROWS * s)
+            }
+            Scaling::Aspect => {
+                let w = width.min(height * Screen::NCOLS / Screen::NROWS);
+                (w, w * Screen::NROWS / Screen::NCOLS)
+            }
+            Scaling::Stretch => (width, height),
+        };
+        let (w, h) = (w.min(width), h.min(height));
+        ((width - w) / 2, (height - h) / 2, w, h)
+    }
+
+    pub fn next(self) -> Scaling {
+        match self {
+            Scaling::Integer => Scaling::Aspect,
+            Scaling::Aspect => Scaling::Stretch,
+            Scaling::Stretch => Scaling::Integer,
+        }
+    }
+}
+
+impl Gui {
+    /// Opens a window where each CHIP-8 pixel is a `scale` x `scale` square,
+    /// with the pixel colors of the theme. The window can be resized; the
+    /// screen then fills it as told by `scaling`.
+    pub fn open(
+        title: &str,
+        scale: usize,
+        theme: &Theme,
+        effects: Effects,
+        scaling: Scaling,
+    ) -> Result<Gui, String> {
+        let scale = scale.max(1);
+        let window = new_window(title, Screen::NCOLS * scale, Screen::NROWS * scale, false)?;
+        let mut gui = Gui {
+            window,
+            title: title.to_string(),
+            buffer: vec![],
+            flat: vec![],
+            on: pixel(rgb(theme.pixel_on)),
+            off: pixel(theme.pixel_off.map_or((0, 0, 0), rgb)),
+            effects,
+            scaling,
+            fullscreen: false,
+            sound: false,
+        };
+        gui.show_settings();
+        Ok(gui)
+    }
+
+    /// Shows a note in the title while the sound timer runs, since there is
+    /// no audio output
+    pub fn sound(&mut self, on: bool) {
+        if self.sound != on {
+            self.sound = on;
+            self.show_settings();
+        }
+    }
+
+    /// Lists the scaling and the effects on in the title, after a note if
+    /// the sound timer runs
+    fn show_settings(&mut self) {
+        let mut names = vec![match self.scaling {
+            Scaling::Integer => "integer",
+            Scaling::Aspect => "aspect",
+            Scaling::Stretch => "stretch",
+        }];
+        names.extend(self.effects.names());
+        let note = if self.sound { "♪ " } else { "" };
+        self.window
+            .set_title(&format!("{note}{} [{}]", self.title, names.join(" ")));
+    }
+
+    /// Reopens the window without borders and on top of the others, or back
+    /// with them, at the same place and with the same size. minifb can
+    /// neither make a window fullscreen nor tell the size of the monitor, so
+    /// this is as close as it gets, and a maximized window then fills the
+    /// monitor
+    fn toggle_fullscreen(&mut self) -> Result<(), String> {
+        let (x, y) = self.window.get_position();
+        let (width, height) = self.window.get_size();
+        self.fullscreen = !self.fullscreen;
+        self.window = new_window(&self.title, width, height, self.fullscreen)?;
+        self.window.set_position(x, y);
+        self.show_settings();
+        Ok(())
+    }
+
+    /// Toggles the effects with F1, F2 and F3, switches the scaling with F4
+    /// and toggles the fullscreen with F11
+    fn settings_keys(&mut self) -> Result<(), String> {
+        let pressed = |k| self.window.is_key_pressed(k, KeyRepeat::No);
+        let [f1, f2, f3, f4, f11] = [Key::F1, Key::F2, Key::F3, Key::F4, Key::F11].map(pressed);
+        if f11 {
+            self.toggle_fullscreen()?;
+        }
+        if f1 || f2 || f3 || f4 {
+            self.effects.scanlines ^= f1;
+            self.effects.curvature ^= f2;
+            self.effects.glow ^= f3;
+            if f4 {
+                self.scaling = self.scaling.next();
+            }
+            self.show_settings();
+        }
+        Ok(())
+    }
+
+    /// Whether the window is still open. Escape closes it
+    pub fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
+    }
+
+    /// The CHIP-8 keys held down
+    pub fn keys(&self) -> [bool; 16] {
+        KEYS.map(|k| self.window.is_key_down(k))
+    }
+
+    /// Shows the screen, and reads the keyboard. It waits for the next frame,
+    /// at 60 frames per second
+    pub fn draw(&mut self, screen: &Screen) -> Result<(), String> {
+        let (width, height) = self.window.get_size();
+        let (width, height) = (width.max(1), height.max(1));
+        self.buffer.resize(width * height, 0);
+        self.flat.resize(width * height, 0);
+        let (x0, y0, w, h) = self.scaling.layout(width, height);
+        let effects = self.effects;
+        let on = |r: usize, c: usize| screen.rows[r][c];
+        // The neighbours that are on of each pixel, for the glow
+        let mut lit = [[0u8; Screen::NCOLS]; Screen::NROWS];
+        if effects.glow {
+            for (r, row) in lit.iter_mut().enumerate() {
+                for (c, n) in row.iter_mut().enumerate() {
+                    for (dr, dc) in [(0, 1), (2, 1), (1, 0), (1, 2)] {
+                        let (nr, nc) = ((r + dr).wrapping_sub(1), (c + dc).wrapping_sub(1));
+                        if nr < Screen::NROWS && nc < Screen::NCOLS && on(nr, nc) {
+                            *n += 1;
+                        }
+                    }
+                }
+            }
+        }
+        // The screen, in the top left corner of `flat`
+        let row_of = |y: usize| y * Screen::NROWS / h;
+        for y in 0..h {
+            let r = row_of(y);
+            // The last line of a row of pixels, when they are tall enough
+            let scanline = effects.scanlines && h >= 2 * Screen::NROWS && row_of(y + 1) != r;
+            for x in 0..w {
+                let c = x * Screen::NCOLS / w;
+                let mut color = if on(r, c) {
+                    self.on
+                } else {
+                    mix(self.off, self.on, GLOW * lit[r][c] as f32 / 4.0)
+                };
+                if scanline {
+                    color = mix(color, 0, SCANLINE);
+                }
+                self.flat[y * w + x] = color;
+            }
+        }
+        self.buffer.fill(0);
+        for y in 0..h {
+            for x in 0..w {
+                let color = if effects.curvature {
+                    // The pixel centers, from -1 to 1
+                    let u = (x as f32 + 0.5) / w as f32 * 2.0 - 1.0;
+                    let v = (y as f32 + 0.5) / h as f32 * 2.0 - 1.0;
+                    let (su, sv) = (u * (1.0 + CURVE * v * v), v * (1.0 + CURVE * u * u));
+                    if su.abs() > 1.0 || sv.abs() > 1.0 {
+                        0
+                    } else {
+                        let sx = (((su + 1.0) / 2.0 * w as f32) as usize).min(w - 1);
+                        let sy = (((sv + 1.0) / 2.0 * h as f32) as usize).min(h - 1);
+                        self.flat[sy * w + sx]
+                    }
+                } else {
+                    self.flat[y * w + x]
+                };
+                self.buffer[(y0 + y) * width + x0 + x] = color;
+            }
+        }
+        self.window
+            .update_with_buffer(&self.buffer, width, height)
+            .map_err(|e| format!("could not draw the window: {e}"))?;
+        self.settings_keys()
+    }
+}
+
+/// Plays `chip` in a window until it is closed, running `ipf` instructions
+/// in each frame. The timers count down every frame. There is no audio
+/// output: the terminal bell rings when a sound starts, and the title shows a
+/// note while the sound timer runs.
+pub fn play(mut chip: Chip8, mut gui: Gui, ipf: u32) -> Result<(), String> {
+    while gui.is_open() {
+        chip.keys = gui.keys();
+        let silent = chip.sound == 0;
+        for _ in 0..ipf {
+            chip.run_instr();
+        }
+        if silent && chip.sound > 0 {
+            print!("\x07");
+            let _ = std::io::stdout().flush();
+        }
+        gui.sound(chip.sound > 0);
+        chip.tick_timers();
+        gui.draw(&chip.screen)?;
+    }
+    Ok(())
+}
+
+impl Attached {
+    /// Opens the window on its own thread, since it must be redrawn while
+    /// the debugger waits for the terminal
+    pub fn spawn(
+        title: String,
+        scale: usize,
+        theme: Theme,
+        effects: Effects,
+        scaling: Scaling,
+    ) -> Attached {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let window = shared.clone();
+        thread::spawn(move || {
+            let lock = || window.lock().expect("window lock poisoned");
+            let mut gui = match Gui::open(&title, scale, &theme, effects, scaling) {
+                Ok(gui) => gui,
+                Err(e) => {
+                    lock().closed = Some(format!("error: {e}"));
+                    return;
+                }
+            };
+            while gui.is_open() {
+                let screen = lock().screen.clone();
+                if let Err(e) = gui.draw(&screen) {
+                    lock().closed = Some(format!("error: {e}"));
+                    return;
+                }
+                lock().keys = gui.keys();
+            }
+            lock().closed = Some("the window was closed".into());
+        });
+        Attached {
+            shared,
+            keys: [false; 16],
+        }
+    }
+}
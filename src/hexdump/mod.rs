@@ -0,0 +1,78 @@
+//! A dump of a ROM for `chip-8 hexdump`, between `xxd` and a disassembly:
+//! the bytes in hexadecimal and as ASCII, with the instructions that the
+//! code reachable from the start runs next to them.
+
+use super::analysis::reachable;
+
+/// The most bytes in a row of data
+const WIDTH: usize = 8;
+
+/// The bytes as ASCII, with a dot for those that can't be printed
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+/// A byte as a row of a sprite, a block per pixel that is on
+fn glyph(byte: u8) -> String {
+    (0..8)
+        .map(|bit| {
+            if byte & (0x80 >> bit) != 0 {
+                '█'
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+/// Dumps a ROM loaded at `start`, a row per instruction and up to 8 bytes
+/// of data per row otherwise:
+///
+/// ```text
+/// 0x200  00 E0                    ..        CLS
+/// 0x202  A2 2A                    .*        LD I, 0x22A
+/// 0x22A  FF 00 FF 00 3C 00 3C 00  ....<.<.
+/// ```
+///
+/// With `sprites`, the data has a row per byte, drawn as a row of a sprite,
+/// so that the sprites can be seen in the dump
+pub fn hexdump(rom: &[u8], start: u16, sprites: bool) -> String {
+    let code = reachable(rom, start);
+    let mut out = String::new();
+    let mut ix = 0;
+    while ix < rom.len() {
+        let addr = start as usize + ix;
+        let (len, text) = match code.get(&(addr as u16)) {
+            Some(instr) => (2, instr.to_string()),
+            None if sprites => (1, glyph(rom[ix])),
+            None => {
+                // Data, up to the next instruction
+                let len = (1..WIDTH)
+                    .find(|&n| ix + n == rom.len() || code.contains_key(&((addr + n) as u16)))
+                    .unwrap_or(WIDTH);
+                (len, String::new())
+            }
+        };
+        let bytes = &rom[ix..ix + len];
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+        let row = format!(
+            "{addr:#05X}  {:<width$}  {:<WIDTH$}  {text}",
+            hex.join(" "),
+            ascii(bytes),
+            width = 3 * WIDTH - 1
+        );
+        out += row.trim_end();
+        out.push('\n');
+        ix += len;
+    }
+    out
+}
@@ -0,0 +1,202 @@
+//! What can be told about a ROM without running it, for `chip-8 info`: its
+//! size and hash, the memory that it needs, the opcodes of the extensions
+//! that it uses and the quirks that its code depends on.
+
+use super::analysis::*;
+use super::architecture::*;
+use std::collections::BTreeMap;
+
+/// The round constants of SHA-256
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The SHA-256 of some bytes, in hexadecimal. It identifies a ROM, e.g. in
+/// the lists of the archives of ROMs
+pub fn sha256(bytes: &[u8]) -> String {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((bytes.len() as u64 * 8).to_be_bytes());
+    for chunk in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for (k, wi) in K.iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*k)
+                .wrapping_add(wi);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+    h.iter().map(|x| format!("{x:08x}")).collect()
+}
+
+/// A report on a ROM loaded at `start`, a field per line:
+///
+/// ```text
+/// size        246 bytes
+/// sha256      9a1f...
+/// memory      0x200 to 0x2F5, within the 4 KiB of CHIP-8
+/// extensions  SCHIP: 00FF high resolution, DXY0 16x16 sprite (3)
+/// entry       0x200: CLS
+/// platform    schip, from its extensions
+/// quirks      shift_vy off (8XY6), clip on (DXYN)
+/// ```
+///
+/// The opcodes of the extensions are looked for in every word, so data that
+/// happens to look like them counts. The quirks are those that the code
+/// reachable from the start depends on, as the detected platform sets them
+pub fn info(rom: &[u8], start: u16) -> String {
+    let mut out = vec![("size", format!("{} bytes", rom.len()))];
+    out.push(("sha256", sha256(rom)));
+    let end = start as usize + rom.len().max(1) - 1;
+    let memory = if end < Chip8::MEM_SIZE {
+        "within the 4 KiB of CHIP-8"
+    } else if end <= 0xFFFF {
+        "more than the 4 KiB of CHIP-8, as on XO-CHIP"
+    } else {
+        "more than the 64 KiB of XO-CHIP"
+    };
+    out.push(("memory", format!("{start:#05X} to {end:#05X}, {memory}")));
+    out.push(("extensions", extensions(rom)));
+    let entry = match decode(rom, start, start) {
+        Some(instr) => format!("{start:#05X}: {instr}"),
+        None => "none, the ROM is empty".into(),
+    };
+    out.push(("entry", entry));
+    let platform = Platform::detect(rom);
+    let guess = match platform {
+        Platform::Chip8 => "without extensions",
+        _ => "from its extensions",
+    };
+    out.push(("platform", format!("{platform}, {guess}")));
+    out.push(("quirks", quirks(rom, start, platform.quirks())));
+    out.iter()
+        .map(|(name, value)| format!("{name:<12}{value}\n"))
+        .collect()
+}
+
+/// The opcodes of the extensions that a ROM uses, by extension, with the
+/// times that they show when more than once
+fn extensions(rom: &[u8]) -> String {
+    let mut found: BTreeMap<String, BTreeMap<&str, usize>> = BTreeMap::new();
+    for w in rom.chunks_exact(2) {
+        if let Some((platform, opcode)) = Platform::extension(w[0], w[1]) {
+            let name = match platform {
+                Platform::Xochip => "XO-CHIP".to_string(),
+                p => p.to_string().to_uppercase(),
+            };
+            *found.entry(name).or_default().entry(opcode).or_default() += 1;
+        }
+    }
+    if found.is_empty() {
+        return "none".into();
+    }
+    let extensions: Vec<String> = found
+        .iter()
+        .map(|(name, opcodes)| {
+            let opcodes: Vec<String> = opcodes
+                .iter()
+                .map(|(opcode, &n)| match n {
+                    1 => opcode.to_string(),
+                    n => format!("{opcode} ({n})"),
+                })
+                .collect();
+            format!("{name}: {}", opcodes.join(", "))
+        })
+        .collect();
+    extensions.join("; ")
+}
+
+/// The quirks that the reachable code depends on, as `quirks` sets them,
+/// with the first opcode that depends on each
+fn quirks(rom: &[u8], start: u16, quirks: Quirks) -> String {
+    let mut used: BTreeMap<&str, &str> = BTreeMap::new();
+    for instr in reachable(rom, start).values() {
+        let info = instr.info();
+        if let Some((name, _)) = info.quirk.and_then(|q| q.split_once(':')) {
+            used.entry(name).or_insert(info.encoding);
+        }
+    }
+    let named: Vec<String> = quirks
+        .named()
+        .iter()
+        .filter_map(|(name, on)| {
+            let encoding = used.get(name)?;
+            let on = if *on { "on" } else { "off" };
+            Some(format!("{name} {on} ({encoding})"))
+        })
+        .collect();
+    if named.is_empty() {
+        "none matter".into()
+    } else {
+        named.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_vectors() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Two blocks, the padding in the second
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha256(&[b'a'; 1000]),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+}
@@ -3,15 +3,31 @@ use super::base::*;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Address(u16);
 
+impl Address {
+    pub fn new(addr: u16) -> Self {
+        assert!(
+            addr <= 0xFFF,
+            "addresses have 12 bits. Actual value = {addr}"
+        );
+        Address(addr)
+    }
+}
+
 impl Display for Address {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "@{:#05X}", self.0)
     }
 }
 
+impl From<&Address> for u16 {
+    fn from(value: &Address) -> u16 {
+        value.0
+    }
+}
+
 impl From<Address> for u16 {
     fn from(value: Address) -> u16 {
         let Address(v) = value;
@@ -107,15 +123,17 @@ impl RawInstr {
                 r: Register::from(x),
                 s: Register::from(y),
             },
-            [8, x, _, 6] => Instr::ShiftR {
+            [8, x, y, 6] => Instr::ShiftR {
                 r: Register::from(x),
+                s: Register::from(y),
             },
             [8, x, y, 7] => Instr::Lt {
                 r: Register::from(x),
                 s: Register::from(y),
             },
-            [8, x, _, 0xE] => Instr::ShiftL {
+            [8, x, y, 0xE] => Instr::ShiftL {
                 r: Register::from(x),
+                s: Register::from(y),
             },
             [9, x, y, 0] => Instr::SkipNEqV {
                 r: Register::from(x),
@@ -166,7 +184,7 @@ impl RawInstr {
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub enum Instr {
     /// Calls machine code routine. Obsolete instruction that is currently
     /// ignored.
@@ -250,9 +268,11 @@ pub enum Instr {
         s: Register,
     },
 
-    /// least significant bit of r in VF; then r := r >> 1
+    /// least significant bit of r in VF; then r := r >> 1. With the shift
+    /// quirk, s is shifted instead of r
     ShiftR {
         r: Register,
+        s: Register,
     },
 
     /// r := r - s
@@ -267,9 +287,11 @@ pub enum Instr {
         s: Register,
     },
 
-    /// most significant bit of r in VF; then r := r << 1;
+    /// most significant bit of r in VF; then r := r << 1. With the shift
+    /// quirk, s is shifted instead of r
     ShiftL {
         r: Register,
+        s: Register,
     },
 
     /// Skips the next instruction if r != s
@@ -378,9 +400,9 @@ impl Display for Instr {
             Instr::BitXOr { r, s } => write!(f, "XOR {r}, {s}"),
             Instr::Add { r, s } => write!(f, "ADD {r}, {s}"),
             Instr::Sub { r, s } => write!(f, "SUB {r}, {s}"),
-            Instr::ShiftR { r } => write!(f, "SHR {r}"),
+            Instr::ShiftR { r, s } => write!(f, "SHR {r}, {s}"),
             Instr::Lt { r, s } => write!(f, "SUBN {r}, {s}"),
-            Instr::ShiftL { r } => write!(f, "SHL {r}"),
+            Instr::ShiftL { r, s } => write!(f, "SHL {r}, {s}"),
             Instr::SkipNEqV { r, s } => write!(f, "SNE {r}, {s}"),
             Instr::SetI { n } => write!(f, "LD I, {n}"),
             Instr::Jump { n } => write!(f, "JP V0, {n}"),
@@ -397,7 +419,253 @@ impl Display for Instr {
             Instr::StoreBCD { r } => write!(f, "LD B, {r}"),
             Instr::RegDump { x } => write!(f, "LD [I], {x}"),
             Instr::RegLoad { x } => write!(f, "LD {x}, [I]"),
-            Instr::Data(_) => write!(f, "DATA"),
+            Instr::Data(n) => write!(f, "DW {:#06X}", mk_un(n)),
+        }
+    }
+}
+
+/// The base of the numbers in a listing
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Base {
+    Hex,
+    Decimal,
+}
+
+/// What the reference says about an opcode
+pub struct OpcodeInfo {
+    /// The opcode's pattern, e.g. `8XY6`
+    pub encoding: &'static str,
+    pub summary: &'static str,
+    /// The meaning of the variable nibbles of the encoding
+    pub operands: &'static str,
+    /// How the opcode changes VF, if it does
+    pub vf: Option<&'static str>,
+    /// The quirk that changes the opcode's behaviour, if any
+    pub quirk: Option<&'static str>,
+}
+
+impl Instr {
+    /// Like `to_string`, but with all the numbers in one base. Addresses keep
+    /// their `@`.
+    pub fn format(&self, base: Base) -> String {
+        let text = self.to_string();
+        let Some((mnemonic, operands)) = text.split_once(' ') else {
+            return text;
+        };
+        let operands: Vec<String> = operands
+            .split(", ")
+            .map(|op| {
+                let (at, n) = match op.strip_prefix('@') {
+                    Some(n) => ("@", n),
+                    None => ("", op),
+                };
+                let value = match n.strip_prefix("0x") {
+                    Some(hex) => u16::from_str_radix(hex, 16).ok(),
+                    None => n.parse::<u16>().ok(),
+                };
+                match (value, base) {
+                    (Some(v), Base::Hex) => format!("{at}{v:#X}"),
+                    (Some(v), Base::Decimal) => format!("{at}{v}"),
+                    (None, _) => op.to_string(),
+                }
+            })
+            .collect();
+        format!("{mnemonic} {}", operands.join(", "))
+    }
+
+    /// The instruction in Octo's assembly language, e.g. `v0 += 5`. The
+    /// skips are written as the `if`s that compile to them, and what Octo
+    /// has no statement for as bytes.
+    pub fn octo(&self) -> String {
+        let v = |r: &Register| format!("v{:x}", u8::from(r));
+        let addr = |a: &Address| format!("{:#05X}", a.0);
+        match self {
+            Instr::Clear => "clear".into(),
+            Instr::Ret => "return".into(),
+            Instr::Goto { addr: a } => format!("jump {}", addr(a)),
+            Instr::Call { addr: a } => format!(":call {}", addr(a)),
+            Instr::SkipEq { r, c } => format!("if {} != {c:#04X} then", v(r)),
+            Instr::SkipNEq { r, c } => format!("if {} == {c:#04X} then", v(r)),
+            Instr::SkipEqV { r, s } => format!("if {} != {} then", v(r), v(s)),
+            Instr::SkipNEqV { r, s } => format!("if {} == {} then", v(r), v(s)),
+            Instr::Set { r, a } => format!("{} := {a:#04X}", v(r)),
+            Instr::Incr { r, a } => format!("{} += {a:#04X}", v(r)),
+            Instr::Copy { r, s } => format!("{} := {}", v(r), v(s)),
+            Instr::BitOr { r, s } => format!("{} |= {}", v(r), v(s)),
+            Instr::BitAnd { r, s } => format!("{} &= {}", v(r), v(s)),
+            Instr::BitXOr { r, s } => format!("{} ^= {}", v(r), v(s)),
+            Instr::Add { r, s } => format!("{} += {}", v(r), v(s)),
+            Instr::Sub { r, s } => format!("{} -= {}", v(r), v(s)),
+            Instr::ShiftR { r, s } => format!("{} >>= {}", v(r), v(s)),
+            Instr::Lt { r, s } => format!("{} =- {}", v(r), v(s)),
+            Instr::ShiftL { r, s } => format!("{} <<= {}", v(r), v(s)),
+            Instr::SetI { n } => format!("i := {n}"),
+            Instr::Jump { n } => format!("jump0 {n}"),
+            Instr::Rand { r, n } => format!("{} := random {n:#04X}", v(r)),
+            Instr::Draw { x, y, height } => format!("sprite {} {} {height}", v(x), v(y)),
+            Instr::Pressed { r } => format!("if {} -key then", v(r)),
+            Instr::NotPressed { r } => format!("if {} key then", v(r)),
+            Instr::GetDelay { r } => format!("{} := delay", v(r)),
+            Instr::LoadKey { r } => format!("{} := key", v(r)),
+            Instr::SetDelayTimer { r } => format!("delay := {}", v(r)),
+            Instr::SetSoundTimer { r } => format!("buzzer := {}", v(r)),
+            Instr::IncrI { r } => format!("i += {}", v(r)),
+            Instr::SpriteAddr { r } => format!("i := hex {}", v(r)),
+            Instr::StoreBCD { r } => format!("bcd {}", v(r)),
+            Instr::RegDump { x } => format!("save v{:x}", x.0),
+            Instr::RegLoad { x } => format!("load v{:x}", x.0),
+            Instr::System { .. } | Instr::Data(_) => {
+                let [a, b] = self.encode();
+                format!("{a:#04X} {b:#04X}")
+            }
+        }
+    }
+
+    /// The two bytes of the instruction, the inverse of
+    /// [`RawInstr::into_instr`]
+    pub fn encode(&self) -> [u8; 2] {
+        let v = |r: &Register| u8::from(r) as u16;
+        let xy = |op: u16, r: &Register, s: &Register| op | v(r) << 8 | v(s) << 4;
+        let op: u16 = match self {
+            Instr::System { addr } => addr.0,
+            Instr::Clear => 0x00E0,
+            Instr::Ret => 0x00EE,
+            Instr::Goto { addr } => 0x1000 | addr.0,
+            Instr::Call { addr } => 0x2000 | addr.0,
+            Instr::SkipEq { r, c } => 0x3000 | v(r) << 8 | *c as u16,
+            Instr::SkipNEq { r, c } => 0x4000 | v(r) << 8 | *c as u16,
+            Instr::SkipEqV { r, s } => xy(0x5000, r, s),
+            Instr::Set { r, a } => 0x6000 | v(r) << 8 | *a as u16,
+            Instr::Incr { r, a } => 0x7000 | v(r) << 8 | *a as u16,
+            Instr::Copy { r, s } => xy(0x8000, r, s),
+            Instr::BitOr { r, s } => xy(0x8001, r, s),
+            Instr::BitAnd { r, s } => xy(0x8002, r, s),
+            Instr::BitXOr { r, s } => xy(0x8003, r, s),
+            Instr::Add { r, s } => xy(0x8004, r, s),
+            Instr::Sub { r, s } => xy(0x8005, r, s),
+            Instr::ShiftR { r, s } => xy(0x8006, r, s),
+            Instr::Lt { r, s } => xy(0x8007, r, s),
+            Instr::ShiftL { r, s } => xy(0x800E, r, s),
+            Instr::SkipNEqV { r, s } => xy(0x9000, r, s),
+            Instr::SetI { n } => 0xA000 | u16::from(n),
+            Instr::Jump { n } => 0xB000 | u16::from(n),
+            Instr::Rand { r, n } => 0xC000 | v(r) << 8 | *n as u16,
+            Instr::Draw { x, y, height } => xy(0xD000, x, y) | (*height as u16 & 0xF),
+            Instr::Pressed { r } => 0xE09E | v(r) << 8,
+            Instr::NotPressed { r } => 0xE0A1 | v(r) << 8,
+            Instr::GetDelay { r } => 0xF007 | v(r) << 8,
+            Instr::LoadKey { r } => 0xF00A | v(r) << 8,
+            Instr::SetDelayTimer { r } => 0xF015 | v(r) << 8,
+            Instr::SetSoundTimer { r } => 0xF018 | v(r) << 8,
+            Instr::IncrI { r } => 0xF01E | v(r) << 8,
+            Instr::SpriteAddr { r } => 0xF029 | v(r) << 8,
+            Instr::StoreBCD { r } => 0xF033 | v(r) << 8,
+            Instr::RegDump { x } => 0xF055 | (x.0 as u16) << 8,
+            Instr::RegLoad { x } => 0xF065 | (x.0 as u16) << 8,
+            Instr::Data(n) => mk_un(n) as u16,
+        };
+        op.to_be_bytes()
+    }
+
+    pub fn info(&self) -> OpcodeInfo {
+        let info = |encoding, summary, operands| OpcodeInfo {
+            encoding,
+            summary,
+            operands,
+            vf: None,
+            quirk: None,
+        };
+        const XY: &str = "X: destination register, Y: source register";
+        const XNN: &str = "X: register, NN: byte";
+        const NNN: &str = "NNN: address";
+        const X: &str = "X: register";
+        match self {
+            Instr::System { .. } => OpcodeInfo {
+                quirk: Some("ignored, except 00E0 and 00EE"),
+                ..info("0NNN", "calls a machine code routine", NNN)
+            },
+            Instr::Clear => info("00E0", "clears the screen", "none"),
+            Instr::Ret => info("00EE", "returns from a subroutine", "none"),
+            Instr::Goto { .. } => info("1NNN", "jumps to NNN", NNN),
+            Instr::Call { .. } => info("2NNN", "calls the subroutine at NNN", NNN),
+            Instr::SkipEq { .. } => info("3XNN", "skips the next instruction if VX == NN", XNN),
+            Instr::SkipNEq { .. } => info("4XNN", "skips the next instruction if VX != NN", XNN),
+            Instr::SkipEqV { .. } => info("5XY0", "skips the next instruction if VX == VY", XY),
+            Instr::Set { .. } => info("6XNN", "VX := NN", XNN),
+            Instr::Incr { .. } => info("7XNN", "VX := VX + NN, wrapping", XNN),
+            Instr::Copy { .. } => info("8XY0", "VX := VY", XY),
+            Instr::BitOr { .. } => OpcodeInfo {
+                quirk: Some("vf_reset: VF := 0"),
+                ..info("8XY1", "VX := VX | VY", XY)
+            },
+            Instr::BitAnd { .. } => OpcodeInfo {
+                quirk: Some("vf_reset: VF := 0"),
+                ..info("8XY2", "VX := VX & VY", XY)
+            },
+            Instr::BitXOr { .. } => OpcodeInfo {
+                quirk: Some("vf_reset: VF := 0"),
+                ..info("8XY3", "VX := VX ^ VY", XY)
+            },
+            Instr::Add { .. } => OpcodeInfo {
+                vf: Some("1 on carry, 0 otherwise"),
+                ..info("8XY4", "VX := VX + VY", XY)
+            },
+            Instr::Sub { .. } => OpcodeInfo {
+                vf: Some("0 on borrow, 1 otherwise"),
+                ..info("8XY5", "VX := VX - VY", XY)
+            },
+            Instr::ShiftR { .. } => OpcodeInfo {
+                vf: Some("the bit shifted out"),
+                quirk: Some("shift_vy: VX := VY >> 1"),
+                ..info("8XY6", "VX := VX >> 1", XY)
+            },
+            Instr::Lt { .. } => OpcodeInfo {
+                vf: Some("0 on borrow, 1 otherwise"),
+                ..info("8XY7", "VX := VY - VX", XY)
+            },
+            Instr::ShiftL { .. } => OpcodeInfo {
+                vf: Some("the bit shifted out"),
+                quirk: Some("shift_vy: VX := VY << 1"),
+                ..info("8XYE", "VX := VX << 1", XY)
+            },
+            Instr::SkipNEqV { .. } => info("9XY0", "skips the next instruction if VX != VY", XY),
+            Instr::SetI { .. } => info("ANNN", "I := NNN", NNN),
+            Instr::Jump { .. } => OpcodeInfo {
+                quirk: Some("jump_vx: jumps to XNN + VX"),
+                ..info("BNNN", "jumps to NNN + V0", NNN)
+            },
+            Instr::Rand { .. } => info("CXNN", "VX := random byte & NN", XNN),
+            Instr::Draw { .. } => OpcodeInfo {
+                vf: Some("1 if a pixel was turned off, 0 otherwise"),
+                quirk: Some("clip: sprites are clipped at the edges"),
+                ..info(
+                    "DXYN",
+                    "draws N bytes at I as a sprite at (VX, VY)",
+                    "X, Y: coordinate registers, N: height",
+                )
+            },
+            Instr::Pressed { .. } => {
+                info("EX9E", "skips the next instruction if key VX is down", X)
+            }
+            Instr::NotPressed { .. } => {
+                info("EXA1", "skips the next instruction if key VX is up", X)
+            }
+            Instr::GetDelay { .. } => info("FX07", "VX := delay timer", X),
+            Instr::LoadKey { .. } => info("FX0A", "waits for a key and puts it in VX", X),
+            Instr::SetDelayTimer { .. } => info("FX15", "delay timer := VX", X),
+            Instr::SetSoundTimer { .. } => info("FX18", "sound timer := VX", X),
+            Instr::IncrI { .. } => info("FX1E", "I := I + VX", X),
+            Instr::SpriteAddr { .. } => info("FX29", "I := address of the font sprite of VX", X),
+            Instr::StoreBCD { .. } => info("FX33", "stores the decimal digits of VX at I", X),
+            Instr::RegDump { .. } => OpcodeInfo {
+                quirk: Some("load_store_incr_i: I := I + X + 1"),
+                ..info("FX55", "stores V0 to VX at I", "X: last register")
+            },
+            Instr::RegLoad { .. } => OpcodeInfo {
+                quirk: Some("load_store_incr_i: I := I + X + 1"),
+                ..info("FX65", "loads V0 to VX from I", "X: last register")
+            },
+            Instr::Data(_) => info("----", "not an instruction", "none"),
         }
     }
 }
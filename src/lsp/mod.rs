@@ -0,0 +1,474 @@
+//! A language server for the programs of `assemble`, which editors start
+//! with `chip-8 lsp` and talk to on its standard input and output. It
+//! reports the errors of the program as it is written, finds where labels
+//! and constants are defined, explains the instruction under the cursor with
+//! the reference of its opcode, and completes the mnemonics, the registers
+//! and the names.
+//!
+//! The programs with the `.8o` extension are Octo's, as in `assemble`.
+
+use crate::assembler;
+use crate::json::Json;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// The words of Octo that can be completed
+const OCTO_KEYWORDS: [&str; 30] = [
+    ":", ":const", ":calc", ":macro", ":call", ":byte", "loop", "again", "while", "if", "then",
+    "begin", "else", "end", "return", "jump", "jump0", "clear", "sprite", "key", "-key", "delay",
+    "buzzer", "random", "hex", "bcd", "save", "load", "native", "i",
+];
+
+/// The operands written as names, other than the registers
+const OPERANDS: [&str; 7] = ["I", "[I]", "DT", "ST", "K", "F", "B"];
+
+/// The kinds of the completions, as the protocol numbers them
+const KEYWORD: usize = 14;
+const VARIABLE: usize = 6;
+const CONSTANT: usize = 21;
+
+/// Serves the editor until it exits
+pub fn serve() -> io::Result<()> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+    while let Some(message) = read(&mut input)? {
+        let Ok(message) = Json::parse(&message) else {
+            continue;
+        };
+        let method = message
+            .get("method")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let result = server.handle(method, &params);
+        for notification in server.outbox.drain(..) {
+            write(&mut output, &notification)?;
+        }
+        // Only the requests have an id and an answer
+        let Some(id) = message.get("id") else {
+            continue;
+        };
+        let answer = match result {
+            Some(result) => ("result", result),
+            None => {
+                let error = Json::object([
+                    ("code", Json::Number(-32601.0)),
+                    ("message", format!("unknown method {method}").into()),
+                ]);
+                ("error", error)
+            }
+        };
+        let response = Json::object([("jsonrpc", "2.0".into()), ("id", id.clone()), answer]);
+        write(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+/// The body of the next message, after its headers, or `None` at the end of
+/// the input
+fn read(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(ErrorKind::InvalidData, "a message without Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+fn write(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+/// The path of a `file://` URI
+fn path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let hex = tail.get(..2).and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(decoded) if b == b'%' => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn is_octo(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "8o")
+}
+
+fn position(line: usize, character: usize) -> Json {
+    Json::object([("line", line.into()), ("character", character.into())])
+}
+
+/// The position of the byte `ix` of a line, in the UTF-16 code units that
+/// the protocol counts characters in
+fn utf16(line: &str, ix: usize) -> usize {
+    line[..ix].encode_utf16().count()
+}
+
+/// The byte of a line at a position in UTF-16 code units
+fn byte_index(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (ix, c) in line.char_indices() {
+        if units >= character {
+            return ix;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// The byte of a line after its first `n` characters
+fn char_index(line: &str, n: usize) -> usize {
+    line.char_indices().nth(n).map_or(line.len(), |(ix, _)| ix)
+}
+
+/// The range of the bytes `bytes` of the line `line` of a document
+fn range(text: &str, line: usize, bytes: Range<usize>) -> Json {
+    let code = text.lines().nth(line).unwrap_or_default();
+    let (start, end) = (bytes.start.min(code.len()), bytes.end.min(code.len()));
+    Json::object([
+        ("start", position(line, utf16(code, start))),
+        ("end", position(line, utf16(code, end))),
+    ])
+}
+
+/// The words of a line without its comment, and where they start
+fn words(line: &str, octo: bool) -> Vec<(usize, &str)> {
+    let comment = if octo { '#' } else { ';' };
+    let code = line.split(comment).next().unwrap_or_default();
+    let mut out = vec![];
+    let mut start = None;
+    for (ix, c) in code.char_indices().chain([(code.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                out.push((s, &code[s..ix]));
+                start = None;
+            }
+            (false, None) => start = Some(ix),
+            _ => (),
+        }
+    }
+    out
+}
+
+/// The names that a program defines, labels, constants and macros, with
+/// their lines and columns
+fn definitions(text: &str, octo: bool) -> Vec<(String, usize, usize)> {
+    let mut out = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let words = words(line, octo);
+        if octo {
+            for pair in words.windows(2) {
+                if [":", ":const", ":calc", ":macro"].contains(&pair[0].1) {
+                    out.push((pair[1].1.to_string(), n, pair[1].0));
+                }
+            }
+            continue;
+        }
+        match words.as_slice() {
+            [(ix, first), ..] if first.contains(':') => {
+                let label = first.split(':').next().unwrap_or_default();
+                out.push((label.to_string(), n, *ix));
+            }
+            [(ix, name), (_, equ), ..] if equ.eq_ignore_ascii_case("EQU") => {
+                out.push((name.to_string(), n, *ix));
+            }
+            [(_, mac), (ix, name), ..] if mac.eq_ignore_ascii_case("MACRO") => {
+                out.push((name.to_string(), n, *ix));
+            }
+            _ => (),
+        }
+    }
+    out
+}
+
+/// The word at a position, letters, digits and `_`. `character` counts UTF-16
+/// code units, as the protocol does
+fn word_at(text: &str, line: usize, character: usize) -> Option<&str> {
+    let line = text.lines().nth(line)?;
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let at = byte_index(line, character);
+    let start = line[..at]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !is_word(c))
+        .map_or(0, |(ix, c)| ix + c.len_utf8());
+    let end = line[at..]
+        .find(|c| !is_word(c))
+        .map_or(line.len(), |ix| at + ix);
+    Some(&line[start..end]).filter(|w| !w.is_empty())
+}
+
+#[derive(Default)]
+struct Server {
+    /// The text of the open documents, by their URIs
+    documents: HashMap<String, String>,
+    /// The labels of each document, by their addresses, from the last time
+    /// that it assembled
+    labels: HashMap<String, BTreeMap<u16, String>>,
+    /// The notifications to send
+    outbox: Vec<Json>,
+}
+
+impl Server {
+    /// The result of a request, or `None` if it is unknown. The result of a
+    /// notification is not sent
+    fn handle(&mut self, method: &str, params: &Json) -> Option<Json> {
+        let uri = params
+            .at(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let (line, character) = match params.get("position") {
+            Some(p) => (
+                p.get("line").and_then(Json::as_usize).unwrap_or(0),
+                p.get("character").and_then(Json::as_usize).unwrap_or(0),
+            ),
+            None => (0, 0),
+        };
+        Some(match method {
+            "initialize" => {
+                let capabilities = Json::object([
+                    // The whole text on each change
+                    ("textDocumentSync", 1usize.into()),
+                    ("definitionProvider", true.into()),
+                    ("hoverProvider", true.into()),
+                    ("completionProvider", Json::object([])),
+                ]);
+                Json::object([("capabilities", capabilities)])
+            }
+            "shutdown" => Json::Null,
+            "textDocument/didOpen" => {
+                let text = params.at(&["textDocument", "text"]).and_then(Json::as_str);
+                self.documents
+                    .insert(uri.clone(), text.unwrap_or_default().into());
+                self.diagnose(&uri);
+                Json::Null
+            }
+            "textDocument/didChange" => {
+                let changes = match params.get("contentChanges") {
+                    Some(Json::Array(changes)) => changes.as_slice(),
+                    _ => &[],
+                };
+                if let Some(text) = changes.last().and_then(|c| c.get("text")?.as_str()) {
+                    self.documents.insert(uri.clone(), text.into());
+                    self.diagnose(&uri);
+                }
+                Json::Null
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.labels.remove(&uri);
+                self.publish(&uri, vec![]);
+                Json::Null
+            }
+            "textDocument/definition" => self.definition(&uri, line, character),
+            "textDocument/hover" => self.hover(&uri, line, character),
+            "textDocument/completion" => self.completion(&uri),
+            _ => return None,
+        })
+    }
+
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Json>) {
+        let params = Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]);
+        self.outbox.push(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            ("params", params),
+        ]));
+    }
+
+    /// Assembles a document and reports its error, if it has one
+    fn diagnose(&mut self, uri: &str) {
+        let file = path(uri);
+        let text = &self.documents[uri];
+        let assembled = if is_octo(&file) {
+            assembler::octo::assemble(text, &file)
+        } else {
+            assembler::assemble(text, &file)
+        };
+        let e = match assembled {
+            Ok((_, map)) => {
+                self.labels.insert(uri.to_string(), map.labels);
+                return self.publish(uri, vec![]);
+            }
+            Err(e) => e,
+        };
+        // `file:line:column: message`, then the line and a mark under the
+        // text that it is about. An error in an included file is shown at
+        // the start of the document
+        let first = e.lines().next().unwrap_or_default();
+        let here = first
+            .strip_prefix(&format!("{}:", file.display()))
+            .and_then(|rest| {
+                let mut parts = rest.splitn(3, ':');
+                let line: usize = parts.next()?.parse().ok()?;
+                let col: usize = parts.next()?.parse().ok()?;
+                Some((line, col, parts.next()?.trim()))
+            });
+        let (at, message) = match here {
+            // The column and the mark count characters
+            Some((line, col, message)) => {
+                let line = line.saturating_sub(1);
+                let code = text.lines().nth(line).unwrap_or_default();
+                let len = e.lines().last().map_or(0, |l| l.matches('^').count());
+                let col = col.saturating_sub(1);
+                let bytes = char_index(code, col)..char_index(code, col + len.max(1));
+                (range(text, line, bytes), message)
+            }
+            None => {
+                let code = text.lines().next().unwrap_or_default();
+                (range(text, 0, 0..char_index(code, 1)), first)
+            }
+        };
+        let diagnostic = Json::object([
+            ("range", at),
+            ("severity", 1usize.into()),
+            ("source", "chip-8".into()),
+            ("message", message.into()),
+        ]);
+        self.publish(uri, vec![diagnostic]);
+    }
+
+    fn definition(&self, uri: &str, line: usize, character: usize) -> Json {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let Some(word) = word_at(text, line, character) else {
+            return Json::Null;
+        };
+        let found = definitions(text, is_octo(&path(uri)))
+            .into_iter()
+            .find(|(name, _, _)| name == word);
+        match found {
+            Some((name, line, col)) => {
+                let at = range(text, line, col..col + name.len());
+                Json::object([("uri", uri.into()), ("range", at)])
+            }
+            None => Json::Null,
+        }
+    }
+
+    /// The address of a label, or the reference of the opcode of the
+    /// instruction under the cursor
+    fn hover(&self, uri: &str, line: usize, character: usize) -> Json {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let octo = is_octo(&path(uri));
+        let word = word_at(text, line, character).unwrap_or_default();
+        let label = self
+            .labels
+            .get(uri)
+            .and_then(|labels| labels.iter().find(|(_, name)| *name == word));
+        let value = if let Some((addr, name)) = label {
+            format!("`{name}` = `{addr:#05X}`")
+        } else if octo || word.is_empty() {
+            return Json::Null;
+        } else {
+            let names = definitions(text, octo);
+            let names: Vec<&str> = names.iter().map(|(n, _, _)| n.as_str()).collect();
+            let code = text.lines().nth(line).unwrap_or_default();
+            let Some(instr) = assembler::instruction(code, &names) else {
+                return Json::Null;
+            };
+            let info = instr.info();
+            let mut doc = format!(
+                "**{}**: {}\n\n{}",
+                info.encoding, info.summary, info.operands
+            );
+            if let Some(vf) = info.vf {
+                doc += &format!("\n\nVF: {vf}");
+            }
+            if let Some(quirk) = info.quirk {
+                doc += &format!("\n\nQuirk: {quirk}");
+            }
+            doc
+        };
+        let contents = Json::object([("kind", "markdown".into()), ("value", value.into())]);
+        Json::object([("contents", contents)])
+    }
+
+    /// The mnemonics, the registers and the names of the document
+    fn completion(&self, uri: &str) -> Json {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let octo = is_octo(&path(uri));
+        let item = |label: String, kind: usize| {
+            Json::object([("label", label.into()), ("kind", kind.into())])
+        };
+        let mut items = vec![];
+        let (keywords, registers): (&[&str], Vec<String>) = if octo {
+            (&OCTO_KEYWORDS, (0..16).map(|r| format!("v{r:x}")).collect())
+        } else {
+            let registers = (0..16).map(|r| format!("V{r:X}"));
+            let others = OPERANDS.iter().map(|o| o.to_string());
+            (&assembler::MNEMONICS, registers.chain(others).collect())
+        };
+        items.extend(keywords.iter().map(|k| item(k.to_string(), KEYWORD)));
+        items.extend(registers.into_iter().map(|r| item(r, VARIABLE)));
+        for (name, _, _) in definitions(text, octo) {
+            items.push(item(name, CONSTANT));
+        }
+        items.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `é` is 2 bytes and 1 code unit, `😀` 4 bytes and 2 code units
+    const LINE: &str = "é😀 loop: jp loop ; 😀";
+
+    #[test]
+    fn counts_utf16_code_units() {
+        assert_eq!(utf16(LINE, 0), 0);
+        assert_eq!(utf16(LINE, 2), 1);
+        assert_eq!(utf16(LINE, 6), 3);
+        assert_eq!(utf16(LINE, LINE.len()), 22);
+        for ix in [0, 2, 6, 7, 11, LINE.len()] {
+            assert_eq!(byte_index(LINE, utf16(LINE, ix)), ix);
+        }
+        assert_eq!(byte_index(LINE, 100), LINE.len());
+    }
+
+    #[test]
+    fn finds_words_after_wide_characters() {
+        let text = format!("start:\n{LINE}\n");
+        assert_eq!(word_at(&text, 1, 4), Some("loop"));
+        assert_eq!(word_at(&text, 1, 8), Some("loop"));
+        assert_eq!(word_at(&text, 1, 11), Some("jp"));
+        assert_eq!(word_at(&text, 1, 1), None);
+        assert_eq!(word_at(&text, 0, 2), Some("start"));
+        assert_eq!(word_at(&text, 5, 0), None);
+    }
+}
@@ -1,32 +1,88 @@
 #![feature(slice_as_array)]
+mod analysis;
 mod architecture;
+mod assembler;
 mod base;
+mod check;
 mod cli;
+mod compare;
+mod config;
+mod convert;
+mod cores;
+mod database;
 mod debugger;
+mod decompiler;
+mod disassembler;
+mod editor;
 mod emulator;
+mod fetch;
 mod font;
+mod frames;
+mod gallery;
+mod graph;
+mod gui;
+mod hexdump;
+mod info;
+mod json;
 mod language;
+mod lsp;
+mod notes;
+mod patch;
+mod picker;
+mod profile;
+mod prompt;
+mod recent;
+mod recording;
+mod render;
+mod repl;
+mod replay;
+mod savestate;
+mod scaffold;
+mod screenshot;
+mod sourcemap;
+mod sprite;
+mod stats;
+mod suite;
+mod symbols;
+mod theme;
+mod trace;
 
 use architecture::*;
 use clap::{Command, CommandFactory, Parser};
 use clap_complete::generate;
 use cli::args::{Cli, Commands};
+use config::Config;
 use core::default::*;
-use debugger::Debugger;
+use cores::EmulatorCore;
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+};
+use crossterm::execute;
+use debugger::*;
 use language::*;
+use notes::Notes;
+use prompt::{Clip, PromptCommand};
 use ratatui::layout::*;
+use ratatui::style::Color;
 use ratatui::text::*;
 use ratatui::widgets::*;
 use ratatui::{
     Frame,
     style::Stylize,
     text::Line,
-    widgets::{Block, List, Paragraph},
+    widgets::{List, Paragraph},
 };
+use std::collections::BTreeMap;
+use std::fs;
 use std::io;
 use std::io::Result;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use theme::Theme;
 
 fn main() {
     let cli: Cli = Cli::parse();
@@ -37,16 +93,851 @@ fn main() {
             let bin_name = cmd.get_name().to_string();
             generate(*shell, &mut cmd, bin_name, &mut io::stdout());
         }
-        Some(Commands::Run { file }) => {
+        Some(Commands::Run {
+            file,
+            at,
+            sha256,
+            cache_dir,
+            patch: patch_file,
+            watch,
+            quirks: quirks_flag,
+            font,
+            font_file,
+            ipf,
+            no_profile,
+            renderer,
+            phosphor,
+            accessible,
+            scale,
+            fit,
+            gui,
+            window,
+            crt,
+            window_scaling,
+            record,
+            record_scale,
+            dump_frames,
+            dump_format,
+        }) => {
+            let mut recent = recent::Recent::load().expect("Failed to load the recent ROMs");
+            let mut segments = cli::args::segments(at).unwrap_or_else(|e| {
+                eprintln!("--at: {e}");
+                std::process::exit(1);
+            });
+            // Without a ROM, the file at the start of the code is the ROM
+            let rom_segment = segments
+                .iter()
+                .position(|&(addr, _)| addr as usize == Chip8::CODE_START);
+            let file = match (file, rom_segment) {
+                (None, Some(ix)) => Some(segments.remove(ix).1),
+                (file, _) => file.clone(),
+            };
+            let (file, picked) = match file {
+                Some(f) => (f, false),
+                None => {
+                    let terminal = ratatui::init();
+                    let picker = picker::Picker::new(".".into(), recent.roms.clone());
+                    let picked = picker.run(terminal);
+                    ratatui::restore();
+                    match picked.expect("Failed to pick a ROM") {
+                        Some(f) => (f, true),
+                        None => return,
+                    }
+                }
+            };
+            let file = if fetch::is_url(&file) {
+                let cache = cache_dir
+                    .clone()
+                    .or_else(fetch::cache_dir)
+                    .expect("Failed to find the cache directory, give one with --cache-dir");
+                let url = file.to_string_lossy();
+                fetch::fetch(&url, &cache, sha256.as_deref()).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                })
+            } else {
+                file
+            };
+            let file = &file;
+            if let Some(expected) = sha256 {
+                let rom = fs::read(file).expect("Failed to read the ROM");
+                if let Err(e) = fetch::verify(&rom, expected) {
+                    eprintln!("{}: {e}", file.display());
+                    std::process::exit(1);
+                }
+            }
             println!("Beep Boop, I'm CHIP-8 and I'll run {}", file.display());
 
+            let config = Config::load().expect("Failed to load the config file");
+            let mut theme = Theme::from_config(&config).expect("Failed to load the theme");
+            theme.plain = *accessible || config.get("debugger", "accessible") == Some("true");
+            let renderer = match renderer {
+                Some(r) => *r,
+                None if theme.plain => render::Renderer::Ascii,
+                None => render::Renderer::detect(),
+            };
+            let mut keys =
+                command::Keymap::from_config(&config).expect("Failed to load the key bindings");
+            let profile = if *no_profile {
+                None
+            } else {
+                let database = database::Database::load().expect("Failed to load the ROM database");
+                let rom = fs::read(file).expect("Failed to read the ROM");
+                database.get(&rom).cloned()
+            };
+            if let Some(p) = &profile {
+                println!("Known ROM: {p}");
+            }
+            let mut quirks = profile.as_ref().map_or(Platform::Cowgod, |p| p.quirks);
+            // A ROM picked from the recent ones runs with its last settings
+            if picked && let Some(r) = recent.get(file) {
+                quirks = r.quirks;
+                keys.scheme = r.scheme;
+            }
+            // The flags override both
+            let quirks = quirks_flag.unwrap_or(quirks);
+            let ipf = ipf.or(profile.and_then(|p| p.ipf));
             let mut chip = Chip8::new();
-            chip.load_memory(file)
+            chip.quirks = quirks.quirks();
+            chip.font = *font.bytes();
+            if let Some(font_file) = font_file {
+                let bytes = fs::read(font_file).expect("Failed to read the font");
+                (chip.font, chip.big_font) = font::read(&bytes).unwrap_or_else(|e| {
+                    eprintln!("{}: {e}", font_file.display());
+                    std::process::exit(1);
+                });
+            }
+            let ips = patch_file
+                .as_ref()
+                .map(|p| fs::read(p).expect("Failed to read the patch"));
+            let mut rom = fs::read(file).expect("Failed to read the ROM");
+            if let Some(ips) = &ips {
+                let records = patch::apply(&mut rom, ips).unwrap_or_else(|e| {
+                    eprintln!("{}: {e}", patch_file.as_ref().expect("a patch").display());
+                    std::process::exit(1);
+                });
+                println!("Patched with {records} records");
+            }
+            chip.load_bytes(&rom)
                 .expect("Failed to load file from memory");
+            // The ranges of memory that are loaded, with their files
+            let mut loaded = vec![(
+                Chip8::CODE_START,
+                Chip8::CODE_START + rom.len(),
+                file.clone(),
+            )];
+            for (addr, segment) in &segments {
+                let bytes = fs::read(segment).expect("Failed to read the file");
+                let range = (*addr as usize, *addr as usize + bytes.len());
+                if let Some((_, _, other)) = loaded
+                    .iter()
+                    .find(|(start, end, _)| range.0 < *end && *start < range.1)
+                {
+                    eprintln!(
+                        "{} at {addr:#05X} overlaps {}",
+                        segment.display(),
+                        other.display()
+                    );
+                    std::process::exit(1);
+                }
+                chip.load_at(*addr, &bytes).unwrap_or_else(|e| {
+                    eprintln!("{}: {e}", segment.display());
+                    std::process::exit(1);
+                });
+                loaded.push((range.0, range.1, segment.clone()));
+            }
+            let notes = Notes::load(file).expect("Failed to load the notes");
+            let rom = recent::RecentRom {
+                file: file.canonicalize().unwrap_or(file.clone()),
+                quirks,
+                scheme: keys.scheme,
+            };
+            if let Err(e) = recent.add(rom) {
+                eprintln!("Failed to save the recent ROMs: {e}");
+            }
+
+            let effects = if *crt {
+                gui::Effects::crt()
+            } else {
+                gui::Effects::default()
+            };
+            #[cfg(feature = "gui")]
+            let (title, window_scale) = (
+                format!("CHIP-8 {}", file.display()),
+                scale.map_or(gui::SCALE, usize::from),
+            );
+            #[cfg(feature = "gui")]
+            if *gui {
+                let window = gui::Gui::open(&title, window_scale, &theme, effects, *window_scaling)
+                    .expect("Failed to open the window");
+                gui::play(chip, window, ipf.unwrap_or(STEPS_PER_FRAME))
+                    .expect("Failed to run the window");
+                return;
+            }
+            #[cfg(not(feature = "gui"))]
+            if *gui || *window {
+                let _ = (effects, window_scaling, ipf);
+                eprintln!("This chip-8 was built without the window, build it with --features gui");
+                std::process::exit(1);
+            }
+
+            let terminal = ratatui::init();
+            execute!(io::stdout(), EnableMouseCapture).expect("Failed to capture the mouse");
+            let mut app = App::new(
+                chip,
+                file.clone(),
+                quirks,
+                notes,
+                renderer,
+                theme,
+                keys,
+                *phosphor,
+            );
+            app.record_scale = *record_scale;
+            app.patch = ips;
+            if *watch {
+                app.watch = Some(modified(file).expect("Failed to read the ROM"));
+            }
+            #[cfg(feature = "gui")]
+            if *window {
+                app.window = Some(gui::Attached::spawn(
+                    title,
+                    window_scale,
+                    app.theme.clone(),
+                    effects,
+                    *window_scaling,
+                ));
+            }
+            app.max_scale = scale.map(usize::from);
+            app.fit = *fit || config.get("debugger", "fit") == Some("true");
+            app.listing =
+                Listing::from_config(&config).expect("Failed to load the disassembly format");
+            app.recording = record
+                .as_ref()
+                .map(|f| recording::Recording::new(f.clone(), *record_scale));
+            app.frame_dump = dump_frames.as_ref().map(|path| {
+                frames::FrameDump::new(
+                    path.clone(),
+                    *dump_format,
+                    app.theme.pixel_on,
+                    app.theme.pixel_off,
+                )
+                .expect("Failed to open the frame dump")
+            });
+            let result = app.run(terminal);
+            execute!(io::stdout(), DisableMouseCapture).expect("Failed to release the mouse");
+            ratatui::restore();
+            result.expect("Failed to run the debugger");
+        }
+        Some(Commands::Assemble { file, output, map }) => {
+            let source = fs::read_to_string(file).expect("Failed to read the program");
+            let assembled = if file.extension().is_some_and(|e| e == "8o") {
+                assembler::octo::assemble(&source, file)
+            } else {
+                assembler::assemble(&source, file)
+            };
+            let (rom, source_map) = match assembled {
+                Ok(assembled) => assembled,
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            };
+            let output = output.clone().unwrap_or(file.with_extension("ch8"));
+            fs::write(&output, &rom).expect("Failed to write the ROM");
+            println!("{} bytes written to {}", rom.len(), output.display());
+            if *map {
+                source_map
+                    .save(&output)
+                    .expect("Failed to write the source map");
+                let path = sourcemap::SourceMap::path(&output);
+                println!("source map written to {}", path.display());
+            }
+        }
+        Some(Commands::Lsp) => lsp::serve().expect("Failed to serve the language server"),
+        Some(Commands::Fmt { files, check }) => {
+            let mut unformatted = 0;
+            for file in files {
+                let source = fs::read_to_string(file).expect("Failed to read the program");
+                let formatted = if file.extension().is_some_and(|e| e == "8o") {
+                    assembler::format::format_octo(&source)
+                } else {
+                    assembler::format::format(&source)
+                };
+                if formatted == source {
+                    continue;
+                }
+                if *check {
+                    println!("{} is not formatted", file.display());
+                    unformatted += 1;
+                } else {
+                    fs::write(file, formatted).expect("Failed to write the program");
+                    println!("formatted {}", file.display());
+                }
+            }
+            if unformatted > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Disassemble {
+            file,
+            start,
+            output,
+            format,
+            write_symbols,
+            no_labels,
+            traverse,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let code = traverse.then(|| analysis::reachable(&rom, *start));
+            // The names of the symbol file, if the ROM has one
+            let mut symbols = symbols::Symbols::load(file)
+                .expect("Failed to read the symbol file")
+                .unwrap_or_default();
+            // The inferred labels, where the symbol file has no name
+            if !*no_labels {
+                let mut inferred = match &code {
+                    Some(code) => symbols::Symbols::infer_instrs(code.values()),
+                    None => symbols::Symbols::infer_code(&rom),
+                };
+                inferred.labels.append(&mut symbols.labels);
+                symbols = inferred;
+            }
+            if *write_symbols {
+                symbols.save(file).expect("Failed to write the symbol file");
+                let path = symbols::Symbols::path(file);
+                eprintln!(
+                    "{} labels written to {}",
+                    symbols.labels.len(),
+                    path.display()
+                );
+            }
+            let listing = disassembler::disassemble(&rom, *start, *format, &symbols, code.as_ref());
+            match output {
+                Some(o) => fs::write(o, listing).expect("Failed to write the listing"),
+                None => print!("{listing}"),
+            }
+        }
+        Some(Commands::Decompile {
+            file,
+            start,
+            output,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let symbols = symbols::Symbols::load(file)
+                .expect("Failed to read the symbol file")
+                .unwrap_or_default();
+            let pseudocode = decompiler::decompile(&rom, *start, &symbols);
+            match output {
+                Some(o) => fs::write(o, pseudocode).expect("Failed to write the pseudocode"),
+                None => print!("{pseudocode}"),
+            }
+        }
+        Some(Commands::Cfg {
+            file,
+            start,
+            output,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let symbols = symbols::Symbols::load(file)
+                .expect("Failed to read the symbol file")
+                .unwrap_or_default();
+            let dot = graph::cfg(&rom, *start, &symbols);
+            match output {
+                Some(o) => fs::write(o, dot).expect("Failed to write the graph"),
+                None => print!("{dot}"),
+            }
+        }
+        Some(Commands::Calls {
+            file,
+            start,
+            dot,
+            output,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let symbols = symbols::Symbols::load(file)
+                .expect("Failed to read the symbol file")
+                .unwrap_or_default();
+            let graph = if *dot {
+                graph::calls_dot(&rom, *start, &symbols)
+            } else {
+                graph::calls_tree(&rom, *start, &symbols)
+            };
+            match output {
+                Some(o) => fs::write(o, graph).expect("Failed to write the call graph"),
+                None => print!("{graph}"),
+            }
+        }
+        Some(Commands::Info { file, start }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            print!("{}", info::info(&rom, *start));
+            let database = database::Database::load().expect("Failed to load the ROM database");
+            if let Some(p) = database.get(&rom) {
+                println!("{:<12}{p}", "profile");
+            }
+        }
+        Some(Commands::Test {
+            rom: Some(file),
+            steps,
+            quirks,
+            assert_screen,
+            ..
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let quirks = quirks.unwrap_or_else(|| {
+                let database = database::Database::load().expect("Failed to load the ROM database");
+                database.get(&rom).map_or(Platform::Cowgod, |p| p.quirks)
+            });
+            std::panic::set_hook(Box::new(|_| {}));
+            let chip = suite::run(&rom, quirks, *steps).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", file.display());
+                std::process::exit(1);
+            });
+            match assert_screen {
+                None => print!("{}", chip.screen),
+                Some(golden) => match suite::assert_screen(&chip.screen, golden) {
+                    Ok(()) => println!("{}: ok", file.display()),
+                    Err(e) => {
+                        eprintln!("{}: {e}", file.display());
+                        std::process::exit(1);
+                    }
+                },
+            }
+        }
+        Some(Commands::Test {
+            dir: Some(dir),
+            steps,
+            quirks,
+            ..
+        }) => {
+            let roms = suite::roms(dir).expect("Failed to list the ROMs");
+            let database = database::Database::load().expect("Failed to load the ROM database");
+            let platform = |file: &Path| {
+                let profile = || Some(database.get(&fs::read(file).ok()?)?.quirks);
+                quirks.or_else(profile)
+            };
+            // The crashes are reported in the table
+            std::panic::set_hook(Box::new(|_| {}));
+            let reports = suite::smoke_all(&roms, platform, *steps);
+            let width = reports
+                .iter()
+                .map(|r| r.file.display().to_string().len())
+                .max()
+                .unwrap_or(0);
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for r in &reports {
+                *counts.entry(r.outcome.name()).or_default() += 1;
+                let detail = match &r.outcome {
+                    suite::Outcome::Unimplemented(d)
+                    | suite::Outcome::Stuck(d)
+                    | suite::Outcome::Fault(d)
+                    | suite::Outcome::Crash(d) => d.as_str(),
+                    _ => "",
+                };
+                println!(
+                    "{:<width$}  {:<15} {:>8}  {:#05X}  {detail}",
+                    r.file.display(),
+                    r.outcome.name(),
+                    r.steps,
+                    r.pc
+                );
+            }
+            let counts: Vec<String> = counts
+                .iter()
+                .map(|(name, n)| format!("{n} {name}"))
+                .collect();
+            println!("{} ROMs: {}", reports.len(), counts.join(", "));
+            if reports.iter().any(|r| r.outcome.failed()) {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Test { names, .. }) => {
+            // The crashes are reported with the tests that they fail
+            std::panic::set_hook(Box::new(|_| {}));
+            let tests: Vec<&suite::Test> = suite::TESTS
+                .iter()
+                .filter(|t| names.is_empty() || names.iter().any(|n| t.name.contains(n.as_str())))
+                .collect();
+            let width = tests.iter().map(|t| t.name.len()).max().unwrap_or(0);
+            let mut failed = 0;
+            for test in &tests {
+                match suite::check(test) {
+                    Ok(()) => println!("{:<width$}  ok", test.name),
+                    Err((message, screen)) => {
+                        failed += 1;
+                        println!("{:<width$}  FAILED: {message}", test.name);
+                        if let Some(screen) = screen {
+                            print!("{screen}");
+                        }
+                    }
+                }
+            }
+            println!(
+                "{} tests, {} passed, {failed} failed",
+                tests.len(),
+                tests.len() - failed
+            );
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Check { file, start }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let warnings = check::check(&rom, *start);
+            for w in &warnings {
+                println!("{}:{w}", file.display());
+            }
+            if !warnings.is_empty() {
+                println!("{} warnings", warnings.len());
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Compare {
+            file,
+            quirks_a,
+            quirks_b,
+        }) => {
+            let seed: u64 = rand::random();
+            let mut chips = [*quirks_a, *quirks_b].map(|platform| {
+                let mut chip = Chip8::with_seed(seed);
+                chip.quirks = platform.quirks();
+                chip
+            });
+            for chip in &mut chips {
+                chip.load_memory(file)
+                    .expect("Failed to load file from memory");
+            }
+            let [a, b] = chips;
 
             let terminal = ratatui::init();
-            let _result = App::new(chip).run(terminal);
+            let _result = compare::Compare::new(a, b, [*quirks_a, *quirks_b]).run(terminal);
+            ratatui::restore();
+        }
+        Some(Commands::Difftest {
+            file,
+            reference,
+            steps,
+            quirks,
+            seed,
+        }) => {
+            let mut chip = Chip8::with_seed(*seed);
+            chip.quirks = quirks.quirks();
+            chip.load_memory(file)
+                .expect("Failed to load file from memory");
+            let mut reference = cores::ExternalCore::spawn(reference, file)
+                .expect("Failed to start the reference emulator");
+            match cores::difftest(&mut chip, &mut reference, *steps) {
+                None => println!("no divergence after {steps} steps"),
+                Some(d) => {
+                    println!("divergence at step {}: {}", d.step, d.reason.join(", "));
+                    println!("  {:<12} {}", "chip-8:", d.states[0]);
+                    println!("  {:<12} {}", "reference:", d.states[1]);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Screenshot {
+            file,
+            cycles,
+            output,
+            quirks,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let quirks = quirks.unwrap_or_else(|| {
+                let database = database::Database::load().expect("Failed to load the ROM database");
+                database.get(&rom).map_or(Platform::Cowgod, |p| p.quirks)
+            });
+            let config = Config::load().expect("Failed to load the config file");
+            let theme = Theme::from_config(&config).expect("Failed to load the theme");
+            std::panic::set_hook(Box::new(|_| {}));
+            let chip = suite::run(&rom, quirks, *cycles).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", file.display());
+                std::process::exit(1);
+            });
+            screenshot::write(output, &chip.screen, theme.pixel_on, theme.pixel_off)
+                .expect("Failed to write the screenshot");
+        }
+        Some(Commands::Hexdump {
+            file,
+            start,
+            sprites,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            print!("{}", hexdump::hexdump(&rom, *start, *sprites));
+        }
+        Some(Commands::Sprites {
+            file,
+            start,
+            output,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            let sprites = gallery::gallery(&rom, *start);
+            match output {
+                Some(output) if output.extension().is_some_and(|e| e == "png") => {
+                    let config = Config::load().expect("Failed to load the config file");
+                    let theme = Theme::from_config(&config).expect("Failed to load the theme");
+                    let png =
+                        gallery::png(&sprites, screenshot::SCALE, theme.pixel_on, theme.pixel_off);
+                    fs::write(output, png).expect("Failed to write the gallery");
+                }
+                Some(output) => {
+                    fs::write(output, gallery::text(&sprites)).expect("Failed to write the gallery")
+                }
+                None if sprites.is_empty() => println!("No sprites found"),
+                None => print!("{}", gallery::text(&sprites)),
+            }
+        }
+        Some(Commands::Stats { dir, start }) => {
+            let roms: Vec<Vec<u8>> = suite::roms(dir)
+                .expect("Failed to list the ROMs")
+                .iter()
+                .map(|file| fs::read(file).expect("Failed to read the ROM"))
+                .collect();
+            print!("{}", stats::stats(&roms, *start));
+        }
+        Some(Commands::DiffScreens {
+            file,
+            other: Some(other),
+            ..
+        }) => {
+            let screens =
+                [file, other].map(|f| suite::golden(f).expect("Failed to read the screen"));
+            match suite::diff(&screens[0], &screens[1]) {
+                None => println!("the screens are the same"),
+                Some(d) => {
+                    print!("{d}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::DiffScreens {
+            file,
+            other: None,
+            cycles,
+            quirks_a,
+            quirks_b,
+        }) => {
+            let rom = fs::read(file).expect("Failed to read the ROM");
+            std::panic::set_hook(Box::new(|_| {}));
+            let chips = [quirks_a, quirks_b].map(|quirks| {
+                suite::run(&rom, *quirks, *cycles).unwrap_or_else(|e| {
+                    eprintln!("{} with the {quirks} quirks: {e}", file.display());
+                    std::process::exit(1);
+                })
+            });
+            let names = [quirks_a, quirks_b].map(|q| q.to_string());
+            let registers = suite::diff_registers(
+                [&chips[0].state(), &chips[1].state()],
+                [&names[0], &names[1]],
+            );
+            let screens = suite::diff(&chips[0].screen, &chips[1].screen);
+            if registers.is_none() && screens.is_none() {
+                println!("the runs end the same after {cycles} instructions");
+                return;
+            }
+            println!("after {cycles} instructions, + is {quirks_a} and - is {quirks_b}");
+            match registers {
+                Some(d) => print!("{d}"),
+                None => println!("the registers are the same"),
+            }
+            match screens {
+                Some(d) => print!("{d}"),
+                None => println!("the screens are the same"),
+            }
+            std::process::exit(1);
+        }
+        Some(Commands::Convert {
+            file,
+            output,
+            from,
+            to,
+            start,
+        }) => {
+            let contents = fs::read(file).expect("Failed to read the ROM");
+            let from = from.unwrap_or_else(|| convert::RomFormat::of(file));
+            let rom = convert::read(&contents, from).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", file.display());
+                std::process::exit(1);
+            });
+            match output {
+                Some(o) => {
+                    let to = to.unwrap_or_else(|| convert::RomFormat::of(o));
+                    fs::write(o, convert::write(&rom, *start, to))
+                        .expect("Failed to write the ROM");
+                }
+                None => {
+                    let to = to.unwrap_or(convert::RomFormat::Hex);
+                    io::stdout()
+                        .write_all(&convert::write(&rom, *start, to))
+                        .expect("Failed to write the ROM");
+                }
+            }
+        }
+        Some(Commands::Patch {
+            file,
+            patch,
+            output,
+        }) => {
+            let mut rom = fs::read(file).expect("Failed to read the ROM");
+            let ips = fs::read(patch).expect("Failed to read the patch");
+            let records = patch::apply(&mut rom, &ips).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", patch.display());
+                std::process::exit(1);
+            });
+            fs::write(output, &rom).expect("Failed to write the ROM");
+            println!("{records} records applied, {} bytes written", rom.len());
+        }
+        Some(Commands::SpriteEdit {
+            bytes,
+            wide,
+            format,
+        }) => {
+            let bytes = bytes.as_ref().map_or(Ok(vec![]), |b| {
+                convert::read(b.as_bytes(), convert::RomFormat::Hex)
+            });
+            let editor = bytes
+                .and_then(|b| sprite::SpriteEditor::new(&b, *wide))
+                .unwrap_or_else(|e| {
+                    eprintln!("--bytes: {e}");
+                    std::process::exit(1);
+                });
+            let terminal = ratatui::init();
+            let edited = editor.run(terminal);
             ratatui::restore();
+            if let Some(editor) = edited.expect("Failed to run the sprite editor") {
+                print!("{}", editor.write(*format));
+            }
+        }
+        Some(Commands::New { dir }) => {
+            let files = scaffold::new(dir).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(1);
+            });
+            for file in files {
+                println!("created {}", file.display());
+            }
+            println!("`make run` in {} assembles it and runs it", dir.display());
+        }
+        Some(Commands::Repl { quirks }) => {
+            repl::repl(*quirks, io::stdin().lock(), &mut io::stdout())
+                .expect("Failed to run the prompt");
+        }
+        Some(Commands::Trace {
+            file,
+            cycles,
+            format,
+            quirks,
+            output,
+        }) => {
+            let mut chip = Chip8::with_seed(0);
+            chip.quirks = quirks.quirks();
+            chip.load_memory(file)
+                .expect("Failed to load file from memory");
+            let mut out: Box<dyn Write> = match output {
+                Some(o) => Box::new(fs::File::create(o).expect("Failed to create the trace file")),
+                None => Box::new(io::stdout().lock()),
+            };
+            let mut out = io::BufWriter::new(&mut out);
+            let traced = trace::step_trace(&mut chip, *cycles, *format, &mut out);
+            out.flush().expect("Failed to write the trace");
+            if let Err(e) = traced {
+                eprintln!("{}: {e}", file.display());
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Bench {
+            file,
+            cycles,
+            ipf,
+            quirks,
+            core,
+        }) => {
+            let mut chip = Chip8::with_seed(0);
+            chip.quirks = quirks.quirks();
+            chip.load_memory(file)
+                .expect("Failed to load file from memory");
+            let mut cores: Vec<Box<dyn cores::EmulatorCore>> = vec![
+                Box::new(chip.clone()),
+                Box::new(cores::CachedCore::new(chip)),
+            ];
+            for command in core {
+                let external =
+                    cores::ExternalCore::spawn(command, file).expect("Failed to start the core");
+                cores.push(Box::new(external));
+            }
+            let benches: Vec<cores::Bench> = cores
+                .iter_mut()
+                .map(|c| cores::bench(c.as_mut(), *cycles))
+                .collect();
+            let width = benches.iter().map(|b| b.name.len()).max().unwrap_or(0);
+            println!(
+                "{:<width$}  {:>12}  {:>8}  {:>14}  {:>10}",
+                "core", "instructions", "seconds", "instructions/s", "per frame"
+            );
+            for b in &benches {
+                let frame = b.elapsed.as_secs_f64() * *ipf as f64 / b.steps.max(1) as f64;
+                println!(
+                    "{:<width$}  {:>12}  {:>8.3}  {:>12.2} M  {:>7.3} µs",
+                    b.name,
+                    b.steps,
+                    b.elapsed.as_secs_f64(),
+                    b.per_second() / 1e6,
+                    frame * 1e6
+                );
+            }
+            for b in &benches {
+                if let Some(e) = &b.error {
+                    println!("{} stopped after {} instructions: {e}", b.name, b.steps);
+                }
+            }
+        }
+        Some(Commands::Serve { file, quirks, seed }) => {
+            let mut chip = Chip8::with_seed(*seed);
+            chip.quirks = quirks.quirks();
+            chip.load_memory(file)
+                .expect("Failed to load file from memory");
+            cores::serve(&mut chip).expect("Failed to serve");
+        }
+        Some(Commands::Replay {
+            file,
+            replay,
+            quirks,
+        }) => {
+            let replay = replay::Replay::load(replay).expect("Failed to load the replay");
+            let mut chip = Chip8::with_seed(replay.seed);
+            chip.quirks = quirks.quirks();
+            chip.load_memory(file)
+                .expect("Failed to load file from memory");
+            match replay.verify(chip) {
+                Ok(n) => println!("replay matches: {n} checksums verified"),
+                Err(d) => {
+                    println!(
+                        "desync at step {}: expected checksum {:#018X}, found {:#018X}",
+                        d.step, d.expected, d.found
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::ValidateState { file, rom }) => {
+            let state = savestate::SaveState::load(file).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(1);
+            });
+            print!("{}", state.summary());
+            let mut problems = state.problems();
+            if let Some(rom) = rom {
+                let bytes = fs::read(rom).expect("Failed to read the ROM");
+                if !state.is_of(&bytes) {
+                    problems.push(format!("the state is not of {}", rom.display()));
+                }
+            }
+            if !problems.is_empty() {
+                for p in problems {
+                    eprintln!("{}: {p}", file.display());
+                }
+                std::process::exit(1);
+            }
         }
         None => {
             eprintln!("Try --help");
@@ -54,13 +945,549 @@ fn main() {
     }
 }
 
+/// The playback speeds, in steps per second
+const PLAY_RATES: [u32; 4] = [1, 10, 60, 500];
+
+/// A category of the help overlay, with its rows: the keys, what they do and
+/// the names of their commands in the `[debugger.keys]` section of the
+/// config, one for each key, or none
+type HelpGroup = (
+    &'static str,
+    &'static [(&'static str, &'static str, &'static str)],
+);
+
+/// The key bindings listed by the help overlay, by category
+const HELP: [HelpGroup; 8] = [
+    (
+        "Stepping",
+        &[
+            ("n", "step forward", "step_forward"),
+            ("N", "10 steps forward", "big_step_forward"),
+            ("p", "step backward", "step_backward"),
+            ("P", "10 steps backward", "big_step_backward"),
+            ("J", "step to the next source line", "step_line"),
+            ("gg G", "first/newest recorded step", "first_step last_step"),
+            ("m<c> '<c>", "mark step / go to mark", "set_mark goto_mark"),
+        ],
+    ),
+    (
+        "Playback",
+        &[
+            ("r", "run to breakpoint", "continue"),
+            ("s", "play/pause", "play"),
+            ("+ -", "playback speed", "faster slower"),
+            ("i", "fps/ips counter", "rates"),
+            ("H", "register HUD, :hud <regs>", "hud"),
+        ],
+    ),
+    (
+        "Breakpoints",
+        &[
+            ("b", "toggle breakpoint at pc", "toggle_breakpoint"),
+            ("↑ ↓", "select breakpoint", "move_up move_down"),
+            (
+                "t x",
+                "toggle/delete breakpoint",
+                "enable_breakpoint delete_breakpoint",
+            ),
+        ],
+    ),
+    (
+        "Memory",
+        &[
+            (
+                "PgUp PgDn",
+                "scroll memory",
+                "scroll_memory_up scroll_memory_down",
+            ),
+            ("c", "center memory on pc", "center_memory"),
+            ("g<addr>", "go to address", "goto"),
+            ("] [", "next/previous match", "next_match prev_match"),
+            ("u U", "undo/redo manual edit", "undo redo"),
+            ("K", "opcode reference", "reference"),
+            ("z", "source/instructions view", "toggle_source"),
+            ("y", "copy the instructions", "copy"),
+            ("S", "screenshot", "screenshot"),
+            ("w", "start/stop a GIF recording", "record_gif"),
+            ("E", "assembly editor, F5 runs", "editor"),
+        ],
+    ),
+    (
+        "Display",
+        &[
+            ("d", "toggle diff", "toggle_diff"),
+            ("v", "switch display renderer", "next_renderer"),
+            ("f", "toggle phosphor decay", "toggle_phosphor"),
+        ],
+    ),
+    (
+        "Layout",
+        &[
+            ("L", "next layout", "next_layout"),
+            ("1..6", "breakpoints/watches/trace/", ""),
+            ("", "profile/coverage/heatmap tab", ""),
+            (
+                "< >",
+                "narrower/wider memory",
+                "narrower_memory wider_memory",
+            ),
+        ],
+    ),
+    (
+        "Mouse",
+        &[
+            ("click", "focus, toggle breakpoint", ""),
+            ("drag", "timeline under the display", ""),
+        ],
+    ),
+    (
+        "Other",
+        &[
+            (":", "prompt: find search mem note", "prompt"),
+            ("", "break watch dump load poke set", ""),
+            ("", "record trace recent roms pane hud", ""),
+            ("", "open reload copy label state", ""),
+            ("/", "search the instructions", "search"),
+            ("o", "open another ROM", "open"),
+            ("R", "reload the code, keep state", "reload"),
+            ("Alt+0..F", "toggle keypad key", ""),
+            ("?", "show/hide this help", "help"),
+            ("q", "quit", "exit"),
+        ],
+    ),
+];
+
+/// The key bindings of the vim scheme that differ from the default ones
+const VIM_HELP: HelpGroup = (
+    "Vim scheme",
+    &[
+        ("<n><cmd>", "repeat a command n times", ""),
+        ("h l", "focus memory/tabs", "focus_memory focus_tabs"),
+        ("j k", "scroll/select in focused pane", "move_down move_up"),
+        ("/", "search the instructions", "search"),
+        ("n N", "next/previous match", "next_match prev_match"),
+        ("<n>G", "go to step n", ""),
+        ("gt gT", "next/previous tab", ""),
+        ("Ctrl+r", "redo", "redo"),
+    ],
+);
+
+/// Where each pane of the debugger is drawn
+struct Areas {
+    display: Rect,
+    memory: Rect,
+    registers: Rect,
+    stack: Rect,
+    timers: Rect,
+    /// The tabbed pane with the secondary views
+    tabs: Rect,
+    keypad: Rect,
+    sprite: Rect,
+    prompt: Rect,
+}
+
+/// Which of the optional panes are shown
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Shown {
+    memory: bool,
+    /// The registers column, with the timers and the tabs
+    registers: bool,
+    stack: bool,
+}
+
+/// The arrangements of the panes that `L` cycles through
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Preset {
+    /// A small display above all the tool panes
+    Debug,
+    /// A large display above a few tool panes
+    Display,
+    /// Only the display
+    Minimal,
+}
+
+impl Preset {
+    fn next(self) -> Preset {
+        match self {
+            Preset::Debug => Preset::Display,
+            Preset::Display => Preset::Minimal,
+            Preset::Minimal => Preset::Debug,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Preset::Debug => "debug",
+            Preset::Display => "display",
+            Preset::Minimal => "minimal",
+        }
+    }
+
+    fn shown(self) -> Shown {
+        Shown {
+            memory: self != Preset::Minimal,
+            registers: self != Preset::Minimal,
+            stack: self == Preset::Debug,
+        }
+    }
+}
+
+/// The panes that take the arrow keys when they are focused
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Pane {
+    Memory,
+    Tabs,
+}
+
+/// The secondary views, shown one at a time in the tabbed pane
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Tab {
+    Breakpoints,
+    /// The watchpoints and the values they watch
+    Watches,
+    /// The last executed instructions
+    Trace,
+    /// The most executed instructions
+    Profile,
+    /// The parts of the program that were executed
+    Coverage,
+    /// How often each instruction of the program was executed
+    Heatmap,
+}
+
+impl Tab {
+    const ALL: [Tab; 6] = [
+        Tab::Breakpoints,
+        Tab::Watches,
+        Tab::Trace,
+        Tab::Profile,
+        Tab::Coverage,
+        Tab::Heatmap,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Tab::Breakpoints => "Breakpoints",
+            Tab::Watches => "Watches",
+            Tab::Trace => "Trace",
+            Tab::Profile => "Profile",
+            Tab::Coverage => "Coverage",
+            Tab::Heatmap => "Heatmap",
+        }
+    }
+}
+
+/// The frames drawn and the steps run per second while playing, measured
+/// over the last full second
+struct Rates {
+    /// When the current second started
+    since: Instant,
+    frames: u32,
+    steps: u32,
+    fps: u32,
+    ips: u32,
+}
+
+impl Rates {
+    fn new() -> Rates {
+        Rates {
+            since: Instant::now(),
+            frames: 0,
+            steps: 0,
+            fps: 0,
+            ips: 0,
+        }
+    }
+
+    fn tick(&mut self, frames: u32, steps: usize) {
+        self.frames += frames;
+        self.steps += steps as u32;
+        let secs = self.since.elapsed().as_secs_f64();
+        if secs >= 1.0 {
+            *self = Rates {
+                fps: (self.frames as f64 / secs).round() as u32,
+                ips: (self.steps as f64 / secs).round() as u32,
+                ..Rates::new()
+            };
+        }
+    }
+}
+
+/// How the memory pane lists the instructions. It is set in the
+/// `[disassembly]` section of the config:
+///
+/// ```toml
+/// [disassembly]
+/// window = 8
+/// bytes = false
+/// base = "decimal"
+/// ```
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Listing {
+    /// The instructions shown before and after the center. `None` fills the
+    /// pane
+    window: Option<u16>,
+    /// Whether the raw bytes of each instruction are shown
+    bytes: bool,
+    /// The base of the operands. `None` writes addresses in hex and bytes in
+    /// decimal
+    base: Option<Base>,
+}
+
+impl Default for Listing {
+    fn default() -> Self {
+        Listing {
+            window: None,
+            bytes: true,
+            base: None,
+        }
+    }
+}
+
+impl Listing {
+    pub fn from_config(config: &Config) -> std::result::Result<Listing, String> {
+        let get = |key| config.get("disassembly", key);
+        let window = get("window")
+            .map(|w| {
+                base::parse_number(w)
+                    .map(|w| w as u16)
+                    .ok_or(format!("disassembly.window: not a number: {w}"))
+            })
+            .transpose()?;
+        let bytes = match get("bytes") {
+            None | Some("true") => true,
+            Some("false") => false,
+            Some(b) => {
+                return Err(format!(
+                    "disassembly.bytes: expected true or false, not {b}"
+                ));
+            }
+        };
+        let base = match get("base") {
+            None => None,
+            Some("hex") => Some(Base::Hex),
+            Some("decimal") => Some(Base::Decimal),
+            Some(b) => {
+                return Err(format!(
+                    "disassembly.base: expected hex or decimal, not {b}"
+                ));
+            }
+        };
+        Ok(Listing {
+            window,
+            bytes,
+            base,
+        })
+    }
+
+    /// The bytes shown before and after the center in a pane of `height`
+    /// rows, borders included
+    fn reach(&self, height: u16) -> i32 {
+        let fill = (height.saturating_sub(2) as i32 - 1) / 2 * 2;
+        self.window.map_or(fill, |w| fill.min(2 * w as i32))
+    }
+}
+
+/// The time a file was last modified
+fn modified(file: &Path) -> Result<SystemTime> {
+    fs::metadata(file)?.modified()
+}
+
+/// The lanes of the arrows from jumps to their targets in the memory pane
+const ARROW_LANES: usize = 3;
+
+/// The gutter of the memory pane, with arrows that connect the jumps, calls
+/// and skips to their targets when both are shown. `addrs` are the addresses
+/// of the lines. Returns the gutter of each line.
+fn jump_arrows(c: &Chip8, addrs: &[Option<usize>], theme: &Theme) -> Vec<String> {
+    let row_of = |a: usize| addrs.iter().position(|&x| x == Some(a));
+    let mut arrows: Vec<(usize, usize)> = addrs
+        .iter()
+        .enumerate()
+        .filter_map(|(row, &a)| {
+            let a = a?;
+            let target = match RawInstr::from_bytes([c.memory[a], c.memory[a + 1]]).into_instr() {
+                Instr::Goto { addr } | Instr::Call { addr } => u16::from(addr) as usize,
+                Instr::SkipEq { .. }
+                | Instr::SkipNEq { .. }
+                | Instr::SkipEqV { .. }
+                | Instr::SkipNEqV { .. }
+                | Instr::Pressed { .. }
+                | Instr::NotPressed { .. } => a + 4,
+                _ => return None,
+            };
+            row_of(target).filter(|&t| t != row).map(|t| (row, t))
+        })
+        .collect();
+    // Short arrows take the inner lanes
+    arrows.sort_by_key(|&(from, to)| from.abs_diff(to));
+    let mut cells = vec![[' '; ARROW_LANES + 1]; addrs.len()];
+    let mut lanes: Vec<Vec<(usize, usize)>> = vec![vec![]; ARROW_LANES];
+    let g = |fancy: &'static str, plain: &'static str| {
+        theme.glyph(fancy, plain).chars().next().expect("a glyph")
+    };
+    for (from, to) in arrows {
+        let (top, bottom) = (from.min(to), from.max(to));
+        let Some(lane) = lanes
+            .iter()
+            .position(|l| l.iter().all(|&(t, b)| bottom < t || b < top))
+        else {
+            continue;
+        };
+        lanes[lane].push((top, bottom));
+        let col = ARROW_LANES - 1 - lane;
+        for row in cells.iter_mut().take(bottom).skip(top + 1) {
+            row[col] = g("│", "|");
+        }
+        cells[top][col] = g("┌", "+");
+        cells[bottom][col] = g("└", "+");
+        for row in [from, to] {
+            for cell in &mut cells[row][col + 1..ARROW_LANES] {
+                *cell = g("─", "-");
+            }
+        }
+        cells[from][ARROW_LANES] = g("─", "-");
+        cells[to][ARROW_LANES] = '>';
+    }
+    cells.into_iter().map(|r| r.iter().collect()).collect()
+}
+
+/// The last search in memory
+pub enum Search {
+    /// A byte pattern, see `:find`
+    Bytes(Vec<u8>),
+    /// Text in the disassembled instructions, e.g. `LD I,` or `V7`, see
+    /// `:search`. It ignores the case of ASCII letters.
+    Text(String),
+}
+
+impl Search {
+    /// The addresses and the lengths of the matches in the memory of `c`.
+    /// Instructions are searched at the alignment of the pc, in the text
+    /// that `shown` gives them, as the memory pane shows them.
+    fn matches(&self, c: &Chip8, shown: impl Fn(RawInstr) -> String) -> Vec<(usize, usize)> {
+        match self {
+            Search::Bytes(p) => c.find(p).into_iter().map(|a| (a, p.len())).collect(),
+            Search::Text(t) => {
+                let t = t.to_ascii_uppercase();
+                (c.pc as usize % 2..Chip8::MEM_SIZE - 1)
+                    .step_by(2)
+                    .filter(|&a| {
+                        let raw = RawInstr::from_bytes([c.memory[a], c.memory[a + 1]]);
+                        shown(raw).to_ascii_uppercase().contains(&t)
+                    })
+                    .map(|a| (a, 2))
+                    .collect()
+            }
+        }
+    }
+}
+
 pub struct App {
     debugger: Debugger,
+    /// The file of the running ROM
+    rom: PathBuf,
+    /// The IPS patch applied to the ROM, again when it is reloaded
+    patch: Option<Vec<u8>>,
+    /// With `--watch`, the time the ROM was last modified, to reload it
+    /// when it changes
+    watch: Option<SystemTime>,
+    /// The interpreter whose quirks are emulated
+    platform: Platform,
+    /// The address at the center of the memory pane. When `None`, the pane
+    /// follows the pc
+    memory_center: Option<u16>,
+    /// The text typed in the command prompt, if it is open
+    prompt: Option<String>,
+    /// The last notification for the user, shown below the panes
+    message: Option<String>,
+    /// The byte pattern of the last `:find`
+    search: Option<Search>,
+    /// The user's notes on the ROM's addresses
+    notes: Notes,
+    /// The names of the ROM's addresses
+    symbols: symbols::Symbols,
+    /// The source map of the ROM and its files, if it was assembled with one
+    sources: Option<sourcemap::Sources>,
+    /// Whether the memory pane shows the source instead of the instructions
+    /// when the pc is in it
+    source_view: bool,
+    /// A command that is waiting for the next key, e.g. the name of a mark
+    pending: Option<command::Command>,
+    /// The index of the selected breakpoint in the breakpoints pane
+    selected_breakpoint: usize,
+    /// Whether the debugger is stepping forward on its own
+    playing: bool,
+    /// The playback speed, as an index of `PLAY_RATES`
+    play_rate: usize,
+    /// The measured playback speed
+    rates: Rates,
+    /// Whether the measured speed is shown while playing
+    show_rates: bool,
+    /// Whether the registers are shown over the display while playing
+    hud: bool,
+    /// The registers shown by the HUD after PC, I, DT and ST
+    hud_registers: Vec<Operand>,
+    /// How the display pane draws the pixels
+    renderer: render::Renderer,
+    /// The renderer chosen by the user, if it was replaced by the braille
+    /// one because the terminal is too small for it
+    shrunk: Option<render::Renderer>,
+    /// The largest factor by which the bitmap renderers scale the pixels
+    max_scale: Option<usize>,
+    /// Whether the character renderers are scaled up to fill the display
+    fit: bool,
+    /// The colors of the panes and the display
+    theme: Theme,
+    /// The fading pixels of the display while playing, if phosphor decay is on
+    phosphor: Option<render::Phosphor>,
+    /// Pixels that changed in this many last steps are tinted. 0 is off
+    recent: usize,
+    /// The last position of the mouse
+    hover: Option<Position>,
+    /// The pane that takes the arrow keys
+    focus: Pane,
+    /// The size of the terminal when it was last drawn
+    size: Rect,
+    /// The arrangement of the panes
+    preset: Preset,
+    /// The panes shown, which start as those of the preset
+    shown: Shown,
+    /// The width of the memory pane, relative to the other tool panes' 4
+    memory_width: u16,
+    /// The view shown in the tabbed pane
+    tab: Tab,
+    /// Whether the key bindings are shown over the panes
+    show_help: bool,
+    /// The address of the instruction whose opcode reference is shown over
+    /// the panes
+    reference: Option<u16>,
+    /// The browser of the ROMs to open instead of the running one, if it is
+    /// open
+    picker: Option<picker::Picker>,
+    /// How keys map to commands
+    keys: command::Keymap,
+    /// The count typed before a command with the vim scheme, e.g. `5` in `5j`
+    count: Option<u32>,
+    /// The GIF being recorded, if any
+    recording: Option<recording::Recording>,
+    /// The size of the square each pixel becomes in recordings
+    record_scale: usize,
+    /// How the memory pane lists the instructions
+    listing: Listing,
+    /// Where every frame drawn is written, see `--dump-frames`
+    frame_dump: Option<frames::FrameDump>,
+    /// The native window that follows the debugger, if it is open
+    window: Option<gui::Attached>,
+    /// The assembly editor. It keeps its text while hidden
+    editor: Option<editor::Editor>,
+    /// Whether the editor is shown over the panes
+    show_editor: bool,
 }
 
 impl Widget for &App {
     fn render(self, area: Rect, buf: &mut ratatui::buffer::Buffer) {
-        fn v_table<'a>(d: &Debugger) -> Table<'a> {
+        fn v_table<'a>(d: &Debugger, theme: &Theme) -> Table<'a> {
             let widths = [Constraint::Fill(1), Constraint::Fill(1)];
             let mut rows: Vec<Row> = vec![];
             let ch = &d.peek();
@@ -72,9 +1499,9 @@ impl Widget for &App {
                 {
                     Line::from(vec![
                         Span::from(format!("{reg_name}: ")),
-                        Span::from(prev.to_string()).red(),
-                        Span::from(" → "),
-                        Span::from(now.to_string()).green(),
+                        Span::from(prev.to_string()).fg(theme.diff_off),
+                        Span::from(theme.glyph(" → ", " -> ")),
+                        Span::from(now.to_string()).fg(theme.diff_on),
                     ])
                 } else {
                     Line::from(format!("{reg_name}: {now}"))
@@ -90,155 +1517,1989 @@ impl Widget for &App {
                     pp_register(Register::from(2 * i + 1)),
                 ]))
             }
-            let title: Line = Line::from("Registers").bold().blue().centered();
-            Table::new(rows, widths).block(Block::bordered().title(title))
+            let title: Line = Line::from("Registers");
+            Table::new(rows, widths).block(theme.block(title))
         }
 
-        fn timers_table<'a>(d: &Debugger) -> Table<'a> {
+        fn timers_table<'a>(d: &Debugger, theme: &Theme) -> Table<'a> {
             let widths = [Constraint::Fill(1), Constraint::Fill(1)];
             let mut rows: Vec<Row> = vec![];
             let ch = &d.peek();
+            // A bar of up to 8 blocks for the sound timer, which beeps while
+            // it is not 0
+            let sound = Line::from(vec![
+                Span::from(format!("sound timer: {} ", ch.sound)),
+                Span::from(
+                    theme
+                        .glyph("█", "#")
+                        .repeat((ch.sound as usize).div_ceil(32)),
+                )
+                .fg(theme.recent),
+            ]);
             rows.push(Row::new([
-                format!("sound timer: {}", ch.sound),
-                format!("delay timer: {}", ch.delay),
+                sound,
+                Line::from(format!("delay timer: {}", ch.delay)),
             ]));
-            let title: Line = Line::from("Timers").bold().blue().centered();
-            Table::new(rows, widths).block(Block::bordered().title(title))
+            let title: Line = Line::from("Timers");
+            Table::new(rows, widths).block(theme.block(title))
         }
 
-        fn stack<'a>(d: &Debugger) -> Paragraph<'a> {
-            let title: Line = Line::from("Stack").bold().blue().centered();
+        fn stack<'a>(d: &Debugger, symbols: &symbols::Symbols, theme: &Theme) -> Paragraph<'a> {
+            let title: Line = Line::from("Stack");
             let ch = &d.peek();
-            let sstack = &ch.stack[..ch.sp as usize];
-            let text: String = format!("top ---> {:?}", sstack);
-            Paragraph::new(text)
-                .block(Block::bordered().title(title))
-                .centered()
+            let sstack: Vec<String> = ch.stack[..ch.sp as usize]
+                .iter()
+                .map(|&a| symbols.describe(a))
+                .collect();
+            let text: String = format!("top ---> [{}]", sstack.join(", "));
+            Paragraph::new(text).block(theme.block(title)).centered()
         }
 
-        fn display<'a>(d: &Debugger) -> Paragraph<'a> {
-            let title: Line = Line::from("Chip-8 display").bold().blue().centered();
-            let text: String = d.peek().screen.to_string();
-            Paragraph::new(text)
-                .block(Block::bordered().title(title))
+        fn display<'a>(app: &App, area: Rect) -> Paragraph<'a> {
+            let mut title: Line = Line::from("Chip-8 display");
+            if app.debugger.peek().sound > 0 {
+                title.push_span(
+                    Span::from(app.theme.glyph(" ♪ BEEP", " BEEP")).fg(app.theme.recent),
+                );
+            }
+            let lines = match app.renderer {
+                render::Renderer::Ascii => {
+                    render::ascii(app.scale(area), |r, c| app.pixel_glyph(r, c))
+                }
+                r => r.lines(app.scale(area), app.theme.pixel_off, |r, c| {
+                    app.pixel_color(r, c)
+                }),
+            };
+            Paragraph::new(Text::from(lines))
+                .block(app.theme.block(title))
                 .centered()
         }
 
-        fn memory<'a>(d: &Debugger) -> List<'a> {
-            let string = format!("Memory (step {}/{})", d.step_number(), d.step_max());
-            let title: Line = Line::from(string).bold().blue().centered();
+        /// Renders the memory around the center of the pane, or around the pc
+        /// if it follows it. `height` is the height of the pane, including its
+        /// borders.
+        fn memory<'a>(app: &App, height: u16) -> List<'a> {
+            let (d, notes, theme, listing) = (&app.debugger, &app.notes, &app.theme, app.listing);
+            let (center, search) = (app.memory_center, app.search.as_ref());
+            let focused = app.focus == Pane::Memory;
+            let marks: String = d.marks_here().iter().map(|m| format!(" '{m}")).collect();
+            let string = format!(
+                "Memory (step {}/{}){marks}{}",
+                d.step_number(),
+                d.step_max(),
+                if center.is_some() { " [scrolled]" } else { "" }
+            );
+            let title: Line = Line::from(string);
             let c = &d.peek();
+            let pch = d.peek_prev().filter(|_| d.diff);
+            let changed: Vec<usize> = if pch.is_some() {
+                d.memory_diff()
+            } else {
+                vec![]
+            };
+            let found: Vec<(usize, usize)> =
+                search.map_or(vec![], |s| s.matches(c, |raw| app.shown(raw)));
+            let is_found = |a: usize| found.iter().any(|&(f, len)| f <= a && a < f + len);
             let pc: i32 = c.pc as i32;
-            const H: i32 = 15 * 2;
-            let mut m: Vec<Span> = vec![];
-            for i in (pc - H..=pc + H).step_by(2) {
-                m.push(if i < 0 || i + 1 >= Chip8::MEM_SIZE as i32 {
-                    "-".into()
+            let mid: i32 = center.map_or(pc, |a| a as i32);
+            let h: i32 = listing.reach(height);
+            let addrs: Vec<Option<usize>> = (mid - h..=mid + h)
+                .step_by(2)
+                .map(|i| (i >= 0 && i + 1 < Chip8::MEM_SIZE as i32).then_some(i as usize))
+                .collect();
+            let gutter = jump_arrows(c, &addrs, theme);
+            let mut m: Vec<Line> = vec![];
+            for (row, i) in (mid - h..=mid + h).step_by(2).enumerate() {
+                let arrows = Span::from(gutter[row].clone()).fg(theme.title);
+                m.push(if addrs[row].is_none() {
+                    Line::from(vec![arrows, "-".into()])
                 } else {
                     let ix = i as usize;
                     let raw: RawInstr = RawInstr::from_bytes([c.memory[ix], c.memory[ix + 1]]);
-                    let s = Span::from(format!(
-                        "{raw} {} {}",
-                        raw.clone().into_instr(),
-                        if i == pc {
-                            format!(" <--- pc = {:#06X}", pc)
+                    let addr = Span::from(format!("{ix:#05X}"));
+                    let found = is_found(ix) || is_found(ix + 1);
+                    let mut spans: Vec<Span> = vec![
+                        arrows,
+                        if found {
+                            addr.black().on_yellow()
                         } else {
-                            String::from("")
+                            addr.dim()
+                        },
+                        Span::from(if found && theme.plain { "*" } else { " " }),
+                    ];
+                    match pch {
+                        _ if !listing.bytes => (),
+                        Some(p) if changed.contains(&ix) || changed.contains(&(ix + 1)) => {
+                            let old = RawInstr::from_bytes([p.memory[ix], p.memory[ix + 1]]);
+                            spans.push(Span::from(old.to_string()).fg(theme.diff_off));
+                            spans.push(Span::from(theme.glyph(" → ", " -> ")));
+                            spans.push(Span::from(raw.to_string()).fg(theme.diff_on));
                         }
-                    ));
-                    if i == pc { s.bold() } else { s }
+                        _ => spans.push(Span::from(raw.to_string())),
+                    }
+                    if let Some(label) = app.symbols.get(ix as u16) {
+                        spans.push(Span::from(format!(" {label}:")).fg(theme.title));
+                    }
+                    let instr = app.shown(raw);
+                    // The matched text is highlighted in the instruction, found
+                    // as `Search::matches` finds it
+                    let matched = match search {
+                        Some(Search::Text(t)) if found => {
+                            let t = t.to_ascii_uppercase();
+                            let start = instr.to_ascii_uppercase().find(&t);
+                            start.map(|start| (start, start + t.len()))
+                        }
+                        _ => None,
+                    };
+                    match matched {
+                        Some((start, end)) => {
+                            spans.push(Span::from(format!(" {}", &instr[..start])));
+                            spans.push(
+                                Span::from(instr[start..end].to_string())
+                                    .black()
+                                    .on_yellow(),
+                            );
+                            spans.push(Span::from(instr[end..].to_string()));
+                        }
+                        _ => spans.push(Span::from(format!(" {instr}"))),
+                    }
+                    if i == pc {
+                        spans.push(Span::from(format!("  <--- pc = {pc:#06X}")));
+                    }
+                    for a in [ix, ix + 1] {
+                        if let Some(note) = notes.get(a as u16) {
+                            spans.push(Span::from(format!(" ; {note}")).cyan());
+                        }
+                    }
+                    let l = Line::from(spans);
+                    if i == pc { l.bold() } else { l }
                 })
             }
-            List::new(m).block(Block::bordered().title(title))
+            let mut block = theme.block(title);
+            if focused {
+                block = theme.focused(block);
+            }
+            if !changed.is_empty() {
+                const SHOWN: usize = 4;
+                let mut addrs: Vec<String> = changed
+                    .iter()
+                    .take(SHOWN)
+                    .map(|a| format!("{a:#05X}"))
+                    .collect();
+                if changed.len() > SHOWN {
+                    addrs.push(format!("+{}", changed.len() - SHOWN));
+                }
+                let bottom = format!("changed: {}", addrs.join(", "));
+                block = block.title_bottom(Line::from(bottom).red().centered());
+            }
+            List::new(m).block(block)
         }
 
-        fn help<'a>() -> Paragraph<'a> {
-            let title: Line = Line::from("Help").bold().blue().centered();
-            let lines = vec![
-                Line::from("Chip-8 debugger key bindings:"),
-                Line::from(vec!["n".bold(), " step forward".into()]),
-                Line::from(vec!["N".bold(), " 10 steps forward".into()]),
-                Line::from(vec!["p".bold(), " step backward".into()]),
-                Line::from(vec!["P".bold(), " 10 steps backward".into()]),
-                Line::from(vec!["d".bold(), " toggle diff".into()]),
-                Line::from(vec!["q".bold(), " quit".into()]),
-            ];
-            let text = Text::from(lines);
-            Paragraph::new(text)
-                .block(Block::bordered().title(title))
-                .alignment(Alignment::Left)
-        }
-
-        let root_layout =
-            Layout::vertical([Constraint::Percentage(55), Constraint::Percentage(45)]);
-        let [display_area, tools_area] = root_layout.areas(area);
-        let [help_area, memory_area, registers_area] = Layout::horizontal([
-            Constraint::Percentage(100),
-            Default::default(),
-            Default::default(),
-        ])
-        .areas(tools_area);
-        let [v_area, stack_area, timers_area] = Layout::vertical([
-            Constraint::Percentage(50),
-            Constraint::Percentage(20),
-            Constraint::Percentage(30),
-        ])
-        .areas(registers_area);
+        /// Renders the lines of `file` from `top` on, with the pc's line in
+        /// bold and the lines with breakpoints marked in the gutter
+        fn source<'a>(
+            app: &App,
+            sources: &sourcemap::Sources,
+            file: &Path,
+            top: usize,
+            height: u16,
+        ) -> List<'a> {
+            let (d, theme) = (&app.debugger, &app.theme);
+            let pc = d.peek().pc;
+            let current = sources.line(pc);
+            let breaks: Vec<usize> = d
+                .breakpoints
+                .iter()
+                .filter_map(|b| match b.kind {
+                    BreakKind::Pc(a) if b.enabled => match sources.map.lines.get(&a) {
+                        Some((f, l)) if f == file => Some(*l),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect();
+            let lines = sources.lines(file);
+            let width = lines.len().to_string().len();
+            let items: Vec<Line> = (top..top + height.saturating_sub(2) as usize)
+                .map_while(|n| {
+                    let text = lines.get(n - 1)?;
+                    let mark = if breaks.contains(&n) {
+                        theme.glyph("● ", "* ")
+                    } else {
+                        "  "
+                    };
+                    let mut spans = vec![
+                        Span::from(mark).red(),
+                        Span::from(format!("{n:>width$} ")).dim(),
+                        Span::from(text.clone()),
+                    ];
+                    if current == Some((file, n)) {
+                        spans.push(Span::from(format!("  <--- pc = {pc:#06X}")));
+                        return Some(Line::from(spans).bold());
+                    }
+                    Some(Line::from(spans))
+                })
+                .collect();
+            let name = file.file_name().unwrap_or(file.as_os_str());
+            let title = Line::from(format!(
+                "Source: {} (step {}/{}){}",
+                name.to_string_lossy(),
+                d.step_number(),
+                d.step_max(),
+                if app.memory_center.is_some() {
+                    " [scrolled]"
+                } else {
+                    ""
+                }
+            ));
+            let block = theme.block(title);
+            let block = if app.focus == Pane::Memory {
+                theme.focused(block)
+            } else {
+                block
+            };
+            List::new(items).block(block)
+        }
 
-        let p1 = display(&self.debugger);
-        let mem = memory(&self.debugger);
-        let help = help();
-        p1.render(display_area, buf);
-        Widget::render(mem, memory_area, buf);
-        help.render(help_area, buf);
-        Widget::render(v_table(&self.debugger), v_area, buf);
-        Widget::render(timers_table(&self.debugger), timers_area, buf);
-        Widget::render(stack(&self.debugger), stack_area, buf);
-    }
-}
+        fn sprite<'a>(d: &Debugger, theme: &Theme) -> Paragraph<'a> {
+            let c = &d.peek();
+            let i = c.i as usize;
+            // A DRW with height 0 draws a 16x16 sprite on SCHIP. Otherwise we
+            // preview the height of the DRW about to run, or the maximum of 15.
+            let (rows, row_bytes) = match c.read_instr() {
+                Instr::Draw { height: 0, .. } => (16, 2),
+                Instr::Draw { height, .. } => (height as usize, 1),
+                _ => (15, 1),
+            };
+            let mut lines: Vec<Line> = vec![];
+            for r in 0..rows {
+                let start = i + r * row_bytes;
+                if start + row_bytes > Chip8::MEM_SIZE {
+                    lines.push(Line::from("-"));
+                    continue;
+                }
+                let bytes = &c.memory[start..start + row_bytes];
+                let mut pixels = String::new();
+                for b in bytes {
+                    for bit in (0..8).rev() {
+                        pixels.push_str(if (b >> bit) & 1 == 1 {
+                            theme.glyph("█", "#")
+                        } else {
+                            "."
+                        });
+                    }
+                }
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+                lines.push(Line::from(vec![
+                    Span::from(pixels),
+                    Span::from(format!(" {}", hex.join(" "))).dim(),
+                ]));
+            }
+            let string = format!("Sprite at I = {i:#05X}");
+            let title: Line = Line::from(string);
+            Paragraph::new(Text::from(lines)).block(theme.block(title))
+        }
 
-impl App {
-    /// Construct a new instance of [`App`].
-    pub fn new(chip: Chip8) -> Self {
-        App {
-            debugger: Debugger::new(chip),
+        fn keypad<'a>(d: &Debugger, theme: &Theme) -> Paragraph<'a> {
+            const LAYOUT: [[u8; 4]; 4] = [
+                [0x1, 0x2, 0x3, 0xC],
+                [0x4, 0x5, 0x6, 0xD],
+                [0x7, 0x8, 0x9, 0xE],
+                [0xA, 0x0, 0xB, 0xF],
+            ];
+            let c = &d.peek();
+            let lines: Vec<Line> = LAYOUT
+                .iter()
+                .map(|row| {
+                    Line::from(
+                        row.iter()
+                            .map(|&k| {
+                                let s = if c.keys[k as usize] && theme.plain {
+                                    Span::from(format!("[{k:X}]"))
+                                } else {
+                                    Span::from(format!(" {k:X} "))
+                                };
+                                if c.keys[k as usize] {
+                                    s.bold().black().on_green()
+                                } else {
+                                    s
+                                }
+                            })
+                            .collect::<Vec<Span>>(),
+                    )
+                })
+                .collect();
+            let title: Line = Line::from("Keypad");
+            Paragraph::new(Text::from(lines))
+                .block(theme.block(title))
+                .centered()
         }
-    }
 
-    pub fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self, frame.area())
-    }
+        /// The block of the tabbed pane, with the tab numbers at the bottom
+        fn tabs_block<'a>(tab: Tab, theme: &Theme, focused: bool) -> Block<'a> {
+            let numbers: Vec<Span> = Tab::ALL
+                .iter()
+                .enumerate()
+                .map(|(ix, &t)| {
+                    let n = if t == tab && theme.plain {
+                        Span::from(format!("[{}]", ix + 1))
+                    } else {
+                        Span::from(format!(" {} ", ix + 1))
+                    };
+                    if t == tab { n.reversed() } else { n }
+                })
+                .collect();
+            let block = theme
+                .block(Line::from(tab.name()))
+                .title_bottom(Line::from(numbers).centered());
+            if focused { theme.focused(block) } else { block }
+        }
 
-    pub fn run(mut self, mut terminal: ratatui::DefaultTerminal) -> Result<()> {
-        let (sender, receiver) = mpsc::channel::<command::Command>();
-        thread::spawn(move || {
+        fn breakpoints<'a>(
+            d: &Debugger,
+            selected: usize,
+            theme: &Theme,
+            block: Block<'a>,
+        ) -> List<'a> {
+            let items: Vec<Line> = d
+                .breakpoints
+                .iter()
+                .enumerate()
+                .map(|(ix, b)| {
+                    let l = Line::from(format!(
+                        "{}{} {b}  hits: {}",
+                        if ix == selected && theme.plain {
+                            "> "
+                        } else {
+                            ""
+                        },
+                        if b.enabled { "[x]" } else { "[ ]" },
+                        b.hits
+                    ));
+                    let l = if b.enabled { l } else { l.dim() };
+                    if ix == selected { l.reversed() } else { l }
+                })
+                .collect();
+            List::new(items).block(block)
+        }
+
+        fn watches<'a>(d: &Debugger) -> Vec<Line<'a>> {
+            let c = d.peek();
+            d.breakpoints
+                .iter()
+                .filter_map(|b| match b.kind {
+                    BreakKind::Watch(a) => Some(Line::from(format!(
+                        "{a:#05X} = {:#04X}  hits: {}",
+                        c.memory[a as usize], b.hits
+                    ))),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// The last `height` executed instructions, the newest at the bottom
+        fn trace<'a>(d: &Debugger, height: usize) -> Vec<Line<'a>> {
+            let first = d.p.saturating_sub(height);
+            (first..d.p)
+                .map(|step| {
+                    let c = &d.history[step];
+                    Line::from(vec![
+                        Span::from(format!("{step:>6} {:#05X} ", c.pc)).dim(),
+                        Span::from(c.read_instr().to_string()),
+                    ])
+                })
+                .collect()
+        }
+
+        fn profile<'a>(d: &Debugger, height: usize) -> Vec<Line<'a>> {
+            let counts = profile::exec_counts(&d.history[..=d.p]);
+            let c = d.peek();
+            profile::hottest(&counts, height)
+                .into_iter()
+                .map(|(a, n)| {
+                    let ix = (a as usize).min(Chip8::MEM_SIZE - 2);
+                    let raw = RawInstr::from_bytes([c.memory[ix], c.memory[ix + 1]]);
+                    Line::from(vec![
+                        Span::from(format!("{n:>6} {a:#05X} ")).dim(),
+                        Span::from(raw.into_instr().to_string()),
+                    ])
+                })
+                .collect()
+        }
+
+        fn coverage<'a>(d: &Debugger) -> Vec<Line<'a>> {
+            let counts = profile::exec_counts(&d.history[..=d.p]);
+            let end = profile::rom_end(d.peek());
+            let ranges = profile::covered(&counts, end);
+            let size = end - Chip8::CODE_START;
+            let bytes: usize = ranges.iter().map(|r| r.len()).sum();
+            let mut lines = vec![Line::from(format!(
+                "{bytes}/{size} bytes executed ({}%)",
+                bytes * 100 / size.max(1)
+            ))];
+            lines.extend(
+                ranges
+                    .iter()
+                    .map(|r| Line::from(format!("{:#05X}-{:#05X}", r.start, r.end - 1))),
+            );
+            lines
+        }
+
+        /// One character per instruction of the program, darker the less
+        /// it was executed
+        fn heatmap<'a>(d: &Debugger, width: usize, theme: &Theme) -> Vec<Line<'a>> {
+            let shades = if theme.plain {
+                [".", ":", "+", "*", "#"]
+            } else {
+                ["·", "░", "▒", "▓", "█"]
+            };
+            let counts = profile::exec_counts(&d.history[..=d.p]);
+            let end = profile::rom_end(d.peek());
+            let max = counts.iter().max().copied().unwrap_or(0).max(1) as f64;
+            let per_line = (width.saturating_sub(6) / 2 * 2).max(2);
+            (Chip8::CODE_START..end)
+                .step_by(per_line)
+                .map(|line| {
+                    let cells: String = (line..(line + per_line).min(end))
+                        .step_by(2)
+                        .map(|a| match counts[a].max(counts[a + 1]) {
+                            0 => shades[0],
+                            // Logarithmic, so that loops do not hide the rest
+                            n => shades[1 + ((n as f64).ln() / max.ln().max(1.0) * 3.0) as usize],
+                        })
+                        .collect();
+                    Line::from(vec![
+                        Span::from(format!("{line:#05X} ")).dim(),
+                        cells.into(),
+                    ])
+                })
+                .collect()
+        }
+
+        /// The position of the current step among the recorded ones
+        fn timeline<'a>(d: &Debugger, width: u16, theme: &Theme) -> Line<'a> {
+            let last = width.saturating_sub(1) as usize;
+            let marker = d.p * last / d.p_max.max(1);
+            Line::from(vec![
+                Span::from(theme.glyph("━", "=").repeat(marker)).fg(theme.title),
+                Span::from(theme.glyph("●", "o")).fg(theme.title),
+                Span::from(theme.glyph("─", "-").repeat(last.saturating_sub(marker)))
+                    .fg(theme.border),
+            ])
+        }
+
+        /// The key bindings, grouped by category, in two columns. The keys of
+        /// the remapped commands replace those of the scheme, and the
+        /// remapped commands that no row lists have a category of their own
+        fn help<'a>(theme: &Theme, keymap: &command::Keymap) -> [Paragraph<'a>; 2] {
+            let column = |groups: &[(&'static str, Vec<(String, String)>)]| {
+                let mut lines: Vec<Line> = vec![];
+                for (category, bindings) in groups {
+                    lines.push(Line::from(*category).bold().fg(theme.title));
+                    for (keys, what) in bindings {
+                        lines.push(Line::from(vec![
+                            Span::from(format!("{keys:>10} ")).bold(),
+                            Span::from(what.clone()),
+                        ]));
+                    }
+                    lines.push(Line::from(""));
+                }
+                Paragraph::new(lines)
+            };
+            let mut groups = HELP.to_vec();
+            if keymap.scheme == command::Scheme::Vim {
+                groups.insert(0, VIM_HELP);
+            }
+            let mut listed: Vec<&str> = vec![];
+            let mut groups: Vec<(&'static str, Vec<(String, String)>)> = groups
+                .iter()
+                .map(|&(category, rows)| {
+                    let rows = rows.iter().map(|&(keys, what, names)| {
+                        let names: Vec<&str> = names.split_whitespace().collect();
+                        listed.extend(&names);
+                        let remapped = names.iter().map(|&n| keymap.remapped.get(n));
+                        if names.is_empty() || remapped.clone().all(|k| k.is_none()) {
+                            return (keys.to_string(), what.to_string());
+                        }
+                        let keys: Vec<&str> = keys
+                            .split_whitespace()
+                            .zip(remapped)
+                            .map(|(key, k)| k.map_or(key, |k| k.as_str()))
+                            .collect();
+                        (keys.join(" "), what.to_string())
+                    });
+                    (category, rows.collect())
+                })
+                .collect();
+            let others: Vec<(String, String)> = keymap
+                .remapped
+                .iter()
+                .filter(|(name, _)| !listed.contains(&name.as_str()))
+                .map(|(name, keys)| (keys.clone(), name.clone()))
+                .collect();
+            if !others.is_empty() {
+                groups.push(("Remapped", others));
+            }
+            let (left, right) = groups.split_at(groups.len().div_ceil(2));
+            [column(left), column(right)]
+        }
+
+        fn prompt_line<'a>(
+            prompt: &Option<String>,
+            message: &Option<String>,
+            theme: &Theme,
+        ) -> Line<'a> {
+            match (prompt, message) {
+                (Some(p), _) => Line::from(vec![
+                    ":".bold(),
+                    Span::from(p.clone()),
+                    theme.glyph("█", "_").into(),
+                ]),
+                (None, Some(m)) if m.starts_with("error") => Line::from(m.clone()).red(),
+                (None, Some(m)) => Line::from(m.clone()).italic(),
+                (None, None) => Line::from("? help").dim(),
+            }
+        }
+
+        /// The session at a glance: the ROM, where it is loaded, the quirks,
+        /// the speed and the recorded history
+        fn status<'a>(app: &App) -> Line<'a> {
+            let name = app.rom.file_name().unwrap_or(app.rom.as_os_str());
+            let d = &app.debugger;
+            let history = d.history.len() * std::mem::size_of::<Chip8>();
+            let sep = || Span::from(app.theme.glyph(" │ ", " | ")).dim();
+            let speed = if app.playing {
+                let rate = PLAY_RATES[app.play_rate];
+                Span::from(format!("{} {rate} steps/s", app.theme.glyph("▶", ">"))).green()
+            } else {
+                Span::from("paused")
+            };
+            Line::from(vec![
+                Span::from(name.to_string_lossy().into_owned()).bold(),
+                sep(),
+                Span::from(format!("{:#05X}", Chip8::CODE_START)),
+                sep(),
+                Span::from(app.platform.to_string()),
+                sep(),
+                speed,
+                sep(),
+                Span::from(format!(
+                    "step {}/{} {:.1} MB",
+                    d.p,
+                    d.history.len() - 1,
+                    history as f64 / 1e6
+                )),
+            ])
+        }
+
+        let (min_width, min_height) = self.min_size(self.renderer);
+        if area.width < min_width || area.height < min_height {
+            let lines = vec![
+                Line::from(format!("The terminal is {}x{}", area.width, area.height)),
+                Line::from(format!("resize it to at least {min_width}x{min_height}")).bold(),
+                Line::from("or press L for a smaller layout").dim(),
+            ];
+            let [middle] = Layout::vertical([Constraint::Length(3)])
+                .flex(Flex::Center)
+                .areas(area);
+            Paragraph::new(lines).centered().render(middle, buf);
+            return;
+        }
+
+        let Areas {
+            display: display_area,
+            memory: memory_area,
+            registers: v_area,
+            stack: stack_area,
+            timers: timers_area,
+            tabs: tabs_area,
+            keypad: keypad_area,
+            sprite: sprite_area,
+            prompt: prompt_area,
+        } = self.areas(area);
+
+        let p1 = display(self, display_area);
+        let mem = match self.source_window(memory_area.height) {
+            Some((sources, file, top)) => source(self, sources, file, top, memory_area.height),
+            None => memory(self, memory_area.height),
+        };
+        p1.render(display_area, buf);
+        Widget::render(mem, memory_area, buf);
+        sprite(&self.debugger, &self.theme).render(sprite_area, buf);
+        Widget::render(v_table(&self.debugger, &self.theme), v_area, buf);
+        Widget::render(timers_table(&self.debugger, &self.theme), timers_area, buf);
+        Widget::render(
+            stack(&self.debugger, &self.symbols, &self.theme),
+            stack_area,
+            buf,
+        );
+        keypad(&self.debugger, &self.theme).render(keypad_area, buf);
+        let tabs_block = tabs_block(self.tab, &self.theme, self.focus == Pane::Tabs);
+        let inner = tabs_block.inner(tabs_area);
+        let d = &self.debugger;
+        match self.tab {
+            Tab::Breakpoints => Widget::render(
+                breakpoints(d, self.selected_breakpoint, &self.theme, tabs_block),
+                tabs_area,
+                buf,
+            ),
+            tab => {
+                let lines = match tab {
+                    Tab::Watches => watches(d),
+                    Tab::Trace => trace(d, inner.height as usize),
+                    Tab::Profile => profile(d, inner.height as usize),
+                    Tab::Coverage => coverage(d),
+                    _ => heatmap(d, inner.width as usize, &self.theme),
+                };
+                Paragraph::new(lines)
+                    .block(tabs_block)
+                    .render(tabs_area, buf)
+            }
+        }
+        timeline(&self.debugger, self.timeline_area().width, &self.theme)
+            .render(self.timeline_area(), buf);
+        // The status bar, which the prompt covers while it is open
+        if self.prompt.is_some() {
+            prompt_line(&self.prompt, &self.message, &self.theme).render(prompt_area, buf);
+        } else {
+            let status = status(self);
+            let [left, right] = Layout::horizontal([
+                Constraint::Fill(1),
+                Constraint::Length(status.width() as u16 + 1),
+            ])
+            .areas(prompt_area);
+            status.right_aligned().render(right, buf);
+            match self.hover_readout(display_area) {
+                Some(readout) => Line::from(readout).render(left, buf),
+                None => prompt_line(&None, &self.message, &self.theme).render(left, buf),
+            }
+        }
+        // The overlays while playing are drawn over the top border of the
+        // display
+        let top = Rect::new(
+            display_area.x + 1,
+            display_area.y,
+            display_area.width.saturating_sub(2),
+            1,
+        );
+        if self.playing && self.show_rates {
+            let r = &self.rates;
+            Line::from(format!(" {} fps {} ips ", r.fps, r.ips))
+                .right_aligned()
+                .render(top, buf);
+        }
+        if self.playing && self.hud {
+            let c = self.debugger.peek();
+            let mut text = format!(
+                " PC {:#05X} I {:#05X} DT {} ST {}",
+                c.pc, c.i, c.delay, c.sound
+            );
+            for r in &self.hud_registers {
+                text.push_str(&format!(" {r} {}", r.get(c)));
+            }
+            text.push(' ');
+            Line::from(text).reversed().render(top, buf);
+        }
+        if self.show_help {
+            let [popup] = Layout::horizontal([Constraint::Max(84)])
+                .flex(Flex::Center)
+                .areas(area);
+            let [popup] = Layout::vertical([Constraint::Max(28)])
+                .flex(Flex::Center)
+                .areas(popup);
+            Clear.render(popup, buf);
+            let block = self.theme.block(Line::from("Key bindings"));
+            let inner = block.inner(popup);
+            block.render(popup, buf);
+            let [left, right] = Layout::horizontal([Constraint::Fill(1); 2])
+                .horizontal_margin(1)
+                .areas(inner);
+            let [l, r] = help(&self.theme, &self.keys);
+            l.render(left, buf);
+            r.render(right, buf);
+        }
+        if let Some(addr) = self.reference {
+            let c = self.debugger.peek();
+            let ix = (addr as usize).min(Chip8::MEM_SIZE - 2);
+            let instr = RawInstr::from_bytes([c.memory[ix], c.memory[ix + 1]]).into_instr();
+            let info = instr.info();
+            let row = |name: &str, text: &'static str| {
+                Line::from(vec![Span::from(format!("{name:>9} ")).bold(), text.into()])
+            };
+            let mut lines = vec![
+                Line::from(format!("{addr:#05X}  {instr}")).bold(),
+                row("encoding", info.encoding),
+                row("does", info.summary),
+                row("operands", info.operands),
+            ];
+            if let Some(vf) = info.vf {
+                lines.push(row("VF", vf));
+            }
+            if let Some(quirk) = info.quirk {
+                lines.push(row("quirk", quirk));
+            }
+            let [popup] = Layout::horizontal([Constraint::Max(64)])
+                .flex(Flex::Center)
+                .areas(area);
+            let [popup] = Layout::vertical([Constraint::Length(lines.len() as u16 + 2)])
+                .flex(Flex::Center)
+                .areas(popup);
+            Clear.render(popup, buf);
+            Paragraph::new(lines)
+                .block(self.theme.block(Line::from("Opcode reference")))
+                .render(popup, buf);
+        }
+        if let Some(picker) = &self.picker {
+            let [popup] = Layout::horizontal([Constraint::Max(84)])
+                .flex(Flex::Center)
+                .areas(area);
+            let [popup] = Layout::vertical([Constraint::Max(24)])
+                .flex(Flex::Center)
+                .areas(popup);
+            Clear.render(popup, buf);
+            picker.render(popup, buf);
+        }
+        if self.show_editor
+            && let Some(editor) = &self.editor
+        {
+            let popup = area.inner(Margin::new(2, 1));
+            Clear.render(popup, buf);
+            editor.render(popup, buf);
+        }
+    }
+}
+
+impl App {
+    /// Construct a new instance of [`App`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        chip: Chip8,
+        rom: PathBuf,
+        platform: Platform,
+        notes: Notes,
+        renderer: render::Renderer,
+        theme: Theme,
+        keys: command::Keymap,
+        phosphor: bool,
+    ) -> Self {
+        let mut app = App {
+            debugger: Debugger::new(chip),
+            rom,
+            patch: None,
+            watch: None,
+            platform,
+            memory_center: None,
+            prompt: None,
+            message: None,
+            search: None,
+            notes,
+            symbols: symbols::Symbols::default(),
+            sources: None,
+            source_view: true,
+            pending: None,
+            selected_breakpoint: 0,
+            playing: false,
+            play_rate: 2,
+            rates: Rates::new(),
+            show_rates: false,
+            hud: false,
+            hud_registers: vec![],
+            renderer,
+            shrunk: None,
+            max_scale: None,
+            fit: false,
+            theme,
+            phosphor: phosphor.then(render::Phosphor::new),
+            recent: 0,
+            hover: None,
+            focus: Pane::Tabs,
+            size: Rect::default(),
+            preset: Preset::Debug,
+            shown: Preset::Debug.shown(),
+            memory_width: 4,
+            tab: Tab::Breakpoints,
+            show_help: false,
+            reference: None,
+            picker: None,
+            keys,
+            count: None,
+            recording: None,
+            record_scale: recording::SCALE,
+            listing: Listing::default(),
+            frame_dump: None,
+            window: None,
+            editor: None,
+            show_editor: false,
+        };
+        app.load_symbols();
+        app
+    }
+
+    /// Handles a key press while the command prompt is open
+    pub fn prompt_key(&mut self, k: KeyEvent) {
+        let Some(p) = &mut self.prompt else { return };
+        match k.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Backspace if p.pop().is_none() => self.prompt = None,
+            KeyCode::Enter => {
+                let input = p.clone();
+                self.prompt = None;
+                match PromptCommand::parse(&input) {
+                    Ok(c) => self.run_prompt_command(c),
+                    Err(e) => self.message = Some(format!("error: {e}")),
+                }
+            }
+            KeyCode::Char(c) => p.push(c),
+            _ => (),
+        }
+    }
+
+    /// Completes a command that was waiting for the next key
+    pub fn pending_key(&mut self, pending: command::Command, k: KeyEvent) {
+        let KeyCode::Char(name) = k.code else { return };
+        match pending {
+            command::Command::GotoPrefix if name == 'g' => {
+                self.debugger.goto_step(0);
+            }
+            // `gt` and `gT` switch tabs like in vim
+            command::Command::GotoPrefix if name == 't' || name == 'T' => {
+                let ix = Tab::ALL.iter().position(|&t| t == self.tab).unwrap_or(0);
+                let delta = if name == 't' { 1 } else { Tab::ALL.len() - 1 };
+                self.tab = Tab::ALL[(ix + delta) % Tab::ALL.len()];
+            }
+            command::Command::GotoPrefix => {
+                self.prompt = Some(format!("mem {name}"));
+                self.message = None;
+            }
+            command::Command::SetMark => {
+                self.debugger.set_mark(name);
+                self.message = Some(format!("mark '{name} at step {}", self.debugger.p));
+            }
+            command::Command::GotoMark => match self.debugger.marks.get(&name).copied() {
+                Some(step) if self.debugger.goto_step(step) => (),
+                _ => self.message = Some(format!("no mark '{name}")),
+            },
+            _ => (),
+        }
+    }
+
+    pub fn run_prompt_command(&mut self, c: PromptCommand) {
+        match c {
+            PromptCommand::Find(pattern) => {
+                self.search = Some(Search::Bytes(pattern));
+                self.goto_match(0);
+            }
+            PromptCommand::Search(text) => {
+                self.search = Some(Search::Text(text));
+                self.goto_match(0);
+            }
+            PromptCommand::Mem(addr) => self.memory_center = Some(addr),
+            PromptCommand::Break(kind, condition) => {
+                self.debugger
+                    .breakpoints
+                    .push(Breakpoint::new(kind, condition));
+            }
+            PromptCommand::BreakLine(file, line, condition) => {
+                match self.sources.as_ref().and_then(|s| s.addr(&file, line)) {
+                    Some(addr) => self
+                        .debugger
+                        .breakpoints
+                        .push(Breakpoint::new(BreakKind::Pc(addr), condition)),
+                    None => {
+                        self.message = Some(format!(
+                            "error: no instruction at {}:{line}",
+                            file.display()
+                        ))
+                    }
+                }
+            }
+            PromptCommand::Dump(range, file) => {
+                let bytes = &self.debugger.peek().memory[range.clone()];
+                self.message = Some(match fs::write(&file, bytes) {
+                    Ok(()) => format!(
+                        "wrote {} bytes ({:#05X}..{:#05X}) to {}",
+                        bytes.len(),
+                        range.start,
+                        range.end,
+                        file.display()
+                    ),
+                    Err(e) => format!("error: could not write {}: {e}", file.display()),
+                });
+            }
+            PromptCommand::Record(file) => {
+                let history = &self.debugger.history[..=self.debugger.p_max];
+                let replay = replay::Replay::record(history);
+                self.message = Some(match replay.save(&file) {
+                    Ok(()) => format!(
+                        "recorded {} steps and {} key changes to {}",
+                        self.debugger.p_max,
+                        replay.keys.len(),
+                        file.display()
+                    ),
+                    Err(e) => format!("error: could not write {}: {e}", file.display()),
+                });
+            }
+            PromptCommand::SaveState(file) => {
+                self.message = Some(match self.rom_bytes() {
+                    Ok(rom) => {
+                        let state = savestate::SaveState::new(self.debugger.peek(), &rom);
+                        match state.save(&file) {
+                            Ok(()) => format!("saved the state to {}", file.display()),
+                            Err(e) => format!("error: could not write {}: {e}", file.display()),
+                        }
+                    }
+                    Err(e) => e,
+                });
+            }
+            PromptCommand::LoadState(file) => {
+                let state = match savestate::SaveState::load(&file) {
+                    Ok(state) => state,
+                    Err(e) => {
+                        self.message = Some(format!("error: {e}"));
+                        return;
+                    }
+                };
+                let problems = state.problems();
+                self.message = Some(match self.rom_bytes() {
+                    Err(e) => e,
+                    Ok(rom) if !state.is_of(&rom) => {
+                        format!("error: {} is the state of another ROM", file.display())
+                    }
+                    Ok(_) if !problems.is_empty() => format!("error: {}", problems.join(", ")),
+                    Ok(_) => {
+                        self.debugger.edit(|c| state.restore(c));
+                        format!("loaded the state from {}", file.display())
+                    }
+                });
+            }
+            PromptCommand::TraceExport(file) => {
+                let history = &self.debugger.history[..=self.debugger.p_max];
+                self.message = Some(match fs::write(&file, trace::chrome_trace(history)) {
+                    Ok(()) => format!(
+                        "wrote a trace of {} steps to {}",
+                        self.debugger.p_max,
+                        file.display()
+                    ),
+                    Err(e) => format!("error: could not write {}: {e}", file.display()),
+                });
+            }
+            PromptCommand::Load(file, addr) => {
+                self.message = Some(match fs::read(&file) {
+                    Ok(bytes) if addr as usize + bytes.len() > Chip8::MEM_SIZE => {
+                        format!("error: {} bytes do not fit at {addr:#05X}", bytes.len())
+                    }
+                    Ok(bytes) => {
+                        let start = addr as usize;
+                        self.debugger
+                            .edit(|c| c.memory[start..start + bytes.len()].copy_from_slice(&bytes));
+                        format!("loaded {} bytes at {addr:#05X}", bytes.len())
+                    }
+                    Err(e) => format!("error: could not read {}: {e}", file.display()),
+                });
+            }
+            PromptCommand::Poke(addr, bytes) => {
+                let start = addr as usize;
+                if start + bytes.len() > Chip8::MEM_SIZE {
+                    self.message = Some(format!("error: bytes do not fit at {addr:#05X}"));
+                } else {
+                    self.debugger
+                        .edit(|c| c.memory[start..start + bytes.len()].copy_from_slice(&bytes));
+                }
+            }
+            PromptCommand::Set(r, value) => self.debugger.edit(|c| r.set(c, value)),
+            PromptCommand::Pane(name) => {
+                let shown = match name.as_str() {
+                    "memory" => &mut self.shown.memory,
+                    "registers" => &mut self.shown.registers,
+                    _ => &mut self.shown.stack,
+                };
+                *shown = !*shown;
+            }
+            PromptCommand::Hud(registers) => {
+                self.hud = true;
+                self.hud_registers = registers;
+            }
+            PromptCommand::Recent(steps) => {
+                self.recent = steps;
+                self.message = Some(match steps {
+                    0 => "recent pixel changes are not tinted".into(),
+                    n => format!("tinting pixels changed in the last {n} steps"),
+                });
+            }
+            PromptCommand::Open(file) => self.open(file),
+            PromptCommand::Reload => self.reload(),
+            PromptCommand::Copy(clip) => self.copy(clip),
+            PromptCommand::RecentRoms => {
+                self.message = Some(match recent::Recent::load() {
+                    Ok(r) if r.roms.is_empty() => "no recent ROMs".into(),
+                    Ok(r) => {
+                        let roms: Vec<String> = r
+                            .roms
+                            .iter()
+                            .map(|r| {
+                                let name = r.file.file_name().unwrap_or(r.file.as_os_str());
+                                format!("{} ({})", name.to_string_lossy(), r.quirks)
+                            })
+                            .collect();
+                        format!("recent: {}", roms.join(", "))
+                    }
+                    Err(e) => format!("error: could not load the recent ROMs: {e}"),
+                });
+            }
+            PromptCommand::Note(addr, text) => {
+                self.message = Some(match self.notes.set(addr, text) {
+                    Ok(()) => format!("saved notes to {}", self.notes.path.display()),
+                    Err(e) => format!("error: could not save notes: {e}"),
+                });
+            }
+            PromptCommand::Label(addr, name) => {
+                match name {
+                    Some(name) => self.symbols.labels.insert(addr, name),
+                    None => self.symbols.labels.remove(&addr),
+                };
+                let path = symbols::Symbols::path(&self.rom);
+                self.message = Some(match self.symbols.save(&self.rom) {
+                    Ok(()) => format!("saved the labels to {}", path.display()),
+                    Err(e) => format!("error: could not save the labels: {e}"),
+                });
+            }
+        }
+    }
+
+    /// Replaces the running ROM with the one in `file`, from a new machine
+    /// with the same quirks. The recorded steps, the breakpoints and the marks
+    /// of the old ROM are dropped.
+    pub fn open(&mut self, file: PathBuf) {
+        let mut chip = Chip8::new();
+        chip.quirks = self.platform.quirks();
+        chip.font = self.debugger.peek().font;
+        chip.big_font = self.debugger.peek().big_font;
+        let notes = match chip.load_memory(&file).and_then(|()| Notes::load(&file)) {
+            Ok(n) => n,
+            Err(e) => {
+                self.message = Some(format!("error: could not open {}: {e}", file.display()));
+                return;
+            }
+        };
+        self.restart(chip);
+        self.notes = notes;
+        self.rom = file.clone();
+        if self.watch.is_some() {
+            self.watch = modified(&file).ok();
+        }
+        self.load_symbols();
+        let rom = recent::RecentRom {
+            file: file.canonicalize().unwrap_or(file.clone()),
+            quirks: self.platform,
+            scheme: self.keys.scheme,
+        };
+        self.message = Some(match recent::Recent::load().and_then(|mut r| r.add(rom)) {
+            Ok(()) => format!("opened {}", file.display()),
+            Err(e) => format!(
+                "opened {}, but could not save the recent ROMs: {e}",
+                file.display()
+            ),
+        });
+    }
+
+    /// Loads the source map of the ROM and the symbol file, or else the
+    /// labels of the source map, or infers the labels if it has neither
+    fn load_symbols(&mut self) {
+        self.sources = match sourcemap::Sources::load(&self.rom) {
+            Ok(sources) => sources,
+            Err(e) => {
+                self.message = Some(format!("error: could not load the source map: {e}"));
+                None
+            }
+        };
+        self.symbols = match symbols::Symbols::load(&self.rom) {
+            Ok(Some(s)) => s,
+            Ok(None) => match &self.sources {
+                Some(s) => symbols::Symbols {
+                    labels: s.map.labels.clone(),
+                },
+                None => symbols::Symbols::infer(self.debugger.peek()),
+            },
+            Err(e) => {
+                self.message = Some(format!("error: could not load the symbols: {e}"));
+                symbols::Symbols::infer(self.debugger.peek())
+            }
+        };
+    }
+
+    /// Starts the debugger again with a machine
+    fn restart(&mut self, chip: Chip8) {
+        print!("{}", self.renderer.clear());
+        let diff = self.debugger.diff;
+        self.debugger = Debugger::new(chip);
+        self.debugger.diff = diff;
+        self.memory_center = None;
+        self.search = None;
+        self.selected_breakpoint = 0;
+        self.playing = false;
+        if let Some(ph) = &mut self.phosphor {
+            *ph = render::Phosphor::new();
+        }
+    }
+
+    /// Runs a program assembled in the editor in place of the ROM. The
+    /// notes and the file of the ROM are kept.
+    pub fn run_assembled(&mut self, rom: Vec<u8>) {
+        let mut chip = Chip8::new();
+        chip.quirks = self.platform.quirks();
+        chip.font = self.debugger.peek().font;
+        chip.big_font = self.debugger.peek().big_font;
+        if let Err(e) = chip.load_bytes(&rom) {
+            self.message = Some(format!("error: {e}"));
+            return;
+        }
+        self.restart(chip);
+        self.load_symbols();
+        self.message = Some(format!("assembled {} bytes and loaded them", rom.len()));
+    }
+
+    /// Shows the assembly editor, on the `.s8` file next to the ROM
+    pub fn open_editor(&mut self) {
+        print!("{}", self.renderer.clear());
+        if self.editor.is_none() {
+            match editor::Editor::open(self.rom.with_extension("s8")) {
+                Ok(e) => self.editor = Some(e),
+                Err(e) => {
+                    self.message = Some(format!("error: could not open the source: {e}"));
+                    return;
+                }
+            }
+        }
+        self.show_editor = true;
+    }
+
+    /// Loads the ROM's file again over the code in memory, as a manual edit.
+    /// The registers, the timers and the screen are kept, so a changed routine
+    /// can be tried in the middle of a game.
+    pub fn reload(&mut self) {
+        let bytes = match self.rom_bytes() {
+            Ok(b) if Chip8::CODE_START + b.len() > Chip8::MEM_SIZE => {
+                self.message = Some(format!("error: {} bytes do not fit", b.len()));
+                return;
+            }
+            Ok(b) => b,
+            Err(e) => {
+                self.message = Some(e);
+                return;
+            }
+        };
+        let code = Chip8::CODE_START..Chip8::CODE_START + bytes.len();
+        let c = self.debugger.peek();
+        let changed = code
+            .clone()
+            .filter(|&a| c.memory[a] != bytes[a - code.start])
+            .count();
+        let pc = c.pc as usize;
+        let pc_changed =
+            (pc..pc + 2).any(|a| code.contains(&a) && c.memory[a] != bytes[a - code.start]);
+        self.debugger
+            .edit(|c| c.memory[code.clone()].copy_from_slice(&bytes));
+        self.message = Some(if pc_changed {
+            format!("reloaded {changed} changed bytes, warning: the pc is on changed code")
+        } else {
+            format!("reloaded {changed} changed bytes")
+        });
+        self.load_symbols();
+    }
+
+    /// The bytes of the ROM's file, with the patch applied. The error is a
+    /// message for the status line
+    fn rom_bytes(&self) -> std::result::Result<Vec<u8>, String> {
+        let mut bytes = fs::read(&self.rom)
+            .map_err(|e| format!("error: could not read {}: {e}", self.rom.display()))?;
+        if let Some(ips) = &self.patch {
+            patch::apply(&mut bytes, ips)
+                .map_err(|e| format!("error: could not patch the ROM: {e}"))?;
+        }
+        Ok(bytes)
+    }
+
+    /// Reloads the ROM if it was modified since it was last loaded, with
+    /// `--watch`
+    fn watch_rom(&mut self) {
+        if let Some(seen) = self.watch
+            && let Ok(time) = modified(&self.rom)
+            && time != seen
+        {
+            self.watch = Some(time);
+            self.reload();
+        }
+    }
+
+    /// Puts text in the system clipboard with the OSC 52 escape sequence,
+    /// which most terminals support, also over ssh
+    pub fn copy(&mut self, clip: Clip) {
+        let c = self.debugger.peek();
+        let (text, what) = match clip {
+            Clip::Disassembly => {
+                let memory = self.areas(self.size).memory;
+                let inner = memory.inner(Margin::new(1, 1));
+                let lines: Vec<String> = (inner.y..inner.bottom())
+                    .filter_map(|row| self.memory_addr_at(memory, row))
+                    .map(|a| {
+                        let ix = a as usize;
+                        let raw = RawInstr::from_bytes([c.memory[ix], c.memory[ix + 1]]);
+                        format!("{a:#05X} {raw} {}", raw.clone().into_instr())
+                    })
+                    .collect();
+                (lines.join("\n"), format!("{} instructions", lines.len()))
+            }
+            Clip::Memory(range) => {
+                let lines: Vec<String> = range
+                    .clone()
+                    .step_by(16)
+                    .map(|a| {
+                        let bytes = &c.memory[a..(a + 16).min(range.end)];
+                        let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+                        format!("{a:#05X}: {}", hex.join(" "))
+                    })
+                    .collect();
+                (lines.join("\n"), format!("{} bytes", range.len()))
+            }
+            Clip::Screen => (c.screen.to_string(), "the screen".into()),
+        };
+        print!("\x1b]52;c;{}\x07", render::base64(text.as_bytes()));
+        self.message = Some(match io::stdout().flush() {
+            Ok(()) => format!("copied {what} to the clipboard"),
+            Err(e) => format!("error: could not copy: {e}"),
+        });
+    }
+
+    /// Writes the screen to the screenshots directory as text and as a PNG
+    pub fn screenshot(&mut self) {
+        let screen = &self.debugger.peek().screen;
+        let dir = PathBuf::from(screenshot::DIR);
+        let saved = screenshot::save(&dir, screen, self.theme.pixel_on, self.theme.pixel_off);
+        self.message = Some(match saved {
+            Ok(stem) => format!("saved {}.txt and .png", stem.display()),
+            Err(e) => format!("error: could not save the screenshot: {e}"),
+        });
+    }
+
+    /// Starts recording the screen to a GIF in the screenshots directory,
+    /// or stops and saves the recording
+    pub fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(r) => {
+                let saved = r.save(self.theme.pixel_on, self.theme.pixel_off);
+                self.message = Some(match saved {
+                    Ok(n) => format!("saved {n} frames to {}", r.file.display()),
+                    Err(e) => format!("error: could not save {}: {e}", r.file.display()),
+                });
+            }
+            None => {
+                let dir = PathBuf::from(screenshot::DIR);
+                if let Err(e) = fs::create_dir_all(&dir) {
+                    self.message = Some(format!("error: could not create {}: {e}", dir.display()));
+                    return;
+                }
+                let file = dir.join(format!("chip-8-{}.gif", screenshot::timestamp()));
+                self.message = Some(format!("recording to {}", file.display()));
+                self.recording = Some(recording::Recording::new(file, self.record_scale));
+            }
+        }
+    }
+
+    /// Runs until a breakpoint stops the program
+    pub fn continue_run(&mut self) {
+        const LIMIT: u32 = 10_000;
+        self.message = Some(match self.debugger.continue_run(LIMIT) {
+            Some(ix) => {
+                self.selected_breakpoint = ix;
+                format!("stopped at {}", self.debugger.breakpoints[ix])
+            }
+            None => format!("no breakpoint hit after {LIMIT} steps"),
+        });
+    }
+
+    /// Steps until the pc reaches the start of another line of the source,
+    /// or a breakpoint stops the program
+    pub fn step_line(&mut self) {
+        const LIMIT: u32 = 10_000;
+        let Some(sources) = &self.sources else {
+            self.message = Some("no source map, see assemble --map".into());
+            return;
+        };
+        let start = sources.line(self.debugger.peek().pc);
+        for _ in 0..LIMIT {
+            if let Some(ix) = self.debugger.continue_run(1) {
+                self.selected_breakpoint = ix;
+                self.message = Some(format!("stopped at {}", self.debugger.breakpoints[ix]));
+                return;
+            }
+            let pc = self.debugger.peek().pc;
+            if sources.starts_line(pc) && sources.line(pc) != start {
+                return;
+            }
+        }
+        self.message = Some(format!("no other line reached after {LIMIT} steps"));
+    }
+
+    /// Plays `steps` steps. Playback stops at breakpoints
+    pub fn play_tick(&mut self, steps: u32) {
+        let from = self.debugger.p;
+        let stopped = self.debugger.continue_run(steps);
+        self.rates.tick(0, self.debugger.p - from);
+        if let Some(ix) = stopped {
+            self.playing = false;
+            self.selected_breakpoint = ix;
+            self.message = Some(format!("stopped at {}", self.debugger.breakpoints[ix]));
+        }
+        if let Some(ph) = &mut self.phosphor {
+            ph.frame(&self.debugger.peek().screen);
+        }
+    }
+
+    /// Shows the current screen in the window, and presses the keys held in
+    /// it as a manual edit, like those toggled in the keypad pane
+    fn sync_window(&mut self) {
+        let Some(window) = &mut self.window else {
+            return;
+        };
+        match window.sync(&self.debugger.peek().screen) {
+            Ok(Some(keys)) => self.debugger.edit(|c| c.keys = keys),
+            Ok(None) => (),
+            Err(reason) => {
+                self.message = Some(reason);
+                self.window = None;
+            }
+        }
+    }
+
+    pub fn select_breakpoint(&mut self, delta: i32) {
+        let len = self.debugger.breakpoints.len() as i32;
+        if len > 0 {
+            self.selected_breakpoint =
+                (self.selected_breakpoint as i32 + delta).clamp(0, len - 1) as usize;
+        }
+    }
+
+    /// Centers the memory pane on a match of the current search. A positive
+    /// `direction` moves to the next match after the pane's center, a negative
+    /// one to the previous match and 0 to the first match from the center on.
+    /// The search wraps around the memory.
+    pub fn goto_match(&mut self, direction: i32) {
+        let Some(search) = &self.search else {
+            self.message = Some("no active search".into());
+            return;
+        };
+        let found: Vec<usize> = search
+            .matches(self.debugger.peek(), |raw| self.shown(raw))
+            .into_iter()
+            .map(|(a, _)| a)
+            .collect();
+        let from = self.memory_center.unwrap_or(self.debugger.peek().pc) as usize;
+        let target = match direction {
+            0 => found.iter().find(|&&a| a >= from).or(found.first()),
+            d if d > 0 => found.iter().find(|&&a| a > from).or(found.first()),
+            _ => found.iter().rev().find(|&&a| a < from).or(found.last()),
+        };
+        match target {
+            Some(&a) => {
+                let ix = found.iter().position(|&f| f == a).unwrap_or(0);
+                self.memory_center = Some(a as u16);
+                self.message = Some(format!("match {}/{} at {a:#05X}", ix + 1, found.len()));
+            }
+            None => self.message = Some("not found".into()),
+        }
+    }
+
+    /// An instruction as the memory pane shows it, in the base of the
+    /// listing and with the labels of its address
+    fn shown(&self, raw: RawInstr) -> String {
+        let instr = raw.into_instr();
+        let text = self
+            .listing
+            .base
+            .map_or(instr.to_string(), |b| instr.format(b));
+        self.symbols.label_operand(&instr, &text)
+    }
+
+    pub fn toggle_breakpoint(&mut self, addr: u16) {
+        let added = self.debugger.toggle_breakpoint(addr);
+        let verb = if added { "added" } else { "removed" };
+        self.message = Some(format!("{verb} breakpoint at {addr:#05X}"));
+    }
+
+    /// Handles a left click: it focuses the memory or breakpoints pane,
+    /// toggles a breakpoint at a memory line, selects a breakpoint, or moves
+    /// along the timeline
+    pub fn click(&mut self, pos: Position) {
+        if self.scrub(pos) {
+            return;
+        }
+        let areas = self.areas(self.size);
+        if areas.memory.contains(pos) {
+            self.focus = Pane::Memory;
+            if let Some(addr) = self.memory_addr_at(areas.memory, pos.y) {
+                self.toggle_breakpoint(addr);
+            }
+        } else if areas.tabs.contains(pos) {
+            self.focus = Pane::Tabs;
+            let inner = areas.tabs.inner(Margin::new(1, 1));
+            let ix = pos.y.saturating_sub(inner.y) as usize;
+            if self.tab == Tab::Breakpoints
+                && inner.contains(pos)
+                && ix < self.debugger.breakpoints.len()
+            {
+                self.selected_breakpoint = ix;
+            }
+        }
+    }
+
+    /// The source map, the file shown by the source view of the memory pane
+    /// and the line at its top, when the view is on and the center of the
+    /// pane, usually the pc, is in the source. `height` is that of the pane
+    fn source_window(&self, height: u16) -> Option<(&sourcemap::Sources, &Path, usize)> {
+        let sources = self.sources.as_ref().filter(|_| self.source_view)?;
+        let center = self.memory_center.unwrap_or(self.debugger.peek().pc);
+        let (file, line) = sources.line(center)?;
+        let h = (height.saturating_sub(2) as usize).saturating_sub(1) / 2;
+        Some((sources, file, line.saturating_sub(h).max(1)))
+    }
+
+    /// The address of the instruction at a row of the memory pane. It follows
+    /// the lines drawn by the pane, and in the source view it is the first
+    /// instruction of the line.
+    fn memory_addr_at(&self, memory: Rect, row: u16) -> Option<u16> {
+        let inner = memory.inner(Margin::new(1, 1));
+        if row < inner.y || row >= inner.bottom() {
+            return None;
+        }
+        if let Some((sources, file, top)) = self.source_window(memory.height) {
+            return sources.addr(file, top + (row - inner.y) as usize);
+        }
+        let mid = self.memory_center.unwrap_or(self.debugger.peek().pc) as i32;
+        let h: i32 = self.listing.reach(memory.height);
+        let addr = mid - h + 2 * (row - inner.y) as i32;
+        (addr <= mid + h && (0..Chip8::MEM_SIZE as i32 - 1).contains(&addr)).then_some(addr as u16)
+    }
+
+    /// The columns of the timeline, which is drawn over the bottom border of
+    /// the display
+    fn timeline_area(&self) -> Rect {
+        let display = self.areas(self.size).display;
+        Rect::new(
+            display.x + 1,
+            display.bottom().saturating_sub(1),
+            display.width.saturating_sub(2),
+            1,
+        )
+    }
+
+    /// Moves to the step under `pos` if it is on the timeline
+    pub fn scrub(&mut self, pos: Position) -> bool {
+        let track = self.timeline_area();
+        if !track.contains(pos) || track.width < 2 {
+            return false;
+        }
+        let last = (track.width - 1) as usize;
+        let col = (pos.x - track.x) as usize;
+        let step = (col * self.debugger.p_max + last / 2) / last;
+        self.debugger.goto_step(step);
+        true
+    }
+
+    /// Moves the memory pane `delta` bytes away from its current center
+    pub fn scroll_memory(&mut self, delta: i32) {
+        let from = self.memory_center.unwrap_or(self.debugger.peek().pc) as i32;
+        let to = (from + delta).clamp(0, Chip8::MEM_SIZE as i32 - 2);
+        self.memory_center = Some(to as u16);
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self, frame.area())
+    }
+
+    /// Splits the screen into the display, the tool panes and the prompt line,
+    /// following the preset. Hidden columns give their space to the others,
+    /// and the panes that are hidden get an empty area.
+    fn areas(&self, area: Rect) -> Areas {
+        let display_height = Constraint::Length(self.renderer.height() + 2);
+        let [display, tools, prompt] = Layout::vertical(match self.preset {
+            Preset::Debug => [display_height, Constraint::Fill(1), Constraint::Length(1)],
+            Preset::Display => [
+                Constraint::Fill(1),
+                Constraint::Length(18),
+                Constraint::Length(1),
+            ],
+            Preset::Minimal => [
+                Constraint::Fill(1),
+                Constraint::Length(0),
+                Constraint::Length(1),
+            ],
+        })
+        .areas(area);
+        let shown = self.shown;
+        let width = |shown: bool, w: u16| Constraint::Fill(if shown { w } else { 0 });
+        let [memory, registers_area, inputs_area] = Layout::horizontal([
+            width(shown.memory, self.memory_width),
+            width(shown.registers, 4),
+            width(self.preset != Preset::Minimal, 4),
+        ])
+        .areas(tools);
+        let [registers, stack, timers, tabs] = Layout::vertical([
+            Constraint::Length(11),
+            Constraint::Length(if shown.stack { 3 } else { 0 }),
+            Constraint::Length(3),
+            Constraint::Fill(1),
+        ])
+        .areas(registers_area);
+        let [keypad, sprite] =
+            Layout::vertical([Constraint::Length(6), Constraint::Fill(1)]).areas(inputs_area);
+        Areas {
+            display,
+            memory,
+            registers,
+            stack,
+            timers,
+            tabs,
+            keypad,
+            sprite,
+            prompt,
+        }
+    }
+
+    /// The smallest terminal, in columns and rows, where the panes of the
+    /// preset fit with a renderer. The stack and the tabbed pane may be
+    /// squeezed out.
+    fn min_size(&self, renderer: render::Renderer) -> (u16, u16) {
+        let (width, height) = (renderer.width() + 2, renderer.height() + 2);
+        match self.preset {
+            // The registers and the timers, and the prompt line
+            Preset::Debug => (width.max(80), height + 15),
+            Preset::Display => (width.max(80), height + 19),
+            Preset::Minimal => (width, height + 1),
+        }
+    }
+
+    /// The factor by which the character renderers scale the pixels in the
+    /// display pane at `area`
+    fn scale(&self, area: Rect) -> usize {
+        if self.fit {
+            self.renderer.fit(area.inner(Margin::new(1, 1)))
+        } else {
+            1
+        }
+    }
+
+    /// Switches from the half blocks to the smaller braille renderer when the
+    /// display does not fit the terminal, and back when it fits again
+    fn fit_renderer(&mut self, area: Rect) {
+        let fits = |r: render::Renderer| {
+            let (w, h) = self.min_size(r);
+            area.width >= w && area.height >= h
+        };
+        match self.shrunk {
+            None if self.renderer == render::Renderer::HalfBlock
+                && !fits(render::Renderer::HalfBlock)
+                && fits(render::Renderer::Braille) =>
+            {
+                self.shrunk = Some(self.renderer);
+                self.renderer = render::Renderer::Braille;
+            }
+            Some(r) if fits(r) => {
+                self.renderer = r;
+                self.shrunk = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// The color of a pixel of the display, or `None` if it is off. With the
+    /// diff on, pixels that changed in the last step are highlighted, and
+    /// pixels on that changed in the last `recent` steps are tinted. While
+    /// playing with phosphor decay, pixels that are off fade out.
+    fn pixel_color(&self, r: usize, c: usize) -> Option<Color> {
+        let (d, t) = (&self.debugger, &self.theme);
+        let now = d.peek().screen.rows[r][c];
+        match d.peek_prev().filter(|_| d.diff) {
+            Some(p) if p.screen.rows[r][c] != now => Some(if now { t.diff_on } else { t.diff_off }),
+            _ if now && self.changed_recently(r, c) => Some(t.recent),
+            _ if now => Some(t.pixel_on),
+            _ => match &self.phosphor {
+                Some(ph) if self.playing => ph.color(r, c, t.pixel_on, t.pixel_off),
+                _ => t.pixel_off,
+            },
+        }
+    }
+
+    /// The character of a pixel of the display with the ASCII renderer: `+`
+    /// and `-` for pixels turned on and off in the last step with the diff on,
+    /// `*` for pixels on that changed in the last `recent` steps, and `#` and
+    /// `.` for the others
+    fn pixel_glyph(&self, r: usize, c: usize) -> char {
+        let d = &self.debugger;
+        let now = d.peek().screen.rows[r][c];
+        match d.peek_prev().filter(|_| d.diff) {
+            Some(p) if p.screen.rows[r][c] != now => {
+                if now {
+                    '+'
+                } else {
+                    '-'
+                }
+            }
+            _ if now && self.changed_recently(r, c) => '*',
+            _ if now => '#',
+            _ => '.',
+        }
+    }
+
+    /// Whether a pixel changed in the last `recent` steps
+    fn changed_recently(&self, r: usize, c: usize) -> bool {
+        let d = &self.debugger;
+        let first = d.p.saturating_sub(self.recent);
+        d.history[first..=d.p]
+            .windows(2)
+            .any(|w| w[0].screen.rows[r][c] != w[1].screen.rows[r][c])
+    }
+
+    /// The coordinates and state of the pixels under the mouse, if it is over
+    /// the display
+    fn hover_readout(&self, display_area: Rect) -> Option<String> {
+        let pos = self.hover?;
+        let inner = display_area.inner(Margin::new(1, 1));
+        if !inner.contains(pos) {
+            return None;
+        }
+        let (xs, ys) = self.renderer.pixels_at(
+            inner,
+            self.scale(display_area),
+            self.max_scale,
+            pos.x,
+            pos.y,
+        )?;
+        let screen = &self.debugger.peek().screen;
+        let on = |x: usize, y: usize| screen.rows[y][x];
+        Some(if xs.len() == 1 && ys.len() == 1 {
+            let state = if on(xs.start, ys.start) { "on" } else { "off" };
+            format!("pixel ({}, {}) {state}", xs.start, ys.start)
+        } else {
+            let count = ys
+                .clone()
+                .flat_map(|y| xs.clone().map(move |x| (x, y)))
+                .filter(|&(x, y)| on(x, y))
+                .count();
+            let range = |r: &std::ops::Range<usize>| match r.len() {
+                1 => r.start.to_string(),
+                _ => format!("{}-{}", r.start, r.end - 1),
+            };
+            format!("pixels x {} y {}: {count} on", range(&xs), range(&ys))
+        })
+    }
+
+    /// Draws the display as a bitmap over the terminal, for the bitmap
+    /// renderers
+    fn draw_image(&self, area: Rect) -> Result<()> {
+        let (min_width, min_height) = self.min_size(self.renderer);
+        if self.show_help
+            || self.reference.is_some()
+            || self.picker.is_some()
+            || self.show_editor
+            || area.width < min_width
+            || area.height < min_height
+        {
+            return Ok(());
+        }
+        let inner = self.areas(area).display.inner(Margin::new(1, 1));
+        if let Some(image) = self
+            .renderer
+            .image(inner, self.max_scale, |r, c| self.pixel_color(r, c))
+        {
+            let mut out = io::stdout();
+            out.write_all(image.as_bytes())?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn run(mut self, mut terminal: ratatui::DefaultTerminal) -> Result<()> {
+        let (sender, receiver) = mpsc::channel::<Event>();
+        thread::spawn(move || {
             Self::input_loop(sender);
         });
         loop {
-            terminal.draw(|frame| self.draw(frame))?;
-            match receiver.recv().expect("receiver failed") {
+            self.watch_rom();
+            let size = terminal.size()?;
+            self.fit_renderer(Rect::new(0, 0, size.width, size.height));
+            let frame = terminal.draw(|frame| self.draw(frame))?;
+            self.size = frame.area;
+            self.draw_image(frame.area)?;
+            if let Some(r) = &mut self.recording {
+                r.frame(&self.debugger.peek().screen);
+            }
+            if let Some(d) = &mut self.frame_dump {
+                d.frame(&self.debugger.peek().screen)?;
+            }
+            self.sync_window();
+            if self.playing {
+                self.rates.tick(1, 0);
+            }
+            let e = if self.playing {
+                // Fast playback runs several steps per frame to keep the
+                // terminal at no more than 60 redraws per second
+                let rate = PLAY_RATES[self.play_rate];
+                let fps = rate.min(60);
+                match receiver.recv_timeout(Duration::from_secs(1) / fps) {
+                    Ok(e) => e,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.play_tick(rate / fps);
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => panic!("receiver failed"),
+                }
+            } else if self.window.is_some() || self.watch.is_some() {
+                // The window still takes keys while paused, and the ROM is
+                // still watched
+                match receiver.recv_timeout(Duration::from_secs(1) / 60) {
+                    Ok(e) => e,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => panic!("receiver failed"),
+                }
+            } else {
+                receiver.recv().expect("receiver failed")
+            };
+            if self.prompt.is_some() {
+                if let Event::Key(k) = e
+                    && k.kind == KeyEventKind::Press
+                {
+                    self.prompt_key(k);
+                }
+                continue;
+            }
+            if let Some(picker) = &mut self.picker {
+                if let Event::Key(k) = e
+                    && k.kind == KeyEventKind::Press
+                {
+                    match picker.key(k) {
+                        picker::Choice::Browsing => (),
+                        picker::Choice::Cancel => self.picker = None,
+                        picker::Choice::Open(file) => {
+                            self.picker = None;
+                            self.open(file);
+                        }
+                    }
+                }
+                continue;
+            }
+            if self.show_editor
+                && let Some(editor) = &mut self.editor
+            {
+                if let Event::Key(k) = e
+                    && k.kind == KeyEventKind::Press
+                {
+                    match editor.key(k) {
+                        editor::EditorAction::Editing => (),
+                        editor::EditorAction::Close => self.show_editor = false,
+                        editor::EditorAction::Run(rom) => {
+                            self.show_editor = false;
+                            self.run_assembled(rom);
+                        }
+                    }
+                }
+                continue;
+            }
+            if self.show_help || self.reference.is_some() {
+                // Any key closes the popups
+                if let Event::Key(k) = e
+                    && k.kind == KeyEventKind::Press
+                {
+                    self.show_help = false;
+                    self.reference = None;
+                }
+                continue;
+            }
+            if let Some(pending) = self.pending.take() {
+                if let Event::Key(k) = e
+                    && k.kind == KeyEventKind::Press
+                {
+                    self.pending_key(pending, k);
+                }
+                continue;
+            }
+            let Some(c) = self.keys.command_from_event(e) else {
+                continue;
+            };
+            if let command::Command::Count(digit) = c {
+                let count = self
+                    .count
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add(digit);
+                self.count = Some(count);
+                self.message = Some(count.to_string());
+                continue;
+            }
+            let count = self.count.take();
+            let times = count.unwrap_or(1);
+            match c {
                 command::Command::Exit => break,
                 command::Command::Redraw => (),
+                command::Command::Hover(pos) => self.hover = Some(pos),
                 command::Command::ToggleDiff => self.debugger.diff = !self.debugger.diff,
-                command::Command::StepForward => self.debugger.step_forward(),
+                command::Command::NextRenderer => {
+                    print!("{}", self.renderer.clear());
+                    self.renderer = self.renderer.next();
+                    self.shrunk = None;
+                }
+                command::Command::ShowHelp => {
+                    print!("{}", self.renderer.clear());
+                    self.show_help = true;
+                }
+                command::Command::Reload => self.reload(),
+                command::Command::CopyDisassembly => self.copy(Clip::Disassembly),
+                command::Command::Screenshot => self.screenshot(),
+                command::Command::ToggleRecording => self.toggle_recording(),
+                command::Command::OpenEditor => self.open_editor(),
+                command::Command::ShowReference => {
+                    // The instruction under the mouse, or the one at the
+                    // center of the memory pane
+                    let memory = self.areas(self.size).memory;
+                    let hovered = self
+                        .hover
+                        .filter(|&p| memory.contains(p))
+                        .and_then(|p| self.memory_addr_at(memory, p.y));
+                    let center = self.memory_center.unwrap_or(self.debugger.peek().pc);
+                    print!("{}", self.renderer.clear());
+                    self.reference = Some(hovered.unwrap_or(center));
+                }
+                command::Command::OpenPicker => {
+                    print!("{}", self.renderer.clear());
+                    let dir = match self.rom.parent() {
+                        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+                        _ => PathBuf::from("."),
+                    };
+                    let recent = recent::Recent::load().map_or(vec![], |r| r.roms);
+                    self.picker = Some(picker::Picker::new(dir, recent));
+                }
+                command::Command::ShowTab(ix) => self.tab = Tab::ALL[ix],
+                command::Command::NextPreset => {
+                    print!("{}", self.renderer.clear());
+                    self.preset = self.preset.next();
+                    self.shown = self.preset.shown();
+                    self.message = Some(format!("{} layout", self.preset.name()));
+                }
+                command::Command::ResizeMemory(delta) => {
+                    self.memory_width = self.memory_width.saturating_add_signed(delta).clamp(1, 16)
+                }
+                command::Command::StepForward => self.debugger.steps_forward(times),
                 command::Command::BigStepForward => self.debugger.steps_forward(10),
                 command::Command::BigStepBackward => self.debugger.steps_back(10),
-                command::Command::StepBackward => {
-                    let _ = self.debugger.step_back();
+                command::Command::ToggleKey(k) => self.debugger.toggle_key(k),
+                command::Command::ScrollMemory(delta) => self.scroll_memory(delta),
+                command::Command::CenterMemory => self.memory_center = None,
+                command::Command::OpenPrompt(input) => {
+                    self.prompt = Some(input.into());
+                    self.message = None;
+                }
+                command::Command::SetMark
+                | command::Command::GotoMark
+                | command::Command::GotoPrefix => self.pending = Some(c),
+                command::Command::FirstStep => {
+                    self.debugger.goto_step(0);
+                }
+                command::Command::LastStep => {
+                    // With a count, `G` goes to that step like in vim
+                    let step = count.map_or(self.debugger.history.len() - 1, |n| n as usize);
+                    if !self.debugger.goto_step(step) {
+                        self.message = Some(format!("step {step} is not recorded"));
+                    }
+                }
+                command::Command::Continue => self.continue_run(),
+                command::Command::TogglePlay => {
+                    self.playing = !self.playing;
+                    self.rates = Rates::new();
+                    // The pixels of an earlier playback do not linger
+                    if let Some(ph) = &mut self.phosphor {
+                        *ph = render::Phosphor::new();
+                    }
+                }
+                command::Command::TogglePhosphor => {
+                    self.phosphor = match self.phosphor {
+                        Some(_) => None,
+                        None => Some(render::Phosphor::new()),
+                    }
+                }
+                command::Command::ToggleRates => self.show_rates = !self.show_rates,
+                command::Command::ToggleHud => self.hud = !self.hud,
+                command::Command::PlayRate(delta) => {
+                    let max = PLAY_RATES.len() as i32 - 1;
+                    self.play_rate = (self.play_rate as i32 + delta).clamp(0, max) as usize;
+                    let rate = PLAY_RATES[self.play_rate];
+                    self.message = Some(format!("playback at {rate} steps/s"));
+                }
+                command::Command::Undo => {
+                    if !self.debugger.undo_edit() {
+                        self.message = Some("nothing to undo".into());
+                    }
+                }
+                command::Command::Redo => {
+                    if !self.debugger.redo_edit() {
+                        self.message = Some("nothing to redo".into());
+                    }
+                }
+                command::Command::ToggleBreakpoint => {
+                    self.toggle_breakpoint(self.debugger.peek().pc)
+                }
+                command::Command::MoveFocused(delta) => {
+                    let delta = delta * times as i32;
+                    match self.focus {
+                        Pane::Memory => self.scroll_memory(2 * delta),
+                        Pane::Tabs => self.select_breakpoint(delta),
+                    }
+                }
+                command::Command::FocusMemory => self.focus = Pane::Memory,
+                command::Command::FocusTabs => self.focus = Pane::Tabs,
+                command::Command::Click(pos) => self.click(pos),
+                command::Command::Drag(pos) => {
+                    self.scrub(pos);
+                }
+                command::Command::Scroll(pos, delta) => {
+                    if self.areas(self.size).tabs.contains(pos) {
+                        self.select_breakpoint(delta)
+                    } else {
+                        self.scroll_memory(2 * delta)
+                    }
+                }
+                command::Command::EnableBreakpoint => {
+                    if let Some(b) = self.debugger.breakpoints.get_mut(self.selected_breakpoint) {
+                        b.enabled = !b.enabled;
+                    }
                 }
+                command::Command::DeleteBreakpoint => {
+                    if self.selected_breakpoint < self.debugger.breakpoints.len() {
+                        self.debugger.breakpoints.remove(self.selected_breakpoint);
+                        self.select_breakpoint(0);
+                    }
+                }
+                command::Command::ToggleSource => {
+                    self.source_view = !self.source_view;
+                    if self.sources.is_none() {
+                        self.message = Some("no source map, see assemble --map".into());
+                    }
+                }
+                command::Command::StepLine => (0..times).for_each(|_| self.step_line()),
+                command::Command::NextMatch => (0..times).for_each(|_| self.goto_match(1)),
+                command::Command::PrevMatch => (0..times).for_each(|_| self.goto_match(-1)),
+                command::Command::StepBackward => self.debugger.steps_back(times),
+                command::Command::Count(_) => (),
             }
         }
+        if let Some(r) = &self.recording {
+            r.save(self.theme.pixel_on, self.theme.pixel_off)?;
+        }
         Ok(())
     }
 
-    pub fn input_loop(sender: mpsc::Sender<command::Command>) {
+    pub fn input_loop(sender: mpsc::Sender<Event>) {
         loop {
             match crossterm::event::read() {
-                Ok(e) => {
-                    if let Some(c) = command::Command::command_from_event(e) {
-                        sender.send(c).expect("sender failed");
-                    }
-                }
+                Ok(e) => sender.send(e).expect("sender failed"),
                 Err(_) => panic!("input error"),
             }
         }
@@ -246,9 +3507,167 @@ impl App {
 }
 
 pub mod command {
-    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use super::config::Config;
+    use crossterm::event::{
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    };
+    use ratatui::layout::Position;
+    use std::collections::{BTreeMap, HashMap};
+
+    /// A set of key bindings
+    #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+    pub enum Scheme {
+        /// Single letters, see the help overlay
+        Default,
+        /// Vim-flavored, see `command_from_key_vim`
+        Vim,
+    }
+
+    impl Scheme {
+        pub fn named(name: &str) -> Option<Scheme> {
+            match name {
+                "default" => Some(Scheme::Default),
+                "vim" => Some(Scheme::Vim),
+                _ => None,
+            }
+        }
+
+        pub fn name(self) -> &'static str {
+            match self {
+                Scheme::Default => "default",
+                Scheme::Vim => "vim",
+            }
+        }
+
+        /// The scheme set by `scheme` in the `[debugger]` section of the config
+        pub fn from_config(config: &Config) -> Result<Scheme, String> {
+            match config.get("debugger", "scheme") {
+                None => Ok(Scheme::Default),
+                Some(name) => Scheme::named(name).ok_or(format!(
+                    "unknown key binding scheme {name}, expected default or vim"
+                )),
+            }
+        }
+    }
+
+    /// The key bindings: those of the scheme, with the commands remapped in
+    /// the `[debugger.keys]` section of the config, e.g.
+    ///
+    /// ```toml
+    /// [debugger.keys]
+    /// step_forward = "j Enter"
+    /// exit = "Ctrl+q"
+    /// ```
+    ///
+    /// A remapped command no longer takes its keys from the scheme.
+    pub struct Keymap {
+        pub scheme: Scheme,
+        /// The remapped keys, without the shift modifier
+        bindings: HashMap<(KeyModifiers, KeyCode), Command>,
+        /// The keys of each remapped command, by name, as the help shows them:
+        /// separated by `/`
+        pub remapped: BTreeMap<String, String>,
+    }
+
+    impl Keymap {
+        pub fn from_config(config: &Config) -> Result<Keymap, String> {
+            let scheme = Scheme::from_config(config)?;
+            let mut bindings = HashMap::new();
+            let mut remapped = BTreeMap::new();
+            let mut names: HashMap<(KeyModifiers, KeyCode), &str> = HashMap::new();
+            for (name, keys) in config.sections.get("debugger.keys").into_iter().flatten() {
+                let c = Command::from_name(name)
+                    .ok_or(format!("debugger.keys: unknown command {name}"))?;
+                for key in keys.split_whitespace() {
+                    let k =
+                        parse_key(key).ok_or(format!("debugger.keys.{name}: invalid key {key}"))?;
+                    if let Some(other) = names.insert(k, name) {
+                        return Err(format!(
+                            "debugger.keys: {key} is bound to both {other} and {name}"
+                        ));
+                    }
+                    bindings.insert(k, c);
+                }
+                let keys: Vec<&str> = keys.split_whitespace().collect();
+                remapped.insert(name.clone(), keys.join("/"));
+            }
+            Ok(Keymap {
+                scheme,
+                bindings,
+                remapped,
+            })
+        }
+
+        pub fn command_from_event(&self, e: Event) -> Option<Command> {
+            match e {
+                Event::Key(k) if k.kind == KeyEventKind::Press => {
+                    let key = (k.modifiers.difference(KeyModifiers::SHIFT), k.code);
+                    if let Some(&c) = self.bindings.get(&key) {
+                        return Some(c);
+                    }
+                    let c = Command::command_from_event(e, self.scheme)?;
+                    (!self.bindings.values().any(|&b| b == c)).then_some(c)
+                }
+                _ => Command::command_from_event(e, self.scheme),
+            }
+        }
+    }
+
+    /// Parses a key such as `n`, `Ctrl+c`, `Alt+F5`, `PageUp` or `Space`
+    fn parse_key(s: &str) -> Option<(KeyModifiers, KeyCode)> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(r) = rest.strip_prefix("Ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("Alt+") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let code = match rest {
+            "Esc" => KeyCode::Esc,
+            "Enter" => KeyCode::Enter,
+            "Space" => KeyCode::Char(' '),
+            "Tab" => KeyCode::Tab,
+            "Backspace" => KeyCode::Backspace,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            f if f.len() > 1 && f.starts_with('F') => KeyCode::F(f[1..].parse().ok()?),
+            _ => {
+                let mut chars = rest.chars();
+                let c = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                KeyCode::Char(c)
+            }
+        };
+        Some((modifiers, code))
+    }
 
+    #[derive(PartialEq, Eq, Clone, Copy, Debug)]
     pub enum Command {
+        /// The mouse moved to a cell
+        Hover(Position),
+        /// The left button was pressed on a cell
+        Click(Position),
+        /// The mouse moved to a cell with the left button pressed
+        Drag(Position),
+        /// The wheel turned over a cell, by the given number of lines
+        Scroll(Position, i32),
+        /// Scrolls the focused pane, or moves its selection
+        MoveFocused(i32),
         /// The debugger moves one step forward
         StepForward,
         /// The debugger moves 10 steps forward
@@ -263,20 +3682,204 @@ pub mod command {
         Redraw,
         /// Toggles the debugger's visual diff
         ToggleDiff,
+        /// The display is drawn with the next renderer
+        NextRenderer,
+        /// Shows the key bindings over the panes
+        ShowHelp,
+        /// Shows a browser of the ROMs to open instead of the running one
+        OpenPicker,
+        /// Loads the code of the ROM again, keeping the rest of the state
+        Reload,
+        /// Copies the instructions of the memory pane to the clipboard
+        CopyDisassembly,
+        /// Saves the screen as text and as a PNG
+        Screenshot,
+        /// Starts or stops recording a GIF
+        ToggleRecording,
+        /// Shows the assembly editor
+        OpenEditor,
+        /// Shows the opcode reference of an instruction of the memory pane
+        ShowReference,
+        /// A digit of the count of the next command, with the vim scheme
+        Count(u32),
+        /// Focuses the memory pane
+        FocusMemory,
+        /// Focuses the tabbed pane
+        FocusTabs,
+        /// Shows the tab with the given index in the tabbed pane
+        ShowTab(usize),
+        /// Switches to the next layout preset
+        NextPreset,
+        /// Makes the memory pane wider (positive) or narrower (negative)
+        ResizeMemory(i16),
+        /// Turns phosphor decay on or off
+        TogglePhosphor,
+        /// Toggles a keypad key in the current state
+        ToggleKey(u8),
+        /// Scrolls the memory pane by the given number of bytes
+        ScrollMemory(i32),
+        /// Makes the memory pane follow the pc again
+        CenterMemory,
+        /// Opens the command prompt with some initial input
+        OpenPrompt(&'static str),
+        /// Moves the memory pane to the next search match
+        NextMatch,
+        /// Moves the memory pane to the previous search match
+        PrevMatch,
+        /// Bookmarks the current step with the name of the next key
+        SetMark,
+        /// Moves to the step bookmarked with the name of the next key
+        GotoMark,
+        /// `gg` moves to the first step, `g` followed by an address moves the
+        /// memory pane to the address
+        GotoPrefix,
+        /// Moves to the first step
+        FirstStep,
+        /// Moves to the newest recorded step
+        LastStep,
+        /// Runs until a breakpoint is hit
+        Continue,
+        /// Starts or stops stepping forward automatically
+        TogglePlay,
+        /// Moves to a faster (positive) or slower (negative) playback speed
+        PlayRate(i32),
+        /// Shows or hides the measured frames and steps per second
+        ToggleRates,
+        /// Shows or hides the registers over the display while playing
+        ToggleHud,
+        /// Undoes the last manual edit
+        Undo,
+        /// Redoes the last undone manual edit
+        Redo,
+        /// Adds or removes a breakpoint at the current pc
+        ToggleBreakpoint,
+        /// Enables or disables the selected breakpoint
+        EnableBreakpoint,
+        /// Deletes the selected breakpoint
+        DeleteBreakpoint,
+        /// Switches the memory pane between the source and the instructions
+        ToggleSource,
+        /// Steps to the next line of the source
+        StepLine,
     }
 
     impl Command {
-        pub fn command_from_event(e: Event) -> Option<Command> {
+        /// The command with a name in the `[debugger.keys]` section of the
+        /// config. `tab_1` to `tab_6` show a tab, and `key_0` to `key_f`
+        /// toggle a keypad key.
+        pub fn from_name(name: &str) -> Option<Command> {
+            if let Some(n) = name.strip_prefix("tab_") {
+                return match n.parse::<usize>() {
+                    Ok(n @ 1..=6) => Some(Command::ShowTab(n - 1)),
+                    _ => None,
+                };
+            }
+            if let Some(k) = name.strip_prefix("key_") {
+                return match k.len() {
+                    1 => u8::from_str_radix(k, 16).ok().map(Command::ToggleKey),
+                    _ => None,
+                };
+            }
+            Some(match name {
+                "exit" => Command::Exit,
+                "redraw" => Command::Redraw,
+                "step_forward" => Command::StepForward,
+                "big_step_forward" => Command::BigStepForward,
+                "step_backward" => Command::StepBackward,
+                "big_step_backward" => Command::BigStepBackward,
+                "first_step" => Command::FirstStep,
+                "last_step" => Command::LastStep,
+                "set_mark" => Command::SetMark,
+                "goto_mark" => Command::GotoMark,
+                "goto" => Command::GotoPrefix,
+                "continue" => Command::Continue,
+                "play" => Command::TogglePlay,
+                "faster" => Command::PlayRate(1),
+                "slower" => Command::PlayRate(-1),
+                "rates" => Command::ToggleRates,
+                "hud" => Command::ToggleHud,
+                "toggle_breakpoint" => Command::ToggleBreakpoint,
+                "enable_breakpoint" => Command::EnableBreakpoint,
+                "delete_breakpoint" => Command::DeleteBreakpoint,
+                "toggle_source" => Command::ToggleSource,
+                "step_line" => Command::StepLine,
+                "move_up" => Command::MoveFocused(-1),
+                "move_down" => Command::MoveFocused(1),
+                "focus_memory" => Command::FocusMemory,
+                "focus_tabs" => Command::FocusTabs,
+                "scroll_memory_up" => Command::ScrollMemory(-32),
+                "scroll_memory_down" => Command::ScrollMemory(32),
+                "center_memory" => Command::CenterMemory,
+                "next_match" => Command::NextMatch,
+                "prev_match" => Command::PrevMatch,
+                "undo" => Command::Undo,
+                "redo" => Command::Redo,
+                "toggle_diff" => Command::ToggleDiff,
+                "next_renderer" => Command::NextRenderer,
+                "toggle_phosphor" => Command::TogglePhosphor,
+                "next_layout" => Command::NextPreset,
+                "narrower_memory" => Command::ResizeMemory(-1),
+                "wider_memory" => Command::ResizeMemory(1),
+                "prompt" => Command::OpenPrompt(""),
+                "find" => Command::OpenPrompt("find "),
+                "search" => Command::OpenPrompt("search "),
+                "help" => Command::ShowHelp,
+                "open" => Command::OpenPicker,
+                "reload" => Command::Reload,
+                "copy" => Command::CopyDisassembly,
+                "screenshot" => Command::Screenshot,
+                "record_gif" => Command::ToggleRecording,
+                "editor" => Command::OpenEditor,
+                "reference" => Command::ShowReference,
+                _ => return None,
+            })
+        }
+
+        pub fn command_from_event(e: Event, scheme: Scheme) -> Option<Command> {
             match e {
-                Event::Key(key) if key.kind == KeyEventKind::Press => {
-                    Self::command_from_key_pressed(key)
-                }
+                Event::Key(key) if key.kind == KeyEventKind::Press => match scheme {
+                    Scheme::Default => Self::command_from_key_pressed(key),
+                    Scheme::Vim => Self::command_from_key_vim(key),
+                },
                 Event::Resize { .. } => Some(Command::Redraw),
+                Event::Mouse(m) => Self::command_from_mouse(m),
 
                 _ => None,
             }
         }
 
+        pub fn command_from_mouse(m: MouseEvent) -> Option<Command> {
+            let pos = Position::new(m.column, m.row);
+            match m.kind {
+                MouseEventKind::ScrollUp => Some(Command::Scroll(pos, -1)),
+                MouseEventKind::ScrollDown => Some(Command::Scroll(pos, 1)),
+                MouseEventKind::Down(MouseButton::Left) => Some(Command::Click(pos)),
+                MouseEventKind::Drag(MouseButton::Left) => Some(Command::Drag(pos)),
+                MouseEventKind::Moved => Some(Command::Hover(pos)),
+                _ => None,
+            }
+        }
+
+        /// The vim scheme: counts before commands, `hjkl` to move between
+        /// and within panes, `/` to search and `n`/`N` for the matches. The
+        /// other keys are those of the default scheme.
+        pub fn command_from_key_vim(k: KeyEvent) -> Option<Command> {
+            match (k.modifiers, k.code) {
+                (KeyModifiers::NONE, KeyCode::Char(c @ '0'..='9')) => {
+                    c.to_digit(10).map(Command::Count)
+                }
+                (_, KeyCode::Char('h')) => Some(Command::FocusMemory),
+                (_, KeyCode::Char('l')) => Some(Command::FocusTabs),
+                (_, KeyCode::Char('j')) => Some(Command::MoveFocused(1)),
+                (_, KeyCode::Char('k')) => Some(Command::MoveFocused(-1)),
+                (_, KeyCode::Char('/')) => Some(Command::OpenPrompt("search ")),
+                (_, KeyCode::Char('n')) => Some(Command::NextMatch),
+                (_, KeyCode::Char('N')) => Some(Command::PrevMatch),
+                (KeyModifiers::CONTROL, KeyCode::Char('r')) => Some(Command::Redo),
+                _ => Self::command_from_key_pressed(k),
+            }
+        }
+
         pub fn command_from_key_pressed(k: KeyEvent) -> Option<Command> {
             match (k.modifiers, k.code) {
                 (_, KeyCode::Esc | KeyCode::Char('q'))
@@ -286,9 +3889,55 @@ pub mod command {
                 (_, KeyCode::Enter | KeyCode::Char('n') | KeyCode::Right | KeyCode::Char(' ')) => {
                     Some(Command::StepForward)
                 }
+                (KeyModifiers::ALT, KeyCode::Char(c)) if c.is_ascii_hexdigit() => {
+                    c.to_digit(16).map(|k| Command::ToggleKey(k as u8))
+                }
                 (_, KeyCode::Char('N')) => Some(Command::BigStepForward),
                 (_, KeyCode::Char('P')) => Some(Command::BigStepBackward),
                 (_, KeyCode::Char('d')) => Some(Command::ToggleDiff),
+                (_, KeyCode::Char('v')) => Some(Command::NextRenderer),
+                (_, KeyCode::Char('f')) => Some(Command::TogglePhosphor),
+                (_, KeyCode::Char(c @ '1'..='6')) => {
+                    Some(Command::ShowTab(c as usize - '1' as usize))
+                }
+                (_, KeyCode::Char('L')) => Some(Command::NextPreset),
+                (_, KeyCode::Char('?')) => Some(Command::ShowHelp),
+                (_, KeyCode::Char('o')) => Some(Command::OpenPicker),
+                (_, KeyCode::Char('R')) => Some(Command::Reload),
+                (_, KeyCode::Char('y')) => Some(Command::CopyDisassembly),
+                (_, KeyCode::Char('S')) => Some(Command::Screenshot),
+                (_, KeyCode::Char('w')) => Some(Command::ToggleRecording),
+                (_, KeyCode::Char('E')) => Some(Command::OpenEditor),
+                (_, KeyCode::Char('K')) => Some(Command::ShowReference),
+                (_, KeyCode::Char('<')) => Some(Command::ResizeMemory(-1)),
+                (_, KeyCode::Char('>')) => Some(Command::ResizeMemory(1)),
+                (_, KeyCode::PageUp) => Some(Command::ScrollMemory(-32)),
+                (_, KeyCode::PageDown) => Some(Command::ScrollMemory(32)),
+                (_, KeyCode::Char('c')) => Some(Command::CenterMemory),
+                (_, KeyCode::Char(':')) => Some(Command::OpenPrompt("")),
+                (_, KeyCode::Char('/')) => Some(Command::OpenPrompt("search ")),
+                (_, KeyCode::Char('g')) => Some(Command::GotoPrefix),
+                (_, KeyCode::Home) => Some(Command::FirstStep),
+                (_, KeyCode::End | KeyCode::Char('G')) => Some(Command::LastStep),
+                (_, KeyCode::Char(']')) => Some(Command::NextMatch),
+                (_, KeyCode::Char('m')) => Some(Command::SetMark),
+                (_, KeyCode::Char('r')) => Some(Command::Continue),
+                (_, KeyCode::Char('s')) => Some(Command::TogglePlay),
+                (_, KeyCode::Char('+')) => Some(Command::PlayRate(1)),
+                (_, KeyCode::Char('-')) => Some(Command::PlayRate(-1)),
+                (_, KeyCode::Char('i')) => Some(Command::ToggleRates),
+                (_, KeyCode::Char('H')) => Some(Command::ToggleHud),
+                (_, KeyCode::Char('u')) => Some(Command::Undo),
+                (_, KeyCode::Char('U')) => Some(Command::Redo),
+                (_, KeyCode::Char('b')) => Some(Command::ToggleBreakpoint),
+                (_, KeyCode::Char('z')) => Some(Command::ToggleSource),
+                (_, KeyCode::Char('J')) => Some(Command::StepLine),
+                (_, KeyCode::Up) => Some(Command::MoveFocused(-1)),
+                (_, KeyCode::Down) => Some(Command::MoveFocused(1)),
+                (_, KeyCode::Char('t')) => Some(Command::EnableBreakpoint),
+                (_, KeyCode::Char('x')) => Some(Command::DeleteBreakpoint),
+                (_, KeyCode::Char('\'')) => Some(Command::GotoMark),
+                (_, KeyCode::Char('[')) => Some(Command::PrevMatch),
                 (_, KeyCode::Backspace | KeyCode::Char('p') | KeyCode::Left) => {
                     Some(Command::StepBackward)
                 }
@@ -296,4 +3945,64 @@ pub mod command {
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn keymap(keys: &[(&str, &str)]) -> Result<Keymap, String> {
+            let section = keys
+                .iter()
+                .map(|&(name, keys)| (name.to_string(), keys.to_string()))
+                .collect();
+            let sections = BTreeMap::from([("debugger.keys".to_string(), section)]);
+            Keymap::from_config(&Config { sections })
+        }
+
+        fn press(c: char) -> Event {
+            Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+        }
+
+        #[test]
+        fn parses_keys() {
+            let none = KeyModifiers::NONE;
+            assert_eq!(parse_key("n"), Some((none, KeyCode::Char('n'))));
+            assert_eq!(parse_key("F"), Some((none, KeyCode::Char('F'))));
+            assert_eq!(parse_key("Space"), Some((none, KeyCode::Char(' '))));
+            assert_eq!(parse_key("PageUp"), Some((none, KeyCode::PageUp)));
+            assert_eq!(
+                parse_key("Ctrl+Alt+F5"),
+                Some((KeyModifiers::CONTROL | KeyModifiers::ALT, KeyCode::F(5)))
+            );
+            for invalid in ["", "nn", "Ctrl+", "Fx", "Shift+n"] {
+                assert_eq!(parse_key(invalid), None, "{invalid}");
+            }
+        }
+
+        #[test]
+        fn remapped_keys_replace_the_scheme() {
+            let k = keymap(&[("step_forward", "j Enter")]).unwrap();
+            assert_eq!(k.command_from_event(press('j')), Some(Command::StepForward));
+            assert_eq!(k.command_from_event(press('n')), None);
+            assert_eq!(
+                k.command_from_event(press('p')),
+                Some(Command::StepBackward)
+            );
+            assert_eq!(k.remapped["step_forward"], "j/Enter");
+        }
+
+        #[test]
+        fn rejects_invalid_bindings() {
+            let error = |keys| keymap(keys).err().unwrap();
+            assert_eq!(
+                error(&[("exit", "Ctrl+q"), ("step_forward", "j Ctrl+q")]),
+                "debugger.keys: Ctrl+q is bound to both exit and step_forward"
+            );
+            assert_eq!(error(&[("fly", "f")]), "debugger.keys: unknown command fly");
+            assert_eq!(
+                error(&[("exit", "Ctrl+")]),
+                "debugger.keys.exit: invalid key Ctrl+"
+            );
+        }
+    }
 }
@@ -0,0 +1,71 @@
+use super::architecture::*;
+use super::base::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::*;
+use std::path::{Path, PathBuf};
+
+/// Free-text comments attached to memory addresses. They are stored in a
+/// sidecar file next to the ROM, one note per line: `0x2F0 score table`.
+pub struct Notes {
+    pub path: PathBuf,
+    pub notes: BTreeMap<u16, String>,
+}
+
+impl Notes {
+    /// The sidecar file of `rom.ch8` is `rom.ch8.notes`
+    pub fn sidecar(rom: &Path) -> PathBuf {
+        let mut name = rom.as_os_str().to_owned();
+        name.push(".notes");
+        PathBuf::from(name)
+    }
+
+    /// Loads the notes of a ROM. A missing sidecar file means no notes.
+    pub fn load(rom: &Path) -> Result<Notes> {
+        let path = Self::sidecar(rom);
+        let mut notes = BTreeMap::new();
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                for (n, line) in contents.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let (addr, text) = line.split_once(' ').unwrap_or((line, ""));
+                    let addr = parse_number(addr)
+                        .filter(|&a| (a as usize) < Chip8::MEM_SIZE)
+                        .ok_or(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("{}:{}: invalid address {addr}", path.display(), n + 1),
+                        ))?;
+                    notes.insert(addr as u16, text.trim().to_string());
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        Ok(Notes { path, notes })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut contents = String::new();
+        for (addr, text) in &self.notes {
+            contents.push_str(&format!("{addr:#05X} {text}\n"));
+        }
+        fs::write(&self.path, contents)
+    }
+
+    /// Sets the note at an address and saves the notes. An empty text
+    /// removes the note.
+    pub fn set(&mut self, addr: u16, text: String) -> Result<()> {
+        if text.is_empty() {
+            self.notes.remove(&addr);
+        } else {
+            self.notes.insert(addr, text);
+        }
+        self.save()
+    }
+
+    pub fn get(&self, addr: u16) -> Option<&String> {
+        self.notes.get(&addr)
+    }
+}
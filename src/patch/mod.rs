@@ -0,0 +1,119 @@
+//! IPS patches, the format of most fixes and translations of ROMs: the
+//! header `PATCH`, records that each write bytes at an offset of the ROM,
+//! and the footer `EOF`. A record is a 3-byte offset and a 2-byte size,
+//! followed by that many bytes, or, when the size is 0, by a 2-byte count
+//! and a byte to repeat that many times. The footer can be followed by a
+//! 3-byte size to truncate the ROM to.
+
+use super::architecture::*;
+
+/// The most bytes of a ROM, those that fit in memory after the start of the code
+const MAX_LEN: usize = Chip8::MEM_SIZE - Chip8::CODE_START - 1;
+
+/// Reads a big-endian number of `n` bytes at `ix`
+fn number(ips: &[u8], ix: usize, n: usize) -> Result<usize, String> {
+    let bytes = ips
+        .get(ix..ix + n)
+        .ok_or_else(|| format!("the patch ends in the middle of a record, at byte {ix}"))?;
+    Ok(bytes.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+}
+
+/// Applies an IPS patch to a ROM, which grows with zeros where the patch
+/// writes past its end. Returns the number of records. The patch is refused
+/// if it is not IPS or if it writes where the ROM can't be loaded
+pub fn apply(rom: &mut Vec<u8>, ips: &[u8]) -> Result<usize, String> {
+    if !ips.starts_with(b"PATCH") {
+        return Err("not an IPS patch, it doesn't start with PATCH".into());
+    }
+    let mut ix = 5;
+    let mut records = 0;
+    loop {
+        if ips.get(ix..ix + 3) == Some(b"EOF") {
+            ix += 3;
+            break;
+        }
+        let offset = number(ips, ix, 3)?;
+        let size = number(ips, ix + 3, 2)?;
+        ix += 5;
+        let bytes = if size == 0 {
+            let count = number(ips, ix, 2)?;
+            let value = number(ips, ix + 2, 1)? as u8;
+            ix += 3;
+            vec![value; count]
+        } else {
+            let bytes = ips
+                .get(ix..ix + size)
+                .ok_or_else(|| format!("the patch ends in the middle of a record, at byte {ix}"))?;
+            ix += size;
+            bytes.to_vec()
+        };
+        let end = offset + bytes.len();
+        if end > MAX_LEN {
+            return Err(format!(
+                "the record at offset {offset:#X} writes up to {:#05X}, past the end of the memory",
+                Chip8::CODE_START + end - 1
+            ));
+        }
+        if rom.len() < end {
+            rom.resize(end, 0);
+        }
+        rom[offset..end].copy_from_slice(&bytes);
+        records += 1;
+    }
+    if ips.len() >= ix + 3 {
+        let len = number(ips, ix, 3)?;
+        rom.truncate(len);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A patch of `records`, each its 5 bytes of offset and size followed by
+    /// its data, and of the size to truncate to, if any
+    fn ips(records: &[&[u8]], truncate: Option<[u8; 3]>) -> Vec<u8> {
+        let mut ips = b"PATCH".to_vec();
+        records.iter().for_each(|r| ips.extend(*r));
+        ips.extend(b"EOF");
+        ips.extend(truncate.iter().flatten());
+        ips
+    }
+
+    #[test]
+    fn records() {
+        let mut rom = vec![1, 2, 3, 4];
+        let patch = ips(
+            &[&[0, 0, 1, 0, 2, 0xAA, 0xBB], &[0, 0, 5, 0, 1, 0xCC]],
+            None,
+        );
+        assert_eq!(apply(&mut rom, &patch), Ok(2));
+        assert_eq!(rom, [1, 0xAA, 0xBB, 4, 0, 0xCC]);
+    }
+
+    #[test]
+    fn rle() {
+        let mut rom = vec![1, 2];
+        let patch = ips(&[&[0, 0, 1, 0, 0, 0, 4, 0xEE]], None);
+        assert_eq!(apply(&mut rom, &patch), Ok(1));
+        assert_eq!(rom, [1, 0xEE, 0xEE, 0xEE, 0xEE]);
+    }
+
+    #[test]
+    fn truncate() {
+        let mut rom = vec![1, 2, 3, 4, 5];
+        let patch = ips(&[&[0, 0, 0, 0, 1, 9]], Some([0, 0, 3]));
+        assert_eq!(apply(&mut rom, &patch), Ok(1));
+        assert_eq!(rom, [9, 2, 3]);
+    }
+
+    #[test]
+    fn refused() {
+        let mut rom = vec![1, 2];
+        assert!(apply(&mut rom, b"PAT").is_err());
+        assert!(apply(&mut rom, b"PATCH\0\0\0\0\x04\x01").is_err());
+        let past = [0, 0x0D, 0xFF, 0, 1, 1];
+        assert!(apply(&mut rom, &ips(&[&past], None)).is_err());
+    }
+}
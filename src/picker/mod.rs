@@ -0,0 +1,196 @@
+use super::architecture::*;
+use super::recent::RecentRom;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::*;
+use ratatui::style::Stylize;
+use ratatui::text::*;
+use ratatui::widgets::*;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// The extensions of the files listed by the picker
+const EXTENSIONS: [&str; 2] = ["ch8", "c8"];
+
+/// A browser of the ROMs in a directory and its subdirectories, below the
+/// ROMs opened last
+pub struct Picker {
+    pub dir: PathBuf,
+    recent: Vec<RecentRom>,
+    /// The parent directory, the subdirectories and the ROMs, in this order
+    entries: Vec<PathBuf>,
+    /// The index of the selected row, in the recent ROMs and then the entries
+    selected: usize,
+    /// An error reading the directory
+    error: Option<String>,
+}
+
+/// What a key did to the picker
+pub enum Choice {
+    /// The picker is still open
+    Browsing,
+    /// The picker was closed without a ROM
+    Cancel,
+    /// A ROM was chosen
+    Open(PathBuf),
+}
+
+impl Picker {
+    pub fn new(dir: PathBuf, recent: Vec<RecentRom>) -> Picker {
+        let mut picker = Picker {
+            // A relative path would have no parent to go up to
+            dir: dir.canonicalize().unwrap_or(dir),
+            recent,
+            entries: vec![],
+            selected: 0,
+            error: None,
+        };
+        picker.list();
+        picker.selected = 0;
+        picker
+    }
+
+    /// Reads the entries of the current directory
+    fn list(&mut self) {
+        self.selected = self.recent.len();
+        self.entries.clear();
+        if let Some(parent) = self.dir.parent() {
+            self.entries.push(parent.to_path_buf());
+        }
+        let read = match fs::read_dir(&self.dir) {
+            Ok(r) => r,
+            Err(e) => {
+                self.error = Some(format!("could not read {}: {e}", self.dir.display()));
+                return;
+            }
+        };
+        self.error = None;
+        let (mut dirs, mut roms): (Vec<PathBuf>, Vec<PathBuf>) = read
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| !name(p).starts_with('.'))
+            .filter(|p| p.is_dir() || is_rom(p))
+            .partition(|p| p.is_dir());
+        dirs.sort();
+        roms.sort();
+        self.entries.extend(dirs);
+        self.entries.extend(roms);
+    }
+
+    /// The path of the selected row
+    fn chosen(&self) -> Option<&PathBuf> {
+        match self.selected.checked_sub(self.recent.len()) {
+            None => self.recent.get(self.selected).map(|r| &r.file),
+            Some(ix) => self.entries.get(ix),
+        }
+    }
+
+    pub fn key(&mut self, k: KeyEvent) -> Choice {
+        let last = (self.recent.len() + self.entries.len()).saturating_sub(1);
+        match (k.modifiers, k.code) {
+            (_, KeyCode::Esc | KeyCode::Char('q'))
+            | (KeyModifiers::CONTROL, KeyCode::Char('c')) => return Choice::Cancel,
+            (_, KeyCode::Up | KeyCode::Char('k')) => {
+                self.selected = self.selected.saturating_sub(1)
+            }
+            (_, KeyCode::Down | KeyCode::Char('j')) => {
+                self.selected = (self.selected + 1).min(last)
+            }
+            (_, KeyCode::Home) => self.selected = 0,
+            (_, KeyCode::End) => self.selected = last,
+            (_, KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h')) => {
+                if let Some(parent) = self.dir.parent() {
+                    self.dir = parent.to_path_buf();
+                    self.list();
+                }
+            }
+            (_, KeyCode::Enter | KeyCode::Right | KeyCode::Char('l')) => match self.chosen() {
+                Some(p) if p.is_dir() => {
+                    self.dir = p.clone();
+                    self.list();
+                }
+                Some(p) => return Choice::Open(p.clone()),
+                None => (),
+            },
+            _ => (),
+        }
+        Choice::Browsing
+    }
+
+    /// The size and the detected platform of the selected ROM
+    fn preview(&self) -> Option<String> {
+        let path = self.chosen().filter(|p| is_rom(p))?;
+        Some(match fs::read(path) {
+            Ok(rom) => format!("{} bytes, {}", rom.len(), Platform::detect(&rom)),
+            Err(e) => format!("could not read: {e}"),
+        })
+    }
+
+    /// Shows the picker until a ROM is chosen. Returns `None` if it is closed
+    /// without one.
+    pub fn run(mut self, mut terminal: ratatui::DefaultTerminal) -> Result<Option<PathBuf>> {
+        loop {
+            terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
+            let Event::Key(k) = crossterm::event::read()? else {
+                continue;
+            };
+            if k.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.key(k) {
+                Choice::Browsing => (),
+                Choice::Cancel => return Ok(None),
+                Choice::Open(p) => return Ok(Some(p)),
+            }
+        }
+    }
+}
+
+impl Widget for &Picker {
+    fn render(self, area: Rect, buf: &mut ratatui::buffer::Buffer) {
+        let recent = self.recent.iter().map(|r| {
+            Line::from(vec![
+                Span::from(r.file.display().to_string()),
+                Span::from(format!("  {}, {} keys", r.quirks, r.scheme.name())).dim(),
+            ])
+        });
+        let entries = self.entries.iter().enumerate().map(|(ix, p)| {
+            if ix == 0 && self.dir.parent() == Some(p.as_path()) {
+                Line::from("../").bold()
+            } else if p.is_dir() {
+                Line::from(format!("{}/", name(p))).bold()
+            } else {
+                Line::from(name(p))
+            }
+        });
+        let items: Vec<Line> = recent
+            .chain(entries)
+            .enumerate()
+            .map(|(ix, l)| if ix == self.selected { l.reversed() } else { l })
+            .collect();
+        let title = Line::from(format!("Open a ROM: {}", self.dir.display()))
+            .bold()
+            .blue()
+            .centered();
+        let bottom = match (&self.error, self.preview()) {
+            (Some(e), _) => Line::from(e.clone()).red(),
+            (None, Some(p)) => Line::from(p),
+            (None, None) => Line::from("enter open  backspace up  q cancel").dim(),
+        };
+        let block = Block::bordered()
+            .title(title)
+            .title_bottom(bottom.centered());
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        StatefulWidget::render(List::new(items).block(block), area, buf, &mut state);
+    }
+}
+
+fn name(p: &Path) -> String {
+    p.file_name()
+        .map_or(String::new(), |n| n.to_string_lossy().into_owned())
+}
+
+pub fn is_rom(p: &Path) -> bool {
+    p.is_file()
+        && p.extension()
+            .is_some_and(|e| EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
@@ -0,0 +1,48 @@
+use super::architecture::*;
+use std::ops::Range;
+
+/// How many times the instruction at each address was executed in a run
+pub fn exec_counts(history: &[Chip8]) -> Vec<u32> {
+    let mut counts = vec![0; Chip8::MEM_SIZE];
+    // The last state has not executed its instruction yet
+    for c in history.iter().take(history.len().saturating_sub(1)) {
+        counts[c.pc as usize % Chip8::MEM_SIZE] += 1;
+    }
+    counts
+}
+
+/// The `n` most executed addresses with their counts, most executed first
+pub fn hottest(counts: &[u32], n: usize) -> Vec<(u16, u32)> {
+    let mut hot: Vec<(u16, u32)> = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| **n > 0)
+        .map(|(a, &n)| (a as u16, n))
+        .collect();
+    hot.sort_by_key(|&(a, n)| (std::cmp::Reverse(n), a));
+    hot.truncate(n);
+    hot
+}
+
+/// The end of the program: the address after its last non-zero byte
+pub fn rom_end(c: &Chip8) -> usize {
+    c.memory
+        .iter()
+        .rposition(|&b| b != 0)
+        .map_or(Chip8::CODE_START, |a| (a + 1).max(Chip8::CODE_START))
+}
+
+/// The ranges of bytes of the program that were executed, as instructions of
+/// two bytes
+pub fn covered(counts: &[u32], end: usize) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    let executed = counts.iter().enumerate().take(end).skip(Chip8::CODE_START);
+    for (a, _) in executed.filter(|(_, n)| **n > 0) {
+        let instr = a..(a + 2).min(Chip8::MEM_SIZE);
+        match ranges.last_mut() {
+            Some(r) if r.end >= instr.start => r.end = r.end.max(instr.end),
+            _ => ranges.push(instr),
+        }
+    }
+    ranges
+}
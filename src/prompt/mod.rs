@@ -0,0 +1,428 @@
+use super::architecture::*;
+use super::base::*;
+use super::debugger::*;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// A command typed in the debugger prompt, e.g. `:find 0xAA 0xBB`
+#[derive(PartialEq, Eq, Debug)]
+pub enum PromptCommand {
+    /// Searches the memory for a byte pattern
+    Find(Vec<u8>),
+    /// Searches the disassembled instructions for text, e.g. `LD I,`
+    Search(String),
+    /// Centers the memory pane at an address
+    Mem(u16),
+    /// Attaches a note to an address. An empty note removes it
+    Note(u16, String),
+    /// Names an address in the symbol file. No name removes it
+    Label(u16, Option<String>),
+    /// Adds a breakpoint or a watchpoint
+    Break(BreakKind, Option<Condition>),
+    /// Adds a breakpoint on a line of the source, e.g. `break game.s8:12`
+    BreakLine(PathBuf, usize, Option<Condition>),
+    /// Writes a memory range of the current state to a file
+    Dump(Range<usize>, PathBuf),
+    /// Copies a file into memory at an address
+    Load(PathBuf, u16),
+    /// Writes bytes into memory at an address
+    Poke(u16, Vec<u8>),
+    /// Sets a register
+    Set(Operand, u16),
+    /// Writes the recorded run to a replay file
+    Record(PathBuf),
+    /// Writes the recorded run as a Chrome trace
+    TraceExport(PathBuf),
+    /// Writes the current state to a file
+    SaveState(PathBuf),
+    /// Replaces the current state with one saved from the same ROM
+    LoadState(PathBuf),
+    /// Tints the pixels that changed in the last steps. 0 turns it off
+    Recent(usize),
+    /// Lists the ROMs opened last
+    RecentRoms,
+    /// Replaces the running ROM with another one
+    Open(PathBuf),
+    /// Copies something to the system clipboard
+    Copy(Clip),
+    /// Loads the code of the running ROM again, keeping the rest of the state
+    Reload,
+    /// Shows or hides a pane: `memory`, `registers` or `stack`
+    Pane(String),
+    /// Shows the HUD while playing, with these registers after PC, I, DT and ST
+    Hud(Vec<Operand>),
+}
+
+/// What `:copy` puts in the clipboard
+#[derive(PartialEq, Eq, Debug)]
+pub enum Clip {
+    /// The instructions shown in the memory pane
+    Disassembly,
+    /// A hex dump of a memory range
+    Memory(Range<usize>),
+    /// The display as text
+    Screen,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Token {
+    /// A whitespace separated word
+    Word(String),
+    /// A double quoted string
+    Quoted(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".into()),
+                }
+            }
+            tokens.push(Token::Quoted(s));
+        } else {
+            let mut s = String::new();
+            while let Some(&c) = chars.peek()
+                && !c.is_whitespace()
+            {
+                s.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(s));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Numbers are bytes and strings are their ASCII bytes
+fn parse_pattern(args: &[Token]) -> Result<Vec<u8>, String> {
+    let mut pattern: Vec<u8> = vec![];
+    for a in args {
+        match a {
+            Token::Word(w) => match parse_number(w) {
+                Some(n) if n <= u8::MAX as u32 => pattern.push(n as u8),
+                _ => return Err(format!("not a byte: {w}")),
+            },
+            Token::Quoted(q) => pattern.extend(q.bytes()),
+        }
+    }
+    if pattern.is_empty() {
+        return Err("empty pattern".into());
+    }
+    Ok(pattern)
+}
+
+fn parse_address(arg: &Token) -> Result<u16, String> {
+    match arg {
+        Token::Word(w) => match parse_number(w) {
+            Some(a) if (a as usize) < Chip8::MEM_SIZE => Ok(a as u16),
+            _ => Err(format!("not a valid address: {w}")),
+        },
+        Token::Quoted(q) => Err(format!("not a valid address: \"{q}\"")),
+    }
+}
+
+/// Parses `start..end`, where `end` is exclusive
+fn parse_range(arg: &Token) -> Result<Range<usize>, String> {
+    let invalid = || format!("not a valid range: {arg:?}");
+    let Token::Word(w) = arg else {
+        return Err(invalid());
+    };
+    let (start, end) = w.split_once("..").ok_or_else(invalid)?;
+    match (parse_number(start), parse_number(end)) {
+        (Some(s), Some(e)) if s <= e && (e as usize) <= Chip8::MEM_SIZE => {
+            Ok(s as usize..e as usize)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_path(arg: &Token) -> PathBuf {
+    match arg {
+        Token::Word(w) => PathBuf::from(w),
+        Token::Quoted(q) => PathBuf::from(q),
+    }
+}
+
+/// Parses `[start..end] <file>`. The whole memory is dumped if there is no range
+fn parse_dump(args: &[Token]) -> Result<PromptCommand, String> {
+    match args {
+        [file] => Ok(PromptCommand::Dump(0..Chip8::MEM_SIZE, parse_path(file))),
+        [range, file] => Ok(PromptCommand::Dump(parse_range(range)?, parse_path(file))),
+        _ => Err("expected: dump [start..end] <file>".into()),
+    }
+}
+
+/// The text of the tokens, without the quotes
+fn words(args: &[Token]) -> Vec<&str> {
+    args.iter()
+        .map(|t| match t {
+            Token::Word(w) => w.as_str(),
+            Token::Quoted(q) => q.as_str(),
+        })
+        .collect()
+}
+
+fn parse_note(args: &[Token]) -> Result<(u16, String), String> {
+    let Some((addr, text)) = args.split_first() else {
+        return Err("expected an address".into());
+    };
+    Ok((parse_address(addr)?, words(text).join(" ")))
+}
+
+fn parse_operand(w: &str) -> Result<Operand, String> {
+    match w.strip_prefix(['V', 'v']) {
+        _ if w.eq_ignore_ascii_case("i") => Ok(Operand::I),
+        _ if w.eq_ignore_ascii_case("pc") => Ok(Operand::Pc),
+        _ if w.eq_ignore_ascii_case("dt") => Ok(Operand::Delay),
+        _ if w.eq_ignore_ascii_case("st") => Ok(Operand::Sound),
+        Some(n) if n.len() == 1 => match u8::from_str_radix(n, 16) {
+            Ok(n) => Ok(Operand::V(Register::from(n))),
+            Err(_) => Err(format!("not a register: {w}")),
+        },
+        _ => Err(format!("not a register: {w}")),
+    }
+}
+
+/// Parses `[if] <register> <op> <number>`
+fn parse_condition(args: &[Token]) -> Result<Option<Condition>, String> {
+    let args = match args.split_first() {
+        Some((Token::Word(w), rest)) if w == "if" => rest,
+        _ => args,
+    };
+    match args {
+        [] => Ok(None),
+        [Token::Word(lhs), Token::Word(op), Token::Word(rhs)] => {
+            let op = match op.as_str() {
+                "==" => CmpOp::Eq,
+                "!=" => CmpOp::NEq,
+                "<" => CmpOp::Lt,
+                "<=" => CmpOp::Le,
+                ">" => CmpOp::Gt,
+                ">=" => CmpOp::Ge,
+                _ => return Err(format!("unknown comparison: {op}")),
+            };
+            let rhs = match parse_number(rhs) {
+                Some(n) if n <= u16::MAX as u32 => n as u16,
+                _ => return Err(format!("not a number: {rhs}")),
+            };
+            Ok(Some(Condition {
+                lhs: parse_operand(lhs)?,
+                op,
+                rhs,
+            }))
+        }
+        _ => Err("expected a condition like: if V3 == 5".into()),
+    }
+}
+
+fn parse_break(args: &[Token], kind: fn(u16) -> BreakKind) -> Result<PromptCommand, String> {
+    let Some((addr, cond)) = args.split_first() else {
+        return Err("expected an address".into());
+    };
+    Ok(PromptCommand::Break(
+        kind(parse_address(addr)?),
+        parse_condition(cond)?,
+    ))
+}
+
+/// A breakpoint on a line of the source, `break game.s8:12`
+fn parse_break_line(args: &[Token]) -> Option<Result<PromptCommand, String>> {
+    let (Token::Word(w), cond) = args.split_first()? else {
+        return None;
+    };
+    let (file, line) = w.rsplit_once(':')?;
+    let line = line.parse().ok().filter(|_| !file.is_empty())?;
+    Some(parse_condition(cond).map(|c| PromptCommand::BreakLine(PathBuf::from(file), line, c)))
+}
+
+impl PromptCommand {
+    pub fn parse(input: &str) -> Result<PromptCommand, String> {
+        let tokens = tokenize(input)?;
+        match tokens.split_first() {
+            None => Err("empty command".into()),
+            Some((Token::Word(cmd), args)) => match cmd.as_str() {
+                "find" => parse_pattern(args).map(PromptCommand::Find),
+                "search" => match words(args).join(" ") {
+                    t if t.is_empty() => Err("expected: search <text>".into()),
+                    t => Ok(PromptCommand::Search(t)),
+                },
+                "mem" => match args {
+                    [a] => parse_address(a).map(PromptCommand::Mem),
+                    _ => Err("expected one address".into()),
+                },
+                "note" => parse_note(args).map(|(a, t)| PromptCommand::Note(a, t)),
+                "label" => match args {
+                    [addr] => Ok(PromptCommand::Label(parse_address(addr)?, None)),
+                    [addr, Token::Word(name)] => Ok(PromptCommand::Label(
+                        parse_address(addr)?,
+                        Some(name.clone()),
+                    )),
+                    _ => Err("expected: label <address> [name]".into()),
+                },
+                "break" => {
+                    parse_break_line(args).unwrap_or_else(|| parse_break(args, BreakKind::Pc))
+                }
+                "watch" => parse_break(args, BreakKind::Watch),
+                "dump" => parse_dump(args),
+                "poke" => match args.split_first() {
+                    Some((addr, bytes)) => Ok(PromptCommand::Poke(
+                        parse_address(addr)?,
+                        parse_pattern(bytes)?,
+                    )),
+                    None => Err("expected: poke <address> <bytes>".into()),
+                },
+                "set" => match args {
+                    [Token::Word(r), Token::Word(n)] => match parse_number(n) {
+                        Some(n) if n <= u16::MAX as u32 => {
+                            Ok(PromptCommand::Set(parse_operand(r)?, n as u16))
+                        }
+                        _ => Err(format!("not a number: {n}")),
+                    },
+                    _ => Err("expected: set <register> <value>".into()),
+                },
+                "load" => match args {
+                    [file, addr] => Ok(PromptCommand::Load(parse_path(file), parse_address(addr)?)),
+                    _ => Err("expected: load <file> <address>".into()),
+                },
+                "copy" => match args {
+                    [Token::Word(w)] if w == "disasm" => Ok(PromptCommand::Copy(Clip::Disassembly)),
+                    [Token::Word(w)] if w == "screen" => Ok(PromptCommand::Copy(Clip::Screen)),
+                    [range] => Ok(PromptCommand::Copy(Clip::Memory(parse_range(range)?))),
+                    _ => Err("expected: copy disasm|screen|<start..end>".into()),
+                },
+                "reload" => match args {
+                    [] => Ok(PromptCommand::Reload),
+                    _ => Err("expected: reload".into()),
+                },
+                "open" => match args {
+                    [file] => Ok(PromptCommand::Open(parse_path(file))),
+                    _ => Err("expected: open <file>".into()),
+                },
+                "record" => match args {
+                    [file] => Ok(PromptCommand::Record(parse_path(file))),
+                    _ => Err("expected: record <file>".into()),
+                },
+                "pane" => match args {
+                    [Token::Word(name)]
+                        if ["memory", "registers", "stack"].contains(&name.as_str()) =>
+                    {
+                        Ok(PromptCommand::Pane(name.clone()))
+                    }
+                    _ => Err("expected: pane memory|registers|stack".into()),
+                },
+                "hud" => args
+                    .iter()
+                    .map(|a| match a {
+                        Token::Word(w) => parse_operand(w),
+                        Token::Quoted(q) => Err(format!("not a register: \"{q}\"")),
+                    })
+                    .collect::<Result<Vec<Operand>, String>>()
+                    .map(PromptCommand::Hud),
+                "recent" => match args {
+                    [Token::Word(n)] => match parse_number(n) {
+                        Some(n) => Ok(PromptCommand::Recent(n as usize)),
+                        None => Err(format!("not a number: {n}")),
+                    },
+                    _ => Err("expected: recent <steps>".into()),
+                },
+                "roms" => match args {
+                    [] => Ok(PromptCommand::RecentRoms),
+                    _ => Err("expected: roms".into()),
+                },
+                "trace" => match args {
+                    [Token::Word(sub), file] if sub == "export" => {
+                        Ok(PromptCommand::TraceExport(parse_path(file)))
+                    }
+                    _ => Err("expected: trace export <file>".into()),
+                },
+                "state" => match args {
+                    [Token::Word(sub), file] if sub == "save" => {
+                        Ok(PromptCommand::SaveState(parse_path(file)))
+                    }
+                    [Token::Word(sub), file] if sub == "load" => {
+                        Ok(PromptCommand::LoadState(parse_path(file)))
+                    }
+                    _ => Err("expected: state save|load <file>".into()),
+                },
+                _ => Err(format!("unknown command: {cmd}")),
+            },
+            Some((Token::Quoted(_), _)) => Err("expected a command name".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes() {
+        let word = |w: &str| Token::Word(w.into());
+        assert_eq!(
+            tokenize(r#" find  0xAA "a b"x "#),
+            Ok(vec![
+                word("find"),
+                word("0xAA"),
+                Token::Quoted("a b".into()),
+                word("x")
+            ])
+        );
+        assert_eq!(tokenize(r#"find "ab"#), Err("unterminated string".into()));
+    }
+
+    #[test]
+    fn parses_find() {
+        assert_eq!(
+            PromptCommand::parse(r#"find 0xAA 12 0b1 "Hi""#),
+            Ok(PromptCommand::Find(vec![0xAA, 12, 1, b'H', b'i']))
+        );
+        assert_eq!(
+            PromptCommand::parse("find 0x100"),
+            Err("not a byte: 0x100".into())
+        );
+        assert_eq!(PromptCommand::parse("find"), Err("empty pattern".into()));
+    }
+
+    #[test]
+    fn parses_search_and_mem() {
+        assert_eq!(
+            PromptCommand::parse(r#"search LD  "I,""#),
+            Ok(PromptCommand::Search("LD I,".into()))
+        );
+        assert_eq!(
+            PromptCommand::parse("mem 0xFFF"),
+            Ok(PromptCommand::Mem(0xFFF))
+        );
+        assert_eq!(
+            PromptCommand::parse("mem 0x1000"),
+            Err("not a valid address: 0x1000".into())
+        );
+        assert_eq!(
+            PromptCommand::parse("mem"),
+            Err("expected one address".into())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        assert_eq!(PromptCommand::parse("  "), Err("empty command".into()));
+        assert_eq!(
+            PromptCommand::parse("fnid 1"),
+            Err("unknown command: fnid".into())
+        );
+        assert_eq!(
+            PromptCommand::parse(r#""find" 1"#),
+            Err("expected a command name".into())
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use super::architecture::*;
+use super::command::Scheme;
+use clap::ValueEnum;
+use std::env;
+use std::fs;
+use std::io::*;
+use std::path::{Path, PathBuf};
+
+/// The ROMs opened last, newest first, with the settings they were run with.
+/// They are kept in `chip-8/recent` in the XDG cache directory, one per line:
+/// `cowgod vim /path/to/rom.ch8`.
+pub struct Recent {
+    pub roms: Vec<RecentRom>,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct RecentRom {
+    pub file: PathBuf,
+    pub quirks: Platform,
+    pub scheme: Scheme,
+}
+
+impl Recent {
+    /// The number of ROMs remembered
+    pub const MAX: usize = 10;
+
+    /// `$XDG_CACHE_HOME/chip-8/recent`, or `~/.cache/chip-8/recent`
+    pub fn path() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CACHE_HOME") {
+            Some(d) if !d.is_empty() => PathBuf::from(d),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+        };
+        Some(dir.join("chip-8").join("recent"))
+    }
+
+    /// Loads the list. A missing file means no recent ROMs.
+    pub fn load() -> Result<Recent> {
+        let roms = vec![];
+        let Some(path) = Self::path() else {
+            return Ok(Recent { roms });
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Recent { roms }),
+            Err(e) => return Err(e),
+        };
+        Self::parse(&contents, &path)
+    }
+
+    /// Reads the lines of the file at `path`
+    fn parse(contents: &str, path: &Path) -> Result<Recent> {
+        let mut roms = vec![];
+        for (n, line) in contents.lines().enumerate() {
+            let invalid = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{}:{}: expected <quirks> <scheme> <file>",
+                        path.display(),
+                        n + 1
+                    ),
+                )
+            };
+            let mut fields = line.splitn(3, ' ');
+            let (Some(quirks), Some(scheme), Some(file)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+            roms.push(RecentRom {
+                file: PathBuf::from(file),
+                quirks: Platform::from_str(quirks, true).map_err(|_| invalid())?,
+                scheme: Scheme::named(scheme).ok_or_else(invalid)?,
+            });
+        }
+        Ok(Recent { roms })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.contents())
+    }
+
+    /// The lines of the file
+    fn contents(&self) -> String {
+        let mut contents = String::new();
+        for r in &self.roms {
+            contents.push_str(&format!(
+                "{} {} {}\n",
+                r.quirks,
+                r.scheme.name(),
+                r.file.display()
+            ));
+        }
+        contents
+    }
+
+    /// Puts a ROM at the top of the list and saves it
+    pub fn add(&mut self, rom: RecentRom) -> Result<()> {
+        self.roms.retain(|r| r.file != rom.file);
+        self.roms.insert(0, rom);
+        self.roms.truncate(Self::MAX);
+        self.save()
+    }
+
+    pub fn get(&self, file: &Path) -> Option<&RecentRom> {
+        self.roms.iter().find(|r| r.file == file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<Recent> {
+        Recent::parse(contents, Path::new("recent"))
+    }
+
+    #[test]
+    fn parses_and_writes() {
+        let contents = "cowgod vim /roms/space invaders.ch8\nschip default pong.ch8\n";
+        let recent = parse(contents).unwrap();
+        assert_eq!(
+            recent.roms[0],
+            RecentRom {
+                file: PathBuf::from("/roms/space invaders.ch8"),
+                quirks: Platform::Cowgod,
+                scheme: Scheme::Vim,
+            }
+        );
+        assert_eq!(recent.roms[1].quirks, Platform::Schip);
+        assert_eq!(recent.contents(), contents);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for line in ["cowgod vim", "cowgod emacs pong.ch8", "nes vim pong.ch8"] {
+            let e = parse(&format!("chip8 default a.ch8\n{line}\n"))
+                .err()
+                .unwrap();
+            assert_eq!(
+                e.to_string(),
+                "recent:2: expected <quirks> <scheme> <file>",
+                "{line}"
+            );
+        }
+    }
+}
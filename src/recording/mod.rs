@@ -0,0 +1,161 @@
+use super::architecture::*;
+use super::render::rgb;
+use ratatui::style::Color;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Result;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// The default size in pixels of the square each CHIP-8 pixel becomes
+pub const SCALE: usize = 4;
+
+/// The screens shown while recording, to be saved as an animated GIF
+pub struct Recording {
+    pub file: PathBuf,
+    /// The size in pixels of the square each CHIP-8 pixel becomes
+    scale: usize,
+    start: Instant,
+    /// The screens that differ from the one before, with the time they
+    /// were first shown since the start
+    frames: Vec<(Screen, Duration)>,
+}
+
+impl Recording {
+    pub fn new(file: PathBuf, scale: usize) -> Recording {
+        Recording {
+            file,
+            scale: scale.max(1),
+            start: Instant::now(),
+            frames: vec![],
+        }
+    }
+
+    /// Records the screen drawn now. It is called for every frame; a screen
+    /// that did not change makes the last frame last longer.
+    pub fn frame(&mut self, screen: &Screen) {
+        if self.frames.last().is_none_or(|(s, _)| s != screen) {
+            self.frames.push((screen.clone(), self.start.elapsed()));
+        }
+    }
+
+    /// Writes the GIF. Returns the number of frames in it.
+    pub fn save(&self, on: Color, off: Option<Color>) -> Result<usize> {
+        let (w, h) = (Screen::NCOLS * self.scale, Screen::NROWS * self.scale);
+        let mut out = b"GIF89a".to_vec();
+        out.extend((w as u16).to_le_bytes());
+        out.extend((h as u16).to_le_bytes());
+        // A global color table of 4 colors, the least with a code size of 2
+        out.extend([0b1000_0001, 0, 0]);
+        for c in [off.unwrap_or(Color::Black), on, Color::Black, Color::Black] {
+            let (r, g, b) = rgb(c);
+            out.extend([r, g, b]);
+        }
+        // Loop forever
+        out.extend(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+        let end = self.start.elapsed();
+        // Delays are in hundredths of a second. They are rounded from the
+        // start, so that the errors do not add up
+        let mut at = 0;
+        let mut count = 0;
+        for (ix, (screen, _)) in self.frames.iter().enumerate() {
+            let next = self.frames.get(ix + 1).map_or(end, |(_, t)| *t);
+            let delay = next.as_millis() / 10 - at;
+            // Viewers slow down delays under 2, so shorter frames are
+            // skipped and the next one is shown earlier
+            if delay < 2 && ix + 1 < self.frames.len() {
+                continue;
+            }
+            at += delay;
+            count += 1;
+            out.extend([0x21, 0xF9, 4, 0]);
+            out.extend((delay.min(u16::MAX as u128) as u16).to_le_bytes());
+            out.extend([0, 0]);
+            out.push(0x2C);
+            out.extend([0, 0, 0, 0]);
+            out.extend((w as u16).to_le_bytes());
+            out.extend((h as u16).to_le_bytes());
+            out.push(0);
+            let pixels: Vec<u8> = (0..w * h)
+                .map(|p| screen.rows[p / w / self.scale][p % w / self.scale] as u8)
+                .collect();
+            out.push(2);
+            for block in lzw(&pixels, 2).chunks(255) {
+                out.push(block.len() as u8);
+                out.extend(block);
+            }
+            out.push(0);
+        }
+        out.push(0x3B);
+        fs::write(&self.file, out)?;
+        Ok(count)
+    }
+}
+
+/// Compresses color indices with the variable length LZW of GIF
+fn lzw(pixels: &[u8], min_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_size;
+    let first = clear + 2;
+    let mut out = Bits::default();
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let (mut next, mut size) = (first, min_size + 1);
+    out.write(clear, size);
+    let mut pixels = pixels.iter();
+    if let Some(&p) = pixels.next() {
+        let mut current = p as u16;
+        for &p in pixels {
+            if let Some(&code) = codes.get(&(current, p)) {
+                current = code;
+                continue;
+            }
+            out.write(current, size);
+            // The decoder adds a code after each one it reads, and widens
+            // the codes when the next one would not fit
+            if next >= 1 << size && size < 12 {
+                size += 1;
+            }
+            if next < 4096 {
+                codes.insert((current, p), next);
+                next += 1;
+            } else {
+                out.write(clear, size);
+                codes.clear();
+                (next, size) = (first, min_size + 1);
+            }
+            current = p as u16;
+        }
+        out.write(current, size);
+        if next >= 1 << size && size < 12 {
+            size += 1;
+        }
+    }
+    out.write(clear + 1, size);
+    out.finish()
+}
+
+/// Codes packed from the least significant bit
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    acc: u32,
+    len: u32,
+}
+
+impl Bits {
+    fn write(&mut self, code: u16, size: u32) {
+        self.acc |= (code as u32) << self.len;
+        self.len += size;
+        while self.len >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
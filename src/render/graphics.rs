@@ -0,0 +1,242 @@
+//! Bitmap output with the terminal graphics protocols, and as PNG files.
+//! Images are written directly to the terminal, so they are drawn over the
+//! cells that ratatui leaves blank.
+
+use ratatui::style::Color;
+
+/// A bitmap where each pixel is an index in `palette`
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<usize>,
+    pub palette: Vec<(u8, u8, u8)>,
+}
+
+impl Bitmap {
+    /// Builds the bitmap of a `width` x `height` picture, scaling each pixel
+    /// to a `scale` x `scale` square. Pixels that are `None` are black.
+    pub fn new(
+        width: usize,
+        height: usize,
+        scale: usize,
+        color: impl Fn(usize, usize) -> Option<Color>,
+    ) -> Bitmap {
+        let mut palette = vec![(0, 0, 0)];
+        let mut small = vec![0; width * height];
+        for r in 0..height {
+            for c in 0..width {
+                if let Some(col) = color(r, c) {
+                    let rgb = rgb(col);
+                    small[r * width + c] = match palette.iter().position(|&p| p == rgb) {
+                        Some(ix) => ix,
+                        None => {
+                            palette.push(rgb);
+                            palette.len() - 1
+                        }
+                    };
+                }
+            }
+        }
+        let (w, h) = (width * scale, height * scale);
+        let pixels = (0..w * h)
+            .map(|ix| small[(ix / w / scale) * width + (ix % w) / scale])
+            .collect();
+        Bitmap {
+            width: w,
+            height: h,
+            pixels,
+            palette,
+        }
+    }
+
+    /// Encodes the bitmap as a sixel image
+    pub fn sixel(&self) -> String {
+        let mut out = format!("\x1bP0;0;0q\"1;1;{};{}", self.width, self.height);
+        for (ix, (r, g, b)) in self.palette.iter().enumerate() {
+            let pct = |v: &u8| *v as u32 * 100 / 255;
+            out.push_str(&format!("#{ix};2;{};{};{}", pct(r), pct(g), pct(b)));
+        }
+        for band in (0..self.height).step_by(6) {
+            for ix in 0..self.palette.len() {
+                let sixels: Vec<u8> = (0..self.width)
+                    .map(|x| {
+                        let mut bits = 0;
+                        for dy in 0..6.min(self.height - band) {
+                            if self.pixels[(band + dy) * self.width + x] == ix {
+                                bits |= 1 << dy;
+                            }
+                        }
+                        63 + bits
+                    })
+                    .collect();
+                if sixels.iter().all(|&s| s == 63) {
+                    continue;
+                }
+                out.push_str(&format!("#{ix}"));
+                // Runs of the same sixel are compressed as `!<count><sixel>`
+                for run in sixels.chunk_by(|a, b| a == b) {
+                    match run.len() {
+                        1..=3 => run.iter().for_each(|&s| out.push(s as char)),
+                        n => out.push_str(&format!("!{n}{}", run[0] as char)),
+                    }
+                }
+                out.push('$');
+            }
+            out.push('-');
+        }
+        out.push_str("\x1b\\");
+        out
+    }
+
+    /// Encodes the bitmap as a kitty graphics protocol image. A new image
+    /// with the same id replaces the previous one.
+    pub fn kitty(&self) -> String {
+        let rgb: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|&ix| {
+                let (r, g, b) = self.palette[ix];
+                [r, g, b]
+            })
+            .collect();
+        let data = base64(&rgb);
+        let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+        let mut out = String::new();
+        for (n, chunk) in chunks.iter().enumerate() {
+            let more = (n + 1 < chunks.len()) as u8;
+            let chunk = std::str::from_utf8(chunk).expect("base64 is ascii");
+            if n == 0 {
+                out.push_str(&format!(
+                    "\x1b_Ga=T,f=24,i=1,p=1,q=2,C=1,s={},v={},m={more};{chunk}\x1b\\",
+                    self.width, self.height
+                ));
+            } else {
+                out.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+            }
+        }
+        out
+    }
+
+    /// Encodes the bitmap as an indexed PNG. The image data is not
+    /// compressed, which keeps the encoder small.
+    pub fn png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width) {
+            // Filter type 0: the row as is
+            raw.push(0);
+            raw.extend(row.iter().map(|&ix| ix as u8));
+        }
+        let mut ihdr = vec![];
+        ihdr.extend((self.width as u32).to_be_bytes());
+        ihdr.extend((self.height as u32).to_be_bytes());
+        // 8 bits per pixel, indexed color, deflate, no interlacing
+        ihdr.extend([8, 3, 0, 0, 0]);
+        let plte: Vec<u8> = self
+            .palette
+            .iter()
+            .flat_map(|&(r, g, b)| [r, g, b])
+            .collect();
+        let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut out, b"IHDR", &ihdr);
+        png_chunk(&mut out, b"PLTE", &plte);
+        png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+/// A zlib stream of deflate blocks without compression
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(u16::MAX as usize).collect();
+    for (n, block) in blocks.iter().enumerate() {
+        out.push((n + 1 == blocks.len()) as u8);
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(*block);
+    }
+    if blocks.is_empty() {
+        out.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &x in bytes {
+        a = (a + x as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Removes the images drawn with the kitty graphics protocol
+pub const KITTY_DELETE: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+/// The usual RGB values of the terminal colors
+pub fn rgb(c: Color) -> (u8, u8, u8) {
+    match c {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 0, 0),
+        Color::Green => (0, 205, 0),
+        Color::Yellow => (205, 205, 0),
+        Color::Blue => (0, 0, 238),
+        Color::Magenta => (205, 0, 205),
+        Color::Cyan => (0, 205, 205),
+        Color::Gray => (229, 229, 229),
+        Color::DarkGray => (127, 127, 127),
+        Color::LightRed => (255, 0, 0),
+        Color::LightGreen => (0, 255, 0),
+        Color::LightYellow => (255, 255, 0),
+        Color::LightBlue => (92, 92, 255),
+        Color::LightMagenta => (255, 0, 255),
+        Color::LightCyan => (0, 255, 255),
+        Color::White | Color::Reset | Color::Indexed(_) => (255, 255, 255),
+    }
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,334 @@
+mod graphics;
+
+pub use graphics::{Bitmap, base64, rgb};
+
+use super::architecture::*;
+use clap::ValueEnum;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::text::*;
+use std::env;
+use std::ops::Range;
+
+/// How the pixels of the screen are drawn with terminal characters
+#[derive(PartialEq, Eq, Clone, Copy, Debug, ValueEnum)]
+pub enum Renderer {
+    /// Two vertically stacked pixels per cell, with `▀`, `▄` and `█`
+    HalfBlock,
+    /// 2x4 pixels per cell, with Braille patterns
+    Braille,
+    /// One character per pixel, `#` when it is on, for screen readers and
+    /// terminals without Unicode
+    Ascii,
+    /// A bitmap drawn with the sixel graphics protocol
+    Sixel,
+    /// A bitmap drawn with the kitty graphics protocol
+    Kitty,
+}
+
+impl Renderer {
+    /// The best renderer supported by the terminal, guessed from the
+    /// environment
+    pub fn detect() -> Renderer {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        // Multiplexers do not pass the images through
+        if env::var_os("TMUX").is_some() || term.starts_with("screen") {
+            Renderer::HalfBlock
+        } else if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || ["WezTerm", "ghostty"].contains(&program.as_str())
+        {
+            Renderer::Kitty
+        } else if ["foot", "mlterm", "yaft", "contour"]
+            .iter()
+            .any(|t| term.starts_with(t))
+            || term.contains("sixel")
+        {
+            Renderer::Sixel
+        } else {
+            Renderer::HalfBlock
+        }
+    }
+
+    /// Switches between the character renderers. The bitmap renderers are only
+    /// chosen on the command line, since they need terminal support.
+    pub fn next(self) -> Renderer {
+        match self {
+            Renderer::HalfBlock => Renderer::Braille,
+            Renderer::Braille | Renderer::Ascii | Renderer::Sixel | Renderer::Kitty => {
+                Renderer::HalfBlock
+            }
+        }
+    }
+
+    /// The number of terminal columns taken by the screen
+    pub fn width(&self) -> u16 {
+        match self {
+            Renderer::HalfBlock | Renderer::Ascii | Renderer::Sixel | Renderer::Kitty => {
+                Screen::NCOLS as u16
+            }
+            Renderer::Braille => Screen::NCOLS as u16 / 2,
+        }
+    }
+
+    /// The number of terminal rows taken by the screen
+    pub fn height(&self) -> u16 {
+        match self {
+            Renderer::HalfBlock | Renderer::Sixel | Renderer::Kitty => Screen::NROWS as u16 / 2,
+            Renderer::Braille => Screen::NROWS as u16 / 4,
+            Renderer::Ascii => Screen::NROWS as u16,
+        }
+    }
+
+    /// The escape sequence that removes what the renderer drew outside of
+    /// the ratatui buffer
+    pub fn clear(&self) -> &'static str {
+        match self {
+            Renderer::Kitty => graphics::KITTY_DELETE,
+            _ => "",
+        }
+    }
+
+    /// The largest integer factor by which the character renderers can
+    /// scale the screen to fill `area`. The bitmap renderers always fill it.
+    pub fn fit(&self, area: Rect) -> usize {
+        match self {
+            Renderer::Sixel | Renderer::Kitty => 1,
+            _ => (area.width / self.width())
+                .min(area.height / self.height())
+                .max(1) as usize,
+        }
+    }
+
+    /// The escape sequence that draws the screen as a bitmap centered in
+    /// `area`, for the bitmap renderers. The pixels are scaled by the largest
+    /// integer factor that fits, up to `max_scale`.
+    pub fn image(
+        &self,
+        area: Rect,
+        max_scale: Option<usize>,
+        color: impl Fn(usize, usize) -> Option<Color>,
+    ) -> Option<String> {
+        if !matches!(self, Renderer::Sixel | Renderer::Kitty) {
+            return None;
+        }
+        let (scale, _, x) = bitmap_layout(area, max_scale);
+        let bitmap = graphics::Bitmap::new(Screen::NCOLS, Screen::NROWS, scale, color);
+        let goto = format!("\x1b[{};{}H", area.y + 1, x + 1);
+        Some(match self {
+            Renderer::Sixel => goto + &bitmap.sixel(),
+            _ => goto + &bitmap.kitty(),
+        })
+    }
+
+    /// The columns and rows of the pixels drawn in the cell at `col`, `row`,
+    /// where `area` is where the screen is drawn with the character renderers
+    /// scaled by `scale`, or with the bitmap ones scaled up to `max_scale`.
+    /// `None` if the cell is outside the screen.
+    pub fn pixels_at(
+        &self,
+        area: Rect,
+        scale: usize,
+        max_scale: Option<usize>,
+        col: u16,
+        row: u16,
+    ) -> Option<(Range<usize>, Range<usize>)> {
+        let (col, row) = (
+            col.checked_sub(area.x)? as usize,
+            row.checked_sub(area.y)? as usize,
+        );
+        // A cell may span several pixels, or part of one
+        let px = |cell: usize, size: usize, scale: usize| {
+            cell * size / scale..((cell + 1) * size).div_ceil(scale)
+        };
+        let (xs, ys) = match self {
+            Renderer::HalfBlock | Renderer::Braille | Renderer::Ascii => {
+                // The lines are centered in the area
+                let (w, h) = match self {
+                    Renderer::HalfBlock => (1, 2),
+                    Renderer::Ascii => (1, 1),
+                    _ => (2, 4),
+                };
+                let left = (area.width as usize).saturating_sub(Screen::NCOLS * scale / w) / 2;
+                let c = col.checked_sub(left)?;
+                (px(c, w, scale), px(row, h, scale))
+            }
+            Renderer::Sixel | Renderer::Kitty => {
+                let (scale, (cell_w, cell_h), x) = bitmap_layout(area, max_scale);
+                let c = col.checked_sub((x - area.x) as usize)?;
+                (px(c, cell_w, scale), px(row, cell_h, scale))
+            }
+        };
+        (xs.end <= Screen::NCOLS && ys.end <= Screen::NROWS).then_some((xs, ys))
+    }
+
+    /// Renders the screen with each pixel scaled by `scale`. `color(row, col)`
+    /// is the color of a pixel, or `None` if it is not drawn, and `off` the
+    /// color of the pixels that are off, if they are drawn.
+    pub fn lines<'a>(
+        &self,
+        scale: usize,
+        off: Option<Color>,
+        color: impl Fn(usize, usize) -> Option<Color>,
+    ) -> Vec<Line<'a>> {
+        let lit = |r: usize, c: usize| color(r, c).is_some_and(|col| Some(col) != off);
+        match self {
+            Renderer::HalfBlock => half_blocks(scale, color),
+            Renderer::Braille => braille(scale, off, color),
+            Renderer::Ascii => ascii(scale, |r, c| if lit(r, c) { '#' } else { '.' }),
+            // The bitmap is drawn after ratatui, over blank cells
+            Renderer::Sixel | Renderer::Kitty => vec![],
+        }
+    }
+}
+
+/// The scale of the bitmap drawn in `area`, the size of a cell in pixels, and
+/// the column where the bitmap starts. The bitmap is centered and each pixel
+/// is scaled by the largest integer factor that fits, up to `max_scale`.
+fn bitmap_layout(area: Rect, max_scale: Option<usize>) -> (usize, (usize, usize), u16) {
+    // Terminals that do not report their size in pixels usually have cells
+    // around 8x16 pixels
+    let (cell_w, cell_h) = match crossterm::terminal::window_size() {
+        Ok(s) if s.width > 0 && s.height > 0 && s.columns > 0 && s.rows > 0 => {
+            ((s.width / s.columns) as usize, (s.height / s.rows) as usize)
+        }
+        _ => (8, 16),
+    };
+    let scale = (area.width as usize * cell_w / Screen::NCOLS)
+        .min(area.height as usize * cell_h / Screen::NROWS)
+        .min(max_scale.unwrap_or(usize::MAX))
+        .max(1);
+    let cols = (Screen::NCOLS * scale).div_ceil(cell_w) as u16;
+    let x = area.x + area.width.saturating_sub(cols) / 2;
+    (scale, (cell_w, cell_h), x)
+}
+
+/// Renders the screen with two vertically stacked pixels per cell, which
+/// halves its height and keeps the pixels roughly square. Each pixel is a
+/// `scale` x `scale` square of them.
+pub fn half_blocks<'a>(
+    scale: usize,
+    color: impl Fn(usize, usize) -> Option<Color>,
+) -> Vec<Line<'a>> {
+    let color = |r: usize, c: usize| color(r / scale, c / scale);
+    (0..Screen::NROWS * scale / 2)
+        .map(|r| {
+            let spans: Vec<Span> = (0..Screen::NCOLS * scale)
+                .map(|c| match (color(2 * r, c), color(2 * r + 1, c)) {
+                    (None, None) => Span::from(" "),
+                    (Some(top), None) => Span::styled("▀", Style::new().fg(top)),
+                    (None, Some(bottom)) => Span::styled("▄", Style::new().fg(bottom)),
+                    (Some(top), Some(bottom)) if top == bottom => {
+                        Span::styled("█", Style::new().fg(top))
+                    }
+                    (Some(top), Some(bottom)) => Span::styled("▀", Style::new().fg(top).bg(bottom)),
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Renders the screen with a character per pixel, given by `glyph(row, col)`,
+/// or a `scale` x `scale` square of them
+pub fn ascii<'a>(scale: usize, glyph: impl Fn(usize, usize) -> char) -> Vec<Line<'a>> {
+    (0..Screen::NROWS * scale)
+        .map(|r| {
+            Line::from(
+                (0..Screen::NCOLS * scale)
+                    .map(|c| glyph(r / scale, c / scale))
+                    .collect::<String>(),
+            )
+        })
+        .collect()
+}
+
+/// Renders the screen with a Braille pattern for each 2x4 block of pixels. A
+/// cell has a single color, so the rarest color of its pixels is used, which
+/// keeps a highlighted pixel visible among plain ones. The pixels of color
+/// `off` have no dot, and that color is the background of every cell
+/// instead. Each pixel is a `scale` x `scale` square of dots.
+pub fn braille<'a>(
+    scale: usize,
+    off: Option<Color>,
+    color: impl Fn(usize, usize) -> Option<Color>,
+) -> Vec<Line<'a>> {
+    /// The bit of each dot in a Braille pattern, by row and column
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    let color = |r: usize, c: usize| color(r / scale, c / scale).filter(|&col| Some(col) != off);
+    let style = off.map_or(Style::new(), |bg| Style::new().bg(bg));
+    (0..Screen::NROWS * scale / 4)
+        .map(|r| {
+            let spans: Vec<Span> = (0..Screen::NCOLS * scale / 2)
+                .map(|c| {
+                    let mut bits: u32 = 0;
+                    let mut colors: Vec<Color> = vec![];
+                    for (dy, row) in DOTS.iter().enumerate() {
+                        for (dx, bit) in row.iter().enumerate() {
+                            if let Some(col) = color(4 * r + dy, 2 * c + dx) {
+                                bits |= bit;
+                                colors.push(col);
+                            }
+                        }
+                    }
+                    let rarest = colors
+                        .iter()
+                        .min_by_key(|&&x| colors.iter().filter(|&&y| y == x).count());
+                    let ch = char::from_u32(0x2800 + bits).expect("Braille patterns are chars");
+                    match rarest {
+                        Some(&col) => Span::styled(ch.to_string(), style.fg(col)),
+                        None => Span::styled(ch.to_string(), style),
+                    }
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Phosphor decay: pixels that turn off fade out over a few frames, like on a
+/// CRT, instead of going dark at once. Games that erase and redraw their
+/// sprites flicker much less this way.
+pub struct Phosphor {
+    /// The brightness of each pixel, from `FRAMES` when it is on down to 0
+    glow: [[u8; Screen::NCOLS]; Screen::NROWS],
+}
+
+impl Phosphor {
+    /// The number of frames a pixel takes to fade out
+    pub const FRAMES: u8 = 4;
+
+    pub fn new() -> Phosphor {
+        Phosphor {
+            glow: [[0; Screen::NCOLS]; Screen::NROWS],
+        }
+    }
+
+    /// Advances one frame, with the screen at the end of it
+    pub fn frame(&mut self, screen: &Screen) {
+        for (r, row) in self.glow.iter_mut().enumerate() {
+            for (c, g) in row.iter_mut().enumerate() {
+                *g = if screen.rows[r][c] {
+                    Self::FRAMES
+                } else {
+                    g.saturating_sub(1)
+                };
+            }
+        }
+    }
+
+    /// The color of a pixel that is off, between `on` and `off` depending on
+    /// how long ago it was on. `None` as `off` is black.
+    pub fn color(&self, r: usize, c: usize, on: Color, off: Option<Color>) -> Option<Color> {
+        let g = self.glow[r][c] as u32;
+        if g == 0 {
+            return off;
+        }
+        let ((r1, g1, b1), (r0, g0, b0)) =
+            (graphics::rgb(on), off.map_or((0, 0, 0), graphics::rgb));
+        let n = Self::FRAMES as u32 + 1;
+        let mix = |a: u8, b: u8| ((a as u32 * g + b as u32 * (n - g)) / n) as u8;
+        Some(Color::Rgb(mix(r1, r0), mix(g1, g0), mix(b1, b0)))
+    }
+}
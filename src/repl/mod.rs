@@ -0,0 +1,136 @@
+//! A prompt where instructions are typed one at a time and run right away
+//! on a live machine, for `chip-8 repl`. Each one is written to the memory
+//! at the pc before it runs, so that jumps, calls and skips move through
+//! the memory as they would in a ROM.
+
+use super::architecture::*;
+use super::assembler::repl_instruction;
+use super::cores::{CoreState, EmulatorCore};
+use super::language::*;
+use std::io::{BufRead, Result, Write};
+
+const HELP: &str = "\
+Type an instruction, e.g. LD V0, 5 or DRW V0, V1, 5, to run it at the pc.
+:state       show the registers
+:screen      show the screen
+:key K       press or release the key K, from 0 to F
+:tick [N]    count the timers down N times, 1 by default
+:reset       start over with a new machine
+:help        show this help
+:quit        leave, as the end of the input does";
+
+/// A machine with the font in memory and the quirks of `platform`
+fn machine(platform: Platform) -> Chip8 {
+    let mut chip = Chip8::new();
+    chip.quirks = platform.quirks();
+    chip.load_bytes(&[]).expect("the empty ROM fits");
+    chip
+}
+
+/// The registers, a line for the others and one for V0 to VF
+fn registers(state: &CoreState) -> String {
+    let named = state.named();
+    let (others, v) = named.split_at(5);
+    let line = |regs: &[(String, u16)], width: usize| -> String {
+        regs.iter()
+            .map(|(name, value)| format!("{name}={value:#0width$X}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    format!("{}\n{}", line(others, 5), line(v, 4))
+}
+
+/// Reads instructions and commands from `input` until it ends, and writes
+/// the prompt, the changes of each instruction and the answers to `out`.
+/// The screen is shown after the instructions that change it
+pub fn repl(platform: Platform, input: impl BufRead, out: &mut impl Write) -> Result<()> {
+    let mut chip = machine(platform);
+    writeln!(
+        out,
+        "CHIP-8 with the {platform} quirks. :help lists the commands"
+    )?;
+    write!(out, "> ")?;
+    out.flush()?;
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        let (command, arg) = line
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((line.trim(), ""));
+        match command {
+            ":quit" | ":q" => return Ok(()),
+            ":help" | ":h" => writeln!(out, "{HELP}")?,
+            ":state" => writeln!(out, "{}", registers(&chip.state()))?,
+            ":screen" => write!(out, "{}", chip.screen)?,
+            ":reset" => {
+                chip = machine(platform);
+                writeln!(out, "{}", registers(&chip.state()))?;
+            }
+            ":key" => match u8::from_str_radix(arg.trim(), 16) {
+                Ok(k) if k < 16 => {
+                    let key = &mut chip.keys[k as usize];
+                    *key = !*key;
+                    let held = if *key { "pressed" } else { "released" };
+                    writeln!(out, "key {k:X} {held}")?;
+                }
+                _ => writeln!(out, "expected a key from 0 to F")?,
+            },
+            ":tick" => match arg.trim() {
+                "" => chip.tick_timers(),
+                n => match n.parse::<u32>() {
+                    Ok(n) => (0..n).for_each(|_| chip.tick_timers()),
+                    Err(_) => writeln!(out, "expected a number of ticks")?,
+                },
+            },
+            c if c.starts_with(':') => writeln!(out, "unknown command {c}, see :help")?,
+            _ => match repl_instruction(&line, n + 1) {
+                Ok(Some(instr)) => run(&mut chip, instr, out)?,
+                Ok(None) => (),
+                Err(e) => writeln!(out, "{e}")?,
+            },
+        }
+        write!(out, "> ")?;
+        out.flush()?;
+    }
+    writeln!(out)
+}
+
+/// Writes an instruction at the pc and runs it, and writes what changed
+fn run(chip: &mut Chip8, instr: Instr, out: &mut impl Write) -> Result<()> {
+    let pc = chip.pc as usize;
+    if pc + 1 >= Chip8::MEM_SIZE {
+        return writeln!(out, "the pc {pc:#05X} left the memory, :reset starts over");
+    }
+    if matches!(instr, Instr::Ret) && chip.sp == 0 {
+        return writeln!(out, "the stack is empty");
+    }
+    if matches!(instr, Instr::Call { .. }) && chip.sp as usize == chip.stack.len() {
+        return writeln!(out, "the stack is full");
+    }
+    chip.memory[pc..pc + 2].copy_from_slice(&instr.encode());
+    let before = chip.state();
+    let screen = chip.screen.clone();
+    chip.run_instr();
+    if matches!(instr, Instr::LoadKey { .. }) && chip.pc as usize == pc {
+        writeln!(
+            out,
+            "waits for a key, press one with :key K and run it again"
+        )?;
+    }
+    let changes: Vec<String> = before
+        .named()
+        .into_iter()
+        .zip(chip.state().named())
+        .filter(|((_, a), (_, b))| a != b)
+        .map(|((name, a), (_, b))| format!("{name} {a:#X} → {b:#X}"))
+        .collect();
+    if changes.is_empty() {
+        writeln!(out, "nothing changed")?;
+    } else {
+        writeln!(out, "{}", changes.join(", "))?;
+    }
+    if chip.screen != screen {
+        write!(out, "{}", chip.screen)?;
+    }
+    Ok(())
+}
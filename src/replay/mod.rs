@@ -0,0 +1,206 @@
+use super::architecture::*;
+use super::base::*;
+use std::fs;
+use std::io::*;
+use std::path::Path;
+
+/// A recorded run: the random seed, the keypad input and checksums of the
+/// state every few steps. Replaying the input from the seed must reproduce
+/// the checksums, otherwise the emulator is not deterministic. The file has
+/// one entry per line:
+///
+/// ```text
+/// seed 0x1234
+/// key 120 0x5 down
+/// check 180 0x9AE5E2A1C0FFEE42
+/// ```
+///
+/// Only keypad input is recorded, so runs with other manual edits, such as
+/// pokes, do not replay.
+pub struct Replay {
+    pub seed: u64,
+    /// The steps where keys were pressed or released, with the key and whether
+    /// it is down
+    pub keys: Vec<(usize, u8, bool)>,
+    /// The state checksums at some steps
+    pub checks: Vec<(usize, u64)>,
+}
+
+/// The first step where a replay does not reproduce the recorded state
+pub struct Desync {
+    pub step: usize,
+    pub expected: u64,
+    pub found: u64,
+}
+
+impl Replay {
+    /// Steps between two checksums
+    pub const CHECK_INTERVAL: usize = 60;
+
+    /// Records the run from the first to the last of `history`
+    pub fn record(history: &[Chip8]) -> Replay {
+        let mut keys = vec![];
+        let mut checks = vec![];
+        for (step, c) in history.iter().enumerate() {
+            if step > 0 {
+                let prev = &history[step - 1];
+                for k in 0..16 {
+                    if prev.keys[k] != c.keys[k] {
+                        keys.push((step, k as u8, c.keys[k]));
+                    }
+                }
+            }
+            if step % Self::CHECK_INTERVAL == 0 || step == history.len() - 1 {
+                checks.push((step, c.checksum()));
+            }
+        }
+        Replay {
+            seed: history[0].seed,
+            keys,
+            checks,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut contents = format!("seed {:#X}\n", self.seed);
+        for (step, key, down) in &self.keys {
+            let state = if *down { "down" } else { "up" };
+            contents.push_str(&format!("key {step} {key:#X} {state}\n"));
+        }
+        for (step, sum) in &self.checks {
+            contents.push_str(&format!("check {step} {sum:#018X}\n"));
+        }
+        fs::write(path, contents)
+    }
+
+    pub fn load(path: &Path) -> Result<Replay> {
+        let contents = fs::read_to_string(path)?;
+        let mut replay = Replay {
+            seed: 0,
+            keys: vec![],
+            checks: vec![],
+        };
+        for (n, line) in contents.lines().enumerate() {
+            let invalid = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}:{}: invalid entry {line}", path.display(), n + 1),
+                )
+            };
+            let step = |w: &str| w.parse::<usize>().map_err(|_| invalid());
+            let hex = |w: &str| {
+                u64::from_str_radix(w.trim_start_matches("0x"), 16).map_err(|_| invalid())
+            };
+            match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                [] => (),
+                ["seed", s] => replay.seed = hex(s)?,
+                ["key", s, k, state @ ("down" | "up")] => {
+                    let key = parse_number(k).filter(|&k| k < 16).ok_or_else(invalid)?;
+                    replay.keys.push((step(s)?, key as u8, *state == "down"));
+                }
+                ["check", s, sum] => replay.checks.push((step(s)?, hex(sum)?)),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Replays the run on `chip`, which must be seeded with `seed` and have
+    /// the ROM loaded. Returns the number of checksums verified, or the first
+    /// step whose state does not match.
+    pub fn verify(&self, mut chip: Chip8) -> std::result::Result<usize, Desync> {
+        let last = self.checks.iter().map(|&(s, _)| s).max().unwrap_or(0);
+        let mut keys = self.keys.iter().peekable();
+        let mut checks = self.checks.iter().peekable();
+        for step in 0..=last {
+            if step > 0 {
+                let mut edited = false;
+                while let Some(&(_, key, down)) = keys.next_if(|&&(s, _, _)| s == step) {
+                    chip.keys[key as usize] = down;
+                    edited = true;
+                }
+                if !edited {
+                    chip.run_step(step as u64);
+                }
+            }
+            while let Some(&(_, expected)) = checks.next_if(|&&(s, _)| s == step) {
+                let found = chip.checksum();
+                if found != expected {
+                    return Err(Desync {
+                        step,
+                        expected,
+                        found,
+                    });
+                }
+            }
+        }
+        Ok(self.checks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::Debugger;
+
+    /// Sets DT to 5 and copies it to V0 forever, so that the state depends on
+    /// when the timers tick
+    const DELAY: [u8; 8] = [0x6A, 0x05, 0xFA, 0x15, 0xF0, 0x07, 0x12, 0x04];
+
+    fn debugger() -> Debugger {
+        let mut chip = Chip8::with_seed(7);
+        chip.load_bytes(&DELAY).unwrap();
+        Debugger::new(chip)
+    }
+
+    #[test]
+    fn verifies_recording() {
+        let mut d = debugger();
+        d.steps_forward(45);
+        d.edit(|c| c.keys[5] = true);
+        d.steps_forward(100);
+        let replay = Replay::record(&d.history);
+        assert_eq!(replay.keys, [(46, 5, true)]);
+        let verified = replay.verify(d.history[0].clone());
+        assert!(matches!(verified, Ok(n) if n == replay.checks.len()));
+    }
+
+    #[test]
+    fn reports_desync() {
+        let mut d = debugger();
+        d.steps_forward(130);
+        let mut replay = Replay::record(&d.history);
+        replay.checks[1].1 ^= 1;
+        let Err(desync) = replay.verify(d.history[0].clone()) else {
+            panic!("the replay should not match");
+        };
+        assert_eq!(desync.step, Replay::CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let mut d = debugger();
+        d.steps_forward(3);
+        d.edit(|c| c.keys[0xA] = true);
+        d.steps_forward(3);
+        let replay = Replay::record(&d.history);
+        let path = std::env::temp_dir().join(format!("chip-8-replay-{}", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.seed, 7);
+        assert_eq!(loaded.keys, replay.keys);
+        assert_eq!(loaded.checks, replay.checks);
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let path = std::env::temp_dir().join(format!("chip-8-bad-{}", std::process::id()));
+        fs::write(&path, "seed 0x1\nkey 3 0x10 down\n").unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        let e = loaded.err().unwrap().to_string();
+        assert!(e.ends_with(":2: invalid entry key 3 0x10 down"), "{e}");
+    }
+}
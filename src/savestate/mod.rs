@@ -0,0 +1,350 @@
+use super::architecture::*;
+use super::base::*;
+use super::info::sha256;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::fs;
+use std::io::*;
+use std::num::Wrapping;
+use std::path::Path;
+
+/// The state of a machine saved with `:state save`, with the SHA-256 of the
+/// ROM it runs, so that it is only loaded over the same ROM. The file has
+/// one entry per line, after the version:
+///
+/// ```text
+/// chip-8 state 1
+/// rom 9a1f...
+/// seed 0x1234
+/// pc 0x24E
+/// i 0x3A0
+/// sp 1
+/// dt 0x00
+/// st 0x00
+/// v 0x00 0x1F ... 0x01
+/// stack 0x20C 0x000 ... 0x000
+/// memory 0x200 00E0A2...
+/// screen ..##....
+/// ```
+///
+/// with a `memory` line for each 32 bytes that are not all zero and a
+/// `screen` line for each row. The keypad and the quirks are not saved, and
+/// the random numbers start again from the seed.
+pub struct SaveState {
+    /// The SHA-256 of the ROM, in hexadecimal
+    pub rom: String,
+    pub chip: Chip8,
+}
+
+/// The entries that every state has
+const REQUIRED: [&str; 9] = ["rom", "seed", "pc", "i", "sp", "dt", "st", "v", "stack"];
+
+/// The bytes of a `memory` line
+const MEMORY_LINE: usize = 32;
+
+impl SaveState {
+    /// The version of the format that this emulator writes and reads
+    pub const VERSION: u32 = 1;
+
+    /// The state of `chip`, which runs `rom`
+    pub fn new(chip: &Chip8, rom: &[u8]) -> SaveState {
+        SaveState {
+            rom: sha256(rom),
+            chip: chip.clone(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let c = &self.chip;
+        let mut contents = format!("chip-8 state {}\n", Self::VERSION);
+        contents.push_str(&format!("rom {}\n", self.rom));
+        contents.push_str(&format!("seed {:#X}\n", c.seed));
+        contents.push_str(&format!("pc {:#05X}\n", c.pc));
+        contents.push_str(&format!("i {:#05X}\n", c.i));
+        contents.push_str(&format!("sp {}\n", c.sp));
+        contents.push_str(&format!("dt {:#04X}\n", c.delay));
+        contents.push_str(&format!("st {:#04X}\n", c.sound));
+        let v: Vec<String> = c
+            .registers
+            .iter()
+            .map(|r| format!("{:#04X}", r.0))
+            .collect();
+        contents.push_str(&format!("v {}\n", v.join(" ")));
+        let stack: Vec<String> = c.stack.iter().map(|a| format!("{a:#05X}")).collect();
+        contents.push_str(&format!("stack {}\n", stack.join(" ")));
+        for (n, bytes) in c.memory.chunks(MEMORY_LINE).enumerate() {
+            if bytes.iter().any(|&b| b != 0) {
+                let hex: String = bytes.iter().map(|b| format!("{b:02X}")).collect();
+                contents.push_str(&format!("memory {:#05X} {hex}\n", n * MEMORY_LINE));
+            }
+        }
+        for row in &c.screen.rows {
+            let pixels: String = row.iter().map(|p| if *p { '#' } else { '.' }).collect();
+            contents.push_str(&format!("screen {pixels}\n"));
+        }
+        fs::write(path, contents)
+    }
+
+    /// Reads a state. The version must be the one this emulator writes and
+    /// every entry must be well formed, but the state may still be
+    /// inconsistent, see `problems`
+    pub fn load(path: &Path) -> Result<SaveState> {
+        let contents = fs::read_to_string(path)?;
+        let error = |n: usize, what: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{n}: {what}", path.display()),
+            )
+        };
+        let mut lines = contents.lines().enumerate().map(|(n, l)| (n + 1, l));
+        match lines
+            .next()
+            .map(|(_, l)| l.split_whitespace().collect::<Vec<&str>>())
+        {
+            Some(words) if words.starts_with(&["chip-8", "state"]) => match words.as_slice() {
+                [_, _, v] if parse_number(v) == Some(Self::VERSION) => (),
+                [_, _, v] => {
+                    let expected = Self::VERSION;
+                    return Err(error(1, format!("version {v}, expected {expected}")));
+                }
+                _ => return Err(error(1, "no version".into())),
+            },
+            _ => return Err(error(1, "not a chip-8 state".into())),
+        }
+        let mut state = SaveState {
+            rom: String::new(),
+            chip: Chip8::with_seed(0),
+        };
+        let mut seen: Vec<&str> = vec![];
+        let mut screen: Vec<&str> = vec![];
+        for (n, line) in lines {
+            let invalid = || error(n, format!("invalid entry {line}"));
+            let number =
+                |w: &str, max: u32| parse_number(w).filter(|&x| x <= max).ok_or_else(invalid);
+            let c = &mut state.chip;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => continue,
+                ["rom", hash]
+                    if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()) =>
+                {
+                    state.rom = hash.to_ascii_lowercase()
+                }
+                ["seed", s] => {
+                    c.seed = u64::from_str_radix(s.trim_start_matches("0x"), 16)
+                        .map_err(|_| invalid())?;
+                    c.rng = StdRng::seed_from_u64(c.seed);
+                }
+                ["pc", a] => c.pc = number(a, u16::MAX as u32)? as u16,
+                ["i", a] => c.i = number(a, u16::MAX as u32)? as u16,
+                ["sp", s] => c.sp = number(s, u8::MAX as u32)? as u8,
+                ["dt", t] => c.delay = number(t, u8::MAX as u32)? as u8,
+                ["st", t] => c.sound = number(t, u8::MAX as u32)? as u8,
+                ["v", v @ ..] if v.len() == 16 => {
+                    for (r, w) in c.registers.iter_mut().zip(v) {
+                        *r = Wrapping(number(w, u8::MAX as u32)? as u8);
+                    }
+                }
+                ["stack", s @ ..] if s.len() == 16 => {
+                    for (a, w) in c.stack.iter_mut().zip(s) {
+                        *a = number(w, u16::MAX as u32)? as u16;
+                    }
+                }
+                ["memory", a, hex] if hex.is_ascii() && hex.len() % 2 == 0 => {
+                    let start = number(a, Chip8::MEM_SIZE as u32)? as usize;
+                    let bytes = (0..hex.len())
+                        .step_by(2)
+                        .map(|ix| u8::from_str_radix(&hex[ix..ix + 2], 16))
+                        .collect::<std::result::Result<Vec<u8>, _>>()
+                        .map_err(|_| invalid())?;
+                    c.memory
+                        .get_mut(start..start + bytes.len())
+                        .ok_or_else(invalid)?
+                        .copy_from_slice(&bytes);
+                }
+                ["screen", pixels] => screen.push(pixels),
+                _ => return Err(invalid()),
+            }
+            if let Some(key) = REQUIRED.iter().find(|&&k| k == words[0]) {
+                seen.push(key);
+            }
+        }
+        let last = contents.lines().count();
+        if let Some(missing) = REQUIRED.iter().find(|k| !seen.contains(k)) {
+            return Err(error(last, format!("no {missing}")));
+        }
+        if !screen.is_empty() {
+            state.chip.screen = Screen::parse(&screen.join("\n"))
+                .map_err(|e| error(last, format!("screen: {e}")))?;
+        }
+        Ok(state)
+    }
+
+    /// What makes the state impossible to run from: the stack pointer past
+    /// the stack, or the pc or a return address outside the memory
+    pub fn problems(&self) -> Vec<String> {
+        let c = &self.chip;
+        let mut problems = vec![];
+        let outside = |a: u16| a as usize + 1 >= Chip8::MEM_SIZE;
+        if c.sp as usize > c.stack.len() {
+            problems.push(format!(
+                "SP is {}, past the {} levels of the stack",
+                c.sp,
+                c.stack.len()
+            ));
+        }
+        if outside(c.pc) {
+            problems.push(format!("PC {:#05X} is outside the memory", c.pc));
+        }
+        for (level, &a) in c.stack.iter().enumerate().take(c.sp as usize) {
+            if outside(a) {
+                problems.push(format!(
+                    "the return address {a:#05X} of level {level} is outside the memory"
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Whether the state is of `rom`
+    pub fn is_of(&self, rom: &[u8]) -> bool {
+        sha256(rom) == self.rom
+    }
+
+    /// Copies the state into `chip`, which keeps its keypad and quirks
+    pub fn restore(&self, chip: &mut Chip8) {
+        *chip = Chip8 {
+            keys: chip.keys,
+            quirks: chip.quirks,
+            font: chip.font,
+            big_font: chip.big_font,
+            ..self.chip.clone()
+        };
+    }
+
+    /// The state in a table like that of `chip-8 info`:
+    ///
+    /// ```text
+    /// version     1
+    /// rom         9a1f...
+    /// pc          0x24E
+    /// i           0x3A0
+    /// stack       0x20C
+    /// timers      DT 0x00, ST 0x00
+    /// registers   00 1F 00 ... 01
+    /// seed        0x1234
+    /// screen      312 pixels on
+    /// ```
+    pub fn summary(&self) -> String {
+        let c = &self.chip;
+        let stack: Vec<String> = c
+            .stack
+            .iter()
+            .take(c.sp as usize)
+            .map(|a| format!("{a:#05X}"))
+            .collect();
+        let registers: Vec<String> = c.registers.iter().map(|r| format!("{:02X}", r.0)).collect();
+        let pixels: usize = c.screen.rows.iter().map(|r| r.count_ones()).sum();
+        let rows = [
+            ("version", Self::VERSION.to_string()),
+            ("rom", self.rom.clone()),
+            ("pc", format!("{:#05X}", c.pc)),
+            ("i", format!("{:#05X}", c.i)),
+            (
+                "stack",
+                if stack.is_empty() {
+                    "empty".into()
+                } else {
+                    stack.join(" ")
+                },
+            ),
+            (
+                "timers",
+                format!("DT {:#04X}, ST {:#04X}", c.delay, c.sound),
+            ),
+            ("registers", registers.join(" ")),
+            ("seed", format!("{:#X}", c.seed)),
+            ("screen", format!("{pixels} pixels on")),
+        ];
+        rows.iter().map(|(k, v)| format!("{k:<12}{v}\n")).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: [u8; 6] = [0x6A, 0x05, 0xA2, 0x20, 0xD0, 0x15];
+
+    /// Writes `contents` to a file and loads it as a state
+    fn load(name: &str, contents: &str) -> Result<SaveState> {
+        let path = std::env::temp_dir().join(format!("chip-8-{name}-{}", std::process::id()));
+        fs::write(&path, contents)?;
+        let state = SaveState::load(&path);
+        fs::remove_file(&path)?;
+        state
+    }
+
+    fn running() -> Chip8 {
+        let mut chip = Chip8::with_seed(0x1234);
+        chip.load_bytes(&ROM).unwrap();
+        for _ in 0..3 {
+            chip.run_instr();
+        }
+        chip.delay = 9;
+        chip.push_stack(0x20C);
+        chip
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let chip = running();
+        let path = std::env::temp_dir().join(format!("chip-8-state-{}", std::process::id()));
+        SaveState::new(&chip, &ROM).save(&path).unwrap();
+        let state = SaveState::load(&path);
+        fs::remove_file(&path).unwrap();
+        let state = state.unwrap();
+        assert!(state.is_of(&ROM));
+        assert!(!state.is_of(&ROM[1..]));
+        assert!(state.problems().is_empty());
+        assert_eq!(state.chip.checksum(), chip.checksum());
+        assert_eq!(state.chip.seed, 0x1234);
+        assert_eq!(state.chip.screen.rows, chip.screen.rows);
+    }
+
+    #[test]
+    fn rejects_other_versions_and_missing_entries() {
+        let error = |name, contents| load(name, contents).err().unwrap().to_string();
+        assert!(error("version", "chip-8 state 2\n").ends_with(":1: version 2, expected 1"));
+        assert!(error("header", "chip-8 replay\n").ends_with(":1: not a chip-8 state"));
+        let e = error("missing", "chip-8 state 1\nseed 0x1\npc 0x200\n");
+        assert!(e.ends_with(":3: no rom"), "{e}");
+        let e = error("entry", "chip-8 state 1\nsp 0x100\n");
+        assert!(e.ends_with(":2: invalid entry sp 0x100"), "{e}");
+    }
+
+    #[test]
+    fn finds_problems() {
+        let mut chip = running();
+        chip.pc = 0xFFF;
+        chip.sp = 17;
+        let problems = SaveState::new(&chip, &ROM).problems();
+        assert_eq!(
+            problems,
+            [
+                "SP is 17, past the 16 levels of the stack",
+                "PC 0xFFF is outside the memory",
+            ]
+        );
+        chip.sp = 1;
+        chip.stack[0] = 0x1000;
+        let problems = SaveState::new(&chip, &ROM).problems();
+        assert_eq!(
+            problems,
+            [
+                "PC 0xFFF is outside the memory",
+                "the return address 0x1000 of level 0 is outside the memory",
+            ]
+        );
+    }
+}
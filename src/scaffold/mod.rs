@@ -0,0 +1,96 @@
+//! The starter project of `chip-8 new`: a program that moves a sprite with
+//! the keys, its sprites in a file of their own, and a Makefile that
+//! assembles it and runs it in the debugger.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The program, where `NAME` is replaced by the name of the project
+const MAIN: &str = r#"; NAME: a sprite that W, A, S and D move, keys 5, 7, 8 and 9 of the keypad.
+; `make` assembles it into NAME.ch8, and `make run` runs it in the debugger,
+; which reloads it whenever it is assembled again.
+
+UP EQU 0x5
+LEFT EQU 0x7
+DOWN EQU 0x8
+RIGHT EQU 0x9
+
+main:
+    CLS
+    LD V0, 28            ; x
+    LD V1, 12            ; y
+    LD I, player
+    DRW V0, V1, 8
+loop:
+    LD V2, K
+    DRW V0, V1, 8        ; erases the sprite where it was
+    SNE V2, UP
+    ADD V1, 0xFF
+    SNE V2, DOWN
+    ADD V1, 1
+    SNE V2, LEFT
+    ADD V0, 0xFF
+    SNE V2, RIGHT
+    ADD V0, 1
+    DRW V0, V1, 8
+    JP loop
+
+include "sprites.s8"
+"#;
+
+/// The sprites, as `chip-8 sprite-edit` writes them
+const SPRITES: &str = "; The sprites. `chip-8 sprite-edit --bytes \"3C 7E ...\"` edits one and
+; prints its DB lines.
+
+player:
+    DB 0x3C              ; ..████..
+    DB 0x7E              ; .██████.
+    DB 0xDB              ; ██.██.██
+    DB 0xFF              ; ████████
+    DB 0xFF              ; ████████
+    DB 0x7E              ; .██████.
+    DB 0x24              ; ..█..█..
+    DB 0x42              ; .█....█.
+";
+
+const MAKEFILE: &str = "ROM = NAME.ch8
+
+$(ROM): NAME.s8 sprites.s8
+\tchip-8 assemble NAME.s8 --map
+
+run: $(ROM)
+\tchip-8 run $(ROM) --watch
+
+clean:
+\trm -f $(ROM) NAME.map
+
+.PHONY: run clean
+";
+
+/// Creates the project `dir`, named after its last component. The program
+/// is `NAME.s8`, the name that the editor of the debugger opens for
+/// `NAME.ch8`. Returns the files created. The directory must not exist or
+/// be empty, so that nothing is overwritten
+pub fn new(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let name = dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("{} is not a project name", dir.display()))?;
+    if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!("{} already exists and is not empty", dir.display()));
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {e}", dir.display()))?;
+    let files = [
+        (format!("{name}.s8"), MAIN),
+        ("sprites.s8".to_string(), SPRITES),
+        ("Makefile".to_string(), MAKEFILE),
+    ];
+    let mut created = vec![];
+    for (file, template) in files {
+        let path = dir.join(file);
+        fs::write(&path, template.replace("NAME", name))
+            .map_err(|e| format!("could not write {}: {e}", path.display()))?;
+        created.push(path);
+    }
+    Ok(created)
+}
@@ -0,0 +1,67 @@
+use super::architecture::*;
+use super::render::Bitmap;
+use ratatui::style::Color;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The directory where screenshots are written, relative to the current one
+pub const DIR: &str = "screenshots";
+
+/// The size in pixels of the square each CHIP-8 pixel becomes in the PNG
+pub const SCALE: usize = 8;
+
+/// Writes the screen to `dir` as text art in a `.txt` file and as a scaled
+/// `.png`, both named after the current time. Returns the path without the
+/// extension.
+pub fn save(dir: &Path, screen: &Screen, on: Color, off: Option<Color>) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let stem = dir.join(format!("chip-8-{}", timestamp()));
+    write(&stem.with_extension("txt"), screen, on, off)?;
+    write(&stem.with_extension("png"), screen, on, off)?;
+    Ok(stem)
+}
+
+/// Writes the screen to `path` as a scaled PNG if it has the `.png`
+/// extension, and as text art otherwise
+pub fn write(path: &Path, screen: &Screen, on: Color, off: Option<Color>) -> Result<()> {
+    if path.extension().is_some_and(|e| e == "png") {
+        let bitmap = Bitmap::new(Screen::NCOLS, Screen::NROWS, SCALE, |r, c| {
+            if screen.rows[r][c] { Some(on) } else { off }
+        });
+        fs::write(path, bitmap.png())
+    } else {
+        fs::write(path, screen.to_string())
+    }
+}
+
+/// The current UTC time as `YYYY-MM-DD-HHMMSS`
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (y, m, d) = civil_from_days(days as i64);
+    format!(
+        "{y:04}-{m:02}-{d:02}-{:02}{:02}{:02}",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// The date of a number of days since 1970-01-01, in the proleptic
+/// Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + (m <= 2) as i64;
+    (y, m, d)
+}
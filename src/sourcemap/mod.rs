@@ -0,0 +1,140 @@
+use super::base::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::*;
+use std::path::{Path, PathBuf};
+
+/// Where the bytes of an assembled ROM come from, for debugging at the level
+/// of the source. `assemble --map` writes it next to the ROM, one entry per
+/// line:
+///
+/// ```text
+/// label 0x200 main
+/// line 0x200 3 game.s8
+/// ```
+///
+/// The files are relative to the map.
+#[derive(Default, Debug)]
+pub struct SourceMap {
+    /// The file and the line of the instruction or the data at each address
+    pub lines: BTreeMap<u16, (PathBuf, usize)>,
+    /// The labels, by their addresses
+    pub labels: BTreeMap<u16, String>,
+}
+
+impl SourceMap {
+    /// The source map of `rom.ch8` is `rom.map`
+    pub fn path(rom: &Path) -> PathBuf {
+        rom.with_extension("map")
+    }
+
+    /// Loads the source map of a ROM, if it has one
+    pub fn load(rom: &Path) -> Result<Option<SourceMap>> {
+        let path = Self::path(rom);
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut map = SourceMap::default();
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}:{}: invalid entry: {line}", path.display(), n + 1),
+                )
+            };
+            let mut words = line.splitn(4, ' ');
+            let addr = words
+                .nth(1)
+                .and_then(parse_number)
+                .and_then(|a| u16::try_from(a).ok())
+                .ok_or_else(invalid)?;
+            match (line.split(' ').next(), words.next(), words.next()) {
+                (Some("label"), Some(name), None) => {
+                    map.labels.insert(addr, name.to_string());
+                }
+                (Some("line"), Some(number), Some(file)) => {
+                    let number = number.parse().map_err(|_| invalid())?;
+                    map.lines.insert(addr, (dir.join(file), number));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Some(map))
+    }
+
+    /// Writes the source map of a ROM, next to it
+    pub fn save(&self, rom: &Path) -> Result<()> {
+        let path = Self::path(rom);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut out = String::new();
+        for (addr, name) in &self.labels {
+            out += &format!("label {addr:#05X} {name}\n");
+        }
+        for (addr, (file, line)) in &self.lines {
+            let file = match file.strip_prefix(dir) {
+                Ok(f) => f.to_path_buf(),
+                Err(_) => fs::canonicalize(file).unwrap_or(file.clone()),
+            };
+            out += &format!("line {addr:#05X} {line} {}\n", file.display());
+        }
+        fs::write(path, out)
+    }
+}
+
+/// A source map and the text of its files, for the source view of the
+/// memory pane
+pub struct Sources {
+    pub map: SourceMap,
+    /// The lines of each file. A file that can't be read has none
+    files: HashMap<PathBuf, Vec<String>>,
+}
+
+impl Sources {
+    /// Loads the source map of a ROM, if it has one, and its files
+    pub fn load(rom: &Path) -> Result<Option<Sources>> {
+        let Some(map) = SourceMap::load(rom)? else {
+            return Ok(None);
+        };
+        let mut files = HashMap::new();
+        for (file, _) in map.lines.values() {
+            if !files.contains_key(file) {
+                let text = fs::read_to_string(file).unwrap_or_default();
+                files.insert(file.clone(), text.lines().map(String::from).collect());
+            }
+        }
+        Ok(Some(Sources { map, files }))
+    }
+
+    /// The file and the line of the instruction at `addr`, or of the last one
+    /// before it
+    pub fn line(&self, addr: u16) -> Option<(&Path, usize)> {
+        let (_, (file, line)) = self.map.lines.range(..=addr).next_back()?;
+        Some((file, *line))
+    }
+
+    /// Whether an instruction starts a line at `addr`
+    pub fn starts_line(&self, addr: u16) -> bool {
+        self.map.lines.contains_key(&addr)
+    }
+
+    pub fn lines(&self, file: &Path) -> &[String] {
+        self.files.get(file).map_or(&[], Vec::as_slice)
+    }
+
+    /// The address of the first instruction of a line. `file` can be the
+    /// end of its path, e.g. its name
+    pub fn addr(&self, file: &Path, line: usize) -> Option<u16> {
+        self.map
+            .lines
+            .iter()
+            .find(|(_, (f, l))| *l == line && f.ends_with(file))
+            .map(|(&a, _)| a)
+    }
+}
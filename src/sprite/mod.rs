@@ -0,0 +1,222 @@
+//! A grid editor of sprites for `chip-8 sprite-edit`, 8 pixels wide and 1
+//! to 15 rows tall as `DXYN` draws them, or 16x16 as the `DXY0` of
+//! SUPER-CHIP. The bytes are written as assembler `DB` lines or as
+//! hexadecimal.
+
+use clap::ValueEnum;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::*;
+use ratatui::style::Stylize;
+use ratatui::text::*;
+use ratatui::widgets::*;
+use std::io::Result;
+
+/// The most rows of a sprite 8 pixels wide
+const MAX_HEIGHT: usize = 15;
+
+/// How the bytes of a sprite are written
+#[derive(PartialEq, Eq, Clone, Copy, Debug, ValueEnum)]
+pub enum SpriteFormat {
+    /// A `DB` line per row, with the row drawn in a comment
+    Db,
+    /// The bytes in hexadecimal, on one line
+    Hex,
+}
+
+pub struct SpriteEditor {
+    /// Whether the sprite is 16x16 instead of 8 pixels wide
+    wide: bool,
+    /// The rows, the leftmost pixel in the highest bit of the width
+    rows: Vec<u16>,
+    /// The row and the column of the cursor
+    cursor: (usize, usize),
+}
+
+/// What a key did to the editor
+pub enum Edit {
+    Editing,
+    /// The editor was closed without the sprite
+    Cancel,
+    /// The sprite is done
+    Done,
+}
+
+impl SpriteEditor {
+    /// An editor of the sprite of `bytes`, a byte per row, or two if `wide`.
+    /// Without bytes, the sprite is blank, 5 rows tall as the font or 16x16
+    pub fn new(bytes: &[u8], wide: bool) -> std::result::Result<SpriteEditor, String> {
+        let rows: Vec<u16> = if wide {
+            if bytes.len() > 32 || !bytes.len().is_multiple_of(2) {
+                return Err(format!("a 16x16 sprite has 32 bytes, not {}", bytes.len()));
+            }
+            let mut rows: Vec<u16> = bytes
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            rows.resize(16, 0);
+            rows
+        } else if bytes.len() > MAX_HEIGHT {
+            return Err(format!(
+                "a sprite 8 pixels wide has at most {MAX_HEIGHT} rows, not {}",
+                bytes.len()
+            ));
+        } else if bytes.is_empty() {
+            vec![0; 5]
+        } else {
+            bytes.iter().map(|&b| b as u16).collect()
+        };
+        Ok(SpriteEditor {
+            wide,
+            rows,
+            cursor: (0, 0),
+        })
+    }
+
+    fn width(&self) -> usize {
+        if self.wide { 16 } else { 8 }
+    }
+
+    fn pixel(&self, row: usize, col: usize) -> bool {
+        self.rows[row] & (1 << (self.width() - 1 - col)) != 0
+    }
+
+    /// The bytes of the sprite, as `DXYN` and `DXY0` read them from I
+    pub fn bytes(&self) -> Vec<u8> {
+        self.rows
+            .iter()
+            .flat_map(|&r| {
+                if self.wide {
+                    r.to_be_bytes().to_vec()
+                } else {
+                    vec![r as u8]
+                }
+            })
+            .collect()
+    }
+
+    /// The sprite written as `format`
+    pub fn write(&self, format: SpriteFormat) -> String {
+        match format {
+            SpriteFormat::Hex => {
+                let bytes: Vec<String> = self.bytes().iter().map(|b| format!("{b:02X}")).collect();
+                bytes.join(" ") + "\n"
+            }
+            SpriteFormat::Db => (0..self.rows.len())
+                .map(|r| {
+                    let bytes: Vec<String> = if self.wide {
+                        self.rows[r]
+                            .to_be_bytes()
+                            .iter()
+                            .map(|b| format!("{b:#04X}"))
+                            .collect()
+                    } else {
+                        vec![format!("{:#04X}", self.rows[r])]
+                    };
+                    let art: String = (0..self.width())
+                        .map(|c| if self.pixel(r, c) { '█' } else { '.' })
+                        .collect();
+                    format!("    DB {:<12} ; {art}\n", bytes.join(", "))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn key(&mut self, k: KeyEvent) -> Edit {
+        let (row, col) = self.cursor;
+        let last_col = self.width() - 1;
+        match (k.modifiers, k.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => return Edit::Cancel,
+            (_, KeyCode::Esc | KeyCode::Char('q')) => return Edit::Done,
+            (_, KeyCode::Up | KeyCode::Char('k')) => self.cursor.0 = row.saturating_sub(1),
+            (_, KeyCode::Down | KeyCode::Char('j')) => {
+                self.cursor.0 = (row + 1).min(self.rows.len() - 1)
+            }
+            (_, KeyCode::Left | KeyCode::Char('h')) => self.cursor.1 = col.saturating_sub(1),
+            (_, KeyCode::Right | KeyCode::Char('l')) => self.cursor.1 = (col + 1).min(last_col),
+            (_, KeyCode::Char(' ') | KeyCode::Enter) => {
+                self.rows[row] ^= 1 << (last_col - col);
+            }
+            // A row more or less, for the sprites 8 pixels wide
+            (_, KeyCode::Char('+')) if !self.wide && self.rows.len() < MAX_HEIGHT => {
+                self.rows.push(0)
+            }
+            (_, KeyCode::Char('-')) if !self.wide && self.rows.len() > 1 => {
+                self.rows.pop();
+                self.cursor.0 = row.min(self.rows.len() - 1);
+            }
+            (_, KeyCode::Char('i')) => {
+                let mask = if self.wide { 0xFFFF } else { 0xFF };
+                self.rows.iter_mut().for_each(|r| *r ^= mask);
+            }
+            (_, KeyCode::Char('c')) => self.rows.iter_mut().for_each(|r| *r = 0),
+            _ => (),
+        }
+        Edit::Editing
+    }
+
+    /// Shows the editor until the sprite is done. Returns `None` if it is
+    /// closed without it
+    pub fn run(mut self, mut terminal: ratatui::DefaultTerminal) -> Result<Option<SpriteEditor>> {
+        loop {
+            terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
+            let Event::Key(k) = crossterm::event::read()? else {
+                continue;
+            };
+            if k.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.key(k) {
+                Edit::Editing => (),
+                Edit::Cancel => return Ok(None),
+                Edit::Done => return Ok(Some(self)),
+            }
+        }
+    }
+}
+
+impl Widget for &SpriteEditor {
+    fn render(self, area: Rect, buf: &mut ratatui::buffer::Buffer) {
+        let grid: Vec<Line> = (0..self.rows.len())
+            .map(|r| {
+                let pixels = (0..self.width()).map(|c| {
+                    let span = Span::from(if self.pixel(r, c) { "██" } else { "··" });
+                    if (r, c) == self.cursor {
+                        span.reversed()
+                    } else {
+                        span
+                    }
+                });
+                Line::from(pixels.collect::<Vec<_>>())
+            })
+            .collect();
+        let size = if self.wide {
+            "16x16".to_string()
+        } else {
+            format!("8x{}", self.rows.len())
+        };
+        let title = Line::from(format!("Sprite {size}"))
+            .bold()
+            .blue()
+            .centered();
+        let keys = if self.wide {
+            "space toggle  i invert  c clear  q done  ctrl-c cancel"
+        } else {
+            "space toggle  +/- rows  i invert  c clear  q done  ctrl-c cancel"
+        };
+        let block = Block::bordered()
+            .title(title)
+            .title_bottom(Line::from(keys).dim().centered());
+        let [left, right] = Layout::horizontal([
+            Constraint::Length(2 * self.width() as u16 + 4),
+            Constraint::Fill(1),
+        ])
+        .areas(block.inner(area));
+        block.render(area, buf);
+        Paragraph::new(grid)
+            .block(Block::new().padding(Padding::uniform(1)))
+            .render(left, buf);
+        Paragraph::new(Text::from(self.write(SpriteFormat::Db)))
+            .block(Block::new().padding(Padding::uniform(1)))
+            .render(right, buf);
+    }
+}
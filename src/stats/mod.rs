@@ -0,0 +1,152 @@
+//! Statistics of a collection of ROMs for `chip-8 stats`: the opcodes that
+//! their code runs, the quirks that it depends on, the extensions that they
+//! use and their sizes, to tell which extensions and quirks matter most.
+
+use super::analysis::reachable;
+use super::architecture::*;
+use super::language::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How much an opcode is used
+#[derive(Default)]
+struct Usage {
+    /// The instructions with it in the reachable code of all the ROMs
+    count: usize,
+    /// The ROMs with it
+    roms: usize,
+}
+
+/// A report on ROMs loaded at `start`, e.g.:
+///
+/// ```text
+/// 42 ROMs, 48211 bytes
+/// size        median 512 bytes, from 132 to 3584, none needs more than 4 KiB
+/// platforms   chip8 30, schip 10, xochip 2
+///
+/// opcode  count  ROMs  what it does
+/// 6XNN     4021    42  VX := NN
+///
+/// quirk     ROMs  opcodes
+/// shift_vy    12  8XY6 (40), 8XYE (3)
+///
+/// extension                       ROMs
+/// SCHIP 00FF high resolution        10
+/// ```
+///
+/// The opcodes and the quirks are those of the code reachable from the
+/// start, and the extensions are looked for in every word, as `info` does
+pub fn stats(roms: &[Vec<u8>], start: u16) -> String {
+    let mut opcodes: BTreeMap<&str, (Usage, &str)> = BTreeMap::new();
+    // The ROMs that depend on each quirk, and its opcodes with their counts
+    let mut quirks: BTreeMap<&str, (usize, BTreeMap<&str, usize>)> = BTreeMap::new();
+    let mut extensions: BTreeMap<String, usize> = BTreeMap::new();
+    let mut platforms: BTreeMap<String, usize> = BTreeMap::new();
+    let mut sizes: Vec<usize> = roms.iter().map(Vec::len).collect();
+    for rom in roms {
+        let mut seen = BTreeSet::new();
+        let mut depends = BTreeSet::new();
+        for instr in reachable(rom, start).values() {
+            if let Instr::Data(_) = instr {
+                continue;
+            }
+            let info = instr.info();
+            let (usage, _) = opcodes
+                .entry(info.encoding)
+                .or_insert_with(|| (Usage::default(), info.summary));
+            usage.count += 1;
+            if seen.insert(info.encoding) {
+                usage.roms += 1;
+            }
+            if let Some((name, _)) = info.quirk.and_then(|q| q.split_once(':')) {
+                let (roms, opcodes) = quirks.entry(name).or_default();
+                *opcodes.entry(info.encoding).or_default() += 1;
+                if depends.insert(name) {
+                    *roms += 1;
+                }
+            }
+        }
+        let mut used = BTreeSet::new();
+        for w in rom.chunks_exact(2) {
+            if let Some((platform, opcode)) = Platform::extension(w[0], w[1]) {
+                let name = match platform {
+                    Platform::Xochip => "XO-CHIP".to_string(),
+                    p => p.to_string().to_uppercase(),
+                };
+                used.insert(format!("{name} {opcode}"));
+            }
+        }
+        for extension in used {
+            *extensions.entry(extension).or_default() += 1;
+        }
+        *platforms
+            .entry(Platform::detect(rom).to_string())
+            .or_default() += 1;
+    }
+
+    let mut out = format!(
+        "{} ROMs, {} bytes\n",
+        roms.len(),
+        sizes.iter().sum::<usize>()
+    );
+    sizes.sort();
+    if let (Some(min), Some(max)) = (sizes.first(), sizes.last()) {
+        let big = sizes
+            .iter()
+            .filter(|&&s| start as usize + s > Chip8::MEM_SIZE)
+            .count();
+        let big = match big {
+            0 => "none needs".to_string(),
+            1 => "1 needs".to_string(),
+            n => format!("{n} need"),
+        };
+        out += &format!(
+            "{:<12}median {} bytes, from {min} to {max}, {big} more than 4 KiB\n",
+            "size",
+            sizes[sizes.len() / 2]
+        );
+        let platforms: Vec<String> = platforms.iter().map(|(p, n)| format!("{p} {n}")).collect();
+        out += &format!("{:<12}{}\n", "platforms", platforms.join(", "));
+    }
+
+    let mut opcodes: Vec<_> = opcodes.into_iter().collect();
+    opcodes.sort_by_key(|(_, (usage, _))| Reverse(usage.count));
+    out += &format!(
+        "\n{:<6}  {:>5}  {:>4}  what it does\n",
+        "opcode", "count", "ROMs"
+    );
+    for (encoding, (usage, summary)) in opcodes {
+        out += &format!(
+            "{encoding:<6}  {:>5}  {:>4}  {summary}\n",
+            usage.count, usage.roms
+        );
+    }
+
+    if !quirks.is_empty() {
+        let width = quirks.keys().map(|q| q.len()).max().unwrap_or(0).max(5);
+        out += &format!("\n{:<width$}  {:>4}  opcodes\n", "quirk", "ROMs");
+        for (name, (roms, opcodes)) in quirks {
+            let opcodes: Vec<String> = opcodes
+                .iter()
+                .map(|(opcode, n)| format!("{opcode} ({n})"))
+                .collect();
+            out += &format!("{name:<width$}  {roms:>4}  {}\n", opcodes.join(", "));
+        }
+    }
+
+    if !extensions.is_empty() {
+        let width = extensions
+            .keys()
+            .map(|e| e.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(9);
+        let mut extensions: Vec<_> = extensions.into_iter().collect();
+        extensions.sort_by_key(|&(_, roms)| Reverse(roms));
+        out += &format!("\n{:<width$}  {:>4}\n", "extension", "ROMs");
+        for (extension, roms) in extensions {
+            out += &format!("{extension:<width$}  {roms:>4}\n");
+        }
+    }
+    out
+}
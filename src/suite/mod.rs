@@ -0,0 +1,354 @@
+//! The test ROMs of the `tests` directory, built into the emulator and run
+//! without a terminal by `chip-8 test`. Each one draws its results, so a
+//! test passes when the screen after its steps is the one that it draws
+//! when every check passes. Other ROMs are checked the same way against a
+//! golden screen kept in a file, and whole directories of them for the
+//! crashes of the emulator. Two runs of a ROM, or two saved screens, are
+//! told apart pixel by pixel.
+
+use super::architecture::*;
+use super::cores::CoreState;
+use super::frames;
+use super::language::*;
+use super::picker::is_rom;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+pub struct Test {
+    pub name: &'static str,
+    pub rom: &'static [u8],
+    /// The interpreter whose quirks the ROM expects
+    pub platform: Platform,
+    /// The instructions to run, after which the screen no longer changes
+    pub steps: u32,
+    /// The `Screen::checksum` of the screen after the steps
+    pub screen: u64,
+}
+
+pub const TESTS: [Test; 4] = [
+    Test {
+        name: "1-chip8-logo",
+        rom: include_bytes!("../../tests/1-chip8-logo.ch8"),
+        platform: Platform::Chip8,
+        steps: 1000,
+        screen: 0x04DBDF2A533DF2F0,
+    },
+    Test {
+        name: "2-ibm-logo",
+        rom: include_bytes!("../../tests/2-ibm-logo.ch8"),
+        platform: Platform::Chip8,
+        steps: 1000,
+        screen: 0xE98E7CB4DE4C8D45,
+    },
+    Test {
+        name: "3-corax+",
+        rom: include_bytes!("../../tests/3-corax+.ch8"),
+        platform: Platform::Chip8,
+        steps: 5000,
+        screen: 0x3B8F41404F9CE7D9,
+    },
+    Test {
+        name: "4-flags",
+        rom: include_bytes!("../../tests/4-flags.ch8"),
+        platform: Platform::Chip8,
+        steps: 5000,
+        screen: 0x0B849A70F7CD76DD,
+    },
+];
+
+/// Runs a ROM for `steps` instructions with the quirks of `platform`,
+/// counting the timers down every `STEPS_PER_FRAME`. The random numbers
+/// are the same in every run. An error is a ROM that doesn't fit in memory
+/// or an instruction that the emulator fails to run
+pub fn run(rom: &[u8], platform: Platform, steps: u32) -> Result<Chip8, String> {
+    let mut chip = Chip8::with_seed(0);
+    chip.quirks = platform.quirks();
+    chip.load_bytes(rom).map_err(|e| e.to_string())?;
+    let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+        for step in 1..=steps {
+            chip.run_step(step.into());
+        }
+    }));
+    match ran {
+        Ok(()) => Ok(chip),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "the emulator crashed".into());
+            Err(format!("{message} at {:#05X}", chip.pc))
+        }
+    }
+}
+
+/// Runs a test. An error says how it failed, with the screen that it left
+/// if it ran to the end
+pub fn check(test: &Test) -> Result<(), (String, Option<Box<Screen>>)> {
+    let chip = run(test.rom, test.platform, test.steps).map_err(|e| (e, None))?;
+    let screen = chip.screen.checksum();
+    if screen == test.screen {
+        Ok(())
+    } else {
+        let message = format!(
+            "the screen is {screen:#018X}, expected {:#018X}",
+            test.screen
+        );
+        Err((message, Some(Box::new(chip.screen))))
+    }
+}
+
+/// The screen kept in a golden file: a PGM, as `--dump-frames` writes them,
+/// if it has the `.pgm` extension, and text art, as the screenshots are
+/// saved, otherwise
+pub fn golden(path: &Path) -> Result<Screen, String> {
+    let invalid = |e: String| format!("{}: {e}", path.display());
+    if path.extension().is_some_and(|e| e == "pgm") {
+        let bytes = fs::read(path).map_err(|e| invalid(e.to_string()))?;
+        frames::read_pgm(&bytes).map_err(invalid)
+    } else {
+        let text = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        Screen::parse(&text).map_err(invalid)
+    }
+}
+
+/// The pixels that differ between a screen and the expected one, drawn as
+/// text art where `+` is on but expected off and `-` is off but expected
+/// on, or `None` if the screens are the same
+pub fn diff(screen: &Screen, expected: &Screen) -> Option<String> {
+    if screen == expected {
+        return None;
+    }
+    let mut art = String::new();
+    let mut count = 0;
+    for (row, expected_row) in screen.rows.iter().zip(&expected.rows) {
+        for c in 0..Screen::NCOLS {
+            art.push(match (row[c], expected_row[c]) {
+                (true, true) => '█',
+                (false, false) => '.',
+                (true, false) => '+',
+                (false, true) => '-',
+            });
+            count += usize::from(row[c] != expected_row[c]);
+        }
+        art.push('\n');
+    }
+    Some(format!(
+        "{count} pixels differ, + is on but expected off, - is off but expected on:\n{art}"
+    ))
+}
+
+/// The registers that differ between two machines, as a table with a
+/// column per machine, headed by `names`, or `None` if they are the same
+pub fn diff_registers(states: [&CoreState; 2], names: [&str; 2]) -> Option<String> {
+    let [a, b] = states.map(CoreState::named);
+    let rows: Vec<String> = a
+        .iter()
+        .zip(&b)
+        .filter(|((_, x), (_, y))| x != y)
+        .map(|((name, x), (_, y))| format!("{name:<8}  {x:>#8X}  {y:>#8X}"))
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    let [a, b] = names;
+    let count = match rows.len() {
+        1 => "1 register differs".to_string(),
+        n => format!("{n} registers differ"),
+    };
+    Some(format!(
+        "{count}:\n{:<8}  {a:>8}  {b:>8}\n{}\n",
+        "register",
+        rows.join("\n")
+    ))
+}
+
+/// Checks that a screen is the one of a golden file. The error has the
+/// difference
+pub fn assert_screen(screen: &Screen, path: &Path) -> Result<(), String> {
+    match diff(screen, &golden(path)?) {
+        None => Ok(()),
+        Some(d) => Err(format!("the screen is not {}: {d}", path.display())),
+    }
+}
+
+/// How a smoke run of a ROM ended
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Outcome {
+    /// It ran all its steps
+    Ran,
+    /// It jumps to itself, as ROMs do when they are done
+    Halted,
+    /// It waits for a key, which nobody presses
+    WaitsForKey,
+    /// It runs an instruction that leaves the pc and the rest of the state as
+    /// they were, again and again, so nothing can change. The instruction
+    /// is not a jump, and no interpreter runs it so
+    Stuck(String),
+    /// It reached an opcode that the emulator doesn't run, or ignores
+    Unimplemented(String),
+    /// The ROM did something that no interpreter can run: it overflowed or
+    /// underflowed the stack, left the memory, or doesn't fit in it
+    Fault(String),
+    /// The emulator panicked
+    Crash(String),
+}
+
+impl Outcome {
+    /// The name of the outcome in the summary
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Ran => "ran",
+            Outcome::Halted => "halted",
+            Outcome::WaitsForKey => "waits for a key",
+            Outcome::Stuck(_) => "stuck",
+            Outcome::Unimplemented(_) => "unimplemented",
+            Outcome::Fault(_) => "fault",
+            Outcome::Crash(_) => "crash",
+        }
+    }
+
+    /// Whether the outcome is a bug of the ROM or of the emulator
+    pub fn failed(&self) -> bool {
+        matches!(
+            self,
+            Outcome::Stuck(_) | Outcome::Fault(_) | Outcome::Crash(_)
+        )
+    }
+}
+
+/// A ROM run by `smoke`
+pub struct Report {
+    pub file: PathBuf,
+    /// The instructions run
+    pub steps: u32,
+    /// The pc where it stopped
+    pub pc: u16,
+    pub outcome: Outcome,
+}
+
+/// Runs a ROM for at most `steps` instructions, and stops at the first
+/// sign that it is done or broken. The faults are caught before the
+/// emulator runs the instruction, and the crashes are its panics
+pub fn smoke(file: &Path, platform: Option<Platform>, steps: u32) -> Report {
+    let mut report = Report {
+        file: file.to_path_buf(),
+        steps: 0,
+        pc: Chip8::CODE_START as u16,
+        outcome: Outcome::Ran,
+    };
+    let rom = match fs::read(file) {
+        Ok(rom) => rom,
+        Err(e) => {
+            report.outcome = Outcome::Fault(format!("could not read the ROM: {e}"));
+            return report;
+        }
+    };
+    let mut chip = Chip8::with_seed(0);
+    chip.quirks = platform.unwrap_or(Platform::Cowgod).quirks();
+    if let Err(e) = chip.load_bytes(&rom) {
+        report.outcome = Outcome::Fault(e.to_string());
+        return report;
+    }
+    // The checksum of the state after the last instruction that left the pc
+    // where it was
+    let mut stayed: Option<u64> = None;
+    let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+        while report.steps < steps {
+            report.pc = chip.pc;
+            if let Some(outcome) = fault(&chip) {
+                return outcome;
+            }
+            let instr = chip.read_instr();
+            report.steps += 1;
+            chip.run_step(report.steps.into());
+            if chip.pc == report.pc {
+                match instr {
+                    Instr::Goto { .. } => return Outcome::Halted,
+                    Instr::LoadKey { .. } => return Outcome::WaitsForKey,
+                    _ => {
+                        let sum = chip.checksum();
+                        if stayed == Some(sum) {
+                            return Outcome::Stuck(instr.to_string());
+                        }
+                        stayed = Some(sum);
+                    }
+                }
+            }
+        }
+        Outcome::Ran
+    }));
+    report.outcome = ran.unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "the emulator crashed".into());
+        Outcome::Crash(message)
+    });
+    report
+}
+
+/// What would go wrong running the instruction at the pc, if anything
+fn fault(chip: &Chip8) -> Option<Outcome> {
+    if chip.pc as usize + 1 >= Chip8::MEM_SIZE {
+        return Some(Outcome::Fault("the pc left the memory".into()));
+    }
+    let instr = chip.read_instr();
+    match instr {
+        Instr::Data(_) | Instr::System { .. } => {
+            let opcode = u16::from_be_bytes(instr.encode());
+            Some(Outcome::Unimplemented(format!("{opcode:04X}")))
+        }
+        Instr::Call { .. } if chip.sp as usize == chip.stack.len() => {
+            Some(Outcome::Fault("stack overflow".into()))
+        }
+        Instr::Ret if chip.sp == 0 => Some(Outcome::Fault("stack underflow".into())),
+        _ => None,
+    }
+}
+
+/// The ROMs of a directory and its subdirectories, sorted
+pub fn roms(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            found.extend(roms(&path)?);
+        } else if is_rom(&path) {
+            found.push(path);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Smoke runs the ROMs on a thread per core. `platform` gives the quirks of
+/// each ROM. The reports are in the order of the ROMs
+pub fn smoke_all(
+    roms: &[PathBuf],
+    platform: impl Fn(&Path) -> Option<Platform> + Sync,
+    steps: u32,
+) -> Vec<Report> {
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(vec![]);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..threads.min(roms.len()) {
+            scope.spawn(|| {
+                while let Some(file) = roms.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let report = smoke(file, platform(file), steps);
+                    reports.lock().expect("reports lock poisoned").push(report);
+                }
+            });
+        }
+    });
+    let mut reports = reports.into_inner().expect("reports lock poisoned");
+    reports.sort_by(|a, b| a.file.cmp(&b.file));
+    reports
+}
@@ -0,0 +1,134 @@
+use super::architecture::*;
+use super::base::*;
+use super::language::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::*;
+use std::path::{Path, PathBuf};
+
+/// Names of addresses, shown in place of the raw addresses. They are read
+/// from a `.sym` file next to the ROM, one per line: `0x0200 main`, or else
+/// inferred from the targets of the jumps, calls and `LD I`s.
+#[derive(Default)]
+pub struct Symbols {
+    pub labels: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    /// The symbol file of `rom.ch8` is `rom.sym`
+    pub fn path(rom: &Path) -> PathBuf {
+        rom.with_extension("sym")
+    }
+
+    /// Loads the symbol file of a ROM, if it has one
+    pub fn load(rom: &Path) -> Result<Option<Symbols>> {
+        let path = Self::path(rom);
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut labels = BTreeMap::new();
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |what: &str| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}:{}: {what}", path.display(), n + 1),
+                )
+            };
+            let (addr, name) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid("expected <address> <name>"))?;
+            let addr = parse_number(addr)
+                .filter(|&a| (a as usize) < Chip8::MEM_SIZE)
+                .ok_or_else(|| invalid(&format!("invalid address {addr}")))?;
+            labels.insert(addr as u16, name.trim().to_string());
+        }
+        Ok(Some(Symbols { labels }))
+    }
+
+    /// Writes the symbol file of a ROM
+    pub fn save(&self, rom: &Path) -> Result<()> {
+        let lines: String = self
+            .labels
+            .iter()
+            .map(|(addr, name)| format!("{addr:#06X} {name}\n"))
+            .collect();
+        fs::write(Self::path(rom), lines)
+    }
+
+    /// Labels `L_0300` at the addresses that the program jumps to, calls or
+    /// points I to. The program is decoded in two-byte steps from the start
+    /// of the code to its last non-zero byte.
+    pub fn infer(chip: &Chip8) -> Symbols {
+        let end = chip.memory.iter().rposition(|&b| b != 0).unwrap_or(0) + 1;
+        let code = &chip.memory[Chip8::CODE_START..end.max(Chip8::CODE_START)];
+        Self::infer_code(code)
+    }
+
+    /// Like `infer`, for the code of a ROM
+    pub fn infer_code(code: &[u8]) -> Symbols {
+        let instrs: Vec<Instr> = code
+            .chunks_exact(2)
+            .map(|pair| RawInstr::from_bytes([pair[0], pair[1]]).into_instr())
+            .collect();
+        Self::infer_instrs(&instrs)
+    }
+
+    /// Like `infer`, for some instructions, e.g. those that the disassembler
+    /// finds reachable
+    pub fn infer_instrs<'a>(instrs: impl IntoIterator<Item = &'a Instr>) -> Symbols {
+        let mut labels = BTreeMap::new();
+        for instr in instrs {
+            let target = match instr {
+                Instr::Goto { addr } | Instr::Call { addr } => u16::from(addr),
+                Instr::SetI { n } | Instr::Jump { n } => u16::from(n),
+                _ => continue,
+            };
+            labels.insert(target, format!("L_{target:04X}"));
+        }
+        Symbols { labels }
+    }
+
+    pub fn get(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    /// The name of the routine at `addr`, or `main` if it is the start of
+    /// the program, or else `sub_0300`
+    pub fn routine(&self, addr: u16, start: u16) -> String {
+        match self.get(addr) {
+            Some(name) => name.to_string(),
+            None if addr == start => "main".into(),
+            None => format!("sub_{addr:04X}"),
+        }
+    }
+
+    /// The name of an address, or the nearest name before it and the
+    /// offset, e.g. `draw+6`
+    pub fn describe(&self, addr: u16) -> String {
+        match self.labels.range(..=addr).next_back() {
+            Some((&a, name)) if a == addr => name.clone(),
+            Some((&a, name)) => format!("{name}+{}", addr - a),
+            None => format!("{addr:#05X}"),
+        }
+    }
+
+    /// Replaces the address operand of a jump, a call or an `LD I` in its
+    /// text with its name
+    pub fn label_operand(&self, instr: &Instr, text: &str) -> String {
+        let addr = match instr {
+            Instr::Goto { addr } | Instr::Call { addr } | Instr::System { addr } => u16::from(addr),
+            Instr::SetI { n } | Instr::Jump { n } => u16::from(n),
+            _ => return text.to_string(),
+        };
+        match (self.get(addr), text.rsplit_once(' ')) {
+            (Some(name), Some((before, _))) => format!("{before} {name}"),
+            _ => text.to_string(),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use super::config::Config;
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::symbols::border;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, BorderType};
+use std::str::FromStr;
+
+/// The colors of the debugger. They are set in the `[theme]` section of the
+/// config file, either by `name` of a built-in theme or color by color:
+///
+/// ```toml
+/// [theme]
+/// name = "amber"
+/// border = "#805800"
+/// ```
+///
+/// Colors are names such as `lightgreen`, or `#RRGGBB`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Theme {
+    /// Pixels that are on
+    pub pixel_on: Color,
+    /// Pixels that are off. `None` leaves the terminal background
+    pub pixel_off: Option<Color>,
+    /// Pixels turned on, and new values, in the last step
+    pub diff_on: Color,
+    /// Pixels turned off, and old values, in the last step
+    pub diff_off: Color,
+    /// Pixels that are on and changed in the last steps, see `:recent`
+    pub recent: Color,
+    /// The borders of the panes
+    pub border: Color,
+    /// The titles of the panes
+    pub title: Color,
+    /// Whether the panes are drawn with ASCII characters only, with marks
+    /// where other themes only use colors. See `--accessible`
+    pub plain: bool,
+}
+
+/// The borders of the panes with ASCII characters
+const PLAIN: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// The borders of the focused pane with ASCII characters
+const PLAIN_FOCUSED: border::Set = border::Set {
+    vertical_left: "#",
+    vertical_right: "#",
+    horizontal_top: "=",
+    horizontal_bottom: "=",
+    ..PLAIN
+};
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            pixel_on: Color::White,
+            pixel_off: None,
+            diff_on: Color::Green,
+            diff_off: Color::Red,
+            recent: Color::Yellow,
+            border: Color::Reset,
+            title: Color::Blue,
+            plain: false,
+        }
+    }
+}
+
+impl Theme {
+    pub const NAMES: [&str; 3] = ["default", "green-phosphor", "amber"];
+
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::default()),
+            "green-phosphor" => Some(Theme {
+                pixel_on: Color::Rgb(0x33, 0xFF, 0x33),
+                pixel_off: None,
+                diff_on: Color::Rgb(0xCC, 0xFF, 0xCC),
+                diff_off: Color::Rgb(0x1A, 0x66, 0x1A),
+                recent: Color::Rgb(0xCC, 0xFF, 0x33),
+                border: Color::Rgb(0x1A, 0x99, 0x1A),
+                title: Color::Rgb(0x33, 0xFF, 0x33),
+                plain: false,
+            }),
+            "amber" => Some(Theme {
+                pixel_on: Color::Rgb(0xFF, 0xB0, 0x00),
+                pixel_off: None,
+                diff_on: Color::Rgb(0xFF, 0xE0, 0xA0),
+                diff_off: Color::Rgb(0x80, 0x58, 0x00),
+                recent: Color::Rgb(0xFF, 0x60, 0x00),
+                border: Color::Rgb(0xB0, 0x78, 0x00),
+                title: Color::Rgb(0xFF, 0xB0, 0x00),
+                plain: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The theme set in the config file, or the default one
+    pub fn from_config(config: &Config) -> Result<Theme, String> {
+        let mut theme = match config.get("theme", "name") {
+            Some(name) => Theme::named(name).ok_or(format!(
+                "unknown theme {name}, expected one of: {}",
+                Theme::NAMES.join(", ")
+            ))?,
+            None => Theme::default(),
+        };
+        let color = |key: &str| -> Result<Option<Color>, String> {
+            config
+                .get("theme", key)
+                .map(|v| Color::from_str(v).map_err(|_| format!("theme.{key}: invalid color {v}")))
+                .transpose()
+        };
+        for (key, field) in [
+            ("pixel_on", &mut theme.pixel_on),
+            ("diff_on", &mut theme.diff_on),
+            ("diff_off", &mut theme.diff_off),
+            ("recent", &mut theme.recent),
+            ("border", &mut theme.border),
+            ("title", &mut theme.title),
+        ] {
+            if let Some(c) = color(key)? {
+                *field = c;
+            }
+        }
+        if let Some(c) = color("pixel_off")? {
+            theme.pixel_off = Some(c);
+        }
+        Ok(theme)
+    }
+
+    /// A bordered pane with a centered title
+    pub fn block<'a>(&self, title: Line<'a>) -> Block<'a> {
+        let block = Block::bordered()
+            .border_style(Style::new().fg(self.border))
+            .title(title.bold().fg(self.title).centered());
+        if self.plain {
+            block.border_set(PLAIN)
+        } else {
+            block
+        }
+    }
+
+    /// Marks a pane as the one that takes the arrow keys
+    pub fn focused<'a>(&self, block: Block<'a>) -> Block<'a> {
+        if self.plain {
+            block.border_set(PLAIN_FOCUSED)
+        } else {
+            block.border_type(BorderType::Thick)
+        }
+    }
+
+    /// `fancy`, or `plain` if the panes are drawn with ASCII characters
+    pub fn glyph(&self, fancy: &'static str, plain: &'static str) -> &'static str {
+        if self.plain { plain } else { fancy }
+    }
+}
@@ -0,0 +1,207 @@
+use super::architecture::*;
+use super::cores::EmulatorCore;
+use super::json::Json;
+use super::language::*;
+use clap::ValueEnum;
+use std::io::Write;
+
+/// One event of the Chrome trace event format
+enum TraceEvent {
+    /// A subroutine call starts
+    Begin(usize, String),
+    /// The innermost subroutine call returns
+    End(usize),
+    /// Something happened at a step, with its arguments
+    Instant(usize, String, Vec<(&'static str, u16)>),
+}
+
+impl TraceEvent {
+    fn to_json(&self) -> String {
+        let common = "\"pid\": 1, \"tid\": 1";
+        match self {
+            TraceEvent::Begin(ts, name) => {
+                format!("{{\"name\": \"{name}\", \"ph\": \"B\", \"ts\": {ts}, {common}}}")
+            }
+            TraceEvent::End(ts) => format!("{{\"ph\": \"E\", \"ts\": {ts}, {common}}}"),
+            TraceEvent::Instant(ts, name, args) => {
+                let args: Vec<String> = args.iter().map(|(k, v)| format!("\"{k}\": {v}")).collect();
+                format!(
+                    "{{\"name\": \"{name}\", \"ph\": \"i\", \"s\": \"t\", \"ts\": {ts}, {common}, \"args\": {{{}}}}}",
+                    args.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Converts a run to the Chrome trace event format, which can be opened in
+/// Perfetto or chrome://tracing. Each step lasts one microsecond. Subroutine
+/// calls become nested spans, and draws and timer writes become instant
+/// events.
+pub fn chrome_trace(history: &[Chip8]) -> String {
+    let mut events: Vec<TraceEvent> = vec![];
+    let mut depth: usize = 0;
+    for (step, c) in history
+        .iter()
+        .enumerate()
+        .take(history.len().saturating_sub(1))
+    {
+        match c.read_instr() {
+            Instr::Call { addr } => {
+                let addr: u16 = addr.into();
+                events.push(TraceEvent::Begin(step, format!("sub {addr:#05X}")));
+                depth += 1;
+            }
+            Instr::Ret if depth > 0 => {
+                events.push(TraceEvent::End(step + 1));
+                depth -= 1;
+            }
+            Instr::Draw { x, y, height } => events.push(TraceEvent::Instant(
+                step,
+                "draw".into(),
+                vec![
+                    ("x", c.rv(x) as u16),
+                    ("y", c.rv(y) as u16),
+                    ("height", height as u16),
+                    ("i", c.i),
+                ],
+            )),
+            Instr::Clear => events.push(TraceEvent::Instant(step, "clear".into(), vec![])),
+            Instr::SetDelayTimer { r } => events.push(TraceEvent::Instant(
+                step,
+                "delay timer".into(),
+                vec![("value", c.rv(r) as u16)],
+            )),
+            Instr::SetSoundTimer { r } => events.push(TraceEvent::Instant(
+                step,
+                "sound timer".into(),
+                vec![("value", c.rv(r) as u16)],
+            )),
+            _ => (),
+        }
+    }
+    // Calls that have not returned yet end with the run
+    for _ in 0..depth {
+        events.push(TraceEvent::End(history.len() - 1));
+    }
+    let events: Vec<String> = events
+        .iter()
+        .map(|e| format!("  {}", e.to_json()))
+        .collect();
+    format!(
+        "{{\"displayTimeUnit\": \"ms\", \"traceEvents\": [\n{}\n]}}\n",
+        events.join(",\n")
+    )
+}
+
+/// The syntax of the records of `step_trace`
+#[derive(PartialEq, Eq, Clone, Copy, Debug, ValueEnum)]
+pub enum TraceFormat {
+    /// A line per record, aligned, to read
+    Text,
+    /// A header and a row per record, for spreadsheets
+    Csv,
+    /// An object per line, for other tools
+    Json,
+}
+
+/// One instruction run by `step_trace`
+struct Record {
+    step: u64,
+    pc: u16,
+    opcode: u16,
+    mnemonic: String,
+    /// The registers, other than the PC, that the instruction changed, with
+    /// their new values
+    changes: Vec<(String, u16)>,
+}
+
+impl Record {
+    fn write(&self, format: TraceFormat, out: &mut impl Write) -> std::io::Result<()> {
+        let changes = || -> Vec<String> {
+            self.changes
+                .iter()
+                .map(|(name, v)| format!("{name}={v:#X}"))
+                .collect()
+        };
+        match format {
+            TraceFormat::Text => {
+                let line = format!(
+                    "{:>8} {:#05X} {:04X}  {:<18} {}",
+                    self.step,
+                    self.pc,
+                    self.opcode,
+                    self.mnemonic,
+                    changes().join(" ")
+                );
+                writeln!(out, "{}", line.trim_end())
+            }
+            TraceFormat::Csv => writeln!(
+                out,
+                "{},{:#05X},{:#06X},\"{}\",{}",
+                self.step,
+                self.pc,
+                self.opcode,
+                self.mnemonic.replace('"', "\"\""),
+                changes().join(" ")
+            ),
+            TraceFormat::Json => {
+                let changes = self
+                    .changes
+                    .iter()
+                    .map(|(name, v)| (name.clone(), Json::from(*v as usize)))
+                    .collect();
+                let record = Json::object([
+                    ("step", Json::from(self.step as usize)),
+                    ("pc", Json::from(self.pc as usize)),
+                    ("opcode", Json::from(self.opcode as usize)),
+                    ("mnemonic", Json::from(self.mnemonic.as_str())),
+                    ("changes", Json::Object(changes)),
+                ]);
+                writeln!(out, "{record}")
+            }
+        }
+    }
+}
+
+/// Runs `cycles` instructions without a terminal, counting the timers down
+/// every `STEPS_PER_FRAME`, and writes a record of each one: its step, from
+/// 1, its address, its opcode, its mnemonic and the registers that it
+/// changed. An error is an instruction that can't run, after the records of
+/// those before it, or a failed write
+pub fn step_trace(
+    chip: &mut Chip8,
+    cycles: u64,
+    format: TraceFormat,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let failed = |e: std::io::Error| format!("could not write the trace: {e}");
+    if format == TraceFormat::Csv {
+        writeln!(out, "step,pc,opcode,mnemonic,changes").map_err(failed)?;
+    }
+    for step in 1..=cycles {
+        let before = chip.state();
+        let instr = chip.read_instr();
+        if let Instr::Data(_) = instr {
+            return Err(format!("data at pc {:#05X} is not an instruction", chip.pc));
+        }
+        chip.run_step(step);
+        let after = chip.state();
+        let changes = before
+            .named()
+            .into_iter()
+            .zip(after.named())
+            .filter(|((name, a), (_, b))| a != b && name != "PC")
+            .map(|(_, change)| change)
+            .collect();
+        let record = Record {
+            step,
+            pc: before.pc,
+            opcode: u16::from_be_bytes(instr.encode()),
+            mnemonic: instr.to_string(),
+            changes,
+        };
+        record.write(format, out).map_err(failed)?;
+    }
+    Ok(())
+}